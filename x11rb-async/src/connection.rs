@@ -15,9 +15,13 @@ use x11rb_protocol::{DiscardMode, RawEventAndSeqNumber, SequenceNumber};
 use crate::errors::{ConnectionError, ParseError, ReplyError, ReplyOrIdError};
 use crate::{Cookie, CookieWithFds, VoidCookie};
 
+use futures_lite::Stream;
+
 use std::future::Future;
 use std::io::IoSlice;
+use std::ops::Deref;
 use std::pin::Pin;
+use std::task::{Context, Poll};
 
 pub(crate) type Fut<'a, T, E> = Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'a>>;
 
@@ -458,6 +462,17 @@ pub trait Connection: RequestConnection {
         &self,
     ) -> Fut<'_, RawEventAndSeqNumber<Self::Buf>, ConnectionError>;
 
+    /// Get a [`Stream`] of the events from the X11 server.
+    ///
+    /// The stream borrows the connection. Use [`EventStream::new`] with an `Arc` of the
+    /// connection to get a stream that can be moved around independently.
+    fn events(&self) -> EventStream<'_, &Self>
+    where
+        Self: Sized,
+    {
+        EventStream::new(self)
+    }
+
     /// Poll for a new event from the X11 server.
     fn poll_for_event(&self) -> Result<Option<Event>, ConnectionError> {
         Ok(self.poll_for_event_with_sequence()?.map(|(event, _)| event))
@@ -504,3 +519,115 @@ pub trait Connection: RequestConnection {
     /// ```
     fn generate_id(&self) -> Fut<'_, u32, ReplyOrIdError>;
 }
+
+type EventFilter<'a> = Box<dyn FnMut(&Event) -> bool + Send + 'a>;
+
+/// A [`Stream`] of events from an X11 connection.
+///
+/// `C` is some kind of reference to the connection, for example `&RustConnection` or
+/// `Arc<RustConnection>`. The stream implements the `Stream` trait from `futures-core`, so it
+/// can be used with stream combinators and in `select!`-style macros.
+///
+/// Events are only taken from the connection when the stream produces them, so dropping the stream
+/// does not lose any events. Events that are rejected by the filter set via
+/// [`EventStream::with_filter`] are discarded.
+///
+/// Errors while parsing an event are reported without ending the stream. After any other error,
+/// the connection is broken and the stream ends.
+pub struct EventStream<'a, C> {
+    conn: C,
+    pending: Option<Fut<'a, Event, ConnectionError>>,
+    filter: Option<EventFilter<'a>>,
+    finished: bool,
+}
+
+impl<'a, C> EventStream<'a, C>
+where
+    C: Deref + Clone + Send + 'a,
+    C::Target: Connection,
+{
+    /// Create a new stream of the events of the given connection.
+    pub fn new(conn: C) -> Self {
+        Self {
+            conn,
+            pending: None,
+            filter: None,
+            finished: false,
+        }
+    }
+
+    /// Only produce events for which `filter` returns `true`.
+    ///
+    /// Other events are discarded. This replaces a previously set filter.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use futures_lite::StreamExt;
+    /// # use x11rb_async::connection::Connection;
+    /// # use x11rb_async::protocol::Event;
+    /// # async fn example(conn: &impl Connection) {
+    /// let mut exposes = conn
+    ///     .events()
+    ///     .with_filter(|event| matches!(event, Event::Expose(_)));
+    /// while let Some(event) = exposes.next().await {
+    ///     println!("{:?}", event);
+    /// }
+    /// # }
+    /// ```
+    pub fn with_filter(mut self, filter: impl FnMut(&Event) -> bool + Send + 'a) -> Self {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+}
+
+impl<'a, C> Stream for EventStream<'a, C>
+where
+    C: Deref + Clone + Send + 'a,
+    C::Target: Connection,
+{
+    type Item = Result<Event, ConnectionError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if this.finished {
+                return Poll::Ready(None);
+            }
+            let pending = this.pending.get_or_insert_with(|| {
+                let conn = this.conn.clone();
+                Box::pin(async move { conn.wait_for_event().await })
+            });
+            // The future is kept until it completes so that no wakeup is missed
+            let result = match pending.as_mut().poll(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(result) => result,
+            };
+            this.pending = None;
+            match result {
+                Ok(event) => {
+                    if this.filter.as_mut().map_or(true, |filter| filter(&event)) {
+                        return Poll::Ready(Some(Ok(event)));
+                    }
+                }
+                Err(err) => {
+                    this.finished = !matches!(err, ConnectionError::ParseError(_));
+                    return Poll::Ready(Some(Err(err)));
+                }
+            }
+        }
+    }
+}
+
+// Nothing in `EventStream` is structurally pinned.
+impl<C> Unpin for EventStream<'_, C> {}
+
+impl<C> std::fmt::Debug for EventStream<'_, C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventStream")
+            .field("waiting", &self.pending.is_some())
+            .field("filtered", &self.filter.is_some())
+            .field("finished", &self.finished)
+            .finish_non_exhaustive()
+    }
+}
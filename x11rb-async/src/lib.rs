@@ -40,6 +40,9 @@
 //!   [`blocking::BlockingConnection`] for [`x11rb::xcb_ffi::XCBConnection`]
 //! * `extra-traits`: Implement extra traits for X11 types. This improves the output of the `Debug`
 //!   impl and adds `PartialEq`, `Eq`, `PartialOrd`, `Ord`, and `Hash` where possible.
+//! * `tokio`: Add [`rust_connection::TokioStream`] and
+//!   [`rust_connection::RustConnection::connect_tokio`] for using the tokio reactor instead of
+//!   async-io. This is only available on unix.
 
 // A list of lints that are only #![deny] and not the stronger #![forbid]. Each one has a comment
 // explaining why it gets the weaker treatment.
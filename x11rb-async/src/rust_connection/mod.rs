@@ -13,24 +13,29 @@ use std::sync::Arc;
 use crate::connection::{Connection, Fut, RequestConnection};
 use crate::{Cookie, CookieWithFds, VoidCookie};
 
+pub use x11rb_protocol::connection::Limits;
 use x11rb_protocol::connection::{Connection as ProtoConnection, PollReply, ReplyFdKind};
 use x11rb_protocol::id_allocator::IdAllocator;
 use x11rb_protocol::protocol::bigreq::EnableReply;
 use x11rb_protocol::protocol::xproto::{Setup, QUERY_EXTENSION_REQUEST};
 use x11rb_protocol::x11_utils::{ExtensionInformation, TryParse, TryParseFd, X11Error};
-use x11rb_protocol::xauth::get_auth;
+use x11rb_protocol::xauth::get_auth_for_connection;
 use x11rb_protocol::{DiscardMode, RawFdContainer, SequenceNumber};
 
-use x11rb::connection::{BufWithFds, ReplyOrError};
+use x11rb::connection::{BufWithFds, ConnectionStats, ReplyOrError};
 use x11rb::errors::{ConnectError, ConnectionError, ParseError, ReplyOrIdError};
 
 mod extensions;
 mod nb_connect;
 mod shared_state;
 mod stream;
+#[cfg(all(unix, feature = "tokio"))]
+mod tokio_stream;
 mod write_buffer;
 
 pub use stream::{DefaultStream, Stream, StreamAdaptor, StreamBase};
+#[cfg(all(unix, feature = "tokio"))]
+pub use tokio_stream::{TokioReady, TokioStream};
 use write_buffer::{WriteBuffer, WriteBufferGuard};
 
 /// A pure-Rust async connection to an X11 server.
@@ -93,11 +98,12 @@ impl RustConnection {
         let (stream, screen, (family, address)) = nb_connect::connect(&addrs).await?;
 
         // Wrap the stream in a connection.
+        let local_address = stream.local_address();
         let stream = StreamAdaptor::new(stream)?;
 
         // Use this to get authority information.
         let (auth_name, auth_data) = blocking::unblock(move || {
-            get_auth(family, &address, addrs.display)
+            get_auth_for_connection(family, &address, addrs.display, local_address)
                 .unwrap_or(None)
                 .unwrap_or_else(|| (Vec::new(), Vec::new()))
         })
@@ -244,6 +250,35 @@ impl<S: Stream + Send + Sync> RustConnection<S> {
         ))
     }
 
+    /// Get statistics about the traffic on this connection.
+    ///
+    /// The statistics count everything since the connection was established or since the last call
+    /// to [`RustConnection::reset_stats`].
+    pub fn stats(&self) -> ConnectionStats {
+        self.shared.stats.lock().unwrap().clone()
+    }
+
+    /// Reset the statistics about the traffic on this connection to zero.
+    ///
+    /// The statistics before the reset are returned.
+    pub fn reset_stats(&self) -> ConnectionStats {
+        std::mem::take(&mut *self.shared.stats.lock().unwrap())
+    }
+
+    /// Set limits on the data that is accepted from the X11 server.
+    ///
+    /// When the X11 server sends more data than these limits allow, the driving future fails
+    /// with [`ConnectionError::LimitExceeded`]. Since data was lost, all later waits for replies
+    /// and events fail with the same error. By default, there are no limits.
+    pub fn set_limits(&self, limits: Limits) {
+        self.shared.lock_connection().set_limits(limits);
+    }
+
+    /// Get the limits on the data that is accepted from the X11 server.
+    pub fn limits(&self) -> Limits {
+        self.shared.lock_connection().limits()
+    }
+
     /// Send a request.
     async fn send_request(
         &self,
@@ -337,6 +372,7 @@ impl<S: Stream + Send + Sync> RustConnection<S> {
         bufs: &[io::IoSlice<'_>],
         fds: &mut Vec<RawFdContainer>,
     ) -> Result<WriteBufferGuard<'a>, ConnectionError> {
+        self.shared.stats.lock().unwrap().record_request(bufs);
         write_buffer
             .write_all_vectored(&self.shared.stream, bufs, fds)
             .await?;
@@ -348,6 +384,9 @@ impl<S: Stream + Send + Sync> RustConnection<S> {
         &'a self,
         mut buffer: WriteBufferGuard<'a>,
     ) -> Result<WriteBufferGuard<'a>, ConnectionError> {
+        if buffer.needs_flush() {
+            self.shared.stats.lock().unwrap().record_flush();
+        }
         buffer.flush(&self.shared.stream).await?;
         Ok(buffer)
     }
@@ -401,7 +440,7 @@ impl<S: Stream + Send + Sync> RustConnection<S> {
             }
         };
 
-        self.shared.wait_for_incoming(get_reply).await?
+        self.shared.wait_for_reply(get_reply).await?
     }
 }
 
@@ -531,7 +570,7 @@ impl<S: Stream + Send + Sync> RequestConnection for RustConnection<S> {
                 };
 
                 // Wait for the reply.
-                self.shared.wait_for_incoming(get_reply).await?
+                self.shared.wait_for_reply(get_reply).await?
             }
             .instrument(tracing::info_span!("wait_for_reply", sequence)),
         )
@@ -579,7 +618,7 @@ impl<S: Stream + Send + Sync> RequestConnection for RustConnection<S> {
                     PollReply::Reply(buffer) => Some(Ok(Some(buffer))),
                 };
 
-                self.shared.wait_for_incoming(get_result).await?
+                self.shared.wait_for_reply(get_result).await?
             }
             .instrument(tracing::info_span!("check_for_raw_error", sequence)),
         )
@@ -9,7 +9,9 @@ use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc, Mutex as StdMutex, MutexGuard as StdMutexGuard,
 };
-use x11rb::errors::ConnectionError;
+use std::time::Instant;
+use x11rb::connection::ConnectionStats;
+use x11rb::errors::{ConnectionError, LimitExceeded};
 use x11rb_protocol::connection::Connection as ProtoConnection;
 use x11rb_protocol::packet_reader::PacketReader as ProtoPacketReader;
 use x11rb_protocol::RawFdContainer;
@@ -32,6 +34,16 @@ pub(super) struct SharedState<S> {
 
     /// Flag that indicates that the future for drive() was dropped and we no longer read input.
     driver_dropped: AtomicBool,
+
+    /// The limit that the X11 server exceeded, if any.
+    ///
+    /// Data from the server was lost in this case, so waiting for packets fails with this error.
+    limit_exceeded: StdMutex<Option<LimitExceeded>>,
+
+    /// Statistics about the traffic on the connection.
+    ///
+    /// This is never held across an `.await` point and no other mutex is locked while it is held.
+    pub(super) stats: StdMutex<ConnectionStats>,
 }
 
 impl<S: Stream> SharedState<S> {
@@ -41,6 +53,8 @@ impl<S: Stream> SharedState<S> {
             stream,
             new_input: Event::new(),
             driver_dropped: AtomicBool::new(false),
+            limit_exceeded: Default::default(),
+            stats: Default::default(),
         }
     }
 
@@ -49,6 +63,27 @@ impl<S: Stream> SharedState<S> {
         self.inner.lock().unwrap()
     }
 
+    /// Wait for an incoming reply or error to a request.
+    ///
+    /// This works like [`SharedState::wait_for_incoming`], but records the time spent waiting as a
+    /// round trip in the connection's statistics.
+    pub(super) async fn wait_for_reply<R, F>(&self, mut get_reply: F) -> Result<R, io::Error>
+    where
+        F: FnMut(&mut ProtoConnection) -> Option<R>,
+    {
+        // See if we can find the reply without waiting.
+        if let Some(reply) = get_reply(&mut self.lock_connection()) {
+            return Ok(reply);
+        }
+        let start = Instant::now();
+        let result = self.wait_for_incoming(get_reply).await;
+        self.stats
+            .lock()
+            .unwrap()
+            .record_round_trip(start.elapsed());
+        result
+    }
+
     /// Wait for an incoming packet.
     ///
     /// The given function get_reply should check whether the needed package was already received
@@ -72,6 +107,11 @@ impl<S: Stream> SharedState<S> {
                 return Ok(reply);
             }
 
+            // Maybe the X11 server sent more than the limits allow?
+            if let Some(limit) = *self.limit_exceeded.lock().unwrap() {
+                return Err(limit_exceeded_error(limit));
+            }
+
             // Maybe the future from drive() was dropped?
             // We only check this down here and not before the listener since this is unlikely
             if self.driver_dropped.load(Ordering::SeqCst) {
@@ -101,16 +141,40 @@ impl<S: Stream> SharedState<S> {
         loop {
             for _ in 0..50 {
                 // Try to read packets from the stream.
-                packet_reader.try_read_packets(&self.stream, &mut packets, &mut fds)?;
+                let limits = self.inner.lock().unwrap().limits();
+                packet_reader
+                    .inner
+                    .set_max_packet_length(limits.max_packet_length);
+                let read_result =
+                    packet_reader.try_read_packets(&self.stream, &mut packets, &mut fds);
                 let packet_count = packets.len();
 
                 // Now, actually enqueue the packets.
                 {
-                    let mut inner = self.inner.lock().unwrap();
-                    inner.enqueue_fds(mem::take(&mut fds));
+                    let mut stats = self.stats.lock().unwrap();
                     packets
-                        .drain(..)
-                        .for_each(|packet| inner.enqueue_packet(packet));
+                        .iter()
+                        .for_each(|packet| stats.record_packet(packet));
+                    drop(stats);
+
+                    let mut inner = self.inner.lock().unwrap();
+                    let enqueue_result =
+                        inner.try_enqueue_fds(mem::take(&mut fds)).and_then(|()| {
+                            packets
+                                .drain(..)
+                                .try_for_each(|packet| inner.try_enqueue_packet(packet))
+                        });
+                    drop(inner);
+
+                    // Remember an exceeded limit, so that everyone waiting for a packet gets an
+                    // error once this future is done.
+                    let result = read_result.and(enqueue_result.map_err(limit_exceeded_error));
+                    if let Err(err) = result {
+                        if let Some(limit) = err.get_ref().and_then(|e| e.downcast_ref()) {
+                            *self.limit_exceeded.lock().unwrap() = Some(*limit);
+                        }
+                        return Err(err.into());
+                    }
                 }
 
                 if packet_count > 0 {
@@ -164,7 +228,7 @@ impl PacketReader {
                     }
                     Ok(n) => {
                         tracing::trace!("Read {} bytes directly into large packet", n);
-                        if let Some(packet) = self.inner.advance(n) {
+                        if let Some(packet) = advance(&mut self.inner, n)? {
                             out_packets.push(packet);
                         }
                     }
@@ -199,7 +263,7 @@ impl PacketReader {
                     src = &src[amt_to_read..];
 
                     // advance by the given amount
-                    if let Some(packet) = self.inner.advance(amt_to_read) {
+                    if let Some(packet) = advance(&mut self.inner, amt_to_read)? {
                         out_packets.push(packet);
                     }
                 }
@@ -214,6 +278,16 @@ impl PacketReader {
     }
 }
 
+/// Advance the packet reader, turning an exceeded limit into an I/O error.
+fn advance(inner: &mut ProtoPacketReader, amount: usize) -> io::Result<Option<Vec<u8>>> {
+    inner.try_advance(amount).map_err(limit_exceeded_error)
+}
+
+/// Report an exceeded limit as an I/O error, see `From<io::Error> for ConnectionError`.
+fn limit_exceeded_error(limit: LimitExceeded) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, limit)
+}
+
 #[derive(Debug)]
 pub(super) struct BreakOnDrop<S>(pub(super) Arc<SharedState<S>>);
 
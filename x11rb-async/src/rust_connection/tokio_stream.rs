@@ -0,0 +1,240 @@
+//! A `Stream` implementation that uses the tokio reactor.
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::io;
+use std::os::unix::io::{AsFd, BorrowedFd};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use tokio::io::unix::AsyncFd;
+use tokio::io::Interest;
+use tokio::net::{TcpStream, UnixStream};
+
+use x11rb::errors::{ConnectError, ConnectionError, DisplayParsingError};
+use x11rb::rust_connection::{
+    DefaultStream as X11rbDefaultStream, PollMode, Stream as X11rbStream,
+};
+use x11rb::utils::RawFdContainer;
+use x11rb_protocol::parse_display::ConnectAddress;
+use x11rb_protocol::xauth::{get_auth_for_connection, Family, LocalAddress};
+
+use super::{RustConnection, StreamBase};
+
+/// The address of a peer in a format suitable for xauth.
+type PeerAddr = (Family, Vec<u8>);
+
+/// A stream that is driven by the tokio reactor.
+///
+/// This wraps x11rb's [`DefaultStream`](X11rbDefaultStream), so it supports the same kinds of
+/// connections, including file descriptor passing over Unix domain sockets. All functions that
+/// create a `TokioStream` must be called from within a tokio runtime.
+#[derive(Debug)]
+pub struct TokioStream {
+    inner: AsyncFd<X11rbDefaultStream>,
+}
+
+impl TokioStream {
+    /// Register an already connected stream with the tokio reactor.
+    pub fn new(stream: X11rbDefaultStream) -> io::Result<Self> {
+        let inner = AsyncFd::with_interest(stream, Interest::READABLE | Interest::WRITABLE)?;
+        Ok(Self { inner })
+    }
+
+    /// Try to connect to the X11 server described by the given address.
+    pub async fn connect(addr: &ConnectAddress<'_>) -> io::Result<(Self, PeerAddr)> {
+        match addr {
+            ConnectAddress::Hostname(host, port) => {
+                Self::from_tcp_stream(TcpStream::connect((*host, *port)).await?)
+            }
+            ConnectAddress::Socket(path) => {
+                Self::from_unix_stream(UnixStream::connect(path).await?)
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "The given address family is not implemented",
+            )),
+        }
+    }
+
+    /// Creates a new `TokioStream` from a connected tokio `TcpStream`.
+    ///
+    /// This returns the peer address in a format suitable for [`x11rb_protocol::xauth::get_auth`].
+    pub fn from_tcp_stream(stream: TcpStream) -> io::Result<(Self, PeerAddr)> {
+        let (stream, peer_addr) = X11rbDefaultStream::from_tcp_stream(stream.into_std()?)?;
+        Ok((Self::new(stream)?, peer_addr))
+    }
+
+    /// Creates a new `TokioStream` from a connected tokio `UnixStream`.
+    ///
+    /// This returns the peer address in a format suitable for [`x11rb_protocol::xauth::get_auth`].
+    pub fn from_unix_stream(stream: UnixStream) -> io::Result<(Self, PeerAddr)> {
+        let (stream, peer_addr) = X11rbDefaultStream::from_unix_stream(stream.into_std()?)?;
+        Ok((Self::new(stream)?, peer_addr))
+    }
+
+    /// Get the local end of this connection.
+    ///
+    /// This is in a format suitable for [`x11rb_protocol::xauth::get_auth_for_connection`].
+    pub fn local_address(&self) -> LocalAddress {
+        self.inner.get_ref().local_address()
+    }
+
+    fn poll_ready(&self, cx: &mut Context<'_>, mode: PollMode) -> Poll<io::Result<()>> {
+        loop {
+            let mut guard = if mode.readable() {
+                ready!(self.inner.poll_read_ready(cx))?
+            } else {
+                ready!(self.inner.poll_write_ready(cx))?
+            };
+            // tokio only forgets about readiness when told so. Reading and writing happens
+            // outside of the guard, so check whether the socket really is ready.
+            if is_ready(self.inner.get_ref().as_fd(), mode)? {
+                return Poll::Ready(Ok(()));
+            }
+            guard.clear_ready();
+        }
+    }
+}
+
+/// Check without blocking whether the given FD is readable or writable.
+fn is_ready(fd: BorrowedFd<'_>, mode: PollMode) -> io::Result<bool> {
+    use rustix::event::{poll, PollFd, PollFlags, Timespec};
+    use rustix::io::Errno;
+
+    let flags = if mode.readable() {
+        PollFlags::IN
+    } else {
+        PollFlags::OUT
+    };
+    let mut poll_fds = [PollFd::from_borrowed_fd(fd, flags)];
+    let timeout = Timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    loop {
+        match poll(&mut poll_fds, Some(&timeout)) {
+            // Errors and hangups also count; the following read or write reports them
+            Ok(count) => return Ok(count > 0),
+            Err(Errno::INTR) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// A future for waiting until a [`TokioStream`] is readable or writable.
+#[derive(Debug)]
+pub struct TokioReady<'a> {
+    stream: &'a TokioStream,
+    mode: PollMode,
+}
+
+impl Future for TokioReady<'_> {
+    type Output = io::Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.stream.poll_ready(cx, self.mode)
+    }
+}
+
+impl<'a> StreamBase<'a> for TokioStream {
+    type Readable = TokioReady<'a>;
+    type Writable = TokioReady<'a>;
+
+    fn readable(&'a self) -> Self::Readable {
+        TokioReady {
+            stream: self,
+            mode: PollMode::Readable,
+        }
+    }
+
+    fn writable(&'a self) -> Self::Writable {
+        TokioReady {
+            stream: self,
+            mode: PollMode::Writable,
+        }
+    }
+}
+
+impl X11rbStream for TokioStream {
+    fn poll(&self, mode: PollMode) -> io::Result<()> {
+        self.inner.get_ref().poll(mode)
+    }
+
+    fn poll_with_timeout(&self, mode: PollMode, timeout: Duration) -> io::Result<()> {
+        self.inner.get_ref().poll_with_timeout(mode, timeout)
+    }
+
+    fn read(&self, buf: &mut [u8], fd_storage: &mut Vec<RawFdContainer>) -> io::Result<usize> {
+        self.inner.get_ref().read(buf, fd_storage)
+    }
+
+    fn write(&self, buf: &[u8], fds: &mut Vec<RawFdContainer>) -> io::Result<usize> {
+        self.inner.get_ref().write(buf, fds)
+    }
+
+    fn write_vectored(
+        &self,
+        bufs: &[io::IoSlice<'_>],
+        fds: &mut Vec<RawFdContainer>,
+    ) -> io::Result<usize> {
+        self.inner.get_ref().write_vectored(bufs, fds)
+    }
+}
+
+impl RustConnection<TokioStream> {
+    /// Connect to the X11 server using the tokio reactor.
+    ///
+    /// This works like [`RustConnection::connect`], but does all I/O via tokio. It must be called
+    /// from within a tokio runtime. The returned future drives the packet reader for the
+    /// connection and should be spawned, e.g. via `tokio::spawn`.
+    pub async fn connect_tokio(
+        display_name: Option<&str>,
+    ) -> Result<
+        (
+            Self,
+            usize,
+            impl Future<Output = Result<Infallible, ConnectionError>> + Send,
+        ),
+        ConnectError,
+    > {
+        let addrs = x11rb_protocol::parse_display::parse_display(display_name)?;
+        let screen = addrs.screen.into();
+
+        let mut error = None;
+        for addr in addrs.connect_instruction() {
+            let (stream, (family, address)) = match TokioStream::connect(&addr).await {
+                Ok(result) => result,
+                Err(e) => {
+                    tracing::debug!("Failed to connect to X11 server via {:?}: {:?}", addr, e);
+                    error = Some(e);
+                    continue;
+                }
+            };
+            tracing::trace!("Connected to X11 server via {:?}", addr);
+
+            // Use this to get authority information.
+            let local_address = stream.local_address();
+            let display = addrs.display;
+            let (auth_name, auth_data) = tokio::task::spawn_blocking(move || {
+                get_auth_for_connection(family, &address, display, local_address)
+                    .unwrap_or(None)
+                    .unwrap_or_else(|| (Vec::new(), Vec::new()))
+            })
+            .await
+            .map_err(io::Error::from)?;
+            tracing::trace!("Picked authentication via auth mechanism {:?}", auth_name);
+
+            let (conn, drive) =
+                Self::connect_to_stream_with_auth_info(stream, screen, auth_name, auth_data)
+                    .await?;
+            return Ok((conn, screen, drive));
+        }
+
+        Err(match error {
+            Some(e) => ConnectError::IoError(e),
+            None => DisplayParsingError::Unknown.into(),
+        })
+    }
+}
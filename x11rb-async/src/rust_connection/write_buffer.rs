@@ -78,13 +78,18 @@ impl std::ops::DerefMut for WriteBufferGuard<'_> {
 }
 
 impl WriteBufferInner {
+    /// Check whether there is data or FDs that need to be flushed.
+    pub(super) fn needs_flush(&self) -> bool {
+        !self.buffer.is_empty() || !self.fds.is_empty()
+    }
+
     /// Flush the write buffer.
     pub(super) async fn flush<'b, S: StreamBase<'b>>(
         &mut self,
         stream: &'b S,
     ) -> Result<(), ConnectionError> {
         // If we don't have any data to write, we are done.
-        if self.buffer.is_empty() && self.fds.is_empty() {
+        if !self.needs_flush() {
             return Ok(());
         }
 
@@ -1,13 +1,13 @@
-use futures_lite::future::Ready;
+use futures_lite::future::{ready, Ready};
 use std::io::IoSlice;
 use std::sync::{Arc, Mutex};
 
-use x11rb::errors::ConnectionError;
-use x11rb::protocol::xproto::Setup;
+use x11rb::errors::{ConnectionError, LimitExceeded};
+use x11rb::protocol::xproto::{MapNotifyEvent, Setup};
 use x11rb::rust_connection::{PollMode, Stream as SyncStream};
 use x11rb::utils::RawFdContainer;
 use x11rb_async::connection::{Connection, RequestConnection};
-use x11rb_async::rust_connection::{RustConnection, Stream as AsyncStream, StreamBase};
+use x11rb_async::rust_connection::{Limits, RustConnection, Stream as AsyncStream, StreamBase};
 
 #[derive(Debug, Default)]
 struct FakeStream(Arc<Mutex<Vec<u8>>>);
@@ -116,3 +116,68 @@ fn retry_for_left_over_fds() {
         e => panic!("Unexpected error: {e:?}"),
     }
 }
+
+/// A stream where the server sends some data once.
+#[derive(Debug, Default)]
+struct ServerDataStream(Mutex<Vec<u8>>);
+
+impl SyncStream for ServerDataStream {
+    fn poll(&self, _: PollMode) -> Result<(), std::io::Error> {
+        unimplemented!()
+    }
+    fn read(&self, buf: &mut [u8], _: &mut Vec<RawFdContainer>) -> Result<usize, std::io::Error> {
+        let mut data = self.0.lock().unwrap();
+        if data.is_empty() {
+            return Err(std::io::ErrorKind::WouldBlock.into());
+        }
+        let length = data.len().min(buf.len());
+        buf[..length].copy_from_slice(&data[..length]);
+        let _ = data.drain(..length);
+        Ok(length)
+    }
+    fn write(&self, buf: &[u8], _: &mut Vec<RawFdContainer>) -> Result<usize, std::io::Error> {
+        Ok(buf.len())
+    }
+}
+
+impl StreamBase<'_> for ServerDataStream {
+    type Readable = Ready<std::io::Result<()>>;
+    type Writable = Ready<std::io::Result<()>>;
+
+    fn readable(&self) -> Self::Readable {
+        ready(Ok(()))
+    }
+
+    fn writable(&self) -> Self::Writable {
+        ready(Ok(()))
+    }
+}
+
+#[test]
+fn connection_breaks_on_exceeded_limit() {
+    let event: [u8; 32] = MapNotifyEvent {
+        response_type: 19,
+        ..Default::default()
+    }
+    .into();
+    let stream = ServerDataStream(Mutex::new([event; 3].concat()));
+    let (conn, driver) = RustConnection::for_connected_stream(stream, make_setup()).unwrap();
+    let mut limits = Limits::default();
+    limits.max_queued_events = 2;
+    conn.set_limits(limits);
+
+    // Only two of the three events fit into the queue
+    match async_io::block_on(driver) {
+        Err(ConnectionError::LimitExceeded(LimitExceeded::QueuedEvents)) => {}
+        result => panic!("Unexpected result {result:?}"),
+    }
+
+    // The queued events can still be fetched, but afterwards the connection is broken
+    for _ in 0..2 {
+        assert!(async_io::block_on(conn.wait_for_event()).is_ok());
+    }
+    match async_io::block_on(conn.wait_for_event()) {
+        Err(ConnectionError::LimitExceeded(LimitExceeded::QueuedEvents)) => {}
+        result => panic!("Unexpected result {result:?}"),
+    }
+}
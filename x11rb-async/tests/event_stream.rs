@@ -0,0 +1,91 @@
+#![cfg(unix)]
+
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+use std::sync::Arc;
+
+use futures_lite::{future, StreamExt};
+
+use x11rb::protocol::xproto::{ClientMessageEvent, MapNotifyEvent, Screen, Setup};
+use x11rb::rust_connection::DefaultStream as X11rbDefaultStream;
+use x11rb_async::connection::{Connection, EventStream};
+use x11rb_async::protocol::Event;
+use x11rb_async::rust_connection::{RustConnection, StreamAdaptor};
+
+fn connection(
+    stream: UnixStream,
+) -> (
+    Arc<RustConnection>,
+    std::thread::JoinHandle<x11rb::errors::ConnectionError>,
+) {
+    let (stream, _) = X11rbDefaultStream::from_unix_stream(stream).unwrap();
+    let setup = Setup {
+        resource_id_mask: 0xff,
+        roots: vec![Screen::default()],
+        ..Default::default()
+    };
+    let (conn, drive) =
+        RustConnection::for_connected_stream(StreamAdaptor::new(stream).unwrap(), setup).unwrap();
+    let drive = std::thread::spawn(move || match async_io::block_on(drive) {
+        Ok(never) => match never {},
+        Err(err) => err,
+    });
+    (Arc::new(conn), drive)
+}
+
+#[test]
+fn filtered_events() {
+    let (client, mut server) = UnixStream::pair().unwrap();
+    let (conn, drive) = connection(client);
+
+    async_io::block_on(async {
+        let mut events = EventStream::new(Arc::clone(&conn))
+            .with_filter(|event| matches!(event, Event::ClientMessage(_)));
+        assert!(future::poll_once(events.next()).await.is_none());
+
+        let map_notify: [u8; 32] = MapNotifyEvent {
+            response_type: 19,
+            window: 1,
+            ..Default::default()
+        }
+        .into();
+        let client_message: [u8; 32] = ClientMessageEvent::new(32, 2, 3u32, [4, 5, 6, 7, 8]).into();
+        server.write_all(&map_notify).unwrap();
+        server.write_all(&client_message).unwrap();
+
+        match events.next().await {
+            Some(Ok(Event::ClientMessage(event))) => assert_eq!(event.window, 2),
+            event => panic!("Unexpected event {:?}", event),
+        }
+
+        // Closing the connection produces an error and ends the stream
+        drop(server);
+        assert!(matches!(events.next().await, Some(Err(_))));
+        assert!(events.next().await.is_none());
+    });
+    let _ = drive.join().unwrap();
+}
+
+#[test]
+fn borrowed_events() {
+    let (client, mut server) = UnixStream::pair().unwrap();
+    let (conn, drive) = connection(client);
+
+    let map_notify: [u8; 32] = MapNotifyEvent {
+        response_type: 19,
+        window: 42,
+        ..Default::default()
+    }
+    .into();
+    server.write_all(&map_notify).unwrap();
+
+    async_io::block_on(async {
+        let mut events = conn.events();
+        match events.next().await {
+            Some(Ok(Event::MapNotify(event))) => assert_eq!(event.window, 42),
+            event => panic!("Unexpected event {:?}", event),
+        }
+    });
+    drop(server);
+    let _ = drive.join().unwrap();
+}
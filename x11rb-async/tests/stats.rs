@@ -0,0 +1,62 @@
+#![cfg(unix)]
+
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+
+use futures_lite::future;
+
+use x11rb::protocol::xproto::{MapNotifyEvent, Screen, Setup};
+use x11rb::rust_connection::DefaultStream as X11rbDefaultStream;
+use x11rb_async::connection::Connection;
+use x11rb_async::protocol::xproto::ConnectionExt;
+use x11rb_async::rust_connection::{RustConnection, StreamAdaptor};
+
+#[test]
+fn count_traffic() {
+    let (client, mut server) = UnixStream::pair().unwrap();
+    let (stream, _) = X11rbDefaultStream::from_unix_stream(client).unwrap();
+    let setup = Setup {
+        resource_id_mask: 0xff,
+        roots: vec![Screen::default()],
+        ..Default::default()
+    };
+    let (conn, drive) =
+        RustConnection::for_connected_stream(StreamAdaptor::new(stream).unwrap(), setup).unwrap();
+    let drive = std::thread::spawn(move || async_io::block_on(drive));
+
+    async_io::block_on(async {
+        let cookie = conn.get_input_focus().await.unwrap();
+        let mut reply = Box::pin(cookie.reply());
+        // The reply is only sent once the connection waits for it
+        assert!(future::poll_once(&mut reply).await.is_none());
+        let mut request = [0; 4];
+        server.read_exact(&mut request).unwrap();
+        let mut packet = [0; 32];
+        packet[0] = 1;
+        packet[2..4].copy_from_slice(&1u16.to_ne_bytes());
+        server.write_all(&packet).unwrap();
+        let _ = reply.await.unwrap();
+
+        // Waiting for events is not a round trip
+        let event: [u8; 32] = MapNotifyEvent {
+            response_type: 19,
+            ..Default::default()
+        }
+        .into();
+        server.write_all(&event).unwrap();
+        let _ = conn.wait_for_event().await.unwrap();
+    });
+
+    let stats = conn.stats();
+    assert_eq!((stats.requests, stats.bytes_sent), (1, 4));
+    assert_eq!((stats.replies, stats.errors, stats.events), (1, 0, 1));
+    assert_eq!(stats.bytes_received, 64);
+    assert_eq!(stats.flushes, 1);
+    assert_eq!(stats.round_trips, 1);
+
+    assert_eq!(conn.reset_stats(), stats);
+    assert_eq!(conn.stats().round_trips, 0);
+
+    drop(server);
+    assert!(drive.join().unwrap().is_err());
+}
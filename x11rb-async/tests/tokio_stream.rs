@@ -0,0 +1,96 @@
+#![cfg(all(unix, feature = "tokio"))]
+
+use std::fs::File;
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::net::UnixStream;
+
+use futures_lite::future;
+
+use x11rb::protocol::xproto::{Screen, Setup};
+use x11rb::rust_connection::{DefaultStream as X11rbDefaultStream, Stream as _};
+use x11rb::x11_utils::Serialize;
+use x11rb_async::protocol::xproto::ConnectionExt as _;
+use x11rb_async::rust_connection::{RustConnection, StreamBase as _, TokioStream};
+
+fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .build()
+        .unwrap()
+}
+
+fn tokio_stream(stream: UnixStream) -> TokioStream {
+    let (stream, _) = X11rbDefaultStream::from_unix_stream(stream).unwrap();
+    TokioStream::new(stream).unwrap()
+}
+
+#[test]
+fn read_write_with_fds() {
+    runtime().block_on(async {
+        let (a, b) = tokio::net::UnixStream::pair().unwrap();
+        let (a, _) = TokioStream::from_unix_stream(a).unwrap();
+        let (b, _) = TokioStream::from_unix_stream(b).unwrap();
+
+        let (mut buf, mut fds) = ([0; 4], Vec::new());
+        let err = b.read(&mut buf, &mut fds).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WouldBlock);
+        assert!(future::poll_once(b.readable()).await.is_none());
+
+        a.writable().await.unwrap();
+        let fd = File::open("/dev/null").unwrap().into();
+        assert_eq!(a.write(b"abcd", &mut vec![fd]).unwrap(), 4);
+
+        b.readable().await.unwrap();
+        assert_eq!(b.read(&mut buf, &mut fds).unwrap(), 4);
+        assert_eq!((&buf, fds.len()), (b"abcd", 1));
+
+        // After everything was read, the stream is no longer readable
+        let err = b.read(&mut buf, &mut fds).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WouldBlock);
+        assert!(future::poll_once(b.readable()).await.is_none());
+    });
+}
+
+#[test]
+fn connection_roundtrip() {
+    let (client, mut server) = UnixStream::pair().unwrap();
+
+    let server = std::thread::spawn(move || {
+        // Setup request without authentication
+        let mut request = [0; 12];
+        server.read_exact(&mut request).unwrap();
+        let mut setup = Setup {
+            status: 1,
+            protocol_major_version: 11,
+            resource_id_mask: 0xff,
+            roots: vec![Screen::default()],
+            ..Default::default()
+        }
+        .serialize();
+        let length = u16::try_from((setup.len() - 8) / 4).unwrap();
+        setup[6..8].copy_from_slice(&length.to_ne_bytes());
+        server.write_all(&setup).unwrap();
+
+        // GetInputFocus
+        let mut request = [0; 4];
+        server.read_exact(&mut request).unwrap();
+        let mut reply = [0; 32];
+        reply[0] = 1;
+        reply[2..4].copy_from_slice(&1u16.to_ne_bytes());
+        server.write_all(&reply).unwrap();
+
+        // Keep the connection open until the client is done
+        assert_eq!(server.read(&mut request).unwrap(), 0);
+    });
+
+    runtime().block_on(async {
+        let (conn, drive) = RustConnection::connect_to_stream(tokio_stream(client), 0)
+            .await
+            .unwrap();
+        let _drive = tokio::spawn(drive);
+        let reply = conn.get_input_focus().await.unwrap().reply().await.unwrap();
+        assert_eq!(reply.sequence, 1);
+        drop(conn);
+    });
+    server.join().unwrap();
+}
@@ -5,7 +5,7 @@ use crate::protocol::xproto::{Setup, SetupAuthenticate, SetupFailed, SetupReques
 use crate::x11_utils::{Serialize, TryParse};
 
 #[cfg(feature = "std")]
-use crate::xauth::{get_auth, Family};
+use crate::xauth::{get_auth, get_auth_for_connection, Family, LocalAddress};
 
 use alloc::{vec, vec::Vec};
 
@@ -168,6 +168,26 @@ impl Connect {
         }
     }
 
+    /// Create a new `Connect` from the information necessary to connect to the X11 server and
+    /// the local end of the connection.
+    ///
+    /// Compared to [`Self::new`], this also supports `XDM-AUTHORIZATION-1`, which needs the
+    /// local address. See [`get_auth_for_connection`] for details.
+    ///
+    /// This returns the connection handshake object as well as the setup request to send to the server.
+    #[cfg(feature = "std")]
+    pub fn with_local_address(
+        family: Family,
+        address: &[u8],
+        display: u16,
+        local: LocalAddress,
+    ) -> Result<(Self, Vec<u8>), ConnectError> {
+        let (name, data) = get_auth_for_connection(family, address, display, local)?
+            // fall through to no authorization
+            .unwrap_or_default();
+        Ok(Self::with_authorization(name, data))
+    }
+
     /// Returns the buffer that needs to be filled with incoming data from the server.
     ///
     /// After filling this buffer (using a method like `Read::read`), call [`Self::advance`] with
@@ -3,6 +3,7 @@
 use alloc::collections::VecDeque;
 use alloc::vec::Vec;
 
+use crate::errors::LimitExceeded;
 use crate::utils::RawFdContainer;
 use crate::{DiscardMode, SequenceNumber};
 
@@ -34,6 +35,38 @@ pub enum PollReply {
     Reply(Vec<u8>),
 }
 
+/// Limits on the data that a [`Connection`] accepts from the X11 server.
+///
+/// A misbehaving X11 server could otherwise cause the client to run out of memory. By default,
+/// there are no limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Limits {
+    /// The maximum length of a packet in bytes.
+    ///
+    /// This is not enforced by [`Connection`] itself, but by the I/O backend that reads packets,
+    /// for example with [`crate::packet_reader::PacketReader::set_max_packet_length`].
+    pub max_packet_length: usize,
+    /// The maximum number of events that can be queued, including events in special event
+    /// queues.
+    pub max_queued_events: usize,
+    /// The maximum number of replies and errors that can be queued.
+    pub max_pending_replies: usize,
+    /// The maximum number of received file descriptors that can be queued.
+    pub max_pending_fds: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_packet_length: usize::MAX,
+            max_queued_events: usize::MAX,
+            max_pending_replies: usize::MAX,
+            max_pending_fds: usize::MAX,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 struct SentRequest {
     seqno: SequenceNumber,
@@ -59,11 +92,15 @@ pub struct Connection {
     last_sequence_read: SequenceNumber,
     // Events that were read, but not yet returned to the API user
     pending_events: VecDeque<(SequenceNumber, Vec<u8>)>,
+    // Queues for generic events that should not end up in `pending_events`
+    special_events: Vec<SpecialEventQueue>,
     // Replies that were read, but not yet returned to the API user
     pending_replies: VecDeque<(SequenceNumber, BufWithFds)>,
 
     // FDs that were read, but not yet assigned to any reply
     pending_fds: VecDeque<RawFdContainer>,
+    // Limits on the above queues
+    limits: Limits,
 }
 
 impl Default for Connection {
@@ -84,11 +121,26 @@ impl Connection {
             last_sequence_read: 0,
             sent_requests: VecDeque::new(),
             pending_events: VecDeque::new(),
+            special_events: Vec::new(),
             pending_replies: VecDeque::new(),
             pending_fds: VecDeque::new(),
+            limits: Limits::default(),
         }
     }
 
+    /// Get the limits on the data that is accepted from the X11 server.
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    /// Set the limits on the data that is accepted from the X11 server.
+    ///
+    /// The new limits only apply to data that is enqueued afterwards. Nothing that is already
+    /// queued is discarded.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
     /// Send a request to the X11 server.
     ///
     /// When this returns `None`, a sync with the server is necessary. Afterwards, the caller
@@ -183,14 +235,53 @@ impl Connection {
     /// Add FDs that were received to the internal state.
     ///
     /// This must be called before the corresponding packets are enqueued.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this exceeds [`Limits::max_pending_fds`]. Use [`Connection::try_enqueue_fds`]
+    /// to handle this case.
     pub fn enqueue_fds(&mut self, fds: Vec<RawFdContainer>) {
+        self.try_enqueue_fds(fds)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Add FDs that were received to the internal state.
+    ///
+    /// This must be called before the corresponding packets are enqueued. If this would exceed
+    /// [`Limits::max_pending_fds`], the FDs are closed and an error is returned.
+    pub fn try_enqueue_fds(&mut self, fds: Vec<RawFdContainer>) -> Result<(), LimitExceeded> {
+        if fds.len()
+            > self
+                .limits
+                .max_pending_fds
+                .saturating_sub(self.pending_fds.len())
+        {
+            return Err(LimitExceeded::PendingFds);
+        }
         self.pending_fds.extend(fds);
+        Ok(())
     }
 
     /// An X11 packet was received from the connection and is now enqueued into our state.
     ///
     /// Any FDs that were received must already be enqueued before this can be called.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this exceeds [`Limits::max_queued_events`] or [`Limits::max_pending_replies`].
+    /// Use [`Connection::try_enqueue_packet`] to handle this case.
     pub fn enqueue_packet(&mut self, packet: Vec<u8>) {
+        self.try_enqueue_packet(packet)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// An X11 packet was received from the connection and is now enqueued into our state.
+    ///
+    /// Any FDs that were received must already be enqueued before this can be called. If
+    /// enqueuing the packet would exceed [`Limits::max_queued_events`] or
+    /// [`Limits::max_pending_replies`], the packet is dropped and an error is returned. Since
+    /// the packet is lost, the connection should not be used anymore in this case.
+    pub fn try_enqueue_packet(&mut self, packet: Vec<u8>) -> Result<(), LimitExceeded> {
         let kind = packet[0];
 
         // extract_sequence_number() updates our state and is thus important to call even when we
@@ -214,16 +305,12 @@ impl Connection {
                 match request.discard_mode {
                     Some(DiscardMode::DiscardReplyAndError) => { /* This error should be ignored */
                     }
-                    Some(DiscardMode::DiscardReply) => {
-                        self.pending_events.push_back((seqno, packet))
-                    }
-                    None => self
-                        .pending_replies
-                        .push_back((seqno, (packet, Vec::new()))),
+                    Some(DiscardMode::DiscardReply) => self.push_event(seqno, packet)?,
+                    None => self.push_reply(seqno, (packet, Vec::new()))?,
                 }
             } else {
                 // Unexpected error, send to main loop
-                self.pending_events.push_back((seqno, packet));
+                self.push_event(seqno, packet)?;
             }
         } else if kind == 1 {
             let fds = if request.filter(|r| r.has_fds).is_some() {
@@ -246,12 +333,48 @@ impl Connection {
             if request.filter(|r| r.discard_mode.is_some()).is_some() {
                 // This reply should be discarded
             } else {
-                self.pending_replies.push_back((seqno, (packet, fds)));
+                self.push_reply(seqno, (packet, fds))?;
             }
         } else {
             // It is an event
-            self.pending_events.push_back((seqno, packet));
+            self.push_event(seqno, packet)?;
+        }
+        Ok(())
+    }
+
+    // Add an event to the right queue, if the limit on queued events allows this
+    fn push_event(&mut self, seqno: SequenceNumber, packet: Vec<u8>) -> Result<(), LimitExceeded> {
+        let queued = self.pending_events.len()
+            + self
+                .special_events
+                .iter()
+                .map(|queue| queue.events.len())
+                .sum::<usize>();
+        if queued >= self.limits.max_queued_events {
+            return Err(LimitExceeded::QueuedEvents);
+        }
+        let queue = self
+            .special_events
+            .iter_mut()
+            .find(|queue| queue.matches(&packet));
+        match queue {
+            Some(queue) => queue.events.push_back((seqno, packet)),
+            None => self.pending_events.push_back((seqno, packet)),
+        }
+        Ok(())
+    }
+
+    // Add a reply or an error to the queue, if the limit on pending replies allows this
+    fn push_reply(
+        &mut self,
+        seqno: SequenceNumber,
+        reply: BufWithFds,
+    ) -> Result<(), LimitExceeded> {
+        if self.pending_replies.len() >= self.limits.max_pending_replies {
+            return Err(LimitExceeded::PendingReplies);
         }
+        self.pending_replies.push_back((seqno, reply));
+        Ok(())
     }
 
     /// Check if the server already sent an answer to the request with the given sequence number.
@@ -324,11 +447,96 @@ impl Connection {
             .pop_front()
             .map(|(seqno, event)| (event, seqno))
     }
+
+    /// Send generic events of an extension with the given event ID (EID) to a separate queue.
+    ///
+    /// `extension` is the major opcode of the extension. Events whose response type is
+    /// `GE_GENERIC_EVENT`, whose extension matches and whose event ID field (the 32 bit value at
+    /// offset 12) is `eid` are no longer returned by `poll_for_event_with_sequence()`, but by
+    /// `poll_for_special_event_with_sequence()`. This is the equivalent of libxcb's
+    /// `xcb_register_for_special_xge()`.
+    ///
+    /// Returns `false` if a queue for this combination is already registered.
+    pub fn register_for_special_event(&mut self, extension: u8, eid: u32) -> bool {
+        if self.special_event_queue(extension, eid).is_some() {
+            return false;
+        }
+        self.special_events.push(SpecialEventQueue {
+            extension,
+            eid,
+            events: VecDeque::new(),
+        });
+        true
+    }
+
+    /// Remove a queue that was registered with `register_for_special_event()`.
+    ///
+    /// Events that are still in the queue are discarded.
+    pub fn unregister_for_special_event(&mut self, extension: u8, eid: u32) {
+        self.special_events
+            .retain(|queue| (queue.extension, queue.eid) != (extension, eid));
+    }
+
+    /// Get a pending event from the queue that was registered with `register_for_special_event()`.
+    pub fn poll_for_special_event_with_sequence(
+        &mut self,
+        extension: u8,
+        eid: u32,
+    ) -> Option<RawEventAndSeqNumber> {
+        self.special_event_queue(extension, eid)?
+            .events
+            .pop_front()
+            .map(|(seqno, event)| (event, seqno))
+    }
+
+    fn special_event_queue(&mut self, extension: u8, eid: u32) -> Option<&mut SpecialEventQueue> {
+        self.special_events
+            .iter_mut()
+            .find(|queue| (queue.extension, queue.eid) == (extension, eid))
+    }
+
+    /// Get the first pending event for which `predicate` returns `true`.
+    ///
+    /// All other pending events stay in the queue in their original order.
+    pub fn poll_for_event_with_sequence_matching(
+        &mut self,
+        mut predicate: impl FnMut(&[u8]) -> bool,
+    ) -> Option<RawEventAndSeqNumber> {
+        let index = self
+            .pending_events
+            .iter()
+            .position(|(_, event)| predicate(event))?;
+        self.pending_events
+            .remove(index)
+            .map(|(seqno, event)| (event, seqno))
+    }
+}
+
+/// A queue for generic events, see `Connection::register_for_special_event()`.
+#[derive(Debug)]
+struct SpecialEventQueue {
+    extension: u8,
+    eid: u32,
+    events: VecDeque<(SequenceNumber, Vec<u8>)>,
+}
+
+impl SpecialEventQueue {
+    fn matches(&self, packet: &[u8]) -> bool {
+        use crate::protocol::xproto::GE_GENERIC_EVENT;
+
+        packet.len() >= 16
+            && packet[0] & 0x7f == GE_GENERIC_EVENT
+            && packet[1] == self.extension
+            && packet[12..16] == self.eid.to_ne_bytes()
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{Connection, ReplyFdKind};
+    use alloc::{vec, vec::Vec};
+
+    use super::{Connection, Limits, ReplyFdKind};
+    use crate::errors::LimitExceeded;
 
     #[test]
     fn insert_sync_no_reply() {
@@ -441,4 +649,101 @@ mod test {
         // Now check that the sequence number for the last packet was reconstructed correctly.
         assert!(connection.poll_for_reply_or_error(second_reply).is_some());
     }
+
+    #[test]
+    fn special_events() {
+        fn generic_event(extension: u8, eid: u32) -> Vec<u8> {
+            let mut packet = vec![0; 32];
+            packet[0] = 35;
+            packet[1] = extension;
+            packet[12..16].copy_from_slice(&eid.to_ne_bytes());
+            packet
+        }
+
+        let mut connection = Connection::new();
+        assert!(connection.register_for_special_event(42, 7));
+        assert!(!connection.register_for_special_event(42, 7));
+
+        connection.enqueue_packet(generic_event(42, 7));
+        connection.enqueue_packet(generic_event(42, 8));
+        connection.enqueue_packet(generic_event(43, 7));
+
+        let special = connection.poll_for_special_event_with_sequence(42, 7);
+        assert_eq!(special.map(|(event, _)| event), Some(generic_event(42, 7)));
+        assert!(connection
+            .poll_for_special_event_with_sequence(42, 7)
+            .is_none());
+        let normal = core::iter::from_fn(|| connection.poll_for_event_with_sequence())
+            .map(|(event, _)| event)
+            .collect::<Vec<_>>();
+        assert_eq!(normal, [generic_event(42, 8), generic_event(43, 7)]);
+
+        // After unregistering, events go to the normal queue again
+        connection.enqueue_packet(generic_event(42, 7));
+        connection.unregister_for_special_event(42, 7);
+        assert!(connection
+            .poll_for_special_event_with_sequence(42, 7)
+            .is_none());
+        connection.enqueue_packet(generic_event(42, 7));
+        assert!(connection.poll_for_event_with_sequence().is_some());
+    }
+
+    #[test]
+    fn poll_for_event_matching() {
+        let mut connection = Connection::new();
+        for response_type in [12, 22, 12, 6] {
+            let mut packet = vec![0; 32];
+            packet[0] = response_type;
+            connection.enqueue_packet(packet);
+        }
+
+        // Take the ConfigureNotify (22) out of the middle of the queue
+        let event = connection.poll_for_event_with_sequence_matching(|event| event[0] == 22);
+        assert_eq!(event.map(|(event, _)| event[0]), Some(22));
+        let event = connection.poll_for_event_with_sequence_matching(|event| event[0] == 22);
+        assert!(event.is_none());
+
+        // The remaining events are still in order
+        let remaining = core::iter::from_fn(|| connection.poll_for_event_with_sequence())
+            .map(|(event, _)| event[0])
+            .collect::<Vec<_>>();
+        assert_eq!(remaining, [12, 12, 6]);
+    }
+
+    #[test]
+    fn limits() {
+        let mut connection = Connection::new();
+        connection.set_limits(Limits {
+            max_queued_events: 2,
+            max_pending_replies: 1,
+            ..Limits::default()
+        });
+        assert_eq!(connection.limits().max_queued_events, 2);
+
+        let event = vec![12; 32];
+        assert_eq!(connection.try_enqueue_packet(event.clone()), Ok(()));
+        assert_eq!(connection.try_enqueue_packet(event.clone()), Ok(()));
+        assert_eq!(
+            connection.try_enqueue_packet(event.clone()),
+            Err(LimitExceeded::QueuedEvents)
+        );
+        let _ = connection.poll_for_event_with_sequence().unwrap();
+        assert_eq!(connection.try_enqueue_packet(event), Ok(()));
+
+        for seqno in [1, 2] {
+            assert_eq!(
+                connection.send_request(ReplyFdKind::ReplyWithoutFDs),
+                Some(seqno)
+            );
+        }
+        let mut reply = vec![0; 32];
+        reply[0] = 1;
+        reply[2] = 1;
+        assert_eq!(connection.try_enqueue_packet(reply.clone()), Ok(()));
+        reply[2] = 2;
+        assert_eq!(
+            connection.try_enqueue_packet(reply),
+            Err(LimitExceeded::PendingReplies)
+        );
+    }
 }
@@ -0,0 +1,800 @@
+//! A sans-I/O implementation of everything that an X11 client connection needs.
+//!
+//! [`crate::connection::Connection`] only keeps track of sent requests and received packets.
+//! [`ProtocolDriver`] combines it with a [`PacketReader`], an [`IdAllocator`] and a write buffer.
+//! It also takes care of querying extensions, of the BIG-REQUESTS extension for large requests
+//! and of fetching new X11 IDs via the XC-MISC extension. This allows to drive an X11 connection
+//! from any kind of event loop.
+//!
+//! The driver never does any I/O itself. Instead, the I/O backend has to:
+//!
+//! - Write [`ProtocolDriver::pending_output`] and [`ProtocolDriver::take_pending_fds`] to the X11
+//!   server and report the written bytes via [`ProtocolDriver::advance_output`].
+//! - Pass everything that was read from the X11 server to [`ProtocolDriver::receive`] and
+//!   [`ProtocolDriver::receive_fds`].
+//!
+//! Some operations need a reply from the X11 server that did not arrive yet. Since the driver
+//! cannot wait for it, these operations queue the necessary request and return
+//! [`DriverError::WouldBlock`]. They should be retried once more data was received.
+//!
+//! # Example
+//!
+//! ```
+//! use x11rb_protocol::driver::ProtocolDriver;
+//! use x11rb_protocol::protocol::xproto::{GetInputFocusRequest, Setup};
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let setup = Setup {
+//! #     resource_id_mask: 0xff,
+//! #     maximum_request_length: u16::MAX,
+//! #     ..Default::default()
+//! # };
+//! // The setup is the result of the handshake, see x11rb_protocol::connect::Connect
+//! let mut driver = ProtocolDriver::new(setup)?;
+//! let pending = driver.send_request_with_reply(GetInputFocusRequest)?;
+//!
+//! // Write the pending output to the X11 server...
+//! let written = driver.pending_output().len();
+//! driver.advance_output(written);
+//!
+//! // ...and give everything that is read from the X11 server to the driver
+//! # let mut received = [0; 32];
+//! # received[0] = 1;
+//! # received[2..4].copy_from_slice(&1u16.to_ne_bytes());
+//! driver.receive(&received)?;
+//! if let Some(reply) = driver.poll_for_reply(pending) {
+//!     println!("The input focus is {:?}", reply?.focus);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::connection::{Connection, Limits, ReplyFdKind};
+use crate::errors::{ConnectError, DriverError, LimitExceeded, ParseError};
+use crate::id_allocator::IdAllocator;
+use crate::packet_reader::PacketReader;
+use crate::protocol::xproto::{
+    QueryExtensionReply, QueryExtensionRequest, Setup, GET_INPUT_FOCUS_REQUEST,
+};
+use crate::protocol::{bigreq, xc_misc, Event};
+use crate::utils::RawFdContainer;
+use crate::x11_utils::{
+    ExtInfoProvider, ExtensionInformation, ReplyFDsRequest, ReplyRequest, Request, TryParse,
+    TryParseFd, VoidRequest, X11Error,
+};
+use crate::{BufWithFds, DiscardMode, RawEventAndSeqNumber, SequenceNumber};
+
+/// A request with a reply that was sent by a [`ProtocolDriver`].
+///
+/// This is used to get the reply via [`ProtocolDriver::poll_for_reply`] or
+/// [`ProtocolDriver::poll_for_reply_with_fds`].
+pub struct PendingReply<R> {
+    sequence: SequenceNumber,
+    phantom: PhantomData<fn() -> R>,
+}
+
+impl<R> PendingReply<R> {
+    fn new(sequence: SequenceNumber) -> Self {
+        Self {
+            sequence,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Get the sequence number of the request.
+    pub fn sequence_number(&self) -> SequenceNumber {
+        self.sequence
+    }
+}
+
+impl<R> Clone for PendingReply<R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R> Copy for PendingReply<R> {}
+
+impl<R> fmt::Debug for PendingReply<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PendingReply")
+            .field("sequence", &self.sequence)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ExtensionState {
+    // A QueryExtension request with this sequence number was sent
+    Pending(SequenceNumber),
+    Present(ExtensionInformation),
+    Missing,
+}
+
+#[derive(Debug, Default)]
+struct Extensions(BTreeMap<&'static str, ExtensionState>);
+
+impl Extensions {
+    fn present(&self) -> impl Iterator<Item = (&str, ExtensionInformation)> {
+        self.0.iter().filter_map(|(name, state)| match state {
+            ExtensionState::Present(info) => Some((*name, *info)),
+            _ => None,
+        })
+    }
+}
+
+impl ExtInfoProvider for Extensions {
+    fn get_from_major_opcode(&self, major_opcode: u8) -> Option<(&str, ExtensionInformation)> {
+        self.present()
+            .find(|(_, info)| info.major_opcode == major_opcode)
+    }
+
+    fn get_from_event_code(&self, event_code: u8) -> Option<(&str, ExtensionInformation)> {
+        self.present()
+            .filter(|(_, info)| info.first_event <= event_code)
+            .max_by_key(|(_, info)| info.first_event)
+    }
+
+    fn get_from_error_code(&self, error_code: u8) -> Option<(&str, ExtensionInformation)> {
+        self.present()
+            .filter(|(_, info)| info.first_error <= error_code)
+            .max_by_key(|(_, info)| info.first_error)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum MaxRequestBytes {
+    Unknown,
+    // A BIG-REQUESTS Enable request with this sequence number was sent
+    Requested(SequenceNumber),
+    Known(usize),
+}
+
+/// A sans-I/O X11 client connection.
+///
+/// See the [module documentation](self) for an overview.
+#[derive(Debug)]
+pub struct ProtocolDriver {
+    setup: Setup,
+    connection: Connection,
+    packet_reader: PacketReader,
+    id_allocator: IdAllocator,
+    extensions: Extensions,
+    max_request_bytes: MaxRequestBytes,
+    // A XC-MISC GetXIDRange request with this sequence number was sent
+    xid_range_request: Option<SequenceNumber>,
+    output: Vec<u8>,
+    output_fds: Vec<RawFdContainer>,
+}
+
+impl ProtocolDriver {
+    /// Create a new driver for a connection that completed the setup handshake.
+    ///
+    /// The setup is the result of the handshake, for example from
+    /// [`crate::connect::Connect::into_setup`].
+    pub fn new(setup: Setup) -> Result<Self, ConnectError> {
+        let id_allocator = IdAllocator::new(setup.resource_id_base, setup.resource_id_mask)?;
+        Ok(Self {
+            setup,
+            connection: Connection::new(),
+            packet_reader: PacketReader::new(),
+            id_allocator,
+            extensions: Default::default(),
+            max_request_bytes: MaxRequestBytes::Unknown,
+            xid_range_request: None,
+            output: Vec::new(),
+            output_fds: Vec::new(),
+        })
+    }
+
+    /// Get the setup information that the X11 server sent.
+    pub fn setup(&self) -> &Setup {
+        &self.setup
+    }
+
+    /// Get the limits on the data that is accepted from the X11 server.
+    pub fn limits(&self) -> Limits {
+        self.connection.limits()
+    }
+
+    /// Set the limits on the data that is accepted from the X11 server.
+    ///
+    /// See [`Connection::set_limits`].
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.packet_reader
+            .set_max_packet_length(limits.max_packet_length);
+        self.connection.set_limits(limits);
+    }
+
+    /// Process bytes that were received from the X11 server.
+    ///
+    /// The bytes do not need to form complete packets. Incomplete packets are kept until the rest
+    /// is received. Received file descriptors must be passed to
+    /// [`ProtocolDriver::receive_fds`] before the bytes that were received together with them.
+    ///
+    /// When a limit is exceeded, an error is returned and the connection cannot be used
+    /// reliably anymore.
+    pub fn receive(&mut self, mut data: &[u8]) -> Result<(), LimitExceeded> {
+        while !data.is_empty() {
+            let buffer = self.packet_reader.buffer();
+            let amount = buffer.len().min(data.len());
+            buffer[..amount].copy_from_slice(&data[..amount]);
+            data = &data[amount..];
+            if let Some(packet) = self.packet_reader.try_advance(amount)? {
+                self.connection.try_enqueue_packet(packet)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Process file descriptors that were received from the X11 server.
+    pub fn receive_fds(&mut self, fds: Vec<RawFdContainer>) -> Result<(), LimitExceeded> {
+        self.connection.try_enqueue_fds(fds)
+    }
+
+    /// Check if there is anything that should be written to the X11 server.
+    pub fn has_pending_output(&self) -> bool {
+        !self.output.is_empty() || !self.output_fds.is_empty()
+    }
+
+    /// Get the bytes that should be written to the X11 server.
+    pub fn pending_output(&self) -> &[u8] {
+        &self.output
+    }
+
+    /// Get the file descriptors that should be sent to the X11 server.
+    ///
+    /// The file descriptors must be sent together with the next bytes of
+    /// [`ProtocolDriver::pending_output`] or before them.
+    pub fn take_pending_fds(&mut self) -> Vec<RawFdContainer> {
+        mem::take(&mut self.output_fds)
+    }
+
+    /// Remove bytes that were written to the X11 server from the pending output.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `amount` is larger than the length of [`ProtocolDriver::pending_output`].
+    pub fn advance_output(&mut self, amount: usize) {
+        let _ = self.output.drain(..amount);
+    }
+
+    /// Queue a request for sending.
+    ///
+    /// The request must already contain the correct major opcode. Its length field is replaced
+    /// with a BIG-REQUESTS length if necessary. For this, the driver needs to know the
+    /// maximum request length, see [`ProtocolDriver::maximum_request_bytes`]. While this is
+    /// unknown, large requests fail with [`DriverError::WouldBlock`]. Requests that are larger
+    /// than the maximum request length fail with [`DriverError::MaximumRequestLengthExceeded`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of the request is not a multiple of four or if the length field of a
+    /// request that does not need BIG-REQUESTS is wrong.
+    pub fn send_raw_request(
+        &mut self,
+        request: &[u8],
+        fds: Vec<RawFdContainer>,
+        kind: ReplyFdKind,
+    ) -> Result<SequenceNumber, DriverError> {
+        assert_eq!(
+            request.len() % 4,
+            0,
+            "The length of X11 requests must be a multiple of 4, got {}",
+            request.len()
+        );
+        let wire_length = request.len() / 4;
+
+        let big_length = match u16::try_from(wire_length) {
+            Ok(wire_length) => {
+                let length_field = u16::from_ne_bytes([request[2], request[3]]);
+                assert_eq!(
+                    wire_length, length_field,
+                    "Length field contains incorrect value"
+                );
+                // The X11 server closes the connection on requests that are too large. Until
+                // BIG-REQUESTS is known to be enabled, the limit from the setup applies.
+                let maximum_request_bytes = match self.max_request_bytes {
+                    MaxRequestBytes::Known(length) => length,
+                    _ => usize::from(self.setup.maximum_request_length) * 4,
+                };
+                if request.len() > maximum_request_bytes {
+                    return Err(DriverError::MaximumRequestLengthExceeded);
+                }
+                None
+            }
+            Err(_) => {
+                if request.len() > self.maximum_request_bytes()? {
+                    return Err(DriverError::MaximumRequestLengthExceeded);
+                }
+                // The extended length field adds four more bytes to the request
+                let wire_length = u32::try_from(wire_length + 1)
+                    .map_err(|_| DriverError::MaximumRequestLengthExceeded)?;
+                Some(wire_length)
+            }
+        };
+
+        let sequence = self.next_sequence_number(kind);
+        match big_length {
+            None => self.output.extend_from_slice(request),
+            Some(wire_length) => {
+                // A length field of zero indicates an extended length field
+                self.output
+                    .extend_from_slice(&[request[0], request[1], 0, 0]);
+                self.output.extend_from_slice(&wire_length.to_ne_bytes());
+                self.output.extend_from_slice(&request[4..]);
+            }
+        }
+        self.output_fds.extend(fds);
+        Ok(sequence)
+    }
+
+    // Get the sequence number for a new request, inserting a sync if necessary
+    fn next_sequence_number(&mut self, kind: ReplyFdKind) -> SequenceNumber {
+        if let Some(sequence) = self.connection.send_request(kind) {
+            return sequence;
+        }
+
+        // Too many requests without a reply were sent. A GetInputFocus request makes sure that
+        // sequence numbers can still be reconstructed.
+        let sync = self
+            .connection
+            .send_request(ReplyFdKind::ReplyWithoutFDs)
+            .expect("Sending a HasResponse request should not be blocked by syncs");
+        self.connection
+            .discard_reply(sync, DiscardMode::DiscardReplyAndError);
+        let length = 1u16.to_ne_bytes();
+        self.output
+            .extend_from_slice(&[GET_INPUT_FOCUS_REQUEST, 0, length[0], length[1]]);
+        self.connection
+            .send_request(kind)
+            .expect("Sending a request directly after a sync should not be blocked")
+    }
+
+    // Get the major opcode for a request of the given extension
+    fn extension_opcode(
+        &mut self,
+        extension_name: Option<&'static str>,
+    ) -> Result<u8, DriverError> {
+        match extension_name {
+            None => Ok(0),
+            Some(name) => match self.extension_information(name)? {
+                Some(info) => Ok(info.major_opcode),
+                None => Err(DriverError::UnsupportedExtension),
+            },
+        }
+    }
+
+    /// Queue a request with a reply for sending.
+    ///
+    /// If the request belongs to an extension that was not queried yet, the extension is queried
+    /// and [`DriverError::WouldBlock`] is returned. The request is not sent in this case. Use
+    /// [`ProtocolDriver::prefetch_extension_information`] to query extensions in advance.
+    pub fn send_request_with_reply<R: ReplyRequest>(
+        &mut self,
+        request: R,
+    ) -> Result<PendingReply<R::Reply>, DriverError> {
+        let major_opcode = self.extension_opcode(R::EXTENSION_NAME)?;
+        let (request, fds) = request.serialize(major_opcode);
+        let sequence = self.send_raw_request(&request, fds, ReplyFdKind::ReplyWithoutFDs)?;
+        Ok(PendingReply::new(sequence))
+    }
+
+    /// Queue a request with a reply containing file descriptors for sending.
+    ///
+    /// See [`ProtocolDriver::send_request_with_reply`].
+    pub fn send_request_with_reply_with_fds<R: ReplyFDsRequest>(
+        &mut self,
+        request: R,
+    ) -> Result<PendingReply<R::Reply>, DriverError> {
+        let major_opcode = self.extension_opcode(R::EXTENSION_NAME)?;
+        let (request, fds) = request.serialize(major_opcode);
+        let sequence = self.send_raw_request(&request, fds, ReplyFdKind::ReplyWithFDs)?;
+        Ok(PendingReply::new(sequence))
+    }
+
+    /// Queue a request without a reply for sending.
+    ///
+    /// Errors for this request are returned as events. See
+    /// [`ProtocolDriver::send_request_with_reply`] for requests of extensions.
+    pub fn send_request_without_reply<R: VoidRequest>(
+        &mut self,
+        request: R,
+    ) -> Result<SequenceNumber, DriverError> {
+        let major_opcode = self.extension_opcode(R::EXTENSION_NAME)?;
+        let (request, fds) = request.serialize(major_opcode);
+        self.send_raw_request(&request, fds, ReplyFdKind::NoReply)
+    }
+
+    /// Get the reply or the error for a request, if it was already received.
+    pub fn poll_for_reply<R: TryParse>(
+        &mut self,
+        pending: PendingReply<R>,
+    ) -> Option<Result<R, DriverError>> {
+        let (reply, _fds) = self.connection.poll_for_reply_or_error(pending.sequence)?;
+        Some(self.parse_reply(&reply, R::try_parse))
+    }
+
+    /// Get the reply with file descriptors or the error for a request, if it was already
+    /// received.
+    pub fn poll_for_reply_with_fds<R: TryParseFd>(
+        &mut self,
+        pending: PendingReply<R>,
+    ) -> Option<Result<R, DriverError>> {
+        let (reply, mut fds) = self.connection.poll_for_reply_or_error(pending.sequence)?;
+        Some(self.parse_reply(&reply, |reply| R::try_parse_fd(reply, &mut fds)))
+    }
+
+    /// Get the raw reply or error for the request with the given sequence number, if it was
+    /// already received.
+    pub fn poll_for_raw_reply_or_error(
+        &mut self,
+        sequence: SequenceNumber,
+    ) -> Option<BufWithFds<Vec<u8>>> {
+        self.connection.poll_for_reply_or_error(sequence)
+    }
+
+    /// Discard the reply and/or the error for the request with the given sequence number.
+    pub fn discard_reply(&mut self, sequence: SequenceNumber, mode: DiscardMode) {
+        self.connection.discard_reply(sequence, mode);
+    }
+
+    fn parse_reply<R>(
+        &self,
+        reply: &[u8],
+        parse: impl FnOnce(&[u8]) -> Result<(R, &[u8]), ParseError>,
+    ) -> Result<R, DriverError> {
+        if reply[0] == 0 {
+            return Err(X11Error::try_parse(reply, &self.extensions)?.into());
+        }
+        Ok(parse(reply)?.0)
+    }
+
+    /// Get the next event, if one was received.
+    pub fn poll_for_event(&mut self) -> Result<Option<Event>, ParseError> {
+        match self.connection.poll_for_event_with_sequence() {
+            Some((event, _)) => Event::parse(&event, &self.extensions).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Get the next raw event and its sequence number, if one was received.
+    pub fn poll_for_raw_event_with_sequence(&mut self) -> Option<RawEventAndSeqNumber<Vec<u8>>> {
+        self.connection.poll_for_event_with_sequence()
+    }
+
+    /// Query the X11 server for the given extension, unless this was already done.
+    pub fn prefetch_extension_information(&mut self, extension_name: &'static str) {
+        if self.extensions.0.contains_key(extension_name) {
+            return;
+        }
+        let request = QueryExtensionRequest {
+            name: Cow::Borrowed(extension_name.as_bytes()),
+        };
+        let (request, fds) = Request::serialize(request, 0);
+        let sequence = self
+            .send_raw_request(&request, fds, ReplyFdKind::ReplyWithoutFDs)
+            .expect("QueryExtension requests do not need BIG-REQUESTS");
+        let _ = self
+            .extensions
+            .0
+            .insert(extension_name, ExtensionState::Pending(sequence));
+    }
+
+    /// Get information about the given extension.
+    ///
+    /// Returns `None` if the X11 server does not support the extension. If the extension was not
+    /// queried yet, a query is sent and [`DriverError::WouldBlock`] is returned.
+    pub fn extension_information(
+        &mut self,
+        extension_name: &'static str,
+    ) -> Result<Option<ExtensionInformation>, DriverError> {
+        self.prefetch_extension_information(extension_name);
+        let state = self
+            .extensions
+            .0
+            .get_mut(extension_name)
+            .expect("The extension was just prefetched");
+        if let ExtensionState::Pending(sequence) = *state {
+            let (reply, _) = self
+                .connection
+                .poll_for_reply_or_error(sequence)
+                .ok_or(DriverError::WouldBlock)?;
+            // An X11 error means that the extension cannot be used
+            *state = ExtensionState::Missing;
+            if reply[0] != 0 {
+                let (reply, _) = QueryExtensionReply::try_parse(&reply)?;
+                if reply.present {
+                    *state = ExtensionState::Present(ExtensionInformation {
+                        major_opcode: reply.major_opcode,
+                        first_event: reply.first_event,
+                        first_error: reply.first_error,
+                    });
+                }
+            }
+        }
+        Ok(match *state {
+            ExtensionState::Present(info) => Some(info),
+            _ => None,
+        })
+    }
+
+    /// Start finding out the maximum request length, unless this was already done.
+    pub fn prefetch_maximum_request_bytes(&mut self) {
+        let _ = self.maximum_request_bytes();
+    }
+
+    /// Get the maximum length of a request in bytes.
+    ///
+    /// This uses the BIG-REQUESTS extension if the X11 server supports it. Until its reply
+    /// arrived, [`DriverError::WouldBlock`] is returned.
+    pub fn maximum_request_bytes(&mut self) -> Result<usize, DriverError> {
+        let length = match self.max_request_bytes {
+            MaxRequestBytes::Known(length) => return Ok(length),
+            MaxRequestBytes::Requested(sequence) => {
+                let (reply, _) = self
+                    .connection
+                    .poll_for_reply_or_error(sequence)
+                    .ok_or(DriverError::WouldBlock)?;
+                // If anything failed, fall back to the length from the setup
+                match self.parse_reply(&reply, bigreq::EnableReply::try_parse) {
+                    Ok(reply) => reply.maximum_request_length,
+                    Err(_) => self.setup.maximum_request_length.into(),
+                }
+            }
+            MaxRequestBytes::Unknown => {
+                match self.extension_information(bigreq::X11_EXTENSION_NAME)? {
+                    None => self.setup.maximum_request_length.into(),
+                    Some(info) => {
+                        let (request, fds) =
+                            Request::serialize(bigreq::EnableRequest, info.major_opcode);
+                        let sequence =
+                            self.send_raw_request(&request, fds, ReplyFdKind::ReplyWithoutFDs)?;
+                        self.max_request_bytes = MaxRequestBytes::Requested(sequence);
+                        return Err(DriverError::WouldBlock);
+                    }
+                }
+            }
+        };
+        // The length is in units of four bytes
+        let length = usize::try_from(length)
+            .unwrap_or(usize::MAX)
+            .saturating_mul(4);
+        self.max_request_bytes = MaxRequestBytes::Known(length);
+        Ok(length)
+    }
+
+    /// Generate a new X11 ID.
+    ///
+    /// When all IDs are used up, new ones are requested via the XC-MISC extension. Until its
+    /// reply arrived, [`DriverError::WouldBlock`] is returned.
+    pub fn generate_id(&mut self) -> Result<u32, DriverError> {
+        if let Some(id) = self.id_allocator.generate_id() {
+            return Ok(id);
+        }
+        if let Some(sequence) = self.xid_range_request {
+            let (reply, _) = self
+                .connection
+                .poll_for_reply_or_error(sequence)
+                .ok_or(DriverError::WouldBlock)?;
+            self.xid_range_request = None;
+            let reply = self.parse_reply(&reply, xc_misc::GetXIDRangeReply::try_parse)?;
+            self.id_allocator.update_xid_range(&reply)?;
+            return self
+                .id_allocator
+                .generate_id()
+                .ok_or(DriverError::IdsExhausted);
+        }
+        match self.extension_information(xc_misc::X11_EXTENSION_NAME)? {
+            None => Err(DriverError::IdsExhausted),
+            Some(info) => {
+                let (request, fds) =
+                    Request::serialize(xc_misc::GetXIDRangeRequest, info.major_opcode);
+                let sequence =
+                    self.send_raw_request(&request, fds, ReplyFdKind::ReplyWithoutFDs)?;
+                self.xid_range_request = Some(sequence);
+                Err(DriverError::WouldBlock)
+            }
+        }
+    }
+}
+
+/// The extensions that are known to be present, for example for [`X11Error::display`].
+impl ExtInfoProvider for ProtocolDriver {
+    fn get_from_major_opcode(&self, major_opcode: u8) -> Option<(&str, ExtensionInformation)> {
+        self.extensions.get_from_major_opcode(major_opcode)
+    }
+
+    fn get_from_event_code(&self, event_code: u8) -> Option<(&str, ExtensionInformation)> {
+        self.extensions.get_from_event_code(event_code)
+    }
+
+    fn get_from_error_code(&self, error_code: u8) -> Option<(&str, ExtensionInformation)> {
+        self.extensions.get_from_error_code(error_code)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::string::ToString;
+    use alloc::{vec, vec::Vec};
+
+    use super::ProtocolDriver;
+    use crate::connection::ReplyFdKind;
+    use crate::errors::DriverError;
+    use crate::protocol::bigreq::EnableRequest;
+    use crate::protocol::xproto::{GetInputFocusRequest, Setup};
+
+    fn driver(resource_id_mask: u32) -> ProtocolDriver {
+        ProtocolDriver::new(Setup {
+            resource_id_mask,
+            maximum_request_length: 0xffff,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    fn take_output(driver: &mut ProtocolDriver) -> Vec<u8> {
+        let output = driver.pending_output().to_vec();
+        driver.advance_output(output.len());
+        output
+    }
+
+    fn reply(sequence: u16, data: &[u8]) -> Vec<u8> {
+        let mut reply = vec![0; 32];
+        reply[0] = 1;
+        reply[2..4].copy_from_slice(&sequence.to_ne_bytes());
+        reply[8..8 + data.len()].copy_from_slice(data);
+        reply
+    }
+
+    // The header of a request with length 1
+    fn header(major_opcode: u8, minor_opcode: u8) -> Vec<u8> {
+        let length = 1u16.to_ne_bytes();
+        vec![major_opcode, minor_opcode, length[0], length[1]]
+    }
+
+    // The data of a QueryExtension reply for a present extension
+    fn extension_present(major_opcode: u8) -> [u8; 4] {
+        [1, major_opcode, 0, 0]
+    }
+
+    #[test]
+    fn send_and_receive() {
+        let mut driver = driver(0xff);
+        let pending = driver
+            .send_request_with_reply(GetInputFocusRequest)
+            .unwrap();
+        assert_eq!(pending.sequence_number(), 1);
+        assert!(driver.has_pending_output());
+        assert_eq!(take_output(&mut driver), header(43, 0));
+        assert!(!driver.has_pending_output());
+
+        assert!(driver.poll_for_reply(pending).is_none());
+        let data = reply(1, &42u32.to_ne_bytes());
+        // Packets can arrive in pieces
+        driver.receive(&data[..10]).unwrap();
+        assert!(driver.poll_for_reply(pending).is_none());
+        driver.receive(&data[10..]).unwrap();
+        let reply = driver.poll_for_reply(pending).unwrap().unwrap();
+        assert_eq!(reply.focus, 42);
+    }
+
+    #[test]
+    fn extension_request() {
+        let mut driver = driver(0xff);
+        assert_eq!(
+            driver.send_request_with_reply(EnableRequest).unwrap_err(),
+            DriverError::WouldBlock
+        );
+        // A QueryExtension request was sent instead
+        assert_eq!(take_output(&mut driver)[0], 98);
+
+        driver.receive(&reply(1, &extension_present(133))).unwrap();
+        let pending = driver.send_request_with_reply(EnableRequest).unwrap();
+        assert_eq!(pending.sequence_number(), 2);
+        assert_eq!(take_output(&mut driver), header(133, 0));
+    }
+
+    #[test]
+    fn big_request() {
+        let mut driver = driver(0xff);
+        let mut request = vec![0; 4 * 0x10000];
+        request[0] = 200;
+
+        // First, the extension is queried, then BIG-REQUESTS is enabled
+        for (sequence, data) in [(1, extension_present(133)), (2, 0x20000u32.to_ne_bytes())] {
+            assert_eq!(
+                driver.send_raw_request(&request, Vec::new(), ReplyFdKind::NoReply),
+                Err(DriverError::WouldBlock)
+            );
+            driver.receive(&reply(sequence, &data)).unwrap();
+        }
+        assert_eq!(take_output(&mut driver)[20..], header(133, 0));
+        assert_eq!(driver.maximum_request_bytes(), Ok(4 * 0x20000));
+
+        assert_eq!(
+            driver.send_raw_request(&request, Vec::new(), ReplyFdKind::NoReply),
+            Ok(3)
+        );
+        let output = take_output(&mut driver);
+        assert_eq!(output.len(), request.len() + 4);
+        assert_eq!(output[..4], [200, 0, 0, 0]);
+        assert_eq!(output[4..8], 0x10001u32.to_ne_bytes());
+    }
+
+    #[test]
+    fn request_too_large() {
+        let mut driver = ProtocolDriver::new(Setup {
+            resource_id_mask: 0xff,
+            maximum_request_length: 2,
+            ..Default::default()
+        })
+        .unwrap();
+        let mut request = vec![200, 0, 3, 0];
+        request.resize(12, 0);
+        assert_eq!(
+            driver.send_raw_request(&request, Vec::new(), ReplyFdKind::NoReply),
+            Err(DriverError::MaximumRequestLengthExceeded)
+        );
+        assert!(!driver.has_pending_output());
+
+        request[2] = 2;
+        request.truncate(8);
+        assert_eq!(
+            driver.send_raw_request(&request, Vec::new(), ReplyFdKind::NoReply),
+            Ok(1)
+        );
+    }
+
+    #[test]
+    fn xid_refill() {
+        let mut driver = driver(0b11);
+        for id in 0..4 {
+            assert_eq!(driver.generate_id(), Ok(id));
+        }
+
+        // First, the extension is queried, then a new range of IDs is requested
+        let range = [0x100u32.to_ne_bytes(), 2u32.to_ne_bytes()].concat();
+        for (sequence, data) in [(1, &extension_present(140)[..]), (2, &range[..])] {
+            assert_eq!(driver.generate_id(), Err(DriverError::WouldBlock));
+            driver.receive(&reply(sequence, data)).unwrap();
+        }
+        assert_eq!(take_output(&mut driver)[16..], header(140, 1));
+        assert_eq!(driver.generate_id(), Ok(0x100));
+        assert_eq!(driver.generate_id(), Ok(0x101));
+    }
+
+    #[test]
+    fn x11_error() {
+        let mut driver = driver(0xff);
+        let _ = driver.send_request_with_reply(EnableRequest).unwrap_err();
+        driver.receive(&reply(1, &extension_present(133))).unwrap();
+        let pending = driver.send_request_with_reply(EnableRequest).unwrap();
+
+        let mut error = vec![0; 32];
+        error[1] = 2;
+        error[2..4].copy_from_slice(&2u16.to_ne_bytes());
+        error[4..8].copy_from_slice(&42u32.to_ne_bytes());
+        error[10] = 133;
+        driver.receive(&error).unwrap();
+        let error = match driver.poll_for_reply(pending) {
+            Some(Err(DriverError::X11Error(error))) => error,
+            result => panic!("Unexpected result {:?}", result),
+        };
+        assert_eq!(
+            error.display(&driver).to_string(),
+            "BadValue (2) in BigRequests::Enable, value 0x2a"
+        );
+        assert_eq!(
+            DriverError::X11Error(error).to_string(),
+            "BadValue (2) in ext 133::opcode 0, value 0x2a"
+        );
+    }
+}
@@ -1,6 +1,7 @@
 //! This module contains the current mess that is error handling.
 
 use crate::protocol::xproto::{SetupAuthenticate, SetupFailed};
+use crate::x11_utils::{ExtInfoProvider, ExtensionInformation, X11Error};
 
 pub use crate::id_allocator::IdsExhausted;
 
@@ -76,6 +77,127 @@ impl fmt::Display for ParseError {
     }
 }
 
+/// A limit on the resources used for data from the X11 server was exceeded.
+///
+/// The limits are configured via [`crate::connection::Limits`]. The data that exceeded the limit
+/// was dropped, so the connection cannot be used reliably afterwards.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LimitExceeded {
+    /// A packet was larger than [`crate::connection::Limits::max_packet_length`].
+    ///
+    /// This contains the length of the packet in bytes.
+    PacketLength(usize),
+
+    /// More events than [`crate::connection::Limits::max_queued_events`] were queued.
+    QueuedEvents,
+
+    /// More replies than [`crate::connection::Limits::max_pending_replies`] were queued.
+    PendingReplies,
+
+    /// More file descriptors than [`crate::connection::Limits::max_pending_fds`] were received.
+    PendingFds,
+}
+
+#[cfg(feature = "std")]
+impl Error for LimitExceeded {}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitExceeded::PacketLength(length) => {
+                write!(f, "Received a packet of {length} bytes, which is too large")
+            }
+            LimitExceeded::QueuedEvents => write!(f, "Too many events are queued"),
+            LimitExceeded::PendingReplies => write!(f, "Too many replies are queued"),
+            LimitExceeded::PendingFds => write!(f, "Too many file descriptors were received"),
+        }
+    }
+}
+
+/// An error that occurred in a [`crate::driver::ProtocolDriver`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DriverError {
+    /// The operation needs information from the X11 server that did not arrive yet.
+    ///
+    /// The necessary request was already queued for writing. Write the pending output, receive
+    /// more data from the X11 server and try again.
+    WouldBlock,
+
+    /// The request belongs to an extension that the X11 server does not support.
+    UnsupportedExtension,
+
+    /// A request larger than the maximum request length was sent.
+    MaximumRequestLengthExceeded,
+
+    /// All X11 IDs are in use and no new ones could be obtained via the XC-MISC extension.
+    IdsExhausted,
+
+    /// The X11 server sent an error in response to a request.
+    ///
+    /// The `Display` implementation of this error does not know the extensions of the connection.
+    /// Use [`X11Error::display`] with the [`ProtocolDriver`](crate::driver::ProtocolDriver) to
+    /// also get the names of extension errors and requests.
+    X11Error(X11Error),
+
+    /// Error while parsing some data, see `ParseError`.
+    ParseError(ParseError),
+}
+
+#[cfg(feature = "std")]
+impl Error for DriverError {}
+
+impl fmt::Display for DriverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DriverError::WouldBlock => write!(f, "Waiting for the X11 server"),
+            DriverError::UnsupportedExtension => write!(f, "Unsupported extension"),
+            DriverError::MaximumRequestLengthExceeded => {
+                write!(f, "Maximum request length exceeded")
+            }
+            DriverError::IdsExhausted => IdsExhausted.fmt(f),
+            DriverError::X11Error(err) => err.display(&NoExtensions).fmt(f),
+            DriverError::ParseError(err) => err.fmt(f),
+        }
+    }
+}
+
+/// An [`ExtInfoProvider`] that does not know any extensions.
+struct NoExtensions;
+
+impl ExtInfoProvider for NoExtensions {
+    fn get_from_major_opcode(&self, _major_opcode: u8) -> Option<(&str, ExtensionInformation)> {
+        None
+    }
+
+    fn get_from_event_code(&self, _event_code: u8) -> Option<(&str, ExtensionInformation)> {
+        None
+    }
+
+    fn get_from_error_code(&self, _error_code: u8) -> Option<(&str, ExtensionInformation)> {
+        None
+    }
+}
+
+impl From<X11Error> for DriverError {
+    fn from(err: X11Error) -> Self {
+        DriverError::X11Error(err)
+    }
+}
+
+impl From<ParseError> for DriverError {
+    fn from(err: ParseError) -> Self {
+        DriverError::ParseError(err)
+    }
+}
+
+impl From<IdsExhausted> for DriverError {
+    fn from(_: IdsExhausted) -> Self {
+        DriverError::IdsExhausted
+    }
+}
+
 /// An error that occurred while parsing the `$DISPLAY` environment variable
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[non_exhaustive]
@@ -89,6 +211,11 @@ pub enum DisplayParsingError {
     /// The value of `$DISPLAY` is not valid unicode
     NotUnicode,
 
+    /// The given value is a DECnet address (`host::display`). The input to parsing is provided.
+    ///
+    /// DECnet is not supported.
+    DecnetNotSupported(alloc::boxed::Box<str>),
+
     /// An unknown error occurred during display parsing.
     ///
     /// This is `XCB_CONN_CLOSED_PARSE_ERR`.
@@ -113,6 +240,12 @@ impl fmt::Display for DisplayParsingError {
             DisplayParsingError::NotUnicode => {
                 write!(f, "The value of $DISPLAY is not valid unicode")
             }
+            DisplayParsingError::DecnetNotSupported(dpy) => {
+                write!(
+                    f,
+                    "'{dpy}' is a DECnet address, but DECnet is not supported"
+                )
+            }
             DisplayParsingError::Unknown => {
                 write!(f, "Unknown error while parsing a $DISPLAY address")
             }
@@ -77,6 +77,7 @@ use alloc::vec::Vec;
 
 pub mod connect;
 pub mod connection;
+pub mod driver;
 #[macro_use]
 pub mod x11_utils;
 pub mod errors;
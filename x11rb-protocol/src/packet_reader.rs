@@ -5,6 +5,8 @@ use core::mem::replace;
 
 use alloc::{vec, vec::Vec};
 
+use crate::errors::LimitExceeded;
+
 /// Minimal length of an X11 packet.
 const MINIMAL_PACKET_LENGTH: usize = 32;
 
@@ -15,6 +17,9 @@ pub struct PacketReader {
 
     /// The point at which the packet is already read.
     already_read: usize,
+
+    /// The maximum length of a packet in bytes.
+    max_packet_length: usize,
 }
 
 impl fmt::Debug for PacketReader {
@@ -48,9 +53,19 @@ impl PacketReader {
         Self {
             pending_packet: vec![0; MINIMAL_PACKET_LENGTH],
             already_read: 0,
+            max_packet_length: usize::MAX,
         }
     }
 
+    /// Set the maximum length of a packet in bytes.
+    ///
+    /// Replies and generic events contain a length field. Packets whose length field says that
+    /// they are larger than this limit are rejected before memory for them is allocated, see
+    /// [`PacketReader::try_advance`]. By default, there is no limit.
+    pub fn set_max_packet_length(&mut self, max_packet_length: usize) {
+        self.max_packet_length = max_packet_length;
+    }
+
     /// Get the buffer that the reader should fill with data.
     ///
     /// # Example
@@ -78,7 +93,24 @@ impl PacketReader {
     ///
     /// This will return the packet that was read, if enough bytes were read in order
     /// to form a complete packet.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the packet is larger than the limit set with
+    /// [`PacketReader::set_max_packet_length`]. Use [`PacketReader::try_advance`] to handle this
+    /// case.
     pub fn advance(&mut self, amount: usize) -> Option<Vec<u8>> {
+        self.try_advance(amount)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Advance this buffer by the given amount.
+    ///
+    /// This will return the packet that was read, if enough bytes were read in order
+    /// to form a complete packet. An error is returned if the packet is larger than the limit set
+    /// with [`PacketReader::set_max_packet_length`]. In this case, the rest of the packet is not
+    /// read and the reader cannot be used reliably anymore.
+    pub fn try_advance(&mut self, amount: usize) -> Result<Option<Vec<u8>>, LimitExceeded> {
         self.already_read += amount;
         debug_assert!(self.already_read <= self.pending_packet.len());
 
@@ -87,23 +119,29 @@ impl PacketReader {
             // to form a complete packet
             let extra_length = extra_length(&self.pending_packet);
 
+            // check the limit before allocating anything
+            let total_length = MINIMAL_PACKET_LENGTH.saturating_add(extra_length);
+            if total_length > self.max_packet_length {
+                self.already_read = 0;
+                return Err(LimitExceeded::PacketLength(total_length));
+            }
+
             // tell if we need to read more
             if extra_length > 0 {
-                let total_length = MINIMAL_PACKET_LENGTH + extra_length;
                 self.pending_packet.resize(total_length, 0);
-                return None;
+                return Ok(None);
             }
         } else if self.already_read != self.pending_packet.len() {
             // we haven't read the full packet yet, return
-            return None;
+            return Ok(None);
         }
 
         // we've read in the full packet, return it
         self.already_read = 0;
-        Some(replace(
+        Ok(Some(replace(
             &mut self.pending_packet,
             vec![0; MINIMAL_PACKET_LENGTH],
-        ))
+        )))
     }
 }
 
@@ -127,6 +165,7 @@ fn extra_length(buffer: &[u8]) -> usize {
 #[cfg(all(test, feature = "std"))]
 mod tests {
     use super::PacketReader;
+    use crate::errors::LimitExceeded;
     use alloc::{vec, vec::Vec};
 
     fn test_packets(packets: Vec<Vec<u8>>) {
@@ -244,6 +283,28 @@ mod tests {
         test_packets(packets);
     }
 
+    #[test]
+    fn test_max_packet_length() {
+        let mut reader = PacketReader::new();
+        reader.set_max_packet_length(1000);
+
+        let packet = make_reply_with_length(1200);
+        reader.buffer().copy_from_slice(&packet[..32]);
+        assert_eq!(
+            reader.try_advance(32),
+            Err(LimitExceeded::PacketLength(1200))
+        );
+        // Nothing was allocated for the large packet
+        assert_eq!(reader.remaining_capacity(), 32);
+
+        // Packets below the limit still work
+        let packet = make_reply_with_length(1000);
+        reader.buffer().copy_from_slice(&packet[..32]);
+        assert_eq!(reader.try_advance(32), Ok(None));
+        reader.buffer().copy_from_slice(&packet[32..]);
+        assert_eq!(reader.try_advance(1000 - 32), Ok(Some(packet)));
+    }
+
     #[test]
     fn test_debug_fixed_size_packet() {
         // The debug output includes the length of the packet of the packet and how much was
@@ -32,23 +32,37 @@ pub(super) fn connect_addresses(p: &ParsedDisplay) -> impl Iterator<Item = Conne
 
     let mut targets = Vec::new();
 
-    if (protocol.is_none() || protocol.as_deref() != Some("unix"))
-        && !host.is_empty()
-        && host != "unix"
-    {
-        targets.push(ConnectAddress::Hostname(host, TCP_PORT_BASE + display));
-    } else {
-        if protocol.is_none() || protocol.as_deref() == Some("unix") {
+    // IPv6 addresses can be enclosed in brackets
+    let tcp_host = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host);
+
+    match protocol.as_deref() {
+        Some("tcp" | "inet" | "inet6") => {
+            let host = if host.is_empty() {
+                "localhost"
+            } else {
+                tcp_host
+            };
+            targets.push(ConnectAddress::Hostname(host, TCP_PORT_BASE + display));
+        }
+        None if !host.is_empty() && host != "unix" => {
+            targets.push(ConnectAddress::Hostname(tcp_host, TCP_PORT_BASE + display));
+        }
+        None | Some("unix") => {
             let file_name = format!("/tmp/.X11-unix/X{display}");
             targets.push(ConnectAddress::Socket(file_name));
-        }
 
-        if protocol.is_none() && host.is_empty() {
-            targets.push(ConnectAddress::Hostname(
-                "localhost",
-                TCP_PORT_BASE + display,
-            ));
+            if protocol.is_none() && host.is_empty() {
+                targets.push(ConnectAddress::Hostname(
+                    "localhost",
+                    TCP_PORT_BASE + display,
+                ));
+            }
         }
+        // Unknown protocols cannot be used
+        Some(_) => {}
     }
 
     targets.into_iter()
@@ -60,6 +74,43 @@ mod tests {
     use super::{super::parse_display, ConnectAddress};
     use alloc::{vec, vec::Vec};
 
+    #[test]
+    fn conformance_table() {
+        let unix =
+            |display: u16| ConnectAddress::Socket(alloc::format!("/tmp/.X11-unix/X{display}"));
+        for (input, expected) in [
+            (
+                ":1",
+                vec![unix(1), ConnectAddress::Hostname("localhost", 6001)],
+            ),
+            ("unix:1", vec![unix(1)]),
+            ("unix/:1", vec![unix(1)]),
+            ("host:1", vec![ConnectAddress::Hostname("host", 6001)]),
+            ("::1:1", vec![ConnectAddress::Hostname("::1", 6001)]),
+            ("[::1]:1", vec![ConnectAddress::Hostname("::1", 6001)]),
+            ("[::1]:1.2", vec![ConnectAddress::Hostname("::1", 6001)]),
+            ("tcp/host:1", vec![ConnectAddress::Hostname("host", 6001)]),
+            (
+                "inet/10.0.0.1:1",
+                vec![ConnectAddress::Hostname("10.0.0.1", 6001)],
+            ),
+            ("inet6/[::1]:1", vec![ConnectAddress::Hostname("::1", 6001)]),
+            ("inet6/::1:1", vec![ConnectAddress::Hostname("::1", 6001)]),
+            ("tcp/:1", vec![ConnectAddress::Hostname("localhost", 6001)]),
+            (
+                "inet6/:1",
+                vec![ConnectAddress::Hostname("localhost", 6001)],
+            ),
+            ("tcp/unix:1", vec![ConnectAddress::Hostname("unix", 6001)]),
+            ("foo/host:1", vec![]),
+            ("foo/:1", vec![]),
+        ] {
+            let pd = parse_display(Some(input)).unwrap();
+            let ci = pd.connect_instruction().collect::<Vec<_>>();
+            assert_eq!(ci, expected, "Unexpected addresses for {input}");
+        }
+    }
+
     #[test]
     fn basic_test() {
         let pd = parse_display(Some(":0")).unwrap();
@@ -12,12 +12,15 @@ pub struct ParsedDisplay {
     /// The hostname of the computer we nned to connect to.
     ///
     /// This is an empty string if we are connecting to the
-    /// local host.
+    /// local host. IPv6 addresses may be enclosed in brackets,
+    /// e.g. `[::1]`; the brackets are kept here.
     pub host: String,
     /// The protocol we are communicating over.
     ///
     /// This is `None` if the protocol may be determined
-    /// automatically.
+    /// automatically. `unix` selects a Unix domain socket and
+    /// `tcp`, `inet` and `inet6` select TCP. No connection is
+    /// attempted for other protocols.
     pub protocol: Option<String>,
     /// The index of the display we are connecting to.
     pub display: u16,
@@ -62,6 +65,16 @@ pub fn parse_display(dpy_name: Option<&str>) -> Result<ParsedDisplay, DisplayPar
 ///
 /// The parameter `file_exists` is called to check whether a given string refers to an existing
 /// file. This function does not need to check the file type.
+///
+/// The accepted syntax is the same as libxcb's:
+///
+/// - `[protocol/]host:display[.screen]`, where the host may be empty, a host name, an IPv4
+///   address or an IPv6 address, optionally enclosed in brackets like `[::1]`.
+/// - `/path/to/socket[.screen]` and `unix:/path/to/socket[.screen]` for a Unix domain socket
+///   that exists.
+///
+/// DECnet addresses of the form `host::display` are recognised, but rejected with
+/// [`DisplayParsingError::DecnetNotSupported`].
 pub fn parse_display_with_file_exists_callback(
     dpy_name: &str,
     file_exists: impl Fn(&str) -> bool,
@@ -69,11 +82,16 @@ pub fn parse_display_with_file_exists_callback(
     let malformed = || DisplayParsingError::MalformedValue(dpy_name.to_string().into());
     let map_malformed = |_| malformed();
 
-    if dpy_name.starts_with('/') {
-        return parse_display_direct_path(dpy_name, file_exists);
-    }
-    if let Some(remaining) = dpy_name.strip_prefix("unix:") {
-        return parse_display_direct_path(remaining, file_exists);
+    // Like libxcb, fall back to normal parsing if no such file exists, e.g. for "unix:0"
+    let direct_path = if dpy_name.starts_with('/') {
+        Some(dpy_name)
+    } else {
+        dpy_name.strip_prefix("unix:")
+    };
+    if let Some(path) = direct_path {
+        if let Some(parsed) = parse_display_direct_path(path, file_exists) {
+            return Ok(parsed);
+        }
     }
 
     // Everything up to the last '/' is the protocol. This part is optional.
@@ -99,6 +117,14 @@ pub fn parse_display_with_file_exists_callback(
         screen.parse().map_err(map_malformed)?,
     );
 
+    // DECnet uses "host::display". Like Xlib, "::" at the end of the host is still an IPv6
+    // address, e.g. "1:::0" refers to display 0 on "1::".
+    if host == ":" || (host.ends_with(':') && !host.ends_with("::")) {
+        return Err(DisplayParsingError::DecnetNotSupported(
+            dpy_name.to_string().into(),
+        ));
+    }
+
     let host = host.to_string();
     let protocol = protocol.map(|p| p.to_string());
     Ok(ParsedDisplay {
@@ -113,9 +139,9 @@ pub fn parse_display_with_file_exists_callback(
 fn parse_display_direct_path(
     dpy_name: &str,
     file_exists: impl Fn(&str) -> bool,
-) -> Result<ParsedDisplay, DisplayParsingError> {
+) -> Option<ParsedDisplay> {
     if file_exists(dpy_name) {
-        return Ok(ParsedDisplay {
+        return Some(ParsedDisplay {
             host: dpy_name.to_string(),
             protocol: Some("unix".to_string()),
             display: 0,
@@ -124,21 +150,17 @@ fn parse_display_direct_path(
     }
 
     // Optionally, a screen number may be appended as ".n".
-    if let Some((path, screen)) = dpy_name.rsplit_once('.') {
-        if file_exists(path) {
-            return Ok(ParsedDisplay {
-                host: path.to_string(),
-                protocol: Some("unix".to_string()),
-                display: 0,
-                screen: screen.parse().map_err(|_| {
-                    DisplayParsingError::MalformedValue(dpy_name.to_string().into())
-                })?,
-            });
-        }
+    let (path, screen) = dpy_name.rsplit_once('.')?;
+    let screen = screen.parse().ok()?;
+    if file_exists(path) {
+        return Some(ParsedDisplay {
+            host: path.to_string(),
+            protocol: Some("unix".to_string()),
+            display: 0,
+            screen,
+        });
     }
-    Err(DisplayParsingError::MalformedValue(
-        dpy_name.to_string().into(),
-    ))
+    None
 }
 
 #[cfg(all(test, feature = "std"))]
@@ -168,6 +190,7 @@ mod test {
         test_missing_input();
         xcb_good_cases();
         xcb_bad_cases();
+        xcb_decnet_cases();
         own_good_cases();
         own_bad_cases();
     }
@@ -178,7 +201,39 @@ mod test {
     }
 
     fn own_good_cases() {
+        fn display(protocol: Option<&str>, host: &str, display: u16, screen: u16) -> ParsedDisplay {
+            ParsedDisplay {
+                host: host.to_string(),
+                protocol: protocol.map(ToString::to_string),
+                display,
+                screen,
+            }
+        }
+
         // The XCB test suite does not test protocol parsing
+        for (input, output) in &[
+            ("unix:0", display(None, "unix", 0, 0)),
+            ("unix:0.1", display(None, "unix", 0, 1)),
+            ("unix/:0", display(Some("unix"), "", 0, 0)),
+            ("tcp/host:1", display(Some("tcp"), "host", 1, 0)),
+            ("tcp/:1.2", display(Some("tcp"), "", 1, 2)),
+            ("inet/127.0.0.1:1", display(Some("inet"), "127.0.0.1", 1, 0)),
+            ("inet6/::1:1", display(Some("inet6"), "::1", 1, 0)),
+            ("inet6/[::1]:1", display(Some("inet6"), "[::1]", 1, 0)),
+            ("inet6/[::1]:1.2", display(Some("inet6"), "[::1]", 1, 2)),
+            (
+                "tcp/[::ffff:10.0.0.1]:3",
+                display(Some("tcp"), "[::ffff:10.0.0.1]", 3, 0),
+            ),
+            ("inet6/:::0", display(Some("inet6"), "::", 0, 0)),
+        ] {
+            assert_eq!(
+                do_parse_display(input).as_ref(),
+                Ok(output),
+                "Failed parsing correctly: {input}"
+            );
+        }
+
         for (input, output) in &[
             (
                 "foo/bar:1",
@@ -490,34 +545,6 @@ mod test {
                     screen: 1,
                 },
             ),
-            // decnet
-            (
-                "myws::0",
-                ParsedDisplay {
-                    host: "myws:".to_string(),
-                    protocol: None,
-                    display: 0,
-                    screen: 0,
-                },
-            ),
-            (
-                "big::0",
-                ParsedDisplay {
-                    host: "big:".to_string(),
-                    protocol: None,
-                    display: 0,
-                    screen: 0,
-                },
-            ),
-            (
-                "hydra::0.1",
-                ParsedDisplay {
-                    host: "hydra:".to_string(),
-                    protocol: None,
-                    display: 0,
-                    screen: 1,
-                },
-            ),
         ] {
             assert_eq!(
                 do_parse_display(input).as_ref(),
@@ -565,6 +592,21 @@ mod test {
         }
     }
 
+    // The first three cases are from libxcb's test suite; (C) 2001-2006 Bart Massey, Jamey Sharp,
+    // and Josh Triplett. libxcb accepts them and then fails to connect. We reject DECnet
+    // addresses while parsing.
+    fn xcb_decnet_cases() {
+        for input in &["myws::0", "big::0", "hydra::0.1", "::0", "tcp/myws::0"] {
+            assert_eq!(
+                do_parse_display(input),
+                Err(DisplayParsingError::DecnetNotSupported(
+                    input.to_string().into()
+                )),
+                "Unexpectedly parsed: {input}"
+            );
+        }
+    }
+
     fn make_unix_path(host: &str, screen: u16) -> Result<ParsedDisplay, DisplayParsingError> {
         Ok(ParsedDisplay {
             host: host.to_string(),
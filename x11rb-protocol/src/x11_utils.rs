@@ -6,9 +6,10 @@
 
 use alloc::string::String;
 use alloc::vec::Vec;
+use core::fmt;
 
 use crate::errors::ParseError;
-use crate::protocol::{request_name, ErrorKind};
+use crate::protocol::{get_request_name, request_name, ErrorKind};
 use crate::utils::RawFdContainer;
 use crate::BufWithFds;
 
@@ -183,6 +184,188 @@ mod serialise_x11error_test {
     }
 }
 
+impl X11Error {
+    /// Get a human-readable description of this error.
+    ///
+    /// The names of the error and of the failed request are looked up via the given
+    /// `ExtInfoProvider`. The result looks like
+    /// `BadWindow (3) in xproto::ConfigureWindow, resource 0x1a00003`.
+    pub fn display<'a>(
+        &'a self,
+        ext_info_provider: &'a dyn ExtInfoProvider,
+    ) -> X11ErrorDisplay<'a> {
+        X11ErrorDisplay {
+            error: self,
+            ext_info_provider,
+        }
+    }
+}
+
+/// Helper for formatting an [`X11Error`], see [`X11Error::display`].
+pub struct X11ErrorDisplay<'a> {
+    error: &'a X11Error,
+    ext_info_provider: &'a dyn ExtInfoProvider,
+}
+
+impl fmt::Display for X11ErrorDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let error = self.error;
+        // From the X11 protocol reference manual:
+        // Error codes 128 through 255 are reserved for extensions.
+        let is_core_error = error.error_code < 128;
+        match error.error_kind {
+            ErrorKind::Unknown(code) => match self.ext_info_provider.get_from_error_code(code) {
+                Some((ext, info)) => {
+                    write!(f, "{ext} error {}", code.wrapping_sub(info.first_error))?
+                }
+                None => f.write_str("Unknown error")?,
+            },
+            kind if is_core_error => write!(f, "Bad{kind:?}")?,
+            kind => write!(f, "{kind:?}")?,
+        }
+        write!(f, " ({}) in ", error.error_code)?;
+
+        match u8::try_from(error.minor_opcode) {
+            Ok(minor_opcode) => {
+                let name =
+                    get_request_name(self.ext_info_provider, error.major_opcode, minor_opcode);
+                // Core requests are reported without a prefix
+                if error.major_opcode < 128 && !name.contains("::") {
+                    f.write_str("xproto::")?;
+                }
+                f.write_str(&name)?;
+            }
+            Err(_) => write!(
+                f,
+                "ext {}::opcode {}",
+                error.major_opcode, error.minor_opcode
+            )?,
+        }
+
+        let label = match error.error_kind {
+            ErrorKind::Colormap
+            | ErrorKind::Cursor
+            | ErrorKind::Drawable
+            | ErrorKind::Font
+            | ErrorKind::GContext
+            | ErrorKind::IDChoice
+            | ErrorKind::Pixmap
+            | ErrorKind::Window => Some("resource"),
+            ErrorKind::Atom => Some("atom"),
+            ErrorKind::Value => Some("value"),
+            // The other core errors do not use the bad value
+            _ if is_core_error => None,
+            _ => Some("bad value"),
+        };
+        if let Some(label) = label {
+            write!(f, ", {label} {:#x}", error.bad_value)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for X11ErrorDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("X11ErrorDisplay")
+            .field("error", self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod display_x11error_test {
+    use alloc::string::ToString;
+
+    use super::{ErrorKind, ExtInfoProvider, ExtensionInformation, X11Error};
+
+    const XFIXES: ExtensionInformation = ExtensionInformation {
+        major_opcode: 140,
+        first_event: 90,
+        first_error: 150,
+    };
+
+    struct Provider;
+
+    impl ExtInfoProvider for Provider {
+        fn get_from_major_opcode(&self, major_opcode: u8) -> Option<(&str, ExtensionInformation)> {
+            Some(("XFIXES", XFIXES)).filter(|_| major_opcode == XFIXES.major_opcode)
+        }
+        fn get_from_event_code(&self, _event_code: u8) -> Option<(&str, ExtensionInformation)> {
+            unimplemented!()
+        }
+        fn get_from_error_code(&self, error_code: u8) -> Option<(&str, ExtensionInformation)> {
+            Some(("XFIXES", XFIXES)).filter(|_| error_code == XFIXES.first_error)
+        }
+    }
+
+    fn error(error_code: u8, major_opcode: u8, minor_opcode: u16, bad_value: u32) -> X11Error {
+        X11Error {
+            error_kind: ErrorKind::from_wire_error_code(error_code, &Provider),
+            error_code,
+            sequence: 1,
+            bad_value,
+            minor_opcode,
+            major_opcode,
+            extension_name: None,
+            request_name: None,
+        }
+    }
+
+    #[test]
+    fn core_errors() {
+        let configure_window = error(3, 12, 0, 0x1a00003);
+        assert_eq!(
+            configure_window.display(&Provider).to_string(),
+            "BadWindow (3) in xproto::ConfigureWindow, resource 0x1a00003"
+        );
+        let intern_atom = error(2, 16, 0, 42);
+        assert_eq!(
+            intern_atom.display(&Provider).to_string(),
+            "BadValue (2) in xproto::InternAtom, value 0x2a"
+        );
+        let map_window = error(8, 8, 0, 123);
+        assert_eq!(
+            map_window.display(&Provider).to_string(),
+            "BadMatch (8) in xproto::MapWindow"
+        );
+    }
+
+    #[test]
+    fn unknown_errors() {
+        let unknown = error(200, 201, 3, 7);
+        assert_eq!(
+            unknown.display(&Provider).to_string(),
+            "Unknown error (200) in ext 201::opcode 3, bad value 0x7"
+        );
+        let unknown_core_request = error(1, 126, 0, 0);
+        assert_eq!(
+            unknown_core_request.display(&Provider).to_string(),
+            "BadRequest (1) in xproto::opcode 126"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "xfixes")]
+    fn extension_error() {
+        // SetRegion with a bad region
+        let set_region = error(150, 140, 11, 0x200001);
+        assert_eq!(
+            set_region.display(&Provider).to_string(),
+            "XfixesBadRegion (150) in XFixes::SetRegion, bad value 0x200001"
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "xfixes"))]
+    fn extension_error() {
+        let set_region = error(150, 140, 11, 0x200001);
+        assert_eq!(
+            set_region.display(&Provider).to_string(),
+            "XFIXES error 0 (150) in ext XFIXES::opcode 11, bad value 0x200001"
+        );
+    }
+}
+
 /// Information about a X11 extension.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct ExtensionInformation {
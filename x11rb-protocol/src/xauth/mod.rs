@@ -0,0 +1,1100 @@
+//! Helpers for working with `~/.Xauthority`.
+
+#![cfg(feature = "std")]
+
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU32, Ordering};
+use std::io::{Error, ErrorKind, Read, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::protocol::xproto::Family as X11Family;
+
+mod des;
+
+const MIT_MAGIC_COOKIE_1: &[u8] = b"MIT-MAGIC-COOKIE-1";
+const XDM_AUTHORIZATION_1: &[u8] = b"XDM-AUTHORIZATION-1";
+
+/// The length in bytes of a `MIT-MAGIC-COOKIE-1` cookie.
+pub const MIT_MAGIC_COOKIE_1_LEN: usize = 16;
+
+/// A family describes how to interpret some bytes as an address in an `AuthEntry`.
+///
+/// Compared to [`super::protocol::xproto::Family`], this is a `u16` and not an `u8` since
+/// that's what is used in `~/.Xauthority` files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Family(u16);
+
+impl Family {
+    /// IPv4 connection to the server
+    pub const INTERNET: Self = Self(0);
+    /// DECnet
+    pub const DEC_NET: Self = Self(1);
+    /// Chaosnet connection
+    pub const CHAOS: Self = Self(2);
+    /// Family without predefined meaning, but interpreted by the server, for example a user name
+    pub const SERVER_INTERPRETED: Self = Self(5);
+    /// IPv6 connection to the server
+    pub const INTERNET6: Self = Self(6);
+    /// Wildcard matching any protocol family
+    pub const WILD: Self = Self(65535);
+    /// For local non-net authentication
+    pub const LOCAL: Self = Self(256);
+    /// TODO: No idea what this means exactly
+    pub const NETNAME: Self = Self(254);
+    /// Kerberos 5 principal name
+    pub const KRB5_PRINCIPAL: Self = Self(253);
+    /// For local non-net authentication
+    pub const LOCAL_HOST: Self = Self(252);
+}
+
+impl From<X11Family> for Family {
+    fn from(value: X11Family) -> Self {
+        Self(value.into())
+    }
+}
+
+impl From<u16> for Family {
+    fn from(value: u16) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Family> for u16 {
+    fn from(value: Family) -> Self {
+        value.0
+    }
+}
+
+/// A single entry of an `.Xauthority` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthEntry {
+    /// The protocol family to which the entry applies
+    pub family: Family,
+    /// The address of the peer in a family-specific format
+    pub address: Vec<u8>,
+    /// The display number as a decimal string. An empty number matches all displays.
+    pub number: Vec<u8>,
+    /// The name of the authentication method to use for the X11 server described by the previous
+    /// fields.
+    pub name: Vec<u8>,
+    /// Extra data for the authentication method.
+    pub data: Vec<u8>,
+}
+
+impl AuthEntry {
+    /// Create a `MIT-MAGIC-COOKIE-1` entry for the given display.
+    ///
+    /// See [`generate_mit_magic_cookie`] for a way to get a fresh cookie.
+    pub fn mit_magic_cookie(
+        family: Family,
+        address: impl Into<Vec<u8>>,
+        display: u16,
+        cookie: impl Into<Vec<u8>>,
+    ) -> Self {
+        Self {
+            family,
+            address: address.into(),
+            number: display.to_string().into_bytes(),
+            name: MIT_MAGIC_COOKIE_1.to_vec(),
+            data: cookie.into(),
+        }
+    }
+
+    /// Check whether this entry applies to the given display.
+    ///
+    /// This uses the same rules as [`get_auth`]: [`Family::WILD`] matches any address and an
+    /// empty display number matches any display. The authentication method is not checked.
+    pub fn matches(&self, family: Family, address: &[u8], display: u16) -> bool {
+        let display = display.to_string();
+        address_matches((family, address), (self.family, &self.address))
+            && display_number_matches(&self.number, display.as_bytes())
+    }
+
+    /// Check whether this entry describes the same display and method as `other`.
+    ///
+    /// Two such entries cannot usefully exist in the same file, since only the first one would
+    /// ever be used. Unlike [`AuthEntry::matches`], this does not handle wildcards.
+    pub fn same_key(&self, other: &AuthEntry) -> bool {
+        self.family == other.family
+            && self.address == other.address
+            && self.number == other.number
+            && self.name == other.name
+    }
+
+    /// Read a single entry in `.Xauthority` format.
+    ///
+    /// This function returns `Ok(None)` when the end of the input is reached before the entry
+    /// starts.
+    pub fn read_from<R: Read>(read: &mut R) -> Result<Option<Self>, Error> {
+        file::read_entry(read)
+    }
+
+    /// Write this entry in `.Xauthority` format.
+    ///
+    /// This fails with [`ErrorKind::InvalidInput`] if one of the fields is longer than 65535
+    /// bytes, since such an entry cannot be represented in the file format.
+    pub fn write_to<W: Write>(&self, write: &mut W) -> Result<(), Error> {
+        file::write_entry(write, self)
+    }
+}
+
+/// Generate a new random cookie for use with `MIT-MAGIC-COOKIE-1`.
+///
+/// The random bytes are read from `/dev/urandom`. On other systems, this function fails with
+/// [`ErrorKind::Unsupported`] and the cookie has to be generated by the caller.
+pub fn generate_mit_magic_cookie() -> Result<Vec<u8>, Error> {
+    if cfg!(unix) {
+        let mut cookie = alloc::vec![0; MIT_MAGIC_COOKIE_1_LEN];
+        std::fs::File::open("/dev/urandom")?.read_exact(&mut cookie)?;
+        Ok(cookie)
+    } else {
+        Err(Error::new(
+            ErrorKind::Unsupported,
+            "No source of randomness available",
+        ))
+    }
+}
+
+mod file {
+    //! Code for actually reading and writing `~/.Xauthority`.
+
+    use alloc::{string::ToString, vec, vec::Vec};
+    use std::env::var_os;
+    use std::ffi::OsString;
+    use std::fs::{self, File, OpenOptions};
+    use std::io::{BufReader, BufWriter, Error, ErrorKind, Read, Write};
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, SystemTime};
+
+    use super::{AuthEntry, Family};
+
+    /// The number of times [`AuthorityLock::acquire_default`] tries to get the lock.
+    const DEFAULT_LOCK_RETRIES: u32 = 10;
+
+    /// The time [`AuthorityLock::acquire_default`] waits between attempts.
+    const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(1);
+
+    /// Read a single `u16` from an `~/.Xauthority` file.
+    ///
+    /// The file stores these entries in big endian.
+    fn read_u16<R: Read>(read: &mut R) -> Result<u16, Error> {
+        let mut buffer = [0; 2];
+        read.read_exact(&mut buffer)?;
+        Ok(u16::from_be_bytes(buffer))
+    }
+
+    /// Read a single "byte array" from an `~/.Xauthority` file.
+    ///
+    /// The file stores these as a length field followed by a number of bytes that contain the
+    /// actual data.
+    fn read_string<R: Read>(read: &mut R) -> Result<Vec<u8>, Error> {
+        let length = read_u16(read)?;
+        let mut result = vec![0; length.into()];
+        read.read_exact(&mut result[..])?;
+        Ok(result)
+    }
+
+    /// Read a single entry from an `~/.Xauthority` file.
+    ///
+    /// This function tries to return `Ok(None)` when the end of the file is reached. However, the
+    /// code also treats a single byte as 'end of file', because things were simpler to implement
+    /// like this.
+    pub(super) fn read_entry<R: Read>(read: &mut R) -> Result<Option<AuthEntry>, Error> {
+        let family = match read_u16(read) {
+            Ok(family) => family,
+            Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        .into();
+        let address = read_string(read)?;
+        let number = read_string(read)?;
+        let name = read_string(read)?;
+        let data = read_string(read)?;
+        Ok(Some(AuthEntry {
+            family,
+            address,
+            number,
+            name,
+            data,
+        }))
+    }
+
+    /// Write a single "byte array" to an `~/.Xauthority` file.
+    ///
+    /// This is the counterpart to [`read_string`].
+    fn write_string<W: Write>(write: &mut W, string: &[u8]) -> Result<(), Error> {
+        let length = u16::try_from(string.len()).map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                "Xauthority fields cannot be longer than 65535 bytes",
+            )
+        })?;
+        write.write_all(&length.to_be_bytes())?;
+        write.write_all(string)
+    }
+
+    /// Write a single entry to an `~/.Xauthority` file.
+    pub(super) fn write_entry<W: Write>(write: &mut W, entry: &AuthEntry) -> Result<(), Error> {
+        // Check all lengths first so that nothing is written for an invalid entry
+        let fields = [&entry.address, &entry.number, &entry.name, &entry.data];
+        if fields.iter().any(|field| field.len() > u16::MAX.into()) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Xauthority fields cannot be longer than 65535 bytes",
+            ));
+        }
+        write.write_all(&u16::from(entry.family).to_be_bytes())?;
+        for field in fields {
+            write_string(write, field)?;
+        }
+        Ok(())
+    }
+
+    /// Get the file name for `~/.Xauthority` based on environment variables.
+    ///
+    /// This uses `$XAUTHORITY` if it is set and `$HOME/.Xauthority` otherwise. `None` is returned
+    /// if neither variable is set.
+    ///
+    /// The code in libXau contains a special case for Windows (looks like cygwin) that is not
+    /// handled here (yet?).
+    pub fn xauthority_file_name() -> Option<PathBuf> {
+        if let Some(name) = var_os("XAUTHORITY") {
+            return Some(name.into());
+        }
+        var_os("HOME").map(|prefix| {
+            let mut result = PathBuf::new();
+            result.push(prefix);
+            result.push(".Xauthority");
+            result
+        })
+    }
+
+    /// Append a suffix to the file name in a path, e.g. `/foo/bar` becomes `/foo/bar-c`.
+    fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+        let mut result = OsString::from(path);
+        result.push(suffix);
+        result.into()
+    }
+
+    /// A lock on an `.Xauthority` file.
+    ///
+    /// This uses the same protocol as libXau's `XauLockAuth()`, so that it is compatible with
+    /// `xauth` and other X11 tools: The lock is held while the files `<name>-c` and `<name>-l`
+    /// exist. The lock is released when this value is dropped.
+    #[derive(Debug)]
+    pub struct AuthorityLock {
+        creat_name: PathBuf,
+        link_name: PathBuf,
+    }
+
+    impl AuthorityLock {
+        /// Lock the given `.Xauthority` file.
+        ///
+        /// - `retries` is the number of additional attempts that are made if the file is
+        ///   already locked.
+        /// - `timeout` is the time to wait between attempts.
+        /// - `dead` is the age after which an existing lock is considered stale and broken. With
+        ///   `None`, existing locks are never broken.
+        ///
+        /// If the lock could not be acquired, an error of kind [`ErrorKind::TimedOut`] is
+        /// returned.
+        pub fn acquire(
+            path: impl AsRef<Path>,
+            retries: u32,
+            timeout: Duration,
+            dead: Option<Duration>,
+        ) -> Result<Self, Error> {
+            let path = path.as_ref();
+            let creat_name = with_suffix(path, "-c");
+            let link_name = with_suffix(path, "-l");
+
+            if let Some(dead) = dead {
+                let modified = fs::metadata(&creat_name).and_then(|meta| meta.modified());
+                if let Ok(modified) = modified {
+                    let age = SystemTime::now()
+                        .duration_since(modified)
+                        .unwrap_or_default();
+                    if age >= dead {
+                        let _ = fs::remove_file(&creat_name);
+                        let _ = fs::remove_file(&link_name);
+                    }
+                }
+            }
+
+            let mut created = false;
+            for attempt in 0..=retries {
+                if attempt != 0 {
+                    std::thread::sleep(timeout);
+                }
+                if !created {
+                    match OpenOptions::new()
+                        .write(true)
+                        .create_new(true)
+                        .open(&creat_name)
+                    {
+                        Ok(_) => created = true,
+                        Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                        Err(e) => return Err(e),
+                    }
+                }
+                match fs::hard_link(&creat_name, &link_name) {
+                    Ok(()) => {
+                        return Ok(Self {
+                            creat_name,
+                            link_name,
+                        })
+                    }
+                    Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+                    Err(e) => {
+                        let _ = fs::remove_file(&creat_name);
+                        return Err(e);
+                    }
+                }
+            }
+            if created {
+                let _ = fs::remove_file(&creat_name);
+            }
+            Err(Error::new(
+                ErrorKind::TimedOut,
+                "The Xauthority file is locked by another process",
+            ))
+        }
+
+        /// Lock the given `.Xauthority` file with the same settings that `xauth` uses.
+        ///
+        /// This tries for about ten seconds and does not break stale locks.
+        pub fn acquire_default(path: impl AsRef<Path>) -> Result<Self, Error> {
+            Self::acquire(path, DEFAULT_LOCK_RETRIES, DEFAULT_LOCK_TIMEOUT, None)
+        }
+    }
+
+    impl Drop for AuthorityLock {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.creat_name);
+            let _ = fs::remove_file(&self.link_name);
+        }
+    }
+
+    /// Read all entries from the given `.Xauthority` file.
+    ///
+    /// A file that does not exist is treated like an empty file. No lock is taken.
+    pub fn read_authority_file(path: impl AsRef<Path>) -> Result<Vec<AuthEntry>, Error> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        XAuthorityEntries(BufReader::new(file)).collect()
+    }
+
+    /// Replace the content of the given `.Xauthority` file with `entries`.
+    ///
+    /// The entries are first written to `<name>-n`, which is then renamed over the file. On
+    /// unix, the new file is only readable by its owner. No lock is taken; see [`AuthorityLock`].
+    pub fn write_authority_file(
+        path: impl AsRef<Path>,
+        entries: &[AuthEntry],
+    ) -> Result<(), Error> {
+        let path = path.as_ref();
+        let temp_name = with_suffix(path, "-n");
+        let mut options = OpenOptions::new();
+        let _ = options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            let _ = options.mode(0o600);
+        }
+        let result = options.open(&temp_name).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            for entry in entries {
+                write_entry(&mut writer, entry)?;
+            }
+            writer.into_inner()?.sync_all()?;
+            fs::rename(&temp_name, path)
+        });
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_name);
+        }
+        result
+    }
+
+    /// Add entries to the given `.Xauthority` file.
+    ///
+    /// Existing entries for the same family, address, display number and authentication method
+    /// are replaced. The new entries are put at the beginning of the file so that they take
+    /// precedence over wildcard entries. The file is created if it does not exist yet.
+    ///
+    /// The file is locked via [`AuthorityLock::acquire_default`] while it is modified.
+    pub fn merge_authority_entries(
+        path: impl AsRef<Path>,
+        entries: &[AuthEntry],
+    ) -> Result<(), Error> {
+        let path = path.as_ref();
+        let _lock = AuthorityLock::acquire_default(path)?;
+        let old_entries = read_authority_file(path)?;
+        let new_entries = entries
+            .iter()
+            .cloned()
+            .chain(
+                old_entries
+                    .into_iter()
+                    .filter(|old| !entries.iter().any(|new| new.same_key(old))),
+            )
+            .collect::<Vec<_>>();
+        write_authority_file(path, &new_entries)
+    }
+
+    /// Remove all entries for the given display from an `.Xauthority` file.
+    ///
+    /// Only entries whose family, address and display number are exactly equal to the arguments
+    /// are removed; wildcard entries are kept. The number of removed entries is returned.
+    ///
+    /// The file is locked via [`AuthorityLock::acquire_default`] while it is modified.
+    pub fn remove_authority_entries(
+        path: impl AsRef<Path>,
+        family: Family,
+        address: &[u8],
+        display: u16,
+    ) -> Result<usize, Error> {
+        let path = path.as_ref();
+        let _lock = AuthorityLock::acquire_default(path)?;
+        let mut entries = read_authority_file(path)?;
+        let display = display.to_string();
+        let old_len = entries.len();
+        entries.retain(|entry| {
+            entry.family != family || entry.address != address || entry.number != display.as_bytes()
+        });
+        let removed = old_len - entries.len();
+        if removed != 0 {
+            write_authority_file(path, &entries)?;
+        }
+        Ok(removed)
+    }
+
+    /// An iterator over the entries of an `.Xauthority` file
+    #[derive(Debug)]
+    pub(crate) struct XAuthorityEntries(BufReader<File>);
+
+    impl XAuthorityEntries {
+        /// Open `~/.Xauthority` for reading.
+        ///
+        /// This function returns `Ok(None)` when the location of the `.Xauthority` file could not
+        /// be determined. If opening the file failed (for example, because it does not exist),
+        /// that error is returned.
+        pub(crate) fn new() -> Result<Option<XAuthorityEntries>, Error> {
+            xauthority_file_name()
+                .map(File::open)
+                .transpose()?
+                // At this point we have Option<File> and errors while opening the file were
+                // returned to the caller.
+                .map(|file| Ok(XAuthorityEntries(BufReader::new(file))))
+                .transpose()
+        }
+    }
+
+    impl Iterator for XAuthorityEntries {
+        type Item = Result<AuthEntry, Error>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            read_entry(&mut self.0).transpose()
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::super::{AuthEntry, Family};
+        use super::{
+            merge_authority_entries, read_authority_file, read_entry, remove_authority_entries,
+            write_entry, AuthorityLock,
+        };
+        use alloc::{format, vec, vec::Vec};
+        use std::io::{Cursor, ErrorKind};
+        use std::path::PathBuf;
+        use std::time::Duration;
+
+        /// A file name in the temporary directory that is removed again on drop.
+        struct TempFile(PathBuf);
+
+        impl TempFile {
+            fn new(name: &str) -> Self {
+                let path = std::env::temp_dir().join(format!(
+                    "x11rb-xauth-{}-{}",
+                    std::process::id(),
+                    name
+                ));
+                let _ = std::fs::remove_file(&path);
+                Self(path)
+            }
+        }
+
+        impl Drop for TempFile {
+            fn drop(&mut self) {
+                let _ = std::fs::remove_file(&self.0);
+            }
+        }
+
+        #[test]
+        fn test_read() {
+            // Data generated via xauth -f /tmp/file add :1 bar deadbeef
+            let data = [
+                0x01, 0x00, 0x00, 0x07, 0x5a, 0x77, 0x65, 0x69, 0x4c, 0x45, 0x44, 0x00, 0x01, 0x31,
+                0x00, 0x03, 0x62, 0x61, 0x72, 0x00, 0x04, 0xde, 0xad, 0xbe, 0xef,
+            ];
+            let mut cursor = Cursor::new(&data[..]);
+            let entry = read_entry(&mut cursor).unwrap();
+            assert_eq!(
+                entry,
+                Some(AuthEntry {
+                    family: Family::LOCAL,
+                    address: b"ZweiLED".to_vec(),
+                    number: b"1".to_vec(),
+                    name: b"bar".to_vec(),
+                    data: u32::to_be_bytes(0xdead_beef).to_vec(),
+                })
+            );
+        }
+
+        #[test]
+        fn test_read_iterate() {
+            // Data generated via:
+            //   xauth -f /tmp/file add :1 bar deadbeef
+            //   xauth -f /tmp/file add 1.2.3.4:2 baz aabbccdd
+            let data = [
+                0x01, 0x00, 0x00, 0x07, 0x5a, 0x77, 0x65, 0x69, 0x4c, 0x45, 0x44, 0x00, 0x01, 0x31,
+                0x00, 0x03, 0x62, 0x61, 0x72, 0x00, 0x04, 0xde, 0xad, 0xbe, 0xef, 0x00, 0x00, 0x00,
+                0x04, 0x01, 0x02, 0x03, 0x04, 0x00, 0x01, 0x32, 0x00, 0x03, 0x62, 0x61, 0x7a, 0x00,
+                0x04, 0xaa, 0xbb, 0xcc, 0xdd,
+            ];
+            let mut cursor = Cursor::new(&data[..]);
+            for expected in &[
+                AuthEntry {
+                    family: Family::LOCAL,
+                    address: b"ZweiLED".to_vec(),
+                    number: b"1".to_vec(),
+                    name: b"bar".to_vec(),
+                    data: u32::to_be_bytes(0xdead_beef).to_vec(),
+                },
+                AuthEntry {
+                    family: Family::INTERNET,
+                    address: vec![1, 2, 3, 4],
+                    number: b"2".to_vec(),
+                    name: b"baz".to_vec(),
+                    data: u32::to_be_bytes(0xaabb_ccdd).to_vec(),
+                },
+            ] {
+                let entry = read_entry(&mut cursor).unwrap();
+                assert_eq!(entry.as_ref(), Some(expected));
+            }
+            let entry = read_entry(&mut cursor).unwrap();
+            assert_eq!(entry, None);
+        }
+
+        #[test]
+        fn test_write() {
+            // Same data as in test_read()
+            let data = [
+                0x01, 0x00, 0x00, 0x07, 0x5a, 0x77, 0x65, 0x69, 0x4c, 0x45, 0x44, 0x00, 0x01, 0x31,
+                0x00, 0x03, 0x62, 0x61, 0x72, 0x00, 0x04, 0xde, 0xad, 0xbe, 0xef,
+            ];
+            let entry = AuthEntry {
+                family: Family::LOCAL,
+                address: b"ZweiLED".to_vec(),
+                number: b"1".to_vec(),
+                name: b"bar".to_vec(),
+                data: u32::to_be_bytes(0xdead_beef).to_vec(),
+            };
+            let mut output = Vec::new();
+            write_entry(&mut output, &entry).unwrap();
+            assert_eq!(output, data);
+        }
+
+        #[test]
+        fn test_write_too_long() {
+            let entry = AuthEntry::mit_magic_cookie(Family::LOCAL, vec![0; 0x1_0000], 0, []);
+            let mut output = Vec::new();
+            let err = write_entry(&mut output, &entry).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidInput);
+            assert!(output.is_empty());
+        }
+
+        #[test]
+        fn test_merge_and_remove() {
+            let file = TempFile::new("merge");
+            let local1 = AuthEntry::mit_magic_cookie(Family::LOCAL, *b"host", 1, [1; 16]);
+            let local2 = AuthEntry::mit_magic_cookie(Family::LOCAL, *b"host", 2, [2; 16]);
+            let remote = AuthEntry::mit_magic_cookie(Family::INTERNET, [1, 2, 3, 4], 1, [3; 16]);
+
+            assert_eq!(read_authority_file(&file.0).unwrap(), []);
+            merge_authority_entries(&file.0, &[local1, remote.clone()]).unwrap();
+
+            // Replacing an entry puts the new one first
+            let local1 = AuthEntry::mit_magic_cookie(Family::LOCAL, *b"host", 1, [4; 16]);
+            merge_authority_entries(&file.0, &[local1.clone(), local2.clone()]).unwrap();
+            assert_eq!(
+                read_authority_file(&file.0).unwrap(),
+                [local1.clone(), local2, remote.clone()]
+            );
+
+            assert_eq!(
+                remove_authority_entries(&file.0, Family::LOCAL, b"host", 2).unwrap(),
+                1
+            );
+            assert_eq!(
+                remove_authority_entries(&file.0, Family::LOCAL, b"host", 3).unwrap(),
+                0
+            );
+            assert_eq!(read_authority_file(&file.0).unwrap(), [local1, remote]);
+        }
+
+        #[test]
+        fn test_lock() {
+            let file = TempFile::new("lock");
+            let lock = AuthorityLock::acquire(&file.0, 0, Duration::ZERO, None).unwrap();
+            let err = AuthorityLock::acquire(&file.0, 1, Duration::ZERO, None).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::TimedOut);
+
+            // A lock that is old enough is considered stale
+            let lock2 =
+                AuthorityLock::acquire(&file.0, 0, Duration::ZERO, Some(Duration::ZERO)).unwrap();
+            drop(lock);
+            drop(lock2);
+            let _lock = AuthorityLock::acquire(&file.0, 0, Duration::ZERO, None).unwrap();
+        }
+    }
+}
+
+pub use file::{
+    merge_authority_entries, read_authority_file, remove_authority_entries, write_authority_file,
+    xauthority_file_name, AuthorityLock,
+};
+
+fn address_matches(
+    (family1, address1): (Family, &[u8]),
+    (family2, address2): (Family, &[u8]),
+) -> bool {
+    if family1 == Family::WILD || family2 == Family::WILD {
+        true
+    } else if family1 != family2 {
+        false
+    } else {
+        address1 == address2
+    }
+}
+
+fn display_number_matches(entry_number: &[u8], display_number: &[u8]) -> bool {
+    debug_assert!(!display_number.is_empty()); // This case is not handled here and would be a match
+    entry_number.is_empty() || entry_number == display_number
+}
+
+pub(crate) type AuthInfo = (Vec<u8>, Vec<u8>);
+
+/// The local end of a connection to the X11 server.
+///
+/// `XDM-AUTHORIZATION-1` includes the address of the client in the data that is sent to the
+/// server, so this is needed by [`get_auth_for_connection`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalAddress {
+    /// A connection that is not made via TCP, for example via a Unix domain socket.
+    Local,
+    /// A TCP connection where the client uses the given address and port.
+    Tcp(SocketAddr),
+}
+
+/// Counter to make the fake address of local `XDM-AUTHORIZATION-1` connections unique.
+static XDM_NONCE: AtomicU32 = AtomicU32::new(0);
+
+/// Compute the data that is sent for `XDM-AUTHORIZATION-1`.
+///
+/// `auth_data` is the data from the `.Xauthority` entry: 8 bytes that are sent to the server
+/// followed by the 8 byte DES key. `address` and `port` identify the client and `time` is the
+/// current time in seconds since the Unix epoch.
+///
+/// This returns `None` if `auth_data` does not have the expected length.
+fn xdm_authorization_1_data(
+    auth_data: &[u8],
+    address: [u8; 4],
+    port: u16,
+    time: u32,
+) -> Option<Vec<u8>> {
+    if auth_data.len() != 16 {
+        return None;
+    }
+    let (rho, key) = auth_data.split_at(8);
+
+    let mut plain = [0; 24];
+    plain[..8].copy_from_slice(rho);
+    plain[8..12].copy_from_slice(&address);
+    plain[12..14].copy_from_slice(&port.to_be_bytes());
+    plain[14..18].copy_from_slice(&time.to_be_bytes());
+
+    // The key only has 56 bits; the first byte of the key data is ignored. Spread the remaining
+    // bits over eight bytes so that the lowest bit of each byte is the (unused) parity bit.
+    let mut key_bits = [0; 8];
+    key_bits[1..].copy_from_slice(&key[1..]);
+    let key_bits = u64::from_be_bytes(key_bits);
+    let mut des_key = [0; 8];
+    for (index, byte) in des_key.iter_mut().enumerate() {
+        *byte = (((key_bits >> (49 - 7 * index)) & 0x7f) as u8) << 1;
+    }
+
+    // Encrypt in CBC mode with an all-zero IV
+    let mut previous = [0; 8];
+    let mut result = Vec::with_capacity(plain.len());
+    for chunk in plain.chunks(8) {
+        let mut block = previous;
+        block
+            .iter_mut()
+            .zip(chunk)
+            .for_each(|(block, plain)| *block ^= plain);
+        previous = des::encrypt_block(des_key, block);
+        result.extend_from_slice(&previous);
+    }
+    Some(result)
+}
+
+/// Compute the data that is sent for `XDM-AUTHORIZATION-1` from the current time and the local
+/// end of the connection.
+///
+/// This follows libxcb: Local connections use a unique fake address and the process ID as port.
+/// IPv6 addresses cannot be represented and are replaced with zeros, except for IPv4-mapped
+/// addresses.
+fn xdm_authorization_1(auth_data: &[u8], local: LocalAddress) -> Option<Vec<u8>> {
+    let (address, port) = match local {
+        LocalAddress::Local => {
+            let nonce = XDM_NONCE.fetch_add(1, Ordering::Relaxed);
+            let pid = std::process::id() as u16;
+            ((u32::MAX - nonce).to_be_bytes(), pid)
+        }
+        LocalAddress::Tcp(addr) => match addr.ip() {
+            IpAddr::V4(ip) => (ip.octets(), addr.port()),
+            IpAddr::V6(ip) => match ip.octets() {
+                [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, a, b, c, d] => {
+                    ([a, b, c, d], addr.port())
+                }
+                _ => ([0; 4], 0),
+            },
+        },
+    };
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs() as u32);
+    xdm_authorization_1_data(auth_data, address, port, time)
+}
+
+/// Get the authentication information necessary for connecting to the given display.
+///
+/// - `family` is the protocol family that is used for connecting; this describes how to interpret
+///   the `address`.
+/// - `address` is the raw bytes describing the address that is being connected to.
+/// - `display` is the display number.
+///
+/// If successful, this function returns that can be written to the X11 server as authorization
+/// protocol name and data, respectively.
+///
+/// Only `MIT-MAGIC-COOKIE-1` entries are considered, since other authentication methods need
+/// information about the connection. Use [`get_auth_for_connection`] if that is available.
+pub fn get_auth(family: Family, address: &[u8], display: u16) -> Result<Option<AuthInfo>, Error> {
+    match file::XAuthorityEntries::new()? {
+        None => Ok(None),
+        Some(entries) => get_auth_impl(entries, family, address, display),
+    }
+}
+
+/// Get the authentication information necessary for connecting to the given display.
+///
+/// This works like [`get_auth`], but also supports `XDM-AUTHORIZATION-1`, which is preferred
+/// over `MIT-MAGIC-COOKIE-1` if entries for both exist. `local` describes the local end of the
+/// connection, which is part of the data that is sent for `XDM-AUTHORIZATION-1`.
+///
+/// The returned data is only valid for a short time and must not be reused for other
+/// connections.
+pub fn get_auth_for_connection(
+    family: Family,
+    address: &[u8],
+    display: u16,
+    local: LocalAddress,
+) -> Result<Option<AuthInfo>, Error> {
+    match file::XAuthorityEntries::new()? {
+        None => Ok(None),
+        Some(entries) => get_auth_for_connection_impl(entries, family, address, display, local),
+    }
+}
+
+fn get_auth_impl(
+    entries: impl Iterator<Item = Result<AuthEntry, Error>>,
+    family: Family,
+    address: &[u8],
+    display: u16,
+) -> Result<Option<AuthInfo>, Error> {
+    let entry = find_best_entry(entries, family, address, display, &[MIT_MAGIC_COOKIE_1])?;
+    Ok(entry.map(|entry| (entry.name, entry.data)))
+}
+
+fn get_auth_for_connection_impl(
+    entries: impl Iterator<Item = Result<AuthEntry, Error>>,
+    family: Family,
+    address: &[u8],
+    display: u16,
+    local: LocalAddress,
+) -> Result<Option<AuthInfo>, Error> {
+    let methods = [XDM_AUTHORIZATION_1, MIT_MAGIC_COOKIE_1];
+    let entries = entries.filter(|entry| match entry {
+        // Skip entries that cannot be used
+        Ok(entry) => entry.name != XDM_AUTHORIZATION_1 || entry.data.len() == 16,
+        Err(_) => true,
+    });
+    match find_best_entry(entries, family, address, display, &methods)? {
+        Some(entry) if entry.name == XDM_AUTHORIZATION_1 => {
+            let data = xdm_authorization_1(&entry.data, local);
+            Ok(data.map(|data| (entry.name, data)))
+        }
+        Some(entry) => Ok(Some((entry.name, entry.data))),
+        None => Ok(None),
+    }
+}
+
+/// Find the entry that applies to the given display and has the most preferred authentication
+/// method.
+///
+/// `methods` lists the acceptable authentication methods, most preferred first. Among entries
+/// with the same method, the first one wins. This is the same logic as libXau's
+/// `XauGetBestAuthByAddr()`.
+fn find_best_entry(
+    entries: impl Iterator<Item = Result<AuthEntry, Error>>,
+    family: Family,
+    address: &[u8],
+    display: u16,
+    methods: &[&[u8]],
+) -> Result<Option<AuthEntry>, Error> {
+    let display = display.to_string();
+    let display = display.as_bytes();
+
+    let mut best: Option<(usize, AuthEntry)> = None;
+    for entry in entries {
+        let entry = entry?;
+
+        if !address_matches((family, address), (entry.family, &entry.address))
+            || !display_number_matches(&entry.number, display)
+        {
+            continue;
+        }
+        let preference = match methods.iter().position(|method| *method == entry.name) {
+            Some(preference) => preference,
+            None => continue,
+        };
+        if best
+            .as_ref()
+            .map_or(true, |(best_preference, _)| preference < *best_preference)
+        {
+            if preference == 0 {
+                return Ok(Some(entry));
+            }
+            best = Some((preference, entry));
+        }
+    }
+    Ok(best.map(|(_, entry)| entry))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        get_auth_for_connection_impl, get_auth_impl, xdm_authorization_1_data, AuthEntry, Family,
+        LocalAddress, MIT_MAGIC_COOKIE_1, XDM_AUTHORIZATION_1,
+    };
+    use alloc::vec;
+
+    // Call the given function on a matching auth entry. The function can change the entry.
+    // Afterwards, it should still be a match.
+    fn expect_match<F>(f: F)
+    where
+        F: FnOnce(&mut AuthEntry),
+    {
+        let mut entry = AuthEntry {
+            family: Family::LOCAL,
+            address: b"whatever".to_vec(),
+            number: b"42".to_vec(),
+            name: MIT_MAGIC_COOKIE_1.to_vec(),
+            data: b"1234".to_vec(),
+        };
+        f(&mut entry);
+        let entries = vec![Ok(entry)];
+        assert_eq!(
+            get_auth_impl(entries.into_iter(), Family::LOCAL, b"whatever", 42)
+                .unwrap()
+                .unwrap(),
+            (MIT_MAGIC_COOKIE_1.to_vec(), b"1234".to_vec())
+        );
+    }
+
+    // Call the given function on a matching auth entry. The function can change the entry.
+    // Afterwards, it should no longer match.
+    fn expect_mismatch<F>(f: F)
+    where
+        F: FnOnce(&mut AuthEntry),
+    {
+        let mut entry = AuthEntry {
+            family: Family::LOCAL,
+            address: b"whatever".to_vec(),
+            number: b"42".to_vec(),
+            name: MIT_MAGIC_COOKIE_1.to_vec(),
+            data: b"1234".to_vec(),
+        };
+        f(&mut entry);
+        let entries = vec![Ok(entry)];
+        assert_eq!(
+            get_auth_impl(entries.into_iter(), Family::LOCAL, b"whatever", 42).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn direct_match() {
+        // This checks that an auth entry where all members match, really matches
+        expect_match(|_| {});
+    }
+
+    #[test]
+    fn display_wildcard() {
+        expect_match(|entry| entry.number = vec![]);
+    }
+
+    #[test]
+    fn address_wildcard_match1() {
+        expect_match(|entry| entry.family = Family::WILD);
+    }
+
+    #[test]
+    fn address_wildcard_match2() {
+        let entry = AuthEntry {
+            family: Family::LOCAL,
+            address: b"whatever".to_vec(),
+            number: b"42".to_vec(),
+            name: MIT_MAGIC_COOKIE_1.to_vec(),
+            data: b"1234".to_vec(),
+        };
+        let entries = vec![Ok(entry)];
+        assert_eq!(
+            get_auth_impl(entries.into_iter(), Family::WILD, &[], 42)
+                .unwrap()
+                .unwrap(),
+            (MIT_MAGIC_COOKIE_1.to_vec(), b"1234".to_vec())
+        );
+    }
+
+    #[test]
+    fn family_mismatch() {
+        expect_mismatch(|entry| entry.family = Family::KRB5_PRINCIPAL);
+    }
+
+    #[test]
+    fn address_mismatch() {
+        expect_mismatch(|entry| entry.address = b"something else".to_vec());
+    }
+
+    #[test]
+    fn number_mismatch() {
+        expect_mismatch(|entry| entry.number = b"1337".to_vec());
+    }
+
+    #[test]
+    fn protocol_mismatch() {
+        expect_mismatch(|entry| entry.name = b"XDM-AUTHORIZATION-1".to_vec());
+    }
+
+    // Auth data for XDM-AUTHORIZATION-1: 0x0123456789abcdef is sent to the server and
+    // 0x00123456789abcde is the DES key
+    const XDM_DATA: [u8; 16] = [
+        0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef, 0x00, 0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc,
+        0xde,
+    ];
+
+    #[test]
+    fn xdm_authorization_1_tcp() {
+        // Reference data computed with libXdmcp's XdmcpWrap()
+        let expected = [
+            0xf7, 0x68, 0x41, 0x9f, 0xce, 0x83, 0x27, 0xf0, 0x53, 0x35, 0xab, 0x04, 0x45, 0x6e,
+            0xbc, 0x65, 0xb1, 0x27, 0xe0, 0xfd, 0xe4, 0x2a, 0x37, 0x47,
+        ];
+        let data = xdm_authorization_1_data(&XDM_DATA, [192, 168, 1, 2], 54321, 0x5f5e_1000);
+        assert_eq!(data.as_deref(), Some(&expected[..]));
+    }
+
+    #[test]
+    fn xdm_authorization_1_local() {
+        // Reference data computed with libXdmcp's XdmcpWrap()
+        let expected = [
+            0xf7, 0x68, 0x41, 0x9f, 0xce, 0x83, 0x27, 0xf0, 0xd7, 0x26, 0xb7, 0x49, 0xb1, 0xbb,
+            0xba, 0x4d, 0x44, 0x1d, 0xb4, 0xb0, 0x56, 0xb9, 0xb2, 0xdd,
+        ];
+        let data = xdm_authorization_1_data(&XDM_DATA, [0xff; 4], 1234, 0x1234_5678);
+        assert_eq!(data.as_deref(), Some(&expected[..]));
+    }
+
+    #[test]
+    fn xdm_authorization_1_invalid_length() {
+        assert_eq!(xdm_authorization_1_data(&XDM_DATA[1..], [0; 4], 0, 0), None);
+    }
+
+    fn connection_entries() -> [AuthEntry; 2] {
+        [
+            AuthEntry::mit_magic_cookie(Family::LOCAL, *b"whatever", 42, *b"1234"),
+            AuthEntry {
+                family: Family::LOCAL,
+                address: b"whatever".to_vec(),
+                number: b"42".to_vec(),
+                name: XDM_AUTHORIZATION_1.to_vec(),
+                data: XDM_DATA.to_vec(),
+            },
+        ]
+    }
+
+    #[test]
+    fn xdm_authorization_1_preferred() {
+        let entries = connection_entries().map(Ok);
+        let local = LocalAddress::Tcp(([10, 0, 0, 1], 4242).into());
+        let (name, data) = get_auth_for_connection_impl(
+            entries.into_iter(),
+            Family::LOCAL,
+            b"whatever",
+            42,
+            local,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(name, XDM_AUTHORIZATION_1);
+        assert_eq!(data.len(), 24);
+    }
+
+    #[test]
+    fn xdm_authorization_1_invalid_entry_skipped() {
+        let mut entries = connection_entries();
+        entries[1].data.truncate(8);
+        let (name, data) = get_auth_for_connection_impl(
+            entries.map(Ok).into_iter(),
+            Family::LOCAL,
+            b"whatever",
+            42,
+            LocalAddress::Local,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            (name, data),
+            (MIT_MAGIC_COOKIE_1.to_vec(), b"1234".to_vec())
+        );
+    }
+
+    #[test]
+    fn get_auth_ignores_xdm_authorization_1() {
+        let mut entries = connection_entries();
+        entries.reverse();
+        assert_eq!(
+            get_auth_impl(entries.map(Ok).into_iter(), Family::LOCAL, b"whatever", 42)
+                .unwrap()
+                .unwrap(),
+            (MIT_MAGIC_COOKIE_1.to_vec(), b"1234".to_vec())
+        );
+    }
+}
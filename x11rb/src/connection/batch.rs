@@ -0,0 +1,222 @@
+//! Sending many requests at once and collecting all their replies.
+
+use crate::connection::{Connection, RequestConnection};
+use crate::cookie::{Cookie, CookieWithFds, VoidCookie};
+use crate::errors::{ConnectionError, ReplyError};
+use crate::x11_utils::{TryParse, TryParseFd};
+
+/// A batch of requests whose responses are collected together.
+///
+/// Requests are sent via the usual `ConnectionExt` methods and their cookies are added to the
+/// batch. [`RequestBatch::send`] then flushes the connection once and waits for all responses.
+/// Since all requests are sent before the first response is awaited, this needs only a single
+/// round trip to the X11 server.
+///
+/// The result of [`RequestBatch::send`] has the same shape as the entries that were added: A tuple
+/// with one element per call to [`RequestBatch::push`] or [`RequestBatch::push_all`]. Each request
+/// produces its own `Result`, so that an error for one request does not affect the others. See
+/// [`BatchEntry`] for the types that can be added to a batch.
+///
+/// # Example
+///
+/// ```no_run
+/// # use x11rb::connection::{Connection, RequestBatch};
+/// # use x11rb::errors::ConnectionError;
+/// # use x11rb::protocol::xproto::{ConnectionExt as _, Window};
+/// # fn example(conn: &impl Connection, windows: &[Window]) -> Result<(), ConnectionError> {
+/// let (focus, attributes) = RequestBatch::new(conn)
+///     .push(conn.get_input_focus())
+///     .push_all(windows.iter().map(|&w| conn.get_window_attributes(w)))
+///     .send()?;
+/// println!("The input focus is {:?}", focus.map(|reply| reply.focus));
+/// for (window, attributes) in windows.iter().zip(attributes) {
+///     match attributes {
+///         Ok(attributes) => println!("{window}: {:?}", attributes.map_state),
+///         Err(err) => println!("{window}: {err:?}"),
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct RequestBatch<'c, C: ?Sized, E = ()> {
+    conn: &'c C,
+    entries: E,
+}
+
+impl<'c, C: Connection + ?Sized> RequestBatch<'c, C> {
+    /// Create a new, empty batch of requests on the given connection.
+    pub fn new(conn: &'c C) -> Self {
+        Self { conn, entries: () }
+    }
+}
+
+impl<'c, C: Connection + ?Sized, E> RequestBatch<'c, C, E> {
+    /// Add a single entry to this batch.
+    ///
+    /// Usually, the entry is the return value of a `ConnectionExt` method.
+    pub fn push<N>(self, entry: N) -> RequestBatch<'c, C, E::Output>
+    where
+        N: BatchEntry,
+        E: BatchAppend<N>,
+    {
+        RequestBatch {
+            conn: self.conn,
+            entries: self.entries.append(entry),
+        }
+    }
+
+    /// Add many entries of the same type to this batch.
+    ///
+    /// Their results are collected into a single `Vec`.
+    pub fn push_all<I>(self, entries: I) -> RequestBatch<'c, C, E::Output>
+    where
+        I: IntoIterator,
+        I::Item: BatchEntry,
+        E: BatchAppend<Vec<I::Item>>,
+    {
+        self.push(entries.into_iter().collect::<Vec<_>>())
+    }
+
+    /// Flush the connection and wait for the responses to all requests in this batch.
+    ///
+    /// Only an error while flushing the connection is reported as an error of the whole batch.
+    /// All other errors are reported separately for each request.
+    pub fn send(self) -> Result<E::Output, ConnectionError>
+    where
+        E: BatchEntry,
+    {
+        self.conn.flush()?;
+        Ok(self.entries.collect())
+    }
+}
+
+/// Something that can be added to a [`RequestBatch`].
+///
+/// This trait is implemented for the return values of the `ConnectionExt` methods, i.e.
+/// `Result<Cookie, ConnectionError>`, `Result<CookieWithFds, ConnectionError>`, and
+/// `Result<VoidCookie, ConnectionError>`. For cookies with a reply, the result is the parsed reply.
+/// For a `VoidCookie`, the result says whether the request caused an error.
+///
+/// Additionally, this is implemented for `Vec`s and tuples of entries.
+pub trait BatchEntry {
+    /// The result of this entry once all responses arrived.
+    type Output;
+
+    /// Wait for the responses to this entry.
+    fn collect(self) -> Self::Output;
+}
+
+impl<C, R> BatchEntry for Result<Cookie<'_, C, R>, ConnectionError>
+where
+    C: RequestConnection + ?Sized,
+    R: TryParse,
+{
+    type Output = Result<R, ReplyError>;
+
+    fn collect(self) -> Self::Output {
+        self?.reply()
+    }
+}
+
+impl<C, R> BatchEntry for Result<CookieWithFds<'_, C, R>, ConnectionError>
+where
+    C: RequestConnection + ?Sized,
+    R: TryParseFd,
+{
+    type Output = Result<R, ReplyError>;
+
+    fn collect(self) -> Self::Output {
+        self?.reply()
+    }
+}
+
+impl<C> BatchEntry for Result<VoidCookie<'_, C>, ConnectionError>
+where
+    C: RequestConnection + ?Sized,
+{
+    type Output = Result<(), ReplyError>;
+
+    fn collect(self) -> Self::Output {
+        self?.check()
+    }
+}
+
+impl<E: BatchEntry> BatchEntry for Vec<E> {
+    type Output = Vec<E::Output>;
+
+    fn collect(self) -> Self::Output {
+        self.into_iter().map(BatchEntry::collect).collect()
+    }
+}
+
+impl BatchEntry for () {
+    type Output = ();
+
+    fn collect(self) -> Self::Output {}
+}
+
+/// Helper trait for adding another entry to the entries of a [`RequestBatch`].
+///
+/// This is implemented for tuples with up to eleven elements.
+pub trait BatchAppend<N> {
+    /// The entries with the new entry added at the end.
+    type Output;
+
+    /// Add the new entry at the end.
+    fn append(self, entry: N) -> Self::Output;
+}
+
+macro_rules! impl_batch_entry {
+    ($($name:ident)*) => {
+        impl<$($name: BatchEntry),*> BatchEntry for ($($name,)*) {
+            type Output = ($($name::Output,)*);
+
+            #[allow(non_snake_case)]
+            fn collect(self) -> Self::Output {
+                let ($($name,)*) = self;
+                ($($name.collect(),)*)
+            }
+        }
+    };
+}
+
+macro_rules! impl_batch_append {
+    ($($name:ident)*) => {
+        impl<$($name,)* N> BatchAppend<N> for ($($name,)*) {
+            type Output = ($($name,)* N,);
+
+            #[allow(non_snake_case)]
+            fn append(self, entry: N) -> Self::Output {
+                let ($($name,)*) = self;
+                ($($name,)* entry,)
+            }
+        }
+    };
+}
+
+impl_batch_entry!(T1);
+impl_batch_entry!(T1 T2);
+impl_batch_entry!(T1 T2 T3);
+impl_batch_entry!(T1 T2 T3 T4);
+impl_batch_entry!(T1 T2 T3 T4 T5);
+impl_batch_entry!(T1 T2 T3 T4 T5 T6);
+impl_batch_entry!(T1 T2 T3 T4 T5 T6 T7);
+impl_batch_entry!(T1 T2 T3 T4 T5 T6 T7 T8);
+impl_batch_entry!(T1 T2 T3 T4 T5 T6 T7 T8 T9);
+impl_batch_entry!(T1 T2 T3 T4 T5 T6 T7 T8 T9 T10);
+impl_batch_entry!(T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11);
+impl_batch_entry!(T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11 T12);
+
+impl_batch_append!();
+impl_batch_append!(T1);
+impl_batch_append!(T1 T2);
+impl_batch_append!(T1 T2 T3);
+impl_batch_append!(T1 T2 T3 T4);
+impl_batch_append!(T1 T2 T3 T4 T5);
+impl_batch_append!(T1 T2 T3 T4 T5 T6);
+impl_batch_append!(T1 T2 T3 T4 T5 T6 T7);
+impl_batch_append!(T1 T2 T3 T4 T5 T6 T7 T8);
+impl_batch_append!(T1 T2 T3 T4 T5 T6 T7 T8 T9);
+impl_batch_append!(T1 T2 T3 T4 T5 T6 T7 T8 T9 T10);
+impl_batch_append!(T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11);
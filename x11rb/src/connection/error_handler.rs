@@ -0,0 +1,68 @@
+//! Callbacks for X11 errors that would otherwise be delivered as events.
+
+use std::sync::{Arc, Mutex};
+
+use crate::connection::RequestConnection;
+use crate::x11_utils::X11Error;
+
+/// What should happen with an X11 error after an [`ErrorHandler`] saw it?
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorDisposition {
+    /// Deliver the error as an [`Event::Error`](crate::protocol::Event::Error) as usual.
+    Deliver,
+    /// Drop the error so that it never shows up as an event.
+    Discard,
+}
+
+/// A callback for X11 errors that would otherwise be delivered as events.
+///
+/// Errors for requests whose cookie was dropped or whose reply was fetched via one of the
+/// `*_unchecked` functions end up in the event queue. When an error handler is registered on a
+/// connection, it is called for each such error when the error is taken from the event queue. The
+/// [`X11Error`] contains the names of the failed request and of its extension, as far as they are
+/// known to the connection.
+///
+/// The handler is called without holding any locks of the connection, so it may use the
+/// connection.
+pub type ErrorHandler = Arc<dyn Fn(&X11Error) -> ErrorDisposition + Send + Sync>;
+
+/// The storage for an optional [`ErrorHandler`] of a connection.
+#[derive(Default)]
+pub(crate) struct ErrorHandlerSlot(Mutex<Option<ErrorHandler>>);
+
+impl ErrorHandlerSlot {
+    /// Replace the current error handler, returning the old one.
+    pub(crate) fn replace(&self, handler: Option<ErrorHandler>) -> Option<ErrorHandler> {
+        std::mem::replace(&mut *self.0.lock().unwrap(), handler)
+    }
+
+    /// Call the error handler if `packet` is an error.
+    ///
+    /// Returns `true` if the packet should be discarded.
+    pub(crate) fn should_discard<C>(&self, conn: &C, packet: &[u8]) -> bool
+    where
+        C: RequestConnection + ?Sized,
+    {
+        if packet.first() != Some(&0) {
+            return false;
+        }
+        let handler = match &*self.0.lock().unwrap() {
+            Some(handler) => Arc::clone(handler),
+            None => return false,
+        };
+        match conn.parse_error(packet) {
+            Ok(error) => handler(&error) == ErrorDisposition::Discard,
+            // Let the error surface when the event is parsed
+            Err(_) => false,
+        }
+    }
+}
+
+impl std::fmt::Debug for ErrorHandlerSlot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let has_handler = self.0.lock().unwrap().is_some();
+        f.debug_tuple("ErrorHandlerSlot")
+            .field(&has_handler)
+            .finish()
+    }
+}
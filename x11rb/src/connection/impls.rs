@@ -2,6 +2,7 @@
 //! `Box<Connection>`.
 
 use std::io::IoSlice;
+use std::time::Duration;
 
 use x11rb_protocol::x11_utils::{ReplyFDsRequest, ReplyRequest, VoidRequest};
 use x11rb_protocol::{DiscardMode, RawEventAndSeqNumber, SequenceNumber};
@@ -124,6 +125,22 @@ macro_rules! impl_deref_request_connection_inner {
             (**self).wait_for_reply_or_raw_error(sequence)
         }
 
+        fn wait_for_reply_or_error_timeout(
+            &self,
+            sequence: SequenceNumber,
+            timeout: Duration,
+        ) -> Result<Self::Buf, ReplyError> {
+            (**self).wait_for_reply_or_error_timeout(sequence, timeout)
+        }
+
+        fn wait_for_reply_or_raw_error_timeout(
+            &self,
+            sequence: SequenceNumber,
+            timeout: Duration,
+        ) -> Result<ReplyOrError<Self::Buf>, ConnectionError> {
+            (**self).wait_for_reply_or_raw_error_timeout(sequence, timeout)
+        }
+
         fn wait_for_reply(
             &self,
             sequence: SequenceNumber,
@@ -156,6 +173,22 @@ macro_rules! impl_deref_request_connection_inner {
             (**self).check_for_raw_error(sequence)
         }
 
+        fn check_for_error_timeout(
+            &self,
+            sequence: SequenceNumber,
+            timeout: Duration,
+        ) -> Result<(), ReplyError> {
+            (**self).check_for_error_timeout(sequence, timeout)
+        }
+
+        fn check_for_raw_error_timeout(
+            &self,
+            sequence: SequenceNumber,
+            timeout: Duration,
+        ) -> Result<Option<Self::Buf>, ConnectionError> {
+            (**self).check_for_raw_error_timeout(sequence, timeout)
+        }
+
         fn prefetch_maximum_request_bytes(&self) {
             (**self).prefetch_maximum_request_bytes()
         }
@@ -194,6 +227,17 @@ macro_rules! impl_deref_connection_inner {
             (**self).wait_for_raw_event_with_sequence()
         }
 
+        fn wait_for_event_timeout(&self, timeout: Duration) -> Result<Event, ConnectionError> {
+            (**self).wait_for_event_timeout(timeout)
+        }
+
+        fn wait_for_raw_event_with_sequence_timeout(
+            &self,
+            timeout: Duration,
+        ) -> Result<RawEventAndSeqNumber<Self::Buf>, ConnectionError> {
+            (**self).wait_for_raw_event_with_sequence_timeout(timeout)
+        }
+
         fn poll_for_event(&self) -> Result<Option<Event>, ConnectionError> {
             (**self).poll_for_event()
         }
@@ -214,6 +258,39 @@ macro_rules! impl_deref_connection_inner {
             (**self).poll_for_raw_event_with_sequence()
         }
 
+        fn poll_for_event_matching<F>(&self, predicate: F) -> Result<Option<Event>, ConnectionError>
+        where
+            F: FnMut(&Event) -> bool,
+        {
+            (**self).poll_for_event_matching(predicate)
+        }
+
+        fn wait_for_event_matching<F>(
+            &self,
+            predicate: F,
+            timeout: Option<Duration>,
+        ) -> Result<Event, ConnectionError>
+        where
+            F: FnMut(&Event) -> bool,
+        {
+            (**self).wait_for_event_matching(predicate, timeout)
+        }
+
+        fn poll_for_raw_event_with_sequence_matching(
+            &self,
+            predicate: &mut dyn FnMut(&Event) -> bool,
+        ) -> Result<Option<RawEventAndSeqNumber<Self::Buf>>, ConnectionError> {
+            (**self).poll_for_raw_event_with_sequence_matching(predicate)
+        }
+
+        fn wait_for_raw_event_with_sequence_matching(
+            &self,
+            predicate: &mut dyn FnMut(&Event) -> bool,
+            timeout: Option<Duration>,
+        ) -> Result<RawEventAndSeqNumber<Self::Buf>, ConnectionError> {
+            (**self).wait_for_raw_event_with_sequence_matching(predicate, timeout)
+        }
+
         fn flush(&self) -> Result<(), ConnectionError> {
             (**self).flush()
         }
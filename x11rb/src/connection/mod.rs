@@ -4,6 +4,7 @@
 //! used by each concrete implementation of the X11 protocol.
 
 use std::io::IoSlice;
+use std::time::Duration;
 
 use x11rb_protocol::x11_utils::{ReplyFDsRequest, ReplyRequest, VoidRequest};
 
@@ -16,7 +17,15 @@ use crate::x11_utils::{ExtensionInformation, TryParse, TryParseFd, X11Error};
 
 pub use x11rb_protocol::{DiscardMode, RawEventAndSeqNumber, SequenceNumber};
 
+mod batch;
+mod error_handler;
 mod impls;
+mod stats;
+
+pub use batch::{BatchAppend, BatchEntry, RequestBatch};
+pub(crate) use error_handler::ErrorHandlerSlot;
+pub use error_handler::{ErrorDisposition, ErrorHandler};
+pub use stats::{ConnectionStats, RequestStats};
 
 // Used to avoid too-complex types.
 /// A combination of a buffer and a list of file descriptors.
@@ -257,6 +266,44 @@ pub trait RequestConnection {
         sequence: SequenceNumber,
     ) -> Result<ReplyOrError<Self::Buf>, ConnectionError>;
 
+    /// Wait for the reply to a request, but at most for the given duration.
+    ///
+    /// This works like [`RequestConnection::wait_for_reply_or_error`], but returns
+    /// [`ConnectionError::Timeout`] if no reply or error arrived before `timeout` elapsed. In this
+    /// case, the connection stays usable and the caller may wait again for the same request.
+    ///
+    /// Users of this library will most likely not want to use this function directly.
+    fn wait_for_reply_or_error_timeout(
+        &self,
+        sequence: SequenceNumber,
+        timeout: Duration,
+    ) -> Result<Self::Buf, ReplyError> {
+        match self.wait_for_reply_or_raw_error_timeout(sequence, timeout)? {
+            ReplyOrError::Reply(reply) => Ok(reply),
+            ReplyOrError::Error(error) => {
+                Err(ReplyError::X11Error(self.parse_error(error.as_ref())?))
+            }
+        }
+    }
+
+    /// Wait for the reply to a request, but at most for the given duration.
+    ///
+    /// This works like [`RequestConnection::wait_for_reply_or_raw_error`], but returns
+    /// [`ConnectionError::Timeout`] if no reply or error arrived before `timeout` elapsed. In this
+    /// case, the connection stays usable and the caller may wait again for the same request.
+    ///
+    /// The default implementation ignores the timeout and calls `wait_for_reply_or_raw_error()`.
+    ///
+    /// Users of this library will most likely not want to use this function directly.
+    fn wait_for_reply_or_raw_error_timeout(
+        &self,
+        sequence: SequenceNumber,
+        timeout: Duration,
+    ) -> Result<ReplyOrError<Self::Buf>, ConnectionError> {
+        let _ = timeout;
+        self.wait_for_reply_or_raw_error(sequence)
+    }
+
     /// Wait for the reply to a request.
     ///
     /// The given sequence number identifies the request for which replies are expected. If the X11
@@ -318,6 +365,44 @@ pub trait RequestConnection {
         sequence: SequenceNumber,
     ) -> Result<Option<Self::Buf>, ConnectionError>;
 
+    /// Check whether a request that does not have a reply caused an X11 error, but wait at most
+    /// for the given duration.
+    ///
+    /// This works like [`RequestConnection::check_for_error`], but returns
+    /// [`ConnectionError::Timeout`] if the check could not be completed before `timeout` elapsed.
+    /// In this case, the connection stays usable.
+    ///
+    /// Users of this library will most likely not want to use this function directly.
+    fn check_for_error_timeout(
+        &self,
+        sequence: SequenceNumber,
+        timeout: Duration,
+    ) -> Result<(), ReplyError> {
+        match self.check_for_raw_error_timeout(sequence, timeout)? {
+            Some(err) => Err(self.parse_error(err.as_ref())?.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Check whether a request that does not have a reply caused an X11 error, but wait at most
+    /// for the given duration.
+    ///
+    /// This works like [`RequestConnection::check_for_raw_error`], but returns
+    /// [`ConnectionError::Timeout`] if the check could not be completed before `timeout` elapsed.
+    /// In this case, the connection stays usable.
+    ///
+    /// The default implementation ignores the timeout and calls `check_for_raw_error()`.
+    ///
+    /// Users of this library will most likely not want to use this function directly.
+    fn check_for_raw_error_timeout(
+        &self,
+        sequence: SequenceNumber,
+        timeout: Duration,
+    ) -> Result<Option<Self::Buf>, ConnectionError> {
+        let _ = timeout;
+        self.check_for_raw_error(sequence)
+    }
+
     /// Prefetches the maximum request length.
     ///
     /// If the maximum request length is not cached yet, this function sends a `BigRequests::Enable`
@@ -379,6 +464,30 @@ pub trait Connection: RequestConnection {
         &self,
     ) -> Result<RawEventAndSeqNumber<Self::Buf>, ConnectionError>;
 
+    /// Wait for a new event from the X11 server, but at most for the given duration.
+    ///
+    /// If no event arrives before `timeout` elapsed, [`ConnectionError::Timeout`] is returned and
+    /// the connection stays usable.
+    fn wait_for_event_timeout(&self, timeout: Duration) -> Result<Event, ConnectionError> {
+        let (event, _seq) = self.wait_for_raw_event_with_sequence_timeout(timeout)?;
+        self.parse_event(event.as_ref()).map_err(Into::into)
+    }
+
+    /// Wait for a new raw/unparsed event from the X11 server, but at most for the given duration.
+    ///
+    /// If no event arrives before `timeout` elapsed, [`ConnectionError::Timeout`] is returned and
+    /// the connection stays usable.
+    ///
+    /// The default implementation ignores the timeout and calls
+    /// `wait_for_raw_event_with_sequence()`.
+    fn wait_for_raw_event_with_sequence_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<RawEventAndSeqNumber<Self::Buf>, ConnectionError> {
+        let _ = timeout;
+        self.wait_for_raw_event_with_sequence()
+    }
+
     /// Poll for a new event from the X11 server.
     fn poll_for_event(&self) -> Result<Option<Event>, ConnectionError> {
         Ok(self.poll_for_event_with_sequence()?.map(|r| r.0))
@@ -402,6 +511,84 @@ pub trait Connection: RequestConnection {
         &self,
     ) -> Result<Option<RawEventAndSeqNumber<Self::Buf>>, ConnectionError>;
 
+    /// Poll for the first event for which `predicate` returns `true`.
+    ///
+    /// This is similar to `XCheckIfEvent` from Xlib: Events for which `predicate` returns `false`
+    /// stay queued in their original order and are returned by later calls to e.g.
+    /// [`Connection::poll_for_event`]. Events that cannot be parsed never match.
+    ///
+    /// The predicate is called while the connection is internally locked and must not use the
+    /// connection.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use x11rb::connection::Connection;
+    /// # use x11rb::errors::ConnectionError;
+    /// # use x11rb::protocol::{xproto::Window, Event};
+    /// # fn example(conn: &impl Connection, window: Window) -> Result<(), ConnectionError> {
+    /// // Handle only the next Expose event for `window`; all other events stay queued
+    /// let expose = conn.poll_for_event_matching(|event| {
+    ///     matches!(event, Event::Expose(expose) if expose.window == window)
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn poll_for_event_matching<F>(&self, mut predicate: F) -> Result<Option<Event>, ConnectionError>
+    where
+        F: FnMut(&Event) -> bool,
+    {
+        Ok(
+            match self.poll_for_raw_event_with_sequence_matching(&mut predicate)? {
+                Some((event, _seq)) => Some(self.parse_event(event.as_ref())?),
+                None => None,
+            },
+        )
+    }
+
+    /// Wait for the first event for which `predicate` returns `true`.
+    ///
+    /// This works like [`Connection::poll_for_event_matching`], but waits until a matching event
+    /// arrives. With a `timeout`, [`ConnectionError::Timeout`] is returned if no matching event
+    /// arrives before it elapsed.
+    fn wait_for_event_matching<F>(
+        &self,
+        mut predicate: F,
+        timeout: Option<Duration>,
+    ) -> Result<Event, ConnectionError>
+    where
+        F: FnMut(&Event) -> bool,
+    {
+        let (event, _seq) =
+            self.wait_for_raw_event_with_sequence_matching(&mut predicate, timeout)?;
+        self.parse_event(event.as_ref()).map_err(Into::into)
+    }
+
+    /// Poll for the first raw/unparsed event for which `predicate` returns `true`.
+    ///
+    /// See [`Connection::poll_for_event_matching`]. The default implementation returns
+    /// [`ConnectionError::UnsupportedOperation`].
+    fn poll_for_raw_event_with_sequence_matching(
+        &self,
+        predicate: &mut dyn FnMut(&Event) -> bool,
+    ) -> Result<Option<RawEventAndSeqNumber<Self::Buf>>, ConnectionError> {
+        let _ = predicate;
+        Err(ConnectionError::UnsupportedOperation)
+    }
+
+    /// Wait for the first raw/unparsed event for which `predicate` returns `true`.
+    ///
+    /// See [`Connection::wait_for_event_matching`]. The default implementation returns
+    /// [`ConnectionError::UnsupportedOperation`].
+    fn wait_for_raw_event_with_sequence_matching(
+        &self,
+        predicate: &mut dyn FnMut(&Event) -> bool,
+        timeout: Option<Duration>,
+    ) -> Result<RawEventAndSeqNumber<Self::Buf>, ConnectionError> {
+        let _ = (predicate, timeout);
+        Err(ConnectionError::UnsupportedOperation)
+    }
+
     /// Send all pending requests to the server.
     ///
     /// Implementations of this trait may buffer requests for batched sending. When this method is
@@ -0,0 +1,139 @@
+//! Counters for the traffic on a connection.
+
+use std::collections::BTreeMap;
+use std::io::IoSlice;
+use std::time::Duration;
+
+/// Statistics about the traffic on a connection.
+///
+/// This is returned by e.g. [`RustConnection::stats`](crate::rust_connection::RustConnection::stats).
+/// All counters start at zero when the connection is established or when the statistics are
+/// reset.
+///
+/// The `record_*` methods are meant for implementations of connections. Most likely, you do not
+/// need them in your own code.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ConnectionStats {
+    /// The number of requests that were sent, including requests that the connection sent
+    /// internally.
+    pub requests: u64,
+    /// The number of replies that were received.
+    pub replies: u64,
+    /// The number of X11 errors that were received.
+    pub errors: u64,
+    /// The number of events that were received.
+    pub events: u64,
+    /// The number of bytes in all sent requests.
+    pub bytes_sent: u64,
+    /// The number of bytes in all received packets.
+    pub bytes_received: u64,
+    /// The number of times that buffered requests were flushed to the X11 server.
+    pub flushes: u64,
+    /// The number of times that the connection had to wait for the X11 server to answer a request.
+    ///
+    /// This counts waiting for replies and errors, including syncs for checking requests without
+    /// a reply. Waiting for events is not included, since that time is mostly spent idle.
+    pub round_trips: u64,
+    /// The total time spent waiting in [`ConnectionStats::round_trips`].
+    pub round_trip_time: Duration,
+    /// The statistics about core protocol requests.
+    pub core: RequestStats,
+    /// The statistics about extension requests by the major opcode of the extension.
+    ///
+    /// [`RequestConnection::extension_information`](crate::connection::RequestConnection::extension_information)
+    /// provides the major opcode of an extension.
+    pub extensions: BTreeMap<u8, RequestStats>,
+}
+
+/// Statistics about the requests of the core protocol or of an extension.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RequestStats {
+    /// The number of requests that were sent.
+    pub requests: u64,
+    /// The number of bytes in all sent requests.
+    pub bytes_sent: u64,
+}
+
+impl ConnectionStats {
+    /// Record that a request was sent.
+    ///
+    /// The major opcode is taken from the first byte of `bufs`.
+    pub fn record_request(&mut self, bufs: &[IoSlice<'_>]) {
+        let bytes = bufs.iter().map(|buf| buf.len() as u64).sum::<u64>();
+        let stats = match bufs.first().and_then(|buf| buf.first()) {
+            Some(&major_opcode) if major_opcode >= 128 => {
+                self.extensions.entry(major_opcode).or_default()
+            }
+            _ => &mut self.core,
+        };
+        stats.requests += 1;
+        stats.bytes_sent += bytes;
+        self.requests += 1;
+        self.bytes_sent += bytes;
+    }
+
+    /// Record that a packet was received from the X11 server.
+    pub fn record_packet(&mut self, packet: &[u8]) {
+        match packet.first() {
+            Some(0) => self.errors += 1,
+            Some(1) => self.replies += 1,
+            _ => self.events += 1,
+        }
+        self.bytes_received += packet.len() as u64;
+    }
+
+    /// Record that buffered requests were flushed.
+    pub fn record_flush(&mut self) {
+        self.flushes += 1;
+    }
+
+    /// Record that the connection waited for the X11 server to answer a request for the given
+    /// duration.
+    pub fn record_round_trip(&mut self, duration: Duration) {
+        self.round_trips += 1;
+        self.round_trip_time += duration;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::IoSlice;
+    use std::time::Duration;
+
+    use super::{ConnectionStats, RequestStats};
+
+    #[test]
+    fn split_by_extension() {
+        let mut stats = ConnectionStats::default();
+        stats.record_request(&[IoSlice::new(&[43, 0, 1, 0])]);
+        stats.record_request(&[IoSlice::new(&[130, 1, 2, 0]), IoSlice::new(&[0; 4])]);
+        stats.record_request(&[IoSlice::new(&[130, 2, 1, 0])]);
+        stats.record_packet(&[1; 32]);
+        stats.record_packet(&[0; 32]);
+        stats.record_packet(&[35; 40]);
+        stats.record_round_trip(Duration::from_millis(2));
+        stats.record_round_trip(Duration::from_millis(3));
+
+        assert_eq!((stats.requests, stats.bytes_sent), (3, 16));
+        assert_eq!(
+            stats.core,
+            RequestStats {
+                requests: 1,
+                bytes_sent: 4
+            }
+        );
+        assert_eq!(
+            stats.extensions.get(&130),
+            Some(&RequestStats {
+                requests: 2,
+                bytes_sent: 12
+            })
+        );
+        assert_eq!((stats.replies, stats.errors, stats.events), (1, 1, 1));
+        assert_eq!(stats.bytes_received, 104);
+        assert_eq!(stats.round_trips, 2);
+        assert_eq!(stats.round_trip_time, Duration::from_millis(5));
+    }
+}
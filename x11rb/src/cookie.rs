@@ -43,8 +43,32 @@
 //! | ------ | ---------------------------------- | ------------------------- |
 //! | Get    | `Cookie::reply`                    | `Cookie::reply_unchecked` |
 //! | Ignore | `Cookie::discard_reply_and_errors` | Just drop the cookie      |
+//!
+//! # Owned cookies
+//!
+//! All of the above cookies borrow the connection. When the connection is shared via an [`Arc`],
+//! a cookie can be turned into an owned variant via e.g. [`Cookie::into_owned`]. The owned cookies
+//! [`OwnedVoidCookie`], [`OwnedCookie`], and [`OwnedCookieWithFds`] keep the connection alive
+//! and can be stored in structs or sent to other threads. They behave exactly like their borrowing
+//! counterparts.
+//!
+//! ```no_run
+//! # use std::sync::Arc;
+//! # use x11rb::connection::Connection;
+//! # use x11rb::cookie::OwnedCookie;
+//! # use x11rb::protocol::xproto::{ConnectionExt as _, GetInputFocusReply};
+//! # fn example<C: Connection + Send + Sync + 'static>(conn: Arc<C>) {
+//! let cookie: OwnedCookie<C, GetInputFocusReply> = conn.get_input_focus().unwrap().into_owned();
+//! std::thread::spawn(move || {
+//!     let reply = cookie.reply().unwrap();
+//!     println!("The input focus is {:?}", reply.focus);
+//! });
+//! # }
+//! ```
 
 use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
 
 use crate::connection::{BufWithFds, RequestConnection, RequestKind};
 use crate::errors::{ConnectionError, ReplyError};
@@ -103,6 +127,24 @@ where
         connection.check_for_error(sequence)
     }
 
+    /// Check if the original request caused an X11 error, but wait at most for the given
+    /// duration.
+    ///
+    /// If the check cannot be completed in time, [`ConnectionError::Timeout`] is returned. The
+    /// cookie is then dropped, i.e. a later error is treated as an event.
+    pub fn check_timeout(self, timeout: Duration) -> Result<(), ReplyError> {
+        let (connection, sequence) = (self.connection, self.sequence_number);
+        match connection.check_for_error_timeout(sequence, timeout) {
+            Err(ReplyError::ConnectionError(ConnectionError::Timeout)) => {
+                Err(ConnectionError::Timeout.into())
+            }
+            result => {
+                let _ = self.consume();
+                result
+            }
+        }
+    }
+
     /// Ignore all errors to this request.
     ///
     /// Without calling this method, an error becomes available on the connection as an event after
@@ -133,6 +175,17 @@ where
     }
 }
 
+impl<C> VoidCookie<'_, Arc<C>>
+where
+    C: RequestConnection + ?Sized,
+{
+    /// Turn this cookie into a cookie that owns a reference to the connection.
+    pub fn into_owned(self) -> OwnedVoidCookie<C> {
+        let (connection, sequence_number) = self.consume();
+        OwnedVoidCookie::new(Arc::clone(connection), sequence_number)
+    }
+}
+
 impl<C> Drop for VoidCookie<'_, C>
 where
     C: RequestConnection + ?Sized,
@@ -251,6 +304,23 @@ where
         conn.wait_for_reply_or_error(self.raw_cookie.into_sequence_number())
     }
 
+    /// Get the raw reply that the server sent, but wait at most for the given duration.
+    ///
+    /// If no reply arrives in time, [`ConnectionError::Timeout`] is returned. The reply is then
+    /// discarded just like when the cookie is dropped, i.e. a later error is treated as an event.
+    pub fn raw_reply_timeout(self, timeout: Duration) -> Result<C::Buf, ReplyError> {
+        let (conn, sequence) = (self.raw_cookie.connection, self.raw_cookie.sequence_number);
+        match conn.wait_for_reply_or_error_timeout(sequence, timeout) {
+            Err(ReplyError::ConnectionError(ConnectionError::Timeout)) => {
+                Err(ConnectionError::Timeout.into())
+            }
+            result => {
+                let _ = self.raw_cookie.into_sequence_number();
+                result
+            }
+        }
+    }
+
     /// Get the raw reply that the server sent, but have errors handled as events.
     pub fn raw_reply_unchecked(self) -> Result<Option<C::Buf>, ConnectionError> {
         let conn = self.raw_cookie.connection;
@@ -262,6 +332,13 @@ where
         Ok(R::try_parse(self.raw_reply()?.as_ref())?.0)
     }
 
+    /// Get the reply that the server sent, but wait at most for the given duration.
+    ///
+    /// See [`Cookie::raw_reply_timeout`] for what happens when the timeout expires.
+    pub fn reply_timeout(self, timeout: Duration) -> Result<R, ReplyError> {
+        Ok(R::try_parse(self.raw_reply_timeout(timeout)?.as_ref())?.0)
+    }
+
     /// Get the reply that the server sent, but have errors handled as events.
     pub fn reply_unchecked(self) -> Result<Option<R>, ConnectionError> {
         self.raw_reply_unchecked()?
@@ -303,6 +380,18 @@ where
     }
 }
 
+impl<C, R> Cookie<'_, Arc<C>, R>
+where
+    R: TryParse,
+    C: RequestConnection + ?Sized,
+{
+    /// Turn this cookie into a cookie that owns a reference to the connection.
+    pub fn into_owned(self) -> OwnedCookie<C, R> {
+        let connection = Arc::clone(self.raw_cookie.connection);
+        OwnedCookie::new(connection, self.into_sequence_number())
+    }
+}
+
 /// A handle to a response containing `RawFd` from the X11 server.
 ///
 /// When sending a request to the X11 server, this library returns a `Cookie`. This `Cookie` can
@@ -370,6 +459,255 @@ where
     }
 }
 
+impl<C, R> CookieWithFds<'_, Arc<C>, R>
+where
+    R: TryParseFd,
+    C: RequestConnection + ?Sized,
+{
+    /// Turn this cookie into a cookie that owns a reference to the connection.
+    pub fn into_owned(self) -> OwnedCookieWithFds<C, R> {
+        let connection = Arc::clone(self.raw_cookie.connection);
+        OwnedCookieWithFds::new(connection, self.raw_cookie.into_sequence_number())
+    }
+}
+
+/// Internal helper for cookies that own a reference to their connection
+#[derive(Debug)]
+struct OwnedRawCookie<C>
+where
+    C: RequestConnection + ?Sized,
+{
+    connection: Arc<C>,
+    // This is only `None` while the cookie is being consumed
+    sequence_number: Option<SequenceNumber>,
+    kind: RequestKind,
+}
+
+impl<C> OwnedRawCookie<C>
+where
+    C: RequestConnection + ?Sized,
+{
+    fn new(connection: Arc<C>, sequence_number: SequenceNumber, kind: RequestKind) -> Self {
+        OwnedRawCookie {
+            connection,
+            sequence_number: Some(sequence_number),
+            kind,
+        }
+    }
+
+    fn sequence_number(&self) -> SequenceNumber {
+        self.sequence_number
+            .expect("Owned cookie was already consumed")
+    }
+
+    /// Consume this instance without discarding the response.
+    fn into_parts(mut self) -> (Arc<C>, SequenceNumber) {
+        let sequence_number = self.sequence_number();
+        // Prevent drop() from discarding the reply
+        self.sequence_number = None;
+        (Arc::clone(&self.connection), sequence_number)
+    }
+}
+
+impl<C> Drop for OwnedRawCookie<C>
+where
+    C: RequestConnection + ?Sized,
+{
+    fn drop(&mut self) {
+        if let Some(sequence_number) = self.sequence_number.take() {
+            self.connection
+                .discard_reply(sequence_number, self.kind, DiscardMode::DiscardReply);
+        }
+    }
+}
+
+/// A handle to a possible error from the X11 server that owns a reference to the connection.
+///
+/// This is the owned variant of [`VoidCookie`], see [crate::cookie#owned-cookies].
+#[derive(Debug)]
+pub struct OwnedVoidCookie<C>
+where
+    C: RequestConnection + ?Sized,
+{
+    raw_cookie: OwnedRawCookie<C>,
+}
+
+impl<C> OwnedVoidCookie<C>
+where
+    C: RequestConnection + ?Sized,
+{
+    /// Construct a new cookie for the request with the given sequence number.
+    pub fn new(connection: Arc<C>, sequence_number: SequenceNumber) -> Self {
+        OwnedVoidCookie {
+            raw_cookie: OwnedRawCookie::new(connection, sequence_number, RequestKind::IsVoid),
+        }
+    }
+
+    /// Get the sequence number of the request that generated this cookie.
+    pub fn sequence_number(&self) -> SequenceNumber {
+        self.raw_cookie.sequence_number()
+    }
+
+    /// Get the connection that this cookie refers to.
+    pub fn connection(&self) -> &Arc<C> {
+        &self.raw_cookie.connection
+    }
+
+    /// Check if the original request caused an X11 error.
+    pub fn check(self) -> Result<(), ReplyError> {
+        let (connection, sequence) = self.raw_cookie.into_parts();
+        VoidCookie::new(&*connection, sequence).check()
+    }
+
+    /// Check if the original request caused an X11 error, but wait at most for the given
+    /// duration.
+    ///
+    /// See [`VoidCookie::check_timeout`] for what happens when the timeout expires.
+    pub fn check_timeout(self, timeout: Duration) -> Result<(), ReplyError> {
+        let (connection, sequence) = self.raw_cookie.into_parts();
+        VoidCookie::new(&*connection, sequence).check_timeout(timeout)
+    }
+
+    /// Ignore all errors to this request.
+    ///
+    /// See [`VoidCookie::ignore_error`].
+    pub fn ignore_error(self) {
+        let (connection, sequence) = self.raw_cookie.into_parts();
+        VoidCookie::new(&*connection, sequence).ignore_error()
+    }
+}
+
+/// A handle to a response from the X11 server that owns a reference to the connection.
+///
+/// This is the owned variant of [`Cookie`], see [crate::cookie#owned-cookies].
+#[derive(Debug)]
+pub struct OwnedCookie<C, R>
+where
+    C: RequestConnection + ?Sized,
+{
+    raw_cookie: OwnedRawCookie<C>,
+    phantom: PhantomData<R>,
+}
+
+impl<C, R> OwnedCookie<C, R>
+where
+    R: TryParse,
+    C: RequestConnection + ?Sized,
+{
+    /// Construct a new cookie for the request with the given sequence number.
+    pub fn new(connection: Arc<C>, sequence_number: SequenceNumber) -> Self {
+        OwnedCookie {
+            raw_cookie: OwnedRawCookie::new(connection, sequence_number, RequestKind::HasResponse),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Get the sequence number of the request that generated this cookie.
+    pub fn sequence_number(&self) -> SequenceNumber {
+        self.raw_cookie.sequence_number()
+    }
+
+    /// Get the connection that this cookie refers to.
+    pub fn connection(&self) -> &Arc<C> {
+        &self.raw_cookie.connection
+    }
+
+    fn with_cookie<T>(self, f: impl FnOnce(Cookie<'_, C, R>) -> T) -> T {
+        let (connection, sequence) = self.raw_cookie.into_parts();
+        f(Cookie::new(&*connection, sequence))
+    }
+
+    /// Get the raw reply that the server sent.
+    pub fn raw_reply(self) -> Result<C::Buf, ReplyError> {
+        self.with_cookie(|cookie| cookie.raw_reply())
+    }
+
+    /// Get the raw reply that the server sent, but wait at most for the given duration.
+    ///
+    /// See [`Cookie::raw_reply_timeout`] for what happens when the timeout expires.
+    pub fn raw_reply_timeout(self, timeout: Duration) -> Result<C::Buf, ReplyError> {
+        self.with_cookie(|cookie| cookie.raw_reply_timeout(timeout))
+    }
+
+    /// Get the raw reply that the server sent, but have errors handled as events.
+    pub fn raw_reply_unchecked(self) -> Result<Option<C::Buf>, ConnectionError> {
+        self.with_cookie(|cookie| cookie.raw_reply_unchecked())
+    }
+
+    /// Get the reply that the server sent.
+    pub fn reply(self) -> Result<R, ReplyError> {
+        self.with_cookie(|cookie| cookie.reply())
+    }
+
+    /// Get the reply that the server sent, but wait at most for the given duration.
+    ///
+    /// See [`Cookie::raw_reply_timeout`] for what happens when the timeout expires.
+    pub fn reply_timeout(self, timeout: Duration) -> Result<R, ReplyError> {
+        self.with_cookie(|cookie| cookie.reply_timeout(timeout))
+    }
+
+    /// Get the reply that the server sent, but have errors handled as events.
+    pub fn reply_unchecked(self) -> Result<Option<R>, ConnectionError> {
+        self.with_cookie(|cookie| cookie.reply_unchecked())
+    }
+
+    /// Discard all responses to the request this cookie represents, even errors.
+    ///
+    /// Without this function, errors are treated as events after the cookie is dropped.
+    pub fn discard_reply_and_errors(self) {
+        self.with_cookie(|cookie| cookie.discard_reply_and_errors())
+    }
+}
+
+/// A handle to a response containing `RawFd` from the X11 server that owns a reference to the
+/// connection.
+///
+/// This is the owned variant of [`CookieWithFds`], see [crate::cookie#owned-cookies].
+#[derive(Debug)]
+pub struct OwnedCookieWithFds<C, R>
+where
+    C: RequestConnection + ?Sized,
+{
+    raw_cookie: OwnedRawCookie<C>,
+    phantom: PhantomData<R>,
+}
+
+impl<C, R> OwnedCookieWithFds<C, R>
+where
+    R: TryParseFd,
+    C: RequestConnection + ?Sized,
+{
+    /// Construct a new cookie for the request with the given sequence number.
+    pub fn new(connection: Arc<C>, sequence_number: SequenceNumber) -> Self {
+        OwnedCookieWithFds {
+            raw_cookie: OwnedRawCookie::new(connection, sequence_number, RequestKind::HasResponse),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Get the sequence number of the request that generated this cookie.
+    pub fn sequence_number(&self) -> SequenceNumber {
+        self.raw_cookie.sequence_number()
+    }
+
+    /// Get the connection that this cookie refers to.
+    pub fn connection(&self) -> &Arc<C> {
+        &self.raw_cookie.connection
+    }
+
+    /// Get the raw reply that the server sent.
+    pub fn raw_reply(self) -> Result<BufWithFds<C::Buf>, ReplyError> {
+        let (connection, sequence) = self.raw_cookie.into_parts();
+        CookieWithFds::<C, R>::new(&*connection, sequence).raw_reply()
+    }
+
+    /// Get the reply that the server sent.
+    pub fn reply(self) -> Result<R, ReplyError> {
+        let (connection, sequence) = self.raw_cookie.into_parts();
+        CookieWithFds::new(&*connection, sequence).reply()
+    }
+}
+
 macro_rules! multiple_reply_cookie {
     (
         $(#[$meta:meta])*
@@ -2,7 +2,9 @@
 
 use crate::x11_utils::X11Error;
 
-pub use x11rb_protocol::errors::{ConnectError, DisplayParsingError, IdsExhausted, ParseError};
+pub use x11rb_protocol::errors::{
+    ConnectError, DisplayParsingError, IdsExhausted, LimitExceeded, ParseError,
+};
 
 /// An error occurred  while dynamically loading libxcb.
 #[cfg(feature = "dl-libxcb")]
@@ -72,6 +74,23 @@ pub enum ConnectionError {
 
     /// An I/O error occurred on the connection.
     IoError(std::io::Error),
+
+    /// A deadline expired before the awaited reply or event arrived.
+    ///
+    /// Unlike the other variants, this does not mean that the connection is broken. The reply
+    /// that was awaited may still arrive later and can be waited for again.
+    Timeout,
+
+    /// The connection does not implement the requested operation.
+    ///
+    /// This does not mean that the connection is broken.
+    UnsupportedOperation,
+
+    /// The X11 server sent more data than the configured limits allow.
+    ///
+    /// See [`x11rb_protocol::connection::Limits`]. Data from the server was dropped, so the
+    /// connection cannot be used reliably anymore.
+    LimitExceeded(LimitExceeded),
 }
 
 impl std::error::Error for ConnectionError {}
@@ -88,6 +107,11 @@ impl std::fmt::Display for ConnectionError {
             ConnectionError::FdPassingFailed => write!(f, "FD passing failed"),
             ConnectionError::ParseError(err) => err.fmt(f),
             ConnectionError::IoError(err) => err.fmt(f),
+            ConnectionError::Timeout => write!(f, "Operation timed out"),
+            ConnectionError::UnsupportedOperation => {
+                write!(f, "Operation not supported by this connection")
+            }
+            ConnectionError::LimitExceeded(err) => err.fmt(f),
         }
     }
 }
@@ -98,9 +122,22 @@ impl From<ParseError> for ConnectionError {
     }
 }
 
+impl From<LimitExceeded> for ConnectionError {
+    fn from(err: LimitExceeded) -> Self {
+        ConnectionError::LimitExceeded(err)
+    }
+}
+
 impl From<std::io::Error> for ConnectionError {
     fn from(err: std::io::Error) -> Self {
-        ConnectionError::IoError(err)
+        // The I/O code reports exceeded limits as an I/O error that wraps a LimitExceeded
+        match err
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<LimitExceeded>())
+        {
+            Some(limit) => ConnectionError::LimitExceeded(*limit),
+            None => ConnectionError::IoError(err),
+        }
     }
 }
 
@@ -0,0 +1,366 @@
+//! Merging runs of events where only the latest one matters.
+//!
+//! Moving the pointer, resizing a window, or uncovering parts of a window produces many
+//! `MotionNotify`, `ConfigureNotify`, or `Expose` events in a short time. An application that
+//! cannot keep up with these events does not need to handle each one of them individually. The
+//! [`EventCoalescer`] in this module merges such events before they are handled.
+
+use std::collections::VecDeque;
+
+use crate::connection::Connection;
+use crate::errors::ConnectionError;
+use crate::protocol::xproto::{ConfigureNotifyEvent, ExposeEvent, MotionNotifyEvent};
+use crate::protocol::Event;
+
+/// The maximum number of events that [`EventCoalescer::drain`] fetches from the connection.
+///
+/// Without a limit, a client that sends events faster than they are handled would keep the
+/// queue growing forever.
+const DRAIN_LIMIT: usize = 1024;
+
+/// A queue of events that merges consecutive events of the same kind.
+///
+/// When an event is added to the queue and the last event in the queue is of the same kind and
+/// refers to the same window, the two events are merged:
+///
+/// - For `MotionNotify`, only the newer event is kept. This requires the same `event` and `child`
+///   windows and the same `state`.
+/// - For `Expose`, the result covers the bounding box of both rectangles. Its `count` is that of
+///   the newer event, so a `count` of zero still marks the end of a series of `Expose` events.
+/// - For `ConfigureNotify`, only the newer event is kept.
+///
+/// Only directly consecutive events are merged, so the order of all events stays the same. For
+/// example, a `MotionNotify`, a `ButtonPress`, and another `MotionNotify` are all kept.
+///
+/// # Example
+///
+/// ```no_run
+/// # use x11rb::connection::Connection;
+/// # use x11rb::errors::ConnectionError;
+/// # use x11rb::event_coalescer::EventCoalescer;
+/// # fn example(conn: &impl Connection) -> Result<(), ConnectionError> {
+/// let mut events = EventCoalescer::new();
+/// loop {
+///     let event = events.wait_for_event(conn)?;
+///     println!("{:?}", event);
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct EventCoalescer {
+    queue: VecDeque<Event>,
+    motion: bool,
+    expose: bool,
+    configure: bool,
+}
+
+impl Default for EventCoalescer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventCoalescer {
+    /// Create a new, empty queue that merges all supported kinds of events.
+    pub fn new() -> Self {
+        Self {
+            queue: VecDeque::new(),
+            motion: true,
+            expose: true,
+            configure: true,
+        }
+    }
+
+    /// Configure whether `MotionNotify` events are merged.
+    pub fn motion(mut self, enabled: bool) -> Self {
+        self.motion = enabled;
+        self
+    }
+
+    /// Configure whether `Expose` events are merged.
+    pub fn expose(mut self, enabled: bool) -> Self {
+        self.expose = enabled;
+        self
+    }
+
+    /// Configure whether `ConfigureNotify` events are merged.
+    pub fn configure(mut self, enabled: bool) -> Self {
+        self.configure = enabled;
+        self
+    }
+
+    /// Add an event at the end of the queue, merging it with the previous event if possible.
+    pub fn push(&mut self, event: Event) {
+        let merged = match (self.queue.back_mut(), &event) {
+            (Some(Event::MotionNotify(last)), Event::MotionNotify(new)) if self.motion => {
+                merge_motion(last, new)
+            }
+            (Some(Event::Expose(last)), Event::Expose(new)) if self.expose => {
+                merge_expose(last, new)
+            }
+            (Some(Event::ConfigureNotify(last)), Event::ConfigureNotify(new)) if self.configure => {
+                merge_configure(last, new)
+            }
+            _ => false,
+        };
+        if !merged {
+            self.queue.push_back(event);
+        }
+    }
+
+    /// Take the oldest event out of the queue.
+    pub fn pop(&mut self) -> Option<Event> {
+        self.queue.pop_front()
+    }
+
+    /// Get the number of events in the queue.
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Check whether the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Move the events that the connection can provide without blocking into this queue.
+    ///
+    /// At most 1024 events are fetched per call, so that this returns even if events keep
+    /// arriving.
+    pub fn drain<C: Connection + ?Sized>(&mut self, conn: &C) -> Result<(), ConnectionError> {
+        for _ in 0..DRAIN_LIMIT {
+            match conn.poll_for_event()? {
+                Some(event) => self.push(event),
+                None => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the next event without blocking.
+    ///
+    /// The events that are available without blocking are fetched from the connection first, so
+    /// that they can be merged. See [`EventCoalescer::drain`].
+    pub fn poll_for_event<C: Connection + ?Sized>(
+        &mut self,
+        conn: &C,
+    ) -> Result<Option<Event>, ConnectionError> {
+        self.drain(conn)?;
+        Ok(self.pop())
+    }
+
+    /// Get the next event, waiting for one if none is available.
+    ///
+    /// This only blocks if neither this queue nor the connection has an event available.
+    pub fn wait_for_event<C: Connection + ?Sized>(
+        &mut self,
+        conn: &C,
+    ) -> Result<Event, ConnectionError> {
+        if self.is_empty() {
+            let event = conn.wait_for_event()?;
+            self.push(event);
+        }
+        self.drain(conn)?;
+        Ok(self
+            .pop()
+            .expect("The queue should contain at least one event"))
+    }
+}
+
+fn merge_motion(last: &mut MotionNotifyEvent, new: &MotionNotifyEvent) -> bool {
+    let same_target = (last.event, last.child, last.state, last.same_screen)
+        == (new.event, new.child, new.state, new.same_screen);
+    if same_target {
+        *last = *new;
+    }
+    same_target
+}
+
+fn merge_expose(last: &mut ExposeEvent, new: &ExposeEvent) -> bool {
+    if last.window != new.window {
+        return false;
+    }
+    let left = last.x.min(new.x);
+    let top = last.y.min(new.y);
+    let right =
+        (u32::from(last.x) + u32::from(last.width)).max(u32::from(new.x) + u32::from(new.width));
+    let bottom =
+        (u32::from(last.y) + u32::from(last.height)).max(u32::from(new.y) + u32::from(new.height));
+    *last = ExposeEvent {
+        x: left,
+        y: top,
+        width: (right - u32::from(left)).try_into().unwrap_or(u16::MAX),
+        height: (bottom - u32::from(top)).try_into().unwrap_or(u16::MAX),
+        ..*new
+    };
+    true
+}
+
+fn merge_configure(last: &mut ConfigureNotifyEvent, new: &ConfigureNotifyEvent) -> bool {
+    let same_target = (last.event, last.window) == (new.event, new.window);
+    if same_target {
+        *last = *new;
+    }
+    same_target
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use super::{EventCoalescer, DRAIN_LIMIT};
+    use crate::protocol::xproto::{
+        ButtonPressEvent, ConfigureNotifyEvent, ExposeEvent, MotionNotifyEvent, Setup,
+        MOTION_NOTIFY_EVENT,
+    };
+    use crate::protocol::Event;
+    use crate::rust_connection::{PollMode, RustConnection, Stream};
+    use crate::utils::RawFdContainer;
+
+    fn motion(event: u32, x: i16) -> Event {
+        Event::MotionNotify(MotionNotifyEvent {
+            event,
+            event_x: x,
+            ..Default::default()
+        })
+    }
+
+    fn expose(window: u32, x: u16, y: u16, width: u16, height: u16, count: u16) -> Event {
+        Event::Expose(ExposeEvent {
+            window,
+            x,
+            y,
+            width,
+            height,
+            count,
+            ..Default::default()
+        })
+    }
+
+    fn configure(window: u32, width: u16) -> Event {
+        Event::ConfigureNotify(ConfigureNotifyEvent {
+            event: window,
+            window,
+            width,
+            ..Default::default()
+        })
+    }
+
+    // `Event` only implements `PartialEq` with the `extra-traits` feature
+    fn assert_events(actual: &[Event], expected: &[Event]) {
+        assert_eq!(format!("{:?}", actual), format!("{:?}", expected));
+    }
+
+    fn collect(events: impl IntoIterator<Item = Event>) -> Vec<Event> {
+        let mut coalescer = EventCoalescer::new();
+        events.into_iter().for_each(|event| coalescer.push(event));
+        std::iter::from_fn(|| coalescer.pop()).collect()
+    }
+
+    #[test]
+    fn motion_keeps_latest() {
+        let events = collect([motion(1, 0), motion(1, 1), motion(2, 2), motion(2, 3)]);
+        assert_events(&events, &[motion(1, 1), motion(2, 3)]);
+    }
+
+    #[test]
+    fn other_events_are_barriers() {
+        let press = Event::ButtonPress(ButtonPressEvent::default());
+        let input = [
+            motion(1, 0),
+            press.clone(),
+            motion(1, 1),
+            configure(1, 10),
+            configure(1, 20),
+            motion(1, 2),
+            configure(1, 30),
+        ];
+        let expected = [
+            motion(1, 0),
+            press,
+            motion(1, 1),
+            configure(1, 20),
+            motion(1, 2),
+            configure(1, 30),
+        ];
+        assert_events(&collect(input), &expected);
+    }
+
+    #[test]
+    fn expose_union() {
+        let events = collect([
+            expose(1, 10, 10, 5, 5, 2),
+            expose(1, 0, 20, 5, 5, 1),
+            expose(1, 30, 0, 10, 10, 0),
+            expose(2, 0, 0, 1, 1, 0),
+        ]);
+        assert_events(
+            &events,
+            &[expose(1, 0, 0, 40, 25, 0), expose(2, 0, 0, 1, 1, 0)],
+        );
+    }
+
+    #[test]
+    fn disabled_kinds_are_kept() {
+        let mut coalescer = EventCoalescer::new().motion(false).expose(false);
+        coalescer.push(motion(1, 0));
+        coalescer.push(motion(1, 1));
+        coalescer.push(expose(1, 0, 0, 1, 1, 1));
+        coalescer.push(expose(1, 0, 0, 1, 1, 0));
+        coalescer.push(configure(1, 1));
+        coalescer.push(configure(1, 2));
+        assert_eq!(coalescer.len(), 5);
+    }
+
+    /// A stream where the server never stops sending `MotionNotify` events.
+    ///
+    /// Every other read fails with `WouldBlock`, so that each read only provides some events.
+    #[derive(Debug, Default)]
+    struct FloodStream(Mutex<(usize, bool)>);
+
+    impl Stream for FloodStream {
+        fn poll(&self, _mode: PollMode) -> std::io::Result<()> {
+            Ok(())
+        }
+
+        fn read(&self, buf: &mut [u8], _fds: &mut Vec<RawFdContainer>) -> std::io::Result<usize> {
+            let (position, would_block) = &mut *self.0.lock().unwrap();
+            *would_block = !*would_block;
+            if !*would_block {
+                return Err(std::io::ErrorKind::WouldBlock.into());
+            }
+            for byte in buf.iter_mut() {
+                // Alternate between two windows so that the events cannot be merged
+                *byte = match *position % 64 {
+                    0 | 32 => MOTION_NOTIFY_EVENT,
+                    12 => 1,
+                    44 => 2,
+                    _ => 0,
+                };
+                *position += 1;
+            }
+            Ok(buf.len())
+        }
+
+        fn write(&self, buf: &[u8], _fds: &mut Vec<RawFdContainer>) -> std::io::Result<usize> {
+            Ok(buf.len())
+        }
+    }
+
+    #[test]
+    fn drain_is_bounded() {
+        let setup = Setup {
+            resource_id_mask: 0xff,
+            ..Default::default()
+        };
+        let conn = RustConnection::for_connected_stream(FloodStream::default(), setup).unwrap();
+        let mut coalescer = EventCoalescer::new();
+        coalescer.drain(&conn).unwrap();
+        assert_eq!(coalescer.len(), DRAIN_LIMIT);
+        assert!(matches!(coalescer.pop(), Some(Event::MotionNotify(event)) if event.event == 1));
+
+        // Events keep arriving, but getting one still returns
+        assert!(coalescer.poll_for_event(&conn).unwrap().is_some());
+        assert!(coalescer.wait_for_event(&conn).is_ok());
+    }
+}
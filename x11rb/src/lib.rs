@@ -111,7 +111,10 @@
 //! * `extra-traits`: Enable some additional traits for generated code, like `Eq`, `Ord` and
 //!   `Hash`. This is not needed by default and adds a large amount of code that bloats codegen
 //!   time
-//! * `request-parsing`: Add the ability to parse X11 requests. Not normally needed.
+//! * `backtrace`: Capture backtraces for errors found by
+//!   [`rust_connection::RustConnection::set_synchronous`]. This feature requires Rust 1.65.
+//! * `request-parsing`: Add the ability to parse X11 requests. Not normally needed. This also
+//!   enables the fake X11 server in [testing] for unit tests.
 //! * `extra-traits`: Implement extra traits for X11 types. This improves the output of the `Debug`
 //!   impl and adds `PartialEq`, `Eq`, `PartialOrd`, `Ord`, and `Hash` where possible.
 //!
@@ -167,12 +170,18 @@ pub mod cookie;
 #[cfg(feature = "cursor")]
 pub mod cursor;
 pub mod errors;
+pub mod event_coalescer;
 pub mod extension_manager;
 #[cfg(feature = "image")]
 pub mod image;
 pub mod properties;
 pub mod rust_connection;
+pub mod selection;
+#[cfg(feature = "request-parsing")]
+pub mod testing;
 pub mod wrapper;
+pub mod xdnd;
+pub mod xembed;
 #[rustfmt::skip]
 #[allow(missing_docs)]
 pub mod protocol;
@@ -0,0 +1,1380 @@
+//! Client-side support for the Extended Window Manager Hints (EWMH)
+//!
+//! The [EWMH specification](https://specifications.freedesktop.org/wm-spec/latest/) builds on
+//! ICCCM and describes properties and messages for the communication between applications, window
+//! managers and pagers. Unlike the ICCCM properties, none of the EWMH atoms are predefined by the
+//! X11 server. They have to be interned first, which is what [`EwmhAtoms`] is for.
+//!
+//! Usage example:
+//! ```no_run
+//! use x11rb::connection::Connection;
+//! use x11rb::errors::ReplyError;
+//! use x11rb::properties::ewmh::{self, ActiveWindow, EwmhAtoms, SourceIndication, WmName, WmStateAction};
+//! use x11rb::protocol::xproto::Window;
+//!
+//! fn make_fullscreen(conn: &impl Connection, screen_num: usize) -> Result<(), ReplyError> {
+//!     let root = conn.setup().roots[screen_num].root;
+//!     let atoms = EwmhAtoms::new(conn)?.reply()?;
+//!
+//!     let active = match ActiveWindow::get(conn, &atoms, root)?.reply()? {
+//!         Some(ActiveWindow(active)) => active,
+//!         None => return Ok(()), // The window manager does not support _NET_ACTIVE_WINDOW
+//!     };
+//!     if let Some(WmName(name)) = WmName::get(conn, &atoms, active)?.reply()? {
+//!         println!("Making '{}' fullscreen", name);
+//!     }
+//!     let message = ewmh::wm_state_message(
+//!         &atoms,
+//!         active,
+//!         WmStateAction::Add,
+//!         atoms._NET_WM_STATE_FULLSCREEN,
+//!         None,
+//!         SourceIndication::Pager,
+//!     );
+//!     ewmh::send_root_message(conn, root, message)?;
+//!     conn.flush()?;
+//!     Ok(())
+//! }
+//! ```
+
+use crate::connection::RequestConnection;
+use crate::cookie::{Cookie, VoidCookie};
+use crate::errors::{ConnectionError, ParseError, ReplyError};
+use crate::protocol::xproto::{
+    self, Atom, AtomEnum, Button, ClientMessageEvent, EventMask, GetPropertyReply, PropMode,
+    Timestamp, Window,
+};
+
+pub mod wm;
+
+crate::atom_manager! {
+    /// The atoms that are used by EWMH.
+    ///
+    /// The fields are named after the atoms they contain.
+    #[allow(missing_docs)]
+    pub EwmhAtoms:
+    /// A cookie for interning [`EwmhAtoms`].
+    EwmhAtomsCookie {
+        UTF8_STRING,
+        WM_PROTOCOLS,
+
+        // Root window properties
+        _NET_SUPPORTED,
+        _NET_CLIENT_LIST,
+        _NET_CLIENT_LIST_STACKING,
+        _NET_NUMBER_OF_DESKTOPS,
+        _NET_DESKTOP_GEOMETRY,
+        _NET_DESKTOP_VIEWPORT,
+        _NET_CURRENT_DESKTOP,
+        _NET_DESKTOP_NAMES,
+        _NET_ACTIVE_WINDOW,
+        _NET_WORKAREA,
+        _NET_SUPPORTING_WM_CHECK,
+        _NET_VIRTUAL_ROOTS,
+        _NET_SHOWING_DESKTOP,
+
+        // Root window messages
+        _NET_CLOSE_WINDOW,
+        _NET_MOVERESIZE_WINDOW,
+        _NET_WM_MOVERESIZE,
+        _NET_RESTACK_WINDOW,
+        _NET_REQUEST_FRAME_EXTENTS,
+
+        // Application window properties
+        _NET_WM_NAME,
+        _NET_WM_VISIBLE_NAME,
+        _NET_WM_ICON_NAME,
+        _NET_WM_VISIBLE_ICON_NAME,
+        _NET_WM_DESKTOP,
+        _NET_WM_WINDOW_TYPE,
+        _NET_WM_STATE,
+        _NET_WM_ALLOWED_ACTIONS,
+        _NET_WM_STRUT,
+        _NET_WM_STRUT_PARTIAL,
+        _NET_WM_ICON,
+        _NET_WM_PID,
+        _NET_WM_USER_TIME,
+        _NET_FRAME_EXTENTS,
+        _NET_WM_FULLSCREEN_MONITORS,
+
+        // Window manager protocols
+        _NET_WM_PING,
+
+        // Values of _NET_WM_WINDOW_TYPE
+        _NET_WM_WINDOW_TYPE_DESKTOP,
+        _NET_WM_WINDOW_TYPE_DOCK,
+        _NET_WM_WINDOW_TYPE_TOOLBAR,
+        _NET_WM_WINDOW_TYPE_MENU,
+        _NET_WM_WINDOW_TYPE_UTILITY,
+        _NET_WM_WINDOW_TYPE_SPLASH,
+        _NET_WM_WINDOW_TYPE_DIALOG,
+        _NET_WM_WINDOW_TYPE_DROPDOWN_MENU,
+        _NET_WM_WINDOW_TYPE_POPUP_MENU,
+        _NET_WM_WINDOW_TYPE_TOOLTIP,
+        _NET_WM_WINDOW_TYPE_NOTIFICATION,
+        _NET_WM_WINDOW_TYPE_COMBO,
+        _NET_WM_WINDOW_TYPE_DND,
+        _NET_WM_WINDOW_TYPE_NORMAL,
+
+        // Values of _NET_WM_STATE
+        _NET_WM_STATE_MODAL,
+        _NET_WM_STATE_STICKY,
+        _NET_WM_STATE_MAXIMIZED_VERT,
+        _NET_WM_STATE_MAXIMIZED_HORZ,
+        _NET_WM_STATE_SHADED,
+        _NET_WM_STATE_SKIP_TASKBAR,
+        _NET_WM_STATE_SKIP_PAGER,
+        _NET_WM_STATE_HIDDEN,
+        _NET_WM_STATE_FULLSCREEN,
+        _NET_WM_STATE_ABOVE,
+        _NET_WM_STATE_BELOW,
+        _NET_WM_STATE_DEMANDS_ATTENTION,
+        _NET_WM_STATE_FOCUSED,
+
+        // Values of _NET_WM_ALLOWED_ACTIONS
+        _NET_WM_ACTION_MOVE,
+        _NET_WM_ACTION_RESIZE,
+        _NET_WM_ACTION_MINIMIZE,
+        _NET_WM_ACTION_SHADE,
+        _NET_WM_ACTION_STICK,
+        _NET_WM_ACTION_MAXIMIZE_HORZ,
+        _NET_WM_ACTION_MAXIMIZE_VERT,
+        _NET_WM_ACTION_FULLSCREEN,
+        _NET_WM_ACTION_CHANGE_DESKTOP,
+        _NET_WM_ACTION_CLOSE,
+        _NET_WM_ACTION_ABOVE,
+        _NET_WM_ACTION_BELOW,
+    }
+}
+
+/// A value that is stored in an EWMH property.
+trait PropertyValue: Sized {
+    /// The format of the property, either 8 or 32.
+    const FORMAT: u8;
+
+    /// Parse the value of a property that has the expected type and format.
+    fn parse(reply: &GetPropertyReply) -> Result<Self, ParseError>;
+
+    /// Get the data of the property.
+    fn serialize(&self) -> Vec<u8>;
+}
+
+macro_rules! ewmh_property {
+    {
+        $(#[$meta:meta])*
+        pub struct $name:ident(pub $inner:ty);
+        $($rest:tt)*
+    } => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub struct $name(pub $inner);
+
+        impl PropertyValue for $name {
+            const FORMAT: u8 = <$inner as PropertyValue>::FORMAT;
+
+            fn parse(reply: &GetPropertyReply) -> Result<Self, ParseError> {
+                <$inner as PropertyValue>::parse(reply).map(Self)
+            }
+
+            fn serialize(&self) -> Vec<u8> {
+                self.0.serialize()
+            }
+        }
+
+        ewmh_property! { impl $name; $($rest)* }
+    };
+    {
+        impl $name:ident;
+        $(#[$cookie_meta:meta])*
+        pub struct $cookie_name:ident;
+        $property:ident: |$atoms:pat_param| $type_:expr,
+    } => {
+        $(#[$cookie_meta])*
+        #[derive(Debug)]
+        pub struct $cookie_name<'a, Conn: RequestConnection + ?Sized> {
+            cookie: Cookie<'a, Conn, GetPropertyReply>,
+            type_: Atom,
+        }
+
+        impl<'a, Conn> $cookie_name<'a, Conn>
+        where
+            Conn: RequestConnection + ?Sized,
+        {
+            #[doc = concat!(
+                "Send a `GetProperty` request for the `",
+                stringify!($property),
+                "` property of the given window."
+            )]
+            pub fn new(
+                conn: &'a Conn,
+                atoms: &EwmhAtoms,
+                window: Window,
+            ) -> Result<Self, ConnectionError> {
+                let type_ = $name::type_(atoms);
+                let cookie =
+                    xproto::get_property(conn, false, window, atoms.$property, type_, 0, u32::MAX)?;
+                Ok(Self { cookie, type_ })
+            }
+
+            /// Get the reply that the server sent.
+            pub fn reply(self) -> Result<Option<$name>, ReplyError> {
+                Ok(parse_property(&self.cookie.reply()?, self.type_)?)
+            }
+
+            /// Get the reply that the server sent, but have errors handled as events.
+            pub fn reply_unchecked(self) -> Result<Option<$name>, ConnectionError> {
+                let type_ = self.type_;
+                self.cookie
+                    .reply_unchecked()?
+                    .map(|reply| parse_property(&reply, type_))
+                    .transpose()
+                    .map(|e| e.flatten())
+                    .map_err(Into::into)
+            }
+        }
+
+        impl $name {
+            #[doc = concat!(
+                "Send a `GetProperty` request for the `",
+                stringify!($property),
+                "` property of the given window."
+            )]
+            pub fn get<'a, C: RequestConnection + ?Sized>(
+                conn: &'a C,
+                atoms: &EwmhAtoms,
+                window: Window,
+            ) -> Result<$cookie_name<'a, C>, ConnectionError> {
+                $cookie_name::new(conn, atoms, window)
+            }
+
+            #[doc = concat!(
+                "Parse the `",
+                stringify!($property),
+                "` property.\n\nReturns `None` if the property does not exist."
+            )]
+            pub fn from_reply(
+                reply: &GetPropertyReply,
+                atoms: &EwmhAtoms,
+            ) -> Result<Option<Self>, ParseError> {
+                parse_property(reply, Self::type_(atoms))
+            }
+
+            #[doc = concat!(
+                "Set the `",
+                stringify!($property),
+                "` property of the given window to this value."
+            )]
+            pub fn set<'a, C: RequestConnection + ?Sized>(
+                &self,
+                conn: &'a C,
+                atoms: &EwmhAtoms,
+                window: Window,
+            ) -> Result<VoidCookie<'a, C>, ConnectionError> {
+                let data = self.serialize();
+                let length = data.len() / usize::from(Self::FORMAT / 8);
+                xproto::change_property(
+                    conn,
+                    PropMode::REPLACE,
+                    window,
+                    atoms.$property,
+                    Self::type_(atoms),
+                    Self::FORMAT,
+                    length.try_into().expect("Property value too large"),
+                    &data,
+                )
+            }
+
+            fn type_($atoms: &EwmhAtoms) -> Atom {
+                $type_
+            }
+        }
+    };
+}
+
+/// Parse a property that is expected to have the given type.
+///
+/// Returns `Ok(None)` if the property does not exist.
+fn parse_property<T: PropertyValue>(
+    reply: &GetPropertyReply,
+    type_: Atom,
+) -> Result<Option<T>, ParseError> {
+    if reply.type_ == AtomEnum::NONE.into() {
+        return Ok(None);
+    }
+    if reply.type_ != type_ || reply.format != T::FORMAT {
+        return Err(ParseError::InvalidValue);
+    }
+    T::parse(reply).map(Some)
+}
+
+impl PropertyValue for String {
+    const FORMAT: u8 = 8;
+
+    fn parse(reply: &GetPropertyReply) -> Result<Self, ParseError> {
+        String::from_utf8(reply.value.clone()).map_err(|_| ParseError::InvalidValue)
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+impl PropertyValue for Vec<String> {
+    const FORMAT: u8 = 8;
+
+    fn parse(reply: &GetPropertyReply) -> Result<Self, ParseError> {
+        if reply.value.is_empty() {
+            return Ok(Vec::new());
+        }
+        // Each string is terminated by a NUL byte, but the terminator of the last one is optional
+        let value = reply.value.strip_suffix(&[0]).unwrap_or(&reply.value);
+        value
+            .split(|&byte| byte == 0)
+            .map(|string| String::from_utf8(string.to_vec()).map_err(|_| ParseError::InvalidValue))
+            .collect()
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        self.iter()
+            .flat_map(|string| string.bytes().chain(std::iter::once(0)))
+            .collect()
+    }
+}
+
+impl PropertyValue for Vec<u32> {
+    const FORMAT: u8 = 32;
+
+    fn parse(reply: &GetPropertyReply) -> Result<Self, ParseError> {
+        Ok(reply.value32().ok_or(ParseError::InvalidValue)?.collect())
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        self.iter().flat_map(|value| value.to_ne_bytes()).collect()
+    }
+}
+
+impl PropertyValue for u32 {
+    const FORMAT: u8 = 32;
+
+    fn parse(reply: &GetPropertyReply) -> Result<Self, ParseError> {
+        Ok(parse_u32_array::<1>(reply)?[0])
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        self.to_ne_bytes().to_vec()
+    }
+}
+
+/// Parse a list of 32 bit values that must contain at least `N` entries and return the first `N`.
+fn parse_u32_array<const N: usize>(reply: &GetPropertyReply) -> Result<[u32; N], ParseError> {
+    let values = Vec::<u32>::parse(reply)?;
+    let values = values.get(..N).ok_or(ParseError::InsufficientData)?;
+    Ok(values.try_into().unwrap())
+}
+
+/// Parse a list of 32 bit values that consists of groups of `N` entries.
+fn parse_u32_chunks<const N: usize>(reply: &GetPropertyReply) -> Result<Vec<[u32; N]>, ParseError> {
+    let values = Vec::<u32>::parse(reply)?;
+    if values.len() % N != 0 {
+        return Err(ParseError::InvalidValue);
+    }
+    Ok(values
+        .chunks_exact(N)
+        .map(|chunk| chunk.try_into().unwrap())
+        .collect())
+}
+
+/// Serialize 32 bit values for a property.
+fn serialize_u32(values: impl IntoIterator<Item = u32>) -> Vec<u8> {
+    values
+        .into_iter()
+        .flat_map(|value| value.to_ne_bytes())
+        .collect()
+}
+
+// Root window properties
+
+ewmh_property! {
+    /// The `_NET_SUPPORTED` property of the root window.
+    ///
+    /// This lists the atoms of all hints that the window manager supports.
+    pub struct Supported(pub Vec<Atom>);
+    /// A cookie for getting the `_NET_SUPPORTED` property.
+    pub struct SupportedCookie;
+    _NET_SUPPORTED: |_| AtomEnum::ATOM.into(),
+}
+
+ewmh_property! {
+    /// The `_NET_CLIENT_LIST` property of the root window.
+    ///
+    /// This lists all windows managed by the window manager in initial mapping order.
+    pub struct ClientList(pub Vec<Window>);
+    /// A cookie for getting the `_NET_CLIENT_LIST` property.
+    pub struct ClientListCookie;
+    _NET_CLIENT_LIST: |_| AtomEnum::WINDOW.into(),
+}
+
+ewmh_property! {
+    /// The `_NET_CLIENT_LIST_STACKING` property of the root window.
+    ///
+    /// This lists all windows managed by the window manager in bottom-to-top stacking order.
+    pub struct ClientListStacking(pub Vec<Window>);
+    /// A cookie for getting the `_NET_CLIENT_LIST_STACKING` property.
+    pub struct ClientListStackingCookie;
+    _NET_CLIENT_LIST_STACKING: |_| AtomEnum::WINDOW.into(),
+}
+
+ewmh_property! {
+    /// The `_NET_NUMBER_OF_DESKTOPS` property of the root window.
+    #[derive(Copy)]
+    pub struct NumberOfDesktops(pub u32);
+    /// A cookie for getting the `_NET_NUMBER_OF_DESKTOPS` property.
+    pub struct NumberOfDesktopsCookie;
+    _NET_NUMBER_OF_DESKTOPS: |_| AtomEnum::CARDINAL.into(),
+}
+
+/// The size of all desktops, as found in `_NET_DESKTOP_GEOMETRY`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct DesktopGeometry {
+    /// The width of the desktops.
+    pub width: u32,
+    /// The height of the desktops.
+    pub height: u32,
+}
+
+impl PropertyValue for DesktopGeometry {
+    const FORMAT: u8 = 32;
+
+    fn parse(reply: &GetPropertyReply) -> Result<Self, ParseError> {
+        let [width, height] = parse_u32_array(reply)?;
+        Ok(Self { width, height })
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        serialize_u32([self.width, self.height])
+    }
+}
+
+ewmh_property! {
+    impl DesktopGeometry;
+    /// A cookie for getting the `_NET_DESKTOP_GEOMETRY` property.
+    pub struct DesktopGeometryCookie;
+    _NET_DESKTOP_GEOMETRY: |_| AtomEnum::CARDINAL.into(),
+}
+
+/// The top left corner of the visible part of a desktop, as found in `_NET_DESKTOP_VIEWPORT`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Viewport {
+    /// The x coordinate of the top left corner.
+    pub x: u32,
+    /// The y coordinate of the top left corner.
+    pub y: u32,
+}
+
+/// The `_NET_DESKTOP_VIEWPORT` property of the root window.
+///
+/// This contains one viewport for each desktop.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DesktopViewport(pub Vec<Viewport>);
+
+impl PropertyValue for DesktopViewport {
+    const FORMAT: u8 = 32;
+
+    fn parse(reply: &GetPropertyReply) -> Result<Self, ParseError> {
+        let viewports = parse_u32_chunks(reply)?
+            .into_iter()
+            .map(|[x, y]| Viewport { x, y })
+            .collect();
+        Ok(Self(viewports))
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        serialize_u32(self.0.iter().flat_map(|viewport| [viewport.x, viewport.y]))
+    }
+}
+
+ewmh_property! {
+    impl DesktopViewport;
+    /// A cookie for getting the `_NET_DESKTOP_VIEWPORT` property.
+    pub struct DesktopViewportCookie;
+    _NET_DESKTOP_VIEWPORT: |_| AtomEnum::CARDINAL.into(),
+}
+
+ewmh_property! {
+    /// The `_NET_CURRENT_DESKTOP` property of the root window.
+    ///
+    /// This is the index of the current desktop, starting at zero.
+    #[derive(Copy)]
+    pub struct CurrentDesktop(pub u32);
+    /// A cookie for getting the `_NET_CURRENT_DESKTOP` property.
+    pub struct CurrentDesktopCookie;
+    _NET_CURRENT_DESKTOP: |_| AtomEnum::CARDINAL.into(),
+}
+
+ewmh_property! {
+    /// The `_NET_DESKTOP_NAMES` property of the root window.
+    pub struct DesktopNames(pub Vec<String>);
+    /// A cookie for getting the `_NET_DESKTOP_NAMES` property.
+    pub struct DesktopNamesCookie;
+    _NET_DESKTOP_NAMES: |atoms| atoms.UTF8_STRING,
+}
+
+ewmh_property! {
+    /// The `_NET_ACTIVE_WINDOW` property of the root window.
+    ///
+    /// This is `x11rb::NONE` if no window is active.
+    #[derive(Copy)]
+    pub struct ActiveWindow(pub Window);
+    /// A cookie for getting the `_NET_ACTIVE_WINDOW` property.
+    pub struct ActiveWindowCookie;
+    _NET_ACTIVE_WINDOW: |_| AtomEnum::WINDOW.into(),
+}
+
+/// The work area of a desktop, as found in `_NET_WORKAREA`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct WorkArea {
+    /// The x coordinate of the top left corner.
+    pub x: u32,
+    /// The y coordinate of the top left corner.
+    pub y: u32,
+    /// The width of the work area.
+    pub width: u32,
+    /// The height of the work area.
+    pub height: u32,
+}
+
+/// The `_NET_WORKAREA` property of the root window.
+///
+/// This contains one work area for each desktop.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct WorkAreas(pub Vec<WorkArea>);
+
+impl PropertyValue for WorkAreas {
+    const FORMAT: u8 = 32;
+
+    fn parse(reply: &GetPropertyReply) -> Result<Self, ParseError> {
+        let areas = parse_u32_chunks(reply)?
+            .into_iter()
+            .map(|[x, y, width, height]| WorkArea {
+                x,
+                y,
+                width,
+                height,
+            })
+            .collect();
+        Ok(Self(areas))
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        serialize_u32(
+            self.0
+                .iter()
+                .flat_map(|area| [area.x, area.y, area.width, area.height]),
+        )
+    }
+}
+
+ewmh_property! {
+    impl WorkAreas;
+    /// A cookie for getting the `_NET_WORKAREA` property.
+    pub struct WorkAreasCookie;
+    _NET_WORKAREA: |_| AtomEnum::CARDINAL.into(),
+}
+
+ewmh_property! {
+    /// The `_NET_SUPPORTING_WM_CHECK` property.
+    ///
+    /// On the root window and on the window that it refers to, this property contains the window
+    /// that the window manager created for announcing its presence.
+    #[derive(Copy)]
+    pub struct SupportingWmCheck(pub Window);
+    /// A cookie for getting the `_NET_SUPPORTING_WM_CHECK` property.
+    pub struct SupportingWmCheckCookie;
+    _NET_SUPPORTING_WM_CHECK: |_| AtomEnum::WINDOW.into(),
+}
+
+ewmh_property! {
+    /// The `_NET_VIRTUAL_ROOTS` property of the root window.
+    ///
+    /// Window managers that use virtual root windows list them here.
+    pub struct VirtualRoots(pub Vec<Window>);
+    /// A cookie for getting the `_NET_VIRTUAL_ROOTS` property.
+    pub struct VirtualRootsCookie;
+    _NET_VIRTUAL_ROOTS: |_| AtomEnum::WINDOW.into(),
+}
+
+ewmh_property! {
+    /// The `_NET_SHOWING_DESKTOP` property of the root window.
+    ///
+    /// This is `1` if the window manager is in "showing the desktop" mode and `0` otherwise.
+    #[derive(Copy)]
+    pub struct ShowingDesktop(pub u32);
+    /// A cookie for getting the `_NET_SHOWING_DESKTOP` property.
+    pub struct ShowingDesktopCookie;
+    _NET_SHOWING_DESKTOP: |_| AtomEnum::CARDINAL.into(),
+}
+
+// Application window properties
+
+ewmh_property! {
+    /// The `_NET_WM_NAME` property: the title of a window.
+    pub struct WmName(pub String);
+    /// A cookie for getting the `_NET_WM_NAME` property.
+    pub struct WmNameCookie;
+    _NET_WM_NAME: |atoms| atoms.UTF8_STRING,
+}
+
+ewmh_property! {
+    /// The `_NET_WM_VISIBLE_NAME` property.
+    ///
+    /// This property is set by the window manager if it displays a name that differs from
+    /// `_NET_WM_NAME`.
+    pub struct WmVisibleName(pub String);
+    /// A cookie for getting the `_NET_WM_VISIBLE_NAME` property.
+    pub struct WmVisibleNameCookie;
+    _NET_WM_VISIBLE_NAME: |atoms| atoms.UTF8_STRING,
+}
+
+ewmh_property! {
+    /// The `_NET_WM_ICON_NAME` property: the title of an iconified window.
+    pub struct WmIconName(pub String);
+    /// A cookie for getting the `_NET_WM_ICON_NAME` property.
+    pub struct WmIconNameCookie;
+    _NET_WM_ICON_NAME: |atoms| atoms.UTF8_STRING,
+}
+
+ewmh_property! {
+    /// The `_NET_WM_VISIBLE_ICON_NAME` property.
+    ///
+    /// This property is set by the window manager if it displays an icon name that differs from
+    /// `_NET_WM_ICON_NAME`.
+    pub struct WmVisibleIconName(pub String);
+    /// A cookie for getting the `_NET_WM_VISIBLE_ICON_NAME` property.
+    pub struct WmVisibleIconNameCookie;
+    _NET_WM_VISIBLE_ICON_NAME: |atoms| atoms.UTF8_STRING,
+}
+
+ewmh_property! {
+    /// The `_NET_WM_DESKTOP` property.
+    ///
+    /// This is the index of the desktop that the window is on, or `0xFFFFFFFF` if it is shown on
+    /// all desktops. Applications may only set this before mapping the window. Afterwards, a
+    /// client message has to be sent to the root window.
+    #[derive(Copy)]
+    pub struct WmDesktop(pub u32);
+    /// A cookie for getting the `_NET_WM_DESKTOP` property.
+    pub struct WmDesktopCookie;
+    _NET_WM_DESKTOP: |_| AtomEnum::CARDINAL.into(),
+}
+
+ewmh_property! {
+    /// The `_NET_WM_WINDOW_TYPE` property.
+    ///
+    /// This lists window types like `_NET_WM_WINDOW_TYPE_DIALOG` in order of preference.
+    pub struct WmWindowType(pub Vec<Atom>);
+    /// A cookie for getting the `_NET_WM_WINDOW_TYPE` property.
+    pub struct WmWindowTypeCookie;
+    _NET_WM_WINDOW_TYPE: |_| AtomEnum::ATOM.into(),
+}
+
+ewmh_property! {
+    /// The `_NET_WM_STATE` property.
+    ///
+    /// This lists states like `_NET_WM_STATE_FULLSCREEN`. Applications may only set this before
+    /// mapping the window. Afterwards, state changes have to be requested with
+    /// [`wm_state_message`].
+    pub struct WmState(pub Vec<Atom>);
+    /// A cookie for getting the `_NET_WM_STATE` property.
+    pub struct WmStateCookie;
+    _NET_WM_STATE: |_| AtomEnum::ATOM.into(),
+}
+
+ewmh_property! {
+    /// The `_NET_WM_ALLOWED_ACTIONS` property.
+    ///
+    /// The window manager lists actions like `_NET_WM_ACTION_CLOSE` that the user can perform on
+    /// the window.
+    pub struct WmAllowedActions(pub Vec<Atom>);
+    /// A cookie for getting the `_NET_WM_ALLOWED_ACTIONS` property.
+    pub struct WmAllowedActionsCookie;
+    _NET_WM_ALLOWED_ACTIONS: |_| AtomEnum::ATOM.into(),
+}
+
+/// Space reserved at the edges of the screen, as found in `_NET_WM_STRUT`.
+///
+/// Newer applications use [`StrutPartial`] instead.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Strut {
+    /// The space reserved at the left edge.
+    pub left: u32,
+    /// The space reserved at the right edge.
+    pub right: u32,
+    /// The space reserved at the top edge.
+    pub top: u32,
+    /// The space reserved at the bottom edge.
+    pub bottom: u32,
+}
+
+impl PropertyValue for Strut {
+    const FORMAT: u8 = 32;
+
+    fn parse(reply: &GetPropertyReply) -> Result<Self, ParseError> {
+        let [left, right, top, bottom] = parse_u32_array(reply)?;
+        Ok(Self {
+            left,
+            right,
+            top,
+            bottom,
+        })
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        serialize_u32([self.left, self.right, self.top, self.bottom])
+    }
+}
+
+ewmh_property! {
+    impl Strut;
+    /// A cookie for getting the `_NET_WM_STRUT` property.
+    pub struct StrutCookie;
+    _NET_WM_STRUT: |_| AtomEnum::CARDINAL.into(),
+}
+
+/// Space reserved at the edges of the screen, as found in `_NET_WM_STRUT_PARTIAL`.
+///
+/// Each of `left`, `right`, `top` and `bottom` reserves space at that edge of the root window.
+/// The corresponding start and end fields limit the reservation to a part of the edge.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub struct StrutPartial {
+    pub left: u32,
+    pub right: u32,
+    pub top: u32,
+    pub bottom: u32,
+    pub left_start_y: u32,
+    pub left_end_y: u32,
+    pub right_start_y: u32,
+    pub right_end_y: u32,
+    pub top_start_x: u32,
+    pub top_end_x: u32,
+    pub bottom_start_x: u32,
+    pub bottom_end_x: u32,
+}
+
+impl StrutPartial {
+    /// Get the values in the order in which they appear in the property.
+    pub fn to_values(self) -> [u32; 12] {
+        [
+            self.left,
+            self.right,
+            self.top,
+            self.bottom,
+            self.left_start_y,
+            self.left_end_y,
+            self.right_start_y,
+            self.right_end_y,
+            self.top_start_x,
+            self.top_end_x,
+            self.bottom_start_x,
+            self.bottom_end_x,
+        ]
+    }
+}
+
+impl PropertyValue for StrutPartial {
+    const FORMAT: u8 = 32;
+
+    fn parse(reply: &GetPropertyReply) -> Result<Self, ParseError> {
+        let values: [u32; 12] = parse_u32_array(reply)?;
+        Ok(Self {
+            left: values[0],
+            right: values[1],
+            top: values[2],
+            bottom: values[3],
+            left_start_y: values[4],
+            left_end_y: values[5],
+            right_start_y: values[6],
+            right_end_y: values[7],
+            top_start_x: values[8],
+            top_end_x: values[9],
+            bottom_start_x: values[10],
+            bottom_end_x: values[11],
+        })
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        serialize_u32(self.to_values())
+    }
+}
+
+ewmh_property! {
+    impl StrutPartial;
+    /// A cookie for getting the `_NET_WM_STRUT_PARTIAL` property.
+    pub struct StrutPartialCookie;
+    _NET_WM_STRUT_PARTIAL: |_| AtomEnum::CARDINAL.into(),
+}
+
+/// One image of a window's icon, as found in `_NET_WM_ICON`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct WmIconImage {
+    /// The width of the image.
+    pub width: u32,
+    /// The height of the image.
+    pub height: u32,
+    /// The pixels in rows from top to bottom. Each pixel is in ARGB format with the alpha channel
+    /// in the most significant byte.
+    pub pixels: Vec<u32>,
+}
+
+/// The `_NET_WM_ICON` property.
+///
+/// This contains the window's icon in one or more sizes.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct WmIcon(pub Vec<WmIconImage>);
+
+impl PropertyValue for WmIcon {
+    const FORMAT: u8 = 32;
+
+    fn parse(reply: &GetPropertyReply) -> Result<Self, ParseError> {
+        let values = Vec::<u32>::parse(reply)?;
+        let mut remaining = &values[..];
+        let mut images = Vec::new();
+        while let [width, height, rest @ ..] = remaining {
+            let length = usize::try_from(u64::from(*width) * u64::from(*height))
+                .map_err(|_| ParseError::InvalidValue)?;
+            let pixels = rest.get(..length).ok_or(ParseError::InsufficientData)?;
+            images.push(WmIconImage {
+                width: *width,
+                height: *height,
+                pixels: pixels.to_vec(),
+            });
+            remaining = &rest[length..];
+        }
+        if !remaining.is_empty() {
+            return Err(ParseError::InsufficientData);
+        }
+        Ok(Self(images))
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        serialize_u32(self.0.iter().flat_map(|image| {
+            [image.width, image.height]
+                .into_iter()
+                .chain(image.pixels.iter().copied())
+        }))
+    }
+}
+
+ewmh_property! {
+    impl WmIcon;
+    /// A cookie for getting the `_NET_WM_ICON` property.
+    pub struct WmIconCookie;
+    _NET_WM_ICON: |_| AtomEnum::CARDINAL.into(),
+}
+
+ewmh_property! {
+    /// The `_NET_WM_PID` property.
+    ///
+    /// The ID should belong to the process that owns the window on the machine named by
+    /// `WM_CLIENT_MACHINE`.
+    #[derive(Copy)]
+    pub struct WmPid(pub u32);
+    /// A cookie for getting the `_NET_WM_PID` property.
+    pub struct WmPidCookie;
+    _NET_WM_PID: |_| AtomEnum::CARDINAL.into(),
+}
+
+ewmh_property! {
+    /// The `_NET_WM_USER_TIME` property.
+    ///
+    /// This is the time of the last user activity in the window. A value of zero asks the window
+    /// manager to not activate the window when it is mapped.
+    #[derive(Copy)]
+    pub struct WmUserTime(pub Timestamp);
+    /// A cookie for getting the `_NET_WM_USER_TIME` property.
+    pub struct WmUserTimeCookie;
+    _NET_WM_USER_TIME: |_| AtomEnum::CARDINAL.into(),
+}
+
+/// The size of the window manager's decorations, as found in `_NET_FRAME_EXTENTS`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct FrameExtents {
+    /// The width of the left border.
+    pub left: u32,
+    /// The width of the right border.
+    pub right: u32,
+    /// The height of the top border.
+    pub top: u32,
+    /// The height of the bottom border.
+    pub bottom: u32,
+}
+
+impl FrameExtents {
+    /// Get the values in the order in which they appear in the property.
+    pub fn to_values(self) -> [u32; 4] {
+        [self.left, self.right, self.top, self.bottom]
+    }
+}
+
+impl PropertyValue for FrameExtents {
+    const FORMAT: u8 = 32;
+
+    fn parse(reply: &GetPropertyReply) -> Result<Self, ParseError> {
+        let [left, right, top, bottom] = parse_u32_array(reply)?;
+        Ok(Self {
+            left,
+            right,
+            top,
+            bottom,
+        })
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        serialize_u32(self.to_values())
+    }
+}
+
+ewmh_property! {
+    impl FrameExtents;
+    /// A cookie for getting the `_NET_FRAME_EXTENTS` property.
+    pub struct FrameExtentsCookie;
+    _NET_FRAME_EXTENTS: |_| AtomEnum::CARDINAL.into(),
+}
+
+// Client messages
+
+/// Who requests an action from the window manager.
+///
+/// Window managers may treat requests from applications differently than those from pagers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SourceIndication {
+    /// The request comes from a client that predates this part of the specification.
+    Legacy,
+    /// The request comes from a normal application.
+    Application,
+    /// The request comes from a pager or another tool acting on direct user input.
+    Pager,
+}
+
+impl From<SourceIndication> for u32 {
+    fn from(source: SourceIndication) -> u32 {
+        match source {
+            SourceIndication::Legacy => 0,
+            SourceIndication::Application => 1,
+            SourceIndication::Pager => 2,
+        }
+    }
+}
+
+impl TryFrom<u32> for SourceIndication {
+    type Error = ParseError;
+
+    fn try_from(value: u32) -> Result<Self, ParseError> {
+        match value {
+            0 => Ok(SourceIndication::Legacy),
+            1 => Ok(SourceIndication::Application),
+            2 => Ok(SourceIndication::Pager),
+            _ => Err(ParseError::InvalidValue),
+        }
+    }
+}
+
+/// The action of a `_NET_WM_STATE` client message.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WmStateAction {
+    /// Remove the state from the window.
+    Remove,
+    /// Add the state to the window.
+    Add,
+    /// Add the state if it is not set and remove it otherwise.
+    Toggle,
+}
+
+impl From<WmStateAction> for u32 {
+    fn from(action: WmStateAction) -> u32 {
+        match action {
+            WmStateAction::Remove => 0,
+            WmStateAction::Add => 1,
+            WmStateAction::Toggle => 2,
+        }
+    }
+}
+
+impl TryFrom<u32> for WmStateAction {
+    type Error = ParseError;
+
+    fn try_from(value: u32) -> Result<Self, ParseError> {
+        match value {
+            0 => Ok(WmStateAction::Remove),
+            1 => Ok(WmStateAction::Add),
+            2 => Ok(WmStateAction::Toggle),
+            _ => Err(ParseError::InvalidValue),
+        }
+    }
+}
+
+/// The kind of operation that a `_NET_WM_MOVERESIZE` client message starts.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MoveResizeDirection {
+    /// Resize by dragging the top left corner.
+    SizeTopLeft,
+    /// Resize by dragging the top edge.
+    SizeTop,
+    /// Resize by dragging the top right corner.
+    SizeTopRight,
+    /// Resize by dragging the right edge.
+    SizeRight,
+    /// Resize by dragging the bottom right corner.
+    SizeBottomRight,
+    /// Resize by dragging the bottom edge.
+    SizeBottom,
+    /// Resize by dragging the bottom left corner.
+    SizeBottomLeft,
+    /// Resize by dragging the left edge.
+    SizeLeft,
+    /// Move the window with the pointer.
+    Move,
+    /// Resize the window with the keyboard.
+    SizeKeyboard,
+    /// Move the window with the keyboard.
+    MoveKeyboard,
+    /// Cancel an operation that was started before.
+    Cancel,
+}
+
+impl From<MoveResizeDirection> for u32 {
+    fn from(direction: MoveResizeDirection) -> u32 {
+        match direction {
+            MoveResizeDirection::SizeTopLeft => 0,
+            MoveResizeDirection::SizeTop => 1,
+            MoveResizeDirection::SizeTopRight => 2,
+            MoveResizeDirection::SizeRight => 3,
+            MoveResizeDirection::SizeBottomRight => 4,
+            MoveResizeDirection::SizeBottom => 5,
+            MoveResizeDirection::SizeBottomLeft => 6,
+            MoveResizeDirection::SizeLeft => 7,
+            MoveResizeDirection::Move => 8,
+            MoveResizeDirection::SizeKeyboard => 9,
+            MoveResizeDirection::MoveKeyboard => 10,
+            MoveResizeDirection::Cancel => 11,
+        }
+    }
+}
+
+impl TryFrom<u32> for MoveResizeDirection {
+    type Error = ParseError;
+
+    fn try_from(value: u32) -> Result<Self, ParseError> {
+        match value {
+            0 => Ok(MoveResizeDirection::SizeTopLeft),
+            1 => Ok(MoveResizeDirection::SizeTop),
+            2 => Ok(MoveResizeDirection::SizeTopRight),
+            3 => Ok(MoveResizeDirection::SizeRight),
+            4 => Ok(MoveResizeDirection::SizeBottomRight),
+            5 => Ok(MoveResizeDirection::SizeBottom),
+            6 => Ok(MoveResizeDirection::SizeBottomLeft),
+            7 => Ok(MoveResizeDirection::SizeLeft),
+            8 => Ok(MoveResizeDirection::Move),
+            9 => Ok(MoveResizeDirection::SizeKeyboard),
+            10 => Ok(MoveResizeDirection::MoveKeyboard),
+            11 => Ok(MoveResizeDirection::Cancel),
+            _ => Err(ParseError::InvalidValue),
+        }
+    }
+}
+
+/// Build a `_NET_WM_STATE` client message that asks the window manager to change the state of
+/// `window`.
+///
+/// Up to two states can be changed at once. The message has to be sent to the root window, for
+/// example with [`send_root_message`].
+pub fn wm_state_message(
+    atoms: &EwmhAtoms,
+    window: Window,
+    action: WmStateAction,
+    first: Atom,
+    second: Option<Atom>,
+    source: SourceIndication,
+) -> ClientMessageEvent {
+    let data = [
+        action.into(),
+        first,
+        second.unwrap_or(crate::NONE),
+        source.into(),
+        0,
+    ];
+    ClientMessageEvent::new(32, window, atoms._NET_WM_STATE, data)
+}
+
+/// Build a `_NET_ACTIVE_WINDOW` client message that asks the window manager to activate `window`.
+///
+/// `timestamp` should be the time of the user action that caused the request and
+/// `currently_active` the application's window that is currently active, if any. The message has
+/// to be sent to the root window, for example with [`send_root_message`].
+pub fn active_window_message(
+    atoms: &EwmhAtoms,
+    window: Window,
+    source: SourceIndication,
+    timestamp: Timestamp,
+    currently_active: Option<Window>,
+) -> ClientMessageEvent {
+    let data = [
+        source.into(),
+        timestamp,
+        currently_active.unwrap_or(crate::NONE),
+        0,
+        0,
+    ];
+    ClientMessageEvent::new(32, window, atoms._NET_ACTIVE_WINDOW, data)
+}
+
+/// Build a `_NET_WM_MOVERESIZE` client message that asks the window manager to start moving or
+/// resizing `window`.
+///
+/// `x_root` and `y_root` are the position of the pointer and `button` is the pressed button, or
+/// zero for keyboard operations. Before sending this message, the application should release any
+/// pointer grab. The message has to be sent to the root window, for example with
+/// [`send_root_message`].
+pub fn wm_moveresize_message(
+    atoms: &EwmhAtoms,
+    window: Window,
+    x_root: i32,
+    y_root: i32,
+    direction: MoveResizeDirection,
+    button: Button,
+    source: SourceIndication,
+) -> ClientMessageEvent {
+    let data = [
+        x_root as u32,
+        y_root as u32,
+        direction.into(),
+        button.into(),
+        source.into(),
+    ];
+    ClientMessageEvent::new(32, window, atoms._NET_WM_MOVERESIZE, data)
+}
+
+/// Send a client message to the given root window, so that the window manager receives it.
+pub fn send_root_message<C: RequestConnection + ?Sized>(
+    conn: &C,
+    root: Window,
+    message: ClientMessageEvent,
+) -> Result<VoidCookie<'_, C>, ConnectionError> {
+    xproto::send_event(
+        conn,
+        false,
+        root,
+        EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+        message,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        parse_property, ActiveWindow, DesktopGeometry, DesktopNames, DesktopViewport, FrameExtents,
+        PropertyValue, Viewport, WmIcon, WmIconImage, WmName, WorkArea, WorkAreas,
+    };
+    use crate::errors::ParseError;
+    use crate::protocol::xproto::{Atom, AtomEnum, GetPropertyReply};
+
+    const UTF8_STRING: Atom = 1000;
+
+    fn get_property_reply(value: &[u8], format: u8, type_: impl Into<Atom>) -> GetPropertyReply {
+        GetPropertyReply {
+            format,
+            sequence: 0,
+            length: 0,
+            type_: type_.into(),
+            bytes_after: 0,
+            value_len: (value.len() / usize::from(format.max(8) / 8))
+                .try_into()
+                .unwrap(),
+            value: value.to_vec(),
+        }
+    }
+
+    fn reply32(values: &[u32]) -> GetPropertyReply {
+        let value: Vec<u8> = values.iter().flat_map(|v| v.to_ne_bytes()).collect();
+        get_property_reply(&value, 32, AtomEnum::CARDINAL)
+    }
+
+    fn parse_cardinal<T: PropertyValue>(values: &[u32]) -> Result<Option<T>, ParseError> {
+        parse_property(&reply32(values), AtomEnum::CARDINAL.into())
+    }
+
+    #[test]
+    fn test_utf8_string() {
+        let reply = get_property_reply("Grüße".as_bytes(), 8, UTF8_STRING);
+        let name = parse_property::<WmName>(&reply, UTF8_STRING).unwrap();
+        assert_eq!(name, Some(WmName("Grüße".to_string())));
+        assert_eq!(name.unwrap().serialize(), "Grüße".as_bytes());
+
+        let reply = get_property_reply(b"\xff", 8, UTF8_STRING);
+        assert_eq!(
+            parse_property::<WmName>(&reply, UTF8_STRING),
+            Err(ParseError::InvalidValue)
+        );
+    }
+
+    #[test]
+    fn test_utf8_string_list() {
+        for (input, expected) in [
+            (&b""[..], &[][..]),
+            (b"\0", &[""]),
+            (b"One", &["One"]),
+            (b"One\0Two\0", &["One", "Two"]),
+            (b"One\0\0Three", &["One", "", "Three"]),
+        ] {
+            let reply = get_property_reply(input, 8, UTF8_STRING);
+            let names = parse_property::<DesktopNames>(&reply, UTF8_STRING)
+                .unwrap()
+                .unwrap();
+            assert_eq!(names.0, expected);
+        }
+        let names = DesktopNames(vec!["One".to_string(), "Two".to_string()]);
+        assert_eq!(names.serialize(), b"One\0Two\0");
+    }
+
+    #[test]
+    fn test_wrong_type() {
+        let reply = get_property_reply(b"Name", 8, AtomEnum::STRING);
+        assert_eq!(
+            parse_property::<WmName>(&reply, UTF8_STRING),
+            Err(ParseError::InvalidValue)
+        );
+        let reply = get_property_reply(b"Name", 8, AtomEnum::CARDINAL);
+        assert_eq!(
+            parse_property::<u32>(&reply, AtomEnum::CARDINAL.into()),
+            Err(ParseError::InvalidValue)
+        );
+    }
+
+    #[test]
+    fn test_missing() {
+        let reply = get_property_reply(&[], 0, AtomEnum::NONE);
+        assert_eq!(parse_property::<WmName>(&reply, UTF8_STRING), Ok(None));
+        assert_eq!(
+            parse_property::<ActiveWindow>(&reply, AtomEnum::WINDOW.into()),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_u32() {
+        assert_eq!(parse_cardinal::<u32>(&[1234]), Ok(Some(1234)));
+        assert_eq!(
+            parse_cardinal::<u32>(&[]),
+            Err(ParseError::InsufficientData)
+        );
+        assert_eq!(
+            parse_cardinal::<Vec<u32>>(&[1, 2, 3]),
+            Ok(Some(vec![1, 2, 3]))
+        );
+    }
+
+    #[test]
+    fn test_work_areas() {
+        let values = [0, 20, 1920, 1060, 10, 0, 1270, 1024];
+        let areas = parse_cardinal::<WorkAreas>(&values).unwrap().unwrap();
+        assert_eq!(
+            areas.0,
+            [
+                WorkArea {
+                    x: 0,
+                    y: 20,
+                    width: 1920,
+                    height: 1060
+                },
+                WorkArea {
+                    x: 10,
+                    y: 0,
+                    width: 1270,
+                    height: 1024
+                },
+            ]
+        );
+        assert_eq!(areas.serialize(), reply32(&values).value);
+        assert_eq!(
+            parse_cardinal::<WorkAreas>(&[0, 20, 1920]),
+            Err(ParseError::InvalidValue)
+        );
+    }
+
+    #[test]
+    fn test_desktop_geometry_and_viewport() {
+        assert_eq!(
+            parse_cardinal(&[3840, 1080]),
+            Ok(Some(DesktopGeometry {
+                width: 3840,
+                height: 1080
+            }))
+        );
+        assert_eq!(
+            parse_cardinal::<DesktopGeometry>(&[3840]),
+            Err(ParseError::InsufficientData)
+        );
+
+        let viewport = parse_cardinal::<DesktopViewport>(&[0, 0, 1920, 0])
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            viewport.0,
+            [Viewport { x: 0, y: 0 }, Viewport { x: 1920, y: 0 }]
+        );
+        assert_eq!(
+            parse_cardinal::<DesktopViewport>(&[0, 0, 1920]),
+            Err(ParseError::InvalidValue)
+        );
+    }
+
+    #[test]
+    fn test_wm_icon() {
+        let values = [2, 1, 0xff00_0000, 0xffff_ffff, 1, 1, 0x8000_ff00];
+        let icon = parse_cardinal::<WmIcon>(&values).unwrap().unwrap();
+        assert_eq!(
+            icon.0,
+            [
+                WmIconImage {
+                    width: 2,
+                    height: 1,
+                    pixels: vec![0xff00_0000, 0xffff_ffff],
+                },
+                WmIconImage {
+                    width: 1,
+                    height: 1,
+                    pixels: vec![0x8000_ff00],
+                },
+            ]
+        );
+        assert_eq!(icon.serialize(), reply32(&values).value);
+
+        // The second image is missing its pixels
+        assert_eq!(
+            parse_cardinal::<WmIcon>(&[1, 1, 0, 2, 2, 0]),
+            Err(ParseError::InsufficientData)
+        );
+        // A trailing width without a height
+        assert_eq!(
+            parse_cardinal::<WmIcon>(&[1, 1, 0, 2]),
+            Err(ParseError::InsufficientData)
+        );
+    }
+
+    #[test]
+    fn test_frame_extents() {
+        let extents = parse_cardinal::<FrameExtents>(&[1, 2, 20, 3])
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            extents,
+            FrameExtents {
+                left: 1,
+                right: 2,
+                top: 20,
+                bottom: 3
+            }
+        );
+        assert_eq!(extents.to_values(), [1, 2, 20, 3]);
+    }
+}
@@ -0,0 +1,886 @@
+//! Window-manager-side support for the Extended Window Manager Hints (EWMH)
+//!
+//! A window manager announces itself and its state via properties on the root window. [`RootState`]
+//! keeps track of this state and only writes the properties that changed since the last call to
+//! [`RootState::flush`]. Requests from applications and pagers arrive as `ClientMessage` events on
+//! the root window and can be decoded with [`ClientRequest::parse`].
+//!
+//! Usage example:
+//! ```no_run
+//! use x11rb::connection::Connection;
+//! use x11rb::errors::ReplyOrIdError;
+//! use x11rb::properties::ewmh::wm::{ClientRequest, RootState};
+//! use x11rb::properties::ewmh::EwmhAtoms;
+//! use x11rb::protocol::xproto::{ConnectionExt as _, CreateWindowAux, WindowClass};
+//! use x11rb::protocol::Event;
+//!
+//! fn run_wm(conn: &impl Connection, screen_num: usize) -> Result<(), ReplyOrIdError> {
+//!     let root = conn.setup().roots[screen_num].root;
+//!     let atoms = EwmhAtoms::new(conn)?.reply()?;
+//!
+//!     let check_window = conn.generate_id()?;
+//!     conn.create_window(
+//!         0, check_window, root, -1, -1, 1, 1, 0,
+//!         WindowClass::INPUT_ONLY, 0, &CreateWindowAux::new(),
+//!     )?;
+//!     let supported = [atoms._NET_ACTIVE_WINDOW, atoms._NET_CLIENT_LIST, atoms._NET_WM_STATE];
+//!     let mut state = RootState::new(root, check_window, supported.to_vec());
+//!     state.announce(conn, &atoms, "example-wm")?;
+//!     state.set_number_of_desktops(4);
+//!     state.flush(conn, &atoms)?;
+//!     conn.flush()?;
+//!
+//!     loop {
+//!         if let Event::ClientMessage(event) = conn.wait_for_event()? {
+//!             match ClientRequest::parse(&atoms, &event) {
+//!                 Ok(Some(ClientRequest::ActiveWindow { window, .. })) => {
+//!                     state.set_active_window(Some(window));
+//!                 }
+//!                 Ok(Some(ClientRequest::CurrentDesktop { desktop, .. })) => {
+//!                     state.set_current_desktop(desktop);
+//!                 }
+//!                 _ => {}
+//!             }
+//!             state.flush(conn, &atoms)?;
+//!             conn.flush()?;
+//!         }
+//!     }
+//! }
+//! ```
+
+use super::{
+    EwmhAtoms, FrameExtents, MoveResizeDirection, SourceIndication, Viewport, WmName,
+    WmStateAction, WorkArea,
+};
+use crate::connection::RequestConnection;
+use crate::cookie::VoidCookie;
+use crate::errors::{ConnectionError, ParseError};
+use crate::protocol::xproto::{
+    Atom, AtomEnum, Button, ClientMessageEvent, Gravity, PropMode, StackMode, Timestamp, Window,
+};
+use crate::wrapper::ConnectionExt as _;
+use crate::NONE;
+
+/// How a list property changed since it was last written.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ListUpdate {
+    /// The property is up to date.
+    Unchanged,
+    /// Entries starting at the given index were appended.
+    Append(usize),
+    /// The property has to be replaced completely.
+    Replace,
+}
+
+impl ListUpdate {
+    fn append(&mut self, start: usize) {
+        if *self == ListUpdate::Unchanged {
+            *self = ListUpdate::Append(start);
+        }
+    }
+}
+
+/// A flag for each of the single-valued properties.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Properties {
+    number_of_desktops: bool,
+    desktop_geometry: bool,
+    desktop_viewport: bool,
+    current_desktop: bool,
+    desktop_names: bool,
+    active_window: bool,
+    workarea: bool,
+    showing_desktop: bool,
+}
+
+impl Properties {
+    /// The properties that the EWMH requires and that have a sensible default value.
+    const REQUIRED: Self = Self {
+        number_of_desktops: true,
+        desktop_geometry: false,
+        desktop_viewport: false,
+        current_desktop: true,
+        desktop_names: false,
+        active_window: false,
+        workarea: false,
+        showing_desktop: false,
+    };
+}
+
+/// The EWMH properties on the root window that a window manager maintains.
+///
+/// Changes are recorded in this structure and written to the X11 server by [`RootState::flush`].
+/// Only properties that changed are written. Windows added with [`RootState::add_client`] are
+/// appended to the client lists instead of rewriting them.
+///
+/// The first call to [`RootState::flush`] writes the client lists, `_NET_NUMBER_OF_DESKTOPS` and
+/// `_NET_CURRENT_DESKTOP`, which the EWMH requires. The other properties are only written once
+/// their setter was called.
+#[derive(Debug, Clone)]
+pub struct RootState {
+    root: Window,
+    check_window: Window,
+    supported: Vec<Atom>,
+    client_list: Vec<Window>,
+    client_list_stacking: Vec<Window>,
+    number_of_desktops: u32,
+    desktop_geometry: (u32, u32),
+    desktop_viewport: Vec<Viewport>,
+    current_desktop: u32,
+    desktop_names: Vec<String>,
+    active_window: Option<Window>,
+    workarea: Vec<WorkArea>,
+    showing_desktop: bool,
+    client_list_update: ListUpdate,
+    client_list_stacking_update: ListUpdate,
+    // Properties that have a value, either a default or from a setter
+    set: Properties,
+    // Properties that changed since they were last written
+    dirty: Properties,
+}
+
+impl RootState {
+    /// Create a new `RootState` for the given root window.
+    ///
+    /// `check_window` is a child of the root window that the window manager created for
+    /// `_NET_SUPPORTING_WM_CHECK`. `supported` lists the atoms of all supported hints for
+    /// `_NET_SUPPORTED`.
+    pub fn new(root: Window, check_window: Window, supported: Vec<Atom>) -> Self {
+        Self {
+            root,
+            check_window,
+            supported,
+            client_list: Vec::new(),
+            client_list_stacking: Vec::new(),
+            number_of_desktops: 1,
+            desktop_geometry: (0, 0),
+            desktop_viewport: Vec::new(),
+            current_desktop: 0,
+            desktop_names: Vec::new(),
+            active_window: None,
+            workarea: Vec::new(),
+            showing_desktop: false,
+            client_list_update: ListUpdate::Replace,
+            client_list_stacking_update: ListUpdate::Replace,
+            set: Properties::REQUIRED,
+            dirty: Properties::REQUIRED,
+        }
+    }
+
+    /// Get the root window.
+    pub fn root(&self) -> Window {
+        self.root
+    }
+
+    /// Announce the window manager.
+    ///
+    /// This sets `_NET_SUPPORTING_WM_CHECK` on the root window and on the check window, the
+    /// check window's `_NET_WM_NAME` to `wm_name`, and `_NET_SUPPORTED` on the root window.
+    pub fn announce<C: RequestConnection + ?Sized>(
+        &self,
+        conn: &C,
+        atoms: &EwmhAtoms,
+        wm_name: &str,
+    ) -> Result<(), ConnectionError> {
+        for window in [self.check_window, self.root] {
+            let _ = conn.change_property32(
+                PropMode::REPLACE,
+                window,
+                atoms._NET_SUPPORTING_WM_CHECK,
+                AtomEnum::WINDOW,
+                &[self.check_window],
+            )?;
+        }
+        let _ = WmName(wm_name.to_string()).set(conn, atoms, self.check_window)?;
+        let _ = conn.change_property32(
+            PropMode::REPLACE,
+            self.root,
+            atoms._NET_SUPPORTED,
+            AtomEnum::ATOM,
+            &self.supported,
+        )?;
+        Ok(())
+    }
+
+    /// Get the managed windows in initial mapping order.
+    pub fn client_list(&self) -> &[Window] {
+        &self.client_list
+    }
+
+    /// Get the managed windows in bottom-to-top stacking order.
+    pub fn client_list_stacking(&self) -> &[Window] {
+        &self.client_list_stacking
+    }
+
+    /// Add a newly managed window.
+    ///
+    /// The window is added at the end of `_NET_CLIENT_LIST` and at the top of
+    /// `_NET_CLIENT_LIST_STACKING`. Nothing happens if the window is already managed.
+    pub fn add_client(&mut self, window: Window) {
+        if self.client_list.contains(&window) {
+            return;
+        }
+        self.client_list_update.append(self.client_list.len());
+        self.client_list.push(window);
+        self.client_list_stacking_update
+            .append(self.client_list_stacking.len());
+        self.client_list_stacking.push(window);
+    }
+
+    /// Remove a window that is no longer managed.
+    ///
+    /// If the window is the active window, `_NET_ACTIVE_WINDOW` is cleared as well.
+    pub fn remove_client(&mut self, window: Window) {
+        if let Some(index) = self.client_list.iter().position(|&w| w == window) {
+            let _ = self.client_list.remove(index);
+            self.client_list_update = ListUpdate::Replace;
+        }
+        if let Some(index) = self.client_list_stacking.iter().position(|&w| w == window) {
+            let _ = self.client_list_stacking.remove(index);
+            self.client_list_stacking_update = ListUpdate::Replace;
+        }
+        if self.active_window == Some(window) {
+            self.set_active_window(None);
+        }
+    }
+
+    /// Set the stacking order of the managed windows, from bottom to top.
+    pub fn set_client_list_stacking(&mut self, windows: Vec<Window>) {
+        if self.client_list_stacking != windows {
+            self.client_list_stacking = windows;
+            self.client_list_stacking_update = ListUpdate::Replace;
+        }
+    }
+
+    /// Get the number of desktops.
+    pub fn number_of_desktops(&self) -> u32 {
+        self.number_of_desktops
+    }
+
+    /// Set the number of desktops.
+    pub fn set_number_of_desktops(&mut self, number: u32) {
+        if !self.set.number_of_desktops || self.number_of_desktops != number {
+            self.number_of_desktops = number;
+            self.set.number_of_desktops = true;
+            self.dirty.number_of_desktops = true;
+        }
+    }
+
+    /// Get the size of the desktops as `(width, height)`.
+    pub fn desktop_geometry(&self) -> (u32, u32) {
+        self.desktop_geometry
+    }
+
+    /// Set the size of the desktops.
+    ///
+    /// This is the size of the root window unless the window manager supports large desktops.
+    pub fn set_desktop_geometry(&mut self, width: u32, height: u32) {
+        if !self.set.desktop_geometry || self.desktop_geometry != (width, height) {
+            self.desktop_geometry = (width, height);
+            self.set.desktop_geometry = true;
+            self.dirty.desktop_geometry = true;
+        }
+    }
+
+    /// Get the viewports of the desktops.
+    pub fn desktop_viewport(&self) -> &[Viewport] {
+        &self.desktop_viewport
+    }
+
+    /// Set the viewports, one for each desktop.
+    ///
+    /// Window managers that do not support large desktops should set `(0, 0)` for each desktop.
+    pub fn set_desktop_viewport(&mut self, viewports: Vec<Viewport>) {
+        if !self.set.desktop_viewport || self.desktop_viewport != viewports {
+            self.desktop_viewport = viewports;
+            self.set.desktop_viewport = true;
+            self.dirty.desktop_viewport = true;
+        }
+    }
+
+    /// Get the index of the current desktop.
+    pub fn current_desktop(&self) -> u32 {
+        self.current_desktop
+    }
+
+    /// Set the index of the current desktop.
+    pub fn set_current_desktop(&mut self, desktop: u32) {
+        if !self.set.current_desktop || self.current_desktop != desktop {
+            self.current_desktop = desktop;
+            self.set.current_desktop = true;
+            self.dirty.current_desktop = true;
+        }
+    }
+
+    /// Get the names of the desktops.
+    pub fn desktop_names(&self) -> &[String] {
+        &self.desktop_names
+    }
+
+    /// Set the names of the desktops.
+    pub fn set_desktop_names(&mut self, names: Vec<String>) {
+        if !self.set.desktop_names || self.desktop_names != names {
+            self.desktop_names = names;
+            self.set.desktop_names = true;
+            self.dirty.desktop_names = true;
+        }
+    }
+
+    /// Get the active window.
+    pub fn active_window(&self) -> Option<Window> {
+        self.active_window
+    }
+
+    /// Set the active window.
+    pub fn set_active_window(&mut self, window: Option<Window>) {
+        if !self.set.active_window || self.active_window != window {
+            self.active_window = window;
+            self.set.active_window = true;
+            self.dirty.active_window = true;
+        }
+    }
+
+    /// Get the work areas of the desktops.
+    pub fn workarea(&self) -> &[WorkArea] {
+        &self.workarea
+    }
+
+    /// Set the work areas, one for each desktop.
+    pub fn set_workarea(&mut self, areas: Vec<WorkArea>) {
+        if !self.set.workarea || self.workarea != areas {
+            self.workarea = areas;
+            self.set.workarea = true;
+            self.dirty.workarea = true;
+        }
+    }
+
+    /// Get whether the window manager is in "showing the desktop" mode.
+    pub fn showing_desktop(&self) -> bool {
+        self.showing_desktop
+    }
+
+    /// Set whether the window manager is in "showing the desktop" mode.
+    pub fn set_showing_desktop(&mut self, showing: bool) {
+        if !self.set.showing_desktop || self.showing_desktop != showing {
+            self.showing_desktop = showing;
+            self.set.showing_desktop = true;
+            self.dirty.showing_desktop = true;
+        }
+    }
+
+    /// Write all properties that changed since the last call to the root window.
+    ///
+    /// If sending a request fails, the properties that were not written yet are written by the
+    /// next call.
+    pub fn flush<C: RequestConnection + ?Sized>(
+        &mut self,
+        conn: &C,
+        atoms: &EwmhAtoms,
+    ) -> Result<(), ConnectionError> {
+        let root = self.root;
+        write_list(
+            conn,
+            root,
+            atoms._NET_CLIENT_LIST,
+            &self.client_list,
+            &mut self.client_list_update,
+        )?;
+        write_list(
+            conn,
+            root,
+            atoms._NET_CLIENT_LIST_STACKING,
+            &self.client_list_stacking,
+            &mut self.client_list_stacking_update,
+        )?;
+
+        // Each flag is only cleared once its request was sent, so that a failed flush can be
+        // retried
+        let cardinal = AtomEnum::CARDINAL;
+        if self.dirty.number_of_desktops {
+            let _ = conn.change_property32(
+                PropMode::REPLACE,
+                root,
+                atoms._NET_NUMBER_OF_DESKTOPS,
+                cardinal,
+                &[self.number_of_desktops],
+            )?;
+            self.dirty.number_of_desktops = false;
+        }
+        if self.dirty.desktop_geometry {
+            let (width, height) = self.desktop_geometry;
+            let _ = conn.change_property32(
+                PropMode::REPLACE,
+                root,
+                atoms._NET_DESKTOP_GEOMETRY,
+                cardinal,
+                &[width, height],
+            )?;
+            self.dirty.desktop_geometry = false;
+        }
+        if self.dirty.desktop_viewport {
+            let values: Vec<u32> = self
+                .desktop_viewport
+                .iter()
+                .flat_map(|viewport| [viewport.x, viewport.y])
+                .collect();
+            let _ = conn.change_property32(
+                PropMode::REPLACE,
+                root,
+                atoms._NET_DESKTOP_VIEWPORT,
+                cardinal,
+                &values,
+            )?;
+            self.dirty.desktop_viewport = false;
+        }
+        if self.dirty.current_desktop {
+            let _ = conn.change_property32(
+                PropMode::REPLACE,
+                root,
+                atoms._NET_CURRENT_DESKTOP,
+                cardinal,
+                &[self.current_desktop],
+            )?;
+            self.dirty.current_desktop = false;
+        }
+        if self.dirty.desktop_names {
+            let mut names = Vec::new();
+            for name in &self.desktop_names {
+                names.extend_from_slice(name.as_bytes());
+                names.push(0);
+            }
+            let _ = conn.change_property8(
+                PropMode::REPLACE,
+                root,
+                atoms._NET_DESKTOP_NAMES,
+                atoms.UTF8_STRING,
+                &names,
+            )?;
+            self.dirty.desktop_names = false;
+        }
+        if self.dirty.active_window {
+            let _ = conn.change_property32(
+                PropMode::REPLACE,
+                root,
+                atoms._NET_ACTIVE_WINDOW,
+                AtomEnum::WINDOW,
+                &[self.active_window.unwrap_or(NONE)],
+            )?;
+            self.dirty.active_window = false;
+        }
+        if self.dirty.workarea {
+            let values: Vec<u32> = self
+                .workarea
+                .iter()
+                .flat_map(|area| [area.x, area.y, area.width, area.height])
+                .collect();
+            let _ = conn.change_property32(
+                PropMode::REPLACE,
+                root,
+                atoms._NET_WORKAREA,
+                cardinal,
+                &values,
+            )?;
+            self.dirty.workarea = false;
+        }
+        if self.dirty.showing_desktop {
+            let _ = conn.change_property32(
+                PropMode::REPLACE,
+                root,
+                atoms._NET_SHOWING_DESKTOP,
+                cardinal,
+                &[self.showing_desktop.into()],
+            )?;
+            self.dirty.showing_desktop = false;
+        }
+        Ok(())
+    }
+}
+
+/// Write a `WINDOW[]` property according to how it changed.
+fn write_list<C: RequestConnection + ?Sized>(
+    conn: &C,
+    root: Window,
+    property: Atom,
+    windows: &[Window],
+    update: &mut ListUpdate,
+) -> Result<(), ConnectionError> {
+    let (mode, windows) = match *update {
+        ListUpdate::Unchanged => return Ok(()),
+        ListUpdate::Append(start) => (PropMode::APPEND, &windows[start..]),
+        ListUpdate::Replace => (PropMode::REPLACE, windows),
+    };
+    let _ = conn.change_property32(mode, root, property, AtomEnum::WINDOW, windows)?;
+    *update = ListUpdate::Unchanged;
+    Ok(())
+}
+
+/// Set the `_NET_FRAME_EXTENTS` property of the given window.
+pub fn set_frame_extents<'a, C: RequestConnection + ?Sized>(
+    conn: &'a C,
+    atoms: &EwmhAtoms,
+    window: Window,
+    extents: FrameExtents,
+) -> Result<VoidCookie<'a, C>, ConnectionError> {
+    conn.change_property32(
+        PropMode::REPLACE,
+        window,
+        atoms._NET_FRAME_EXTENTS,
+        AtomEnum::CARDINAL,
+        &extents.to_values(),
+    )
+}
+
+/// Set the `_NET_WM_VISIBLE_NAME` property of the given window.
+pub fn set_wm_visible_name<'a, C: RequestConnection + ?Sized>(
+    conn: &'a C,
+    atoms: &EwmhAtoms,
+    window: Window,
+    name: &str,
+) -> Result<VoidCookie<'a, C>, ConnectionError> {
+    conn.change_property8(
+        PropMode::REPLACE,
+        window,
+        atoms._NET_WM_VISIBLE_NAME,
+        atoms.UTF8_STRING,
+        name.as_bytes(),
+    )
+}
+
+/// Set the `_NET_WM_ALLOWED_ACTIONS` property of the given window.
+///
+/// `actions` lists atoms like `_NET_WM_ACTION_CLOSE`.
+pub fn set_wm_allowed_actions<'a, C: RequestConnection + ?Sized>(
+    conn: &'a C,
+    atoms: &EwmhAtoms,
+    window: Window,
+    actions: &[Atom],
+) -> Result<VoidCookie<'a, C>, ConnectionError> {
+    conn.change_property32(
+        PropMode::REPLACE,
+        window,
+        atoms._NET_WM_ALLOWED_ACTIONS,
+        AtomEnum::ATOM,
+        actions,
+    )
+}
+
+/// A request that an application or pager sent to the window manager as a client message.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ClientRequest {
+    /// `_NET_NUMBER_OF_DESKTOPS`: Change the number of desktops.
+    NumberOfDesktops {
+        /// The requested number of desktops.
+        number: u32,
+    },
+
+    /// `_NET_DESKTOP_GEOMETRY`: Change the size of the desktops.
+    DesktopGeometry {
+        /// The requested width.
+        width: u32,
+        /// The requested height.
+        height: u32,
+    },
+
+    /// `_NET_DESKTOP_VIEWPORT`: Change the viewport of the current desktop.
+    DesktopViewport {
+        /// The requested x coordinate of the top left corner.
+        x: u32,
+        /// The requested y coordinate of the top left corner.
+        y: u32,
+    },
+
+    /// `_NET_CURRENT_DESKTOP`: Switch to another desktop.
+    CurrentDesktop {
+        /// The index of the requested desktop.
+        desktop: u32,
+        /// The time of the user action that caused the request.
+        timestamp: Timestamp,
+    },
+
+    /// `_NET_ACTIVE_WINDOW`: Activate a window.
+    ActiveWindow {
+        /// The window to activate.
+        window: Window,
+        /// Who sent the request.
+        source: SourceIndication,
+        /// The time of the user action that caused the request.
+        timestamp: Timestamp,
+        /// The requestor's currently active window.
+        currently_active: Option<Window>,
+    },
+
+    /// `_NET_SHOWING_DESKTOP`: Enter or leave "showing the desktop" mode.
+    ShowingDesktop {
+        /// Whether the desktop should be shown.
+        show: bool,
+    },
+
+    /// `_NET_CLOSE_WINDOW`: Close a window.
+    CloseWindow {
+        /// The window to close.
+        window: Window,
+        /// The time of the user action that caused the request.
+        timestamp: Timestamp,
+        /// Who sent the request.
+        source: SourceIndication,
+    },
+
+    /// `_NET_MOVERESIZE_WINDOW`: Move and/or resize a window.
+    MoveResizeWindow {
+        /// The window to move or resize.
+        window: Window,
+        /// The gravity to use, or zero for the window's gravity.
+        gravity: Gravity,
+        /// Who sent the request.
+        source: SourceIndication,
+        /// The new x coordinate, if it should change.
+        x: Option<i32>,
+        /// The new y coordinate, if it should change.
+        y: Option<i32>,
+        /// The new width, if it should change.
+        width: Option<u32>,
+        /// The new height, if it should change.
+        height: Option<u32>,
+    },
+
+    /// `_NET_WM_MOVERESIZE`: Start an interactive move or resize.
+    WmMoveResize {
+        /// The window to move or resize.
+        window: Window,
+        /// The x coordinate of the pointer on the root window.
+        x_root: i32,
+        /// The y coordinate of the pointer on the root window.
+        y_root: i32,
+        /// The kind of operation.
+        direction: MoveResizeDirection,
+        /// The pressed button, or zero for keyboard operations.
+        button: Button,
+        /// Who sent the request.
+        source: SourceIndication,
+    },
+
+    /// `_NET_RESTACK_WINDOW`: Restack a window relative to a sibling.
+    RestackWindow {
+        /// The window to restack.
+        window: Window,
+        /// Who sent the request.
+        source: SourceIndication,
+        /// The sibling window, if any.
+        sibling: Option<Window>,
+        /// How to restack the window.
+        detail: StackMode,
+    },
+
+    /// `_NET_REQUEST_FRAME_EXTENTS`: Set `_NET_FRAME_EXTENTS` on an unmapped window.
+    RequestFrameExtents {
+        /// The window that needs `_NET_FRAME_EXTENTS`.
+        window: Window,
+    },
+
+    /// `_NET_WM_DESKTOP`: Move a window to another desktop.
+    WmDesktop {
+        /// The window to move.
+        window: Window,
+        /// The index of the desktop, or `0xFFFFFFFF` for all desktops.
+        desktop: u32,
+        /// Who sent the request.
+        source: SourceIndication,
+    },
+
+    /// `_NET_WM_STATE`: Change the state of a window.
+    WmState {
+        /// The window whose state should change.
+        window: Window,
+        /// How the state should change.
+        action: WmStateAction,
+        /// The first state to change.
+        first: Atom,
+        /// The second state to change, if any.
+        second: Option<Atom>,
+        /// Who sent the request.
+        source: SourceIndication,
+    },
+
+    /// `_NET_WM_FULLSCREEN_MONITORS`: Set the monitors that a fullscreen window should cover.
+    ///
+    /// The monitors are indices into the list of monitors returned by Xinerama.
+    WmFullscreenMonitors {
+        /// The fullscreen window.
+        window: Window,
+        /// The monitor whose top edge is the top edge of the window.
+        top: u32,
+        /// The monitor whose bottom edge is the bottom edge of the window.
+        bottom: u32,
+        /// The monitor whose left edge is the left edge of the window.
+        left: u32,
+        /// The monitor whose right edge is the right edge of the window.
+        right: u32,
+        /// Who sent the request.
+        source: SourceIndication,
+    },
+
+    /// `_NET_WM_PING`: An application answered a ping from the window manager.
+    ///
+    /// This is the reply that the application sends back to the root window as a `WM_PROTOCOLS`
+    /// client message.
+    WmPing {
+        /// The window that the ping was sent to.
+        window: Window,
+        /// The timestamp of the ping.
+        timestamp: Timestamp,
+    },
+}
+
+// Bits in the first data value of _NET_MOVERESIZE_WINDOW
+const MOVERESIZE_X: u32 = 1 << 8;
+const MOVERESIZE_Y: u32 = 1 << 9;
+const MOVERESIZE_WIDTH: u32 = 1 << 10;
+const MOVERESIZE_HEIGHT: u32 = 1 << 11;
+
+fn non_none(value: u32) -> Option<u32> {
+    Some(value).filter(|&value| value != NONE)
+}
+
+/// Decode a source indication. Unknown values are treated like requests from old clients.
+fn source(value: u32) -> SourceIndication {
+    value.try_into().unwrap_or(SourceIndication::Legacy)
+}
+
+fn to_i32(value: u32) -> i32 {
+    i32::from_ne_bytes(value.to_ne_bytes())
+}
+
+impl ClientRequest {
+    /// Decode a client message that was sent to the root window.
+    ///
+    /// Returns `Ok(None)` if the message is not one of the EWMH requests known to this function.
+    /// Unknown source indications are reported as [`SourceIndication::Legacy`].
+    pub fn parse(
+        atoms: &EwmhAtoms,
+        event: &ClientMessageEvent,
+    ) -> Result<Option<Self>, ParseError> {
+        let type_ = event.type_;
+        let known = [
+            atoms._NET_NUMBER_OF_DESKTOPS,
+            atoms._NET_DESKTOP_GEOMETRY,
+            atoms._NET_DESKTOP_VIEWPORT,
+            atoms._NET_CURRENT_DESKTOP,
+            atoms._NET_ACTIVE_WINDOW,
+            atoms._NET_SHOWING_DESKTOP,
+            atoms._NET_CLOSE_WINDOW,
+            atoms._NET_MOVERESIZE_WINDOW,
+            atoms._NET_WM_MOVERESIZE,
+            atoms._NET_RESTACK_WINDOW,
+            atoms._NET_REQUEST_FRAME_EXTENTS,
+            atoms._NET_WM_DESKTOP,
+            atoms._NET_WM_STATE,
+            atoms._NET_WM_FULLSCREEN_MONITORS,
+            atoms.WM_PROTOCOLS,
+        ];
+        if !known.contains(&type_) {
+            return Ok(None);
+        }
+        if event.format != 32 {
+            return Err(ParseError::InvalidValue);
+        }
+        let window = event.window;
+        let data = event.data.as_data32();
+        if type_ == atoms.WM_PROTOCOLS && data[0] != atoms._NET_WM_PING {
+            return Ok(None);
+        }
+        let request = if type_ == atoms._NET_NUMBER_OF_DESKTOPS {
+            ClientRequest::NumberOfDesktops { number: data[0] }
+        } else if type_ == atoms._NET_DESKTOP_GEOMETRY {
+            ClientRequest::DesktopGeometry {
+                width: data[0],
+                height: data[1],
+            }
+        } else if type_ == atoms._NET_DESKTOP_VIEWPORT {
+            ClientRequest::DesktopViewport {
+                x: data[0],
+                y: data[1],
+            }
+        } else if type_ == atoms._NET_CURRENT_DESKTOP {
+            ClientRequest::CurrentDesktop {
+                desktop: data[0],
+                timestamp: data[1],
+            }
+        } else if type_ == atoms._NET_ACTIVE_WINDOW {
+            ClientRequest::ActiveWindow {
+                window,
+                source: source(data[0]),
+                timestamp: data[1],
+                currently_active: non_none(data[2]),
+            }
+        } else if type_ == atoms._NET_SHOWING_DESKTOP {
+            ClientRequest::ShowingDesktop { show: data[0] != 0 }
+        } else if type_ == atoms._NET_CLOSE_WINDOW {
+            ClientRequest::CloseWindow {
+                window,
+                timestamp: data[0],
+                source: source(data[1]),
+            }
+        } else if type_ == atoms._NET_MOVERESIZE_WINDOW {
+            let flags = data[0];
+            let is_set = |bit: u32| flags & bit != 0;
+            ClientRequest::MoveResizeWindow {
+                window,
+                gravity: (flags & 0xff).into(),
+                source: source((flags >> 12) & 0xf),
+                x: Some(to_i32(data[1])).filter(|_| is_set(MOVERESIZE_X)),
+                y: Some(to_i32(data[2])).filter(|_| is_set(MOVERESIZE_Y)),
+                width: Some(data[3]).filter(|_| is_set(MOVERESIZE_WIDTH)),
+                height: Some(data[4]).filter(|_| is_set(MOVERESIZE_HEIGHT)),
+            }
+        } else if type_ == atoms._NET_WM_MOVERESIZE {
+            ClientRequest::WmMoveResize {
+                window,
+                x_root: to_i32(data[0]),
+                y_root: to_i32(data[1]),
+                direction: data[2].try_into()?,
+                button: data[3].try_into().map_err(|_| ParseError::InvalidValue)?,
+                source: source(data[4]),
+            }
+        } else if type_ == atoms._NET_RESTACK_WINDOW {
+            ClientRequest::RestackWindow {
+                window,
+                source: source(data[0]),
+                sibling: non_none(data[1]),
+                detail: data[2].into(),
+            }
+        } else if type_ == atoms._NET_REQUEST_FRAME_EXTENTS {
+            ClientRequest::RequestFrameExtents { window }
+        } else if type_ == atoms._NET_WM_DESKTOP {
+            ClientRequest::WmDesktop {
+                window,
+                desktop: data[0],
+                source: source(data[1]),
+            }
+        } else if type_ == atoms._NET_WM_FULLSCREEN_MONITORS {
+            ClientRequest::WmFullscreenMonitors {
+                window,
+                top: data[0],
+                bottom: data[1],
+                left: data[2],
+                right: data[3],
+                source: source(data[4]),
+            }
+        } else if type_ == atoms.WM_PROTOCOLS {
+            // The application sends the ping back with the root window in the window field
+            ClientRequest::WmPing {
+                window: data[2],
+                timestamp: data[1],
+            }
+        } else {
+            ClientRequest::WmState {
+                window,
+                action: data[0].try_into()?,
+                first: data[1],
+                second: non_none(data[2]),
+                source: source(data[3]),
+            }
+        };
+        Ok(Some(request))
+    }
+}
@@ -6,6 +6,8 @@ use crate::errors::{ConnectionError, ParseError, ReplyError};
 use crate::protocol::xproto::{self, Atom, AtomEnum, GetPropertyReply, Window};
 use crate::x11_utils::{Serialize, TryParse};
 
+pub mod ewmh;
+
 macro_rules! property_cookie {
     {
         $(#[$meta:meta])*
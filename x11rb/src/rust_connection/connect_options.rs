@@ -0,0 +1,74 @@
+//! Options for establishing a connection, see
+//! [`RustConnection::connect_with_options`](super::RustConnection::connect_with_options).
+
+use std::time::Duration;
+
+/// How [`RustConnection::connect_with_options`](super::RustConnection::connect_with_options)
+/// proceeds through the candidate addresses of a display.
+///
+/// A display name can describe more than one address. For example, `:0` refers to a Unix domain
+/// socket and, if that cannot be reached, to a TCP connection to `localhost`. Host names can also
+/// resolve to more than one IP address.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AddressPolicy {
+    /// Use the first address that a connection can be established to.
+    ///
+    /// If the setup handshake with this address fails, the error is returned without trying
+    /// other addresses. This is what [`RustConnection::connect`](super::RustConnection::connect)
+    /// does.
+    #[default]
+    FirstConnected,
+
+    /// Try the addresses in order until both connecting and the setup handshake succeed.
+    ///
+    /// This also falls back to the next address when the handshake times out or when the
+    /// X11 server rejects the connection.
+    FirstSuccessful,
+
+    /// Only try the first address.
+    FirstOnly,
+}
+
+/// Options for [`RustConnection::connect_with_options`](super::RustConnection::connect_with_options).
+///
+/// The default options do not have any timeouts and behave like
+/// [`RustConnection::connect`](super::RustConnection::connect).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ConnectOptions {
+    /// The maximum time for establishing a TCP connection.
+    ///
+    /// If the host name resolves to more than one IP address, this is the time for trying all of
+    /// them. `None` means that the operating system's timeout applies. Resolving host names and
+    /// connecting to Unix domain sockets is not affected by this timeout.
+    pub connect_timeout: Option<Duration>,
+
+    /// The maximum time for the setup handshake with the X11 server.
+    ///
+    /// `None` means to wait forever.
+    pub handshake_timeout: Option<Duration>,
+
+    /// How to proceed through the candidate addresses of the display.
+    pub address_policy: AddressPolicy,
+}
+
+impl ConnectOptions {
+    /// Set [`ConnectOptions::connect_timeout`].
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Set [`ConnectOptions::handshake_timeout`].
+    pub fn with_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = Some(timeout);
+        self
+    }
+
+    /// Set [`ConnectOptions::address_policy`].
+    pub fn with_address_policy(mut self, address_policy: AddressPolicy) -> Self {
+        self.address_policy = address_policy;
+        self
+    }
+}
@@ -2,30 +2,46 @@
 
 use std::io::IoSlice;
 use std::sync::{Condvar, Mutex, MutexGuard, TryLockError};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use crate::connection::{
-    compute_length_field, Connection, ReplyOrError, RequestConnection, RequestKind,
+    compute_length_field, Connection, ConnectionStats, ErrorHandler, ErrorHandlerSlot,
+    ReplyOrError, RequestConnection, RequestKind,
 };
 use crate::cookie::{Cookie, CookieWithFds, VoidCookie};
-use crate::errors::DisplayParsingError;
 pub use crate::errors::{ConnectError, ConnectionError, ParseError, ReplyError, ReplyOrIdError};
+use crate::errors::{DisplayParsingError, LimitExceeded};
 use crate::extension_manager::ExtensionManager;
 use crate::protocol::bigreq::{ConnectionExt as _, EnableReply};
 use crate::protocol::xproto::{Setup, GET_INPUT_FOCUS_REQUEST, QUERY_EXTENSION_REQUEST};
+use crate::protocol::Event;
 use crate::utils::RawFdContainer;
 use crate::x11_utils::{ExtensionInformation, TryParse, TryParseFd};
 use x11rb_protocol::connect::Connect;
+pub use x11rb_protocol::connection::Limits;
 use x11rb_protocol::connection::{Connection as ProtoConnection, PollReply, ReplyFdKind};
 use x11rb_protocol::id_allocator::IdAllocator;
-use x11rb_protocol::{xauth::get_auth, DiscardMode, RawEventAndSeqNumber, SequenceNumber};
+use x11rb_protocol::{
+    xauth::get_auth_for_connection, DiscardMode, RawEventAndSeqNumber, SequenceNumber,
+};
 
+mod connect_options;
 mod packet_reader;
+mod record;
+mod special_event;
 mod stream;
+mod synchronous;
 mod write_buffer;
 
+pub use connect_options::{AddressPolicy, ConnectOptions};
 use packet_reader::PacketReader;
+pub use record::{RecordingStream, ReplayStream};
+pub use special_event::SpecialEventQueue;
+#[cfg(all(unix, feature = "allow-unsafe-code"))]
+pub(crate) use stream::poll_fd;
 pub use stream::{DefaultStream, PollMode, Stream};
+use synchronous::SynchronousMode;
+pub use synchronous::{SynchronousError, SynchronousErrorHandler};
 use write_buffer::WriteBuffer;
 
 type Buffer = <RustConnection as RequestConnection>::Buf;
@@ -43,6 +59,9 @@ enum MaxRequestBytes {
 struct ConnectionInner {
     inner: ProtoConnection,
     write_buffer: WriteBuffer,
+    // Set once the X11 server exceeded one of the limits. Data was lost, so the connection is
+    // broken afterwards.
+    limit_exceeded: Option<LimitExceeded>,
 }
 
 type MutexGuardInner<'a> = MutexGuard<'a, ConnectionInner>;
@@ -50,9 +69,33 @@ type MutexGuardInner<'a> = MutexGuard<'a, ConnectionInner>;
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub(crate) enum BlockingMode {
     Blocking,
+    BlockingUntil(Instant),
     NonBlocking,
 }
 
+impl BlockingMode {
+    /// Get the blocking mode for waiting until the given deadline.
+    ///
+    /// Returns `ConnectionError::Timeout` if the deadline already passed.
+    fn until(deadline: Option<Instant>) -> Result<Self, ConnectionError> {
+        match deadline {
+            None => Ok(BlockingMode::Blocking),
+            Some(deadline) if Instant::now() >= deadline => Err(ConnectionError::Timeout),
+            Some(deadline) => Ok(BlockingMode::BlockingUntil(deadline)),
+        }
+    }
+}
+
+/// Report an exceeded limit as an I/O error, see `From<std::io::Error> for ConnectionError`.
+fn limit_exceeded_error(limit: LimitExceeded) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, limit)
+}
+
+/// Compute the deadline for a timeout, or `None` if it is too far in the future.
+fn deadline_after(timeout: Duration) -> Option<Instant> {
+    Instant::now().checked_add(timeout)
+}
+
 /// A connection to an X11 server implemented in pure rust
 ///
 /// This type is generic over `S`, which allows to use a generic stream to communicate with the
@@ -73,6 +116,9 @@ pub struct RustConnection<S: Stream = DefaultStream> {
     extension_manager: Mutex<ExtensionManager>,
     maximum_request_bytes: Mutex<MaxRequestBytes>,
     id_allocator: Mutex<IdAllocator>,
+    error_handler: ErrorHandlerSlot,
+    synchronous: SynchronousMode,
+    stats: Mutex<ConnectionStats>,
 }
 
 // Locking rules
@@ -84,6 +130,7 @@ pub struct RustConnection<S: Stream = DefaultStream> {
 // - maximum_request_bytes
 // - extension_manager
 // - id_allocator
+// - synchronous
 //
 // Then comes `inner`. This mutex protects the information about in-flight requests and packets
 // that were already read from the connection but not given out to callers. This mutex also
@@ -101,6 +148,9 @@ pub struct RustConnection<S: Stream = DefaultStream> {
 //
 // n.b. notgull: write_buffer follows the same rules
 //
+// Finally, `stats` may be locked while any other mutex is held, but no other mutex may be locked
+// while `stats` is held.
+//
 // The condition variable is necessary since one thread may read packets that another thread waits
 // for. Thus, after reading something from the connection, all threads that wait for something have
 // to check if they are the intended recipient.
@@ -110,51 +160,78 @@ impl RustConnection<DefaultStream> {
     ///
     /// If no `dpy_name` is provided, the value from `$DISPLAY` is used.
     pub fn connect(dpy_name: Option<&str>) -> Result<(Self, usize), ConnectError> {
+        Self::connect_with_options(dpy_name, &ConnectOptions::default())
+    }
+
+    /// Establish a new connection with the given options.
+    ///
+    /// If no `dpy_name` is provided, the value from `$DISPLAY` is used.
+    ///
+    /// The options allow to limit the time that is spent on connecting, see [`ConnectOptions`].
+    /// When a timeout expires, an [`std::io::Error`] with kind
+    /// [`std::io::ErrorKind::TimedOut`] is returned.
+    pub fn connect_with_options(
+        dpy_name: Option<&str>,
+        options: &ConnectOptions,
+    ) -> Result<(Self, usize), ConnectError> {
         // Parse display information
         let parsed_display = x11rb_protocol::parse_display::parse_display(dpy_name)?;
         let screen = parsed_display.screen.into();
 
+        let max_addresses = match options.address_policy {
+            AddressPolicy::FirstOnly => 1,
+            _ => usize::MAX,
+        };
+
         // Establish connection by iterating over ConnectAddresses until we find one that
         // works.
         let mut error = None;
-        for addr in parsed_display.connect_instruction() {
+        for addr in parsed_display.connect_instruction().take(max_addresses) {
             let start = Instant::now();
-            match DefaultStream::connect(&addr) {
-                Ok((stream, (family, address))) => {
-                    crate::trace!(
-                        "Connected to X11 server via {:?} in {:?}",
-                        addr,
-                        start.elapsed()
-                    );
-
-                    // we found a stream, get auth information
-                    let (auth_name, auth_data) = get_auth(family, &address, parsed_display.display)
-                        // Ignore all errors while determining auth; instead we just try without auth info.
-                        .unwrap_or(None)
-                        .unwrap_or_else(|| (Vec::new(), Vec::new()));
-                    crate::trace!("Picked authentication via auth mechanism {:?}", auth_name);
-
-                    // finish connecting to server
-                    return Ok((
-                        Self::connect_to_stream_with_auth_info(
-                            stream, screen, auth_name, auth_data,
-                        )?,
-                        screen,
-                    ));
-                }
+            let result = match options.connect_timeout {
+                Some(timeout) => DefaultStream::connect_timeout(&addr, timeout),
+                None => DefaultStream::connect(&addr),
+            };
+            let (stream, (family, address)) = match result {
+                Ok(result) => result,
                 Err(e) => {
                     crate::debug!("Failed to connect to X11 server via {:?}: {:?}", addr, e);
-                    error = Some(e);
+                    error = Some(e.into());
                     continue;
                 }
+            };
+            crate::trace!(
+                "Connected to X11 server via {:?} in {:?}",
+                addr,
+                start.elapsed()
+            );
+
+            // we found a stream, get auth information
+            let (auth_name, auth_data) = get_auth_for_connection(
+                family,
+                &address,
+                parsed_display.display,
+                stream.local_address(),
+            )
+            // Ignore all errors while determining auth; instead we just try without auth info.
+            .unwrap_or(None)
+            .unwrap_or_else(|| (Vec::new(), Vec::new()));
+            crate::trace!("Picked authentication via auth mechanism {:?}", auth_name);
+
+            // finish connecting to server
+            let deadline = options.handshake_timeout.and_then(deadline_after);
+            match Self::connect_to_stream_until(stream, screen, auth_name, auth_data, deadline) {
+                Ok(conn) => return Ok((conn, screen)),
+                Err(e) if options.address_policy == AddressPolicy::FirstSuccessful => {
+                    crate::debug!("Failed to set up connection via {:?}: {:?}", addr, e);
+                    error = Some(e);
+                }
+                Err(e) => return Err(e),
             }
         }
 
         // none of the addresses worked
-        Err(match error {
-            Some(e) => ConnectError::IoError(e),
-            None => DisplayParsingError::Unknown.into(),
-        })
+        Err(error.unwrap_or_else(|| DisplayParsingError::Unknown.into()))
     }
 }
 
@@ -183,6 +260,29 @@ impl<S: Stream> RustConnection<S> {
         auth_name: Vec<u8>,
         auth_data: Vec<u8>,
     ) -> Result<Self, ConnectError> {
+        Self::connect_to_stream_until(stream, screen, auth_name, auth_data, None)
+    }
+
+    /// Establish a new connection to the given stream, giving up at the given deadline.
+    fn connect_to_stream_until(
+        stream: S,
+        screen: usize,
+        auth_name: Vec<u8>,
+        auth_data: Vec<u8>,
+        deadline: Option<Instant>,
+    ) -> Result<Self, ConnectError> {
+        // Wait until the stream is ready, but at most until the deadline
+        let poll = |mode| match deadline {
+            None => stream.poll(mode),
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(timeout) if !timeout.is_zero() => stream.poll_with_timeout(mode, timeout),
+                _ => Err(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    "Timeout during the connection setup",
+                )),
+            },
+        };
+
         let (mut connect, setup_request) = Connect::with_authorization(auth_name, auth_data);
 
         // write the connect() setup request
@@ -194,7 +294,7 @@ impl<S: Stream> RustConnection<S> {
             setup_request.len()
         );
         while nwritten != setup_request.len() {
-            stream.poll(PollMode::Writable)?;
+            poll(PollMode::Writable)?;
             // poll returned successfully, so the stream is writable.
             match stream.write(&setup_request[nwritten..], &mut fds) {
                 Ok(0) => {
@@ -213,7 +313,7 @@ impl<S: Stream> RustConnection<S> {
 
         // read in the setup
         loop {
-            stream.poll(PollMode::Readable)?;
+            poll(PollMode::Readable)?;
             crate::trace!(
                 "Reading connection setup with at least {} bytes remaining",
                 connect.buffer().len()
@@ -263,6 +363,7 @@ impl<S: Stream> RustConnection<S> {
             inner: Mutex::new(ConnectionInner {
                 inner: ProtoConnection::new(),
                 write_buffer: WriteBuffer::new(),
+                limit_exceeded: None,
             }),
             stream,
             packet_reader: Mutex::new(PacketReader::new()),
@@ -271,6 +372,9 @@ impl<S: Stream> RustConnection<S> {
             extension_manager: Default::default(),
             maximum_request_bytes: Mutex::new(MaxRequestBytes::Unknown),
             id_allocator: Mutex::new(id_allocator),
+            error_handler: Default::default(),
+            synchronous: Default::default(),
+            stats: Default::default(),
         })
     }
 
@@ -355,6 +459,7 @@ impl<S: Stream> RustConnection<S> {
         mut bufs: &[IoSlice<'_>],
         mut fds: Vec<RawFdContainer>,
     ) -> std::io::Result<MutexGuardInner<'a>> {
+        self.stats.lock().unwrap().record_request(bufs);
         let mut partial_buf: &[u8] = &[];
         while !partial_buf.is_empty() || !bufs.is_empty() {
             self.stream.poll(PollMode::ReadAndWritable)?;
@@ -421,10 +526,20 @@ impl<S: Stream> RustConnection<S> {
     fn flush_impl<'a>(
         &'a self,
         mut inner: MutexGuardInner<'a>,
-    ) -> std::io::Result<MutexGuardInner<'a>> {
+        deadline: Option<Instant>,
+    ) -> Result<MutexGuardInner<'a>, ConnectionError> {
         // n.b. notgull: inner guard is held
+        if inner.write_buffer.needs_flush() {
+            self.stats.lock().unwrap().record_flush();
+        }
         while inner.write_buffer.needs_flush() {
-            self.stream.poll(PollMode::ReadAndWritable)?;
+            match BlockingMode::until(deadline)? {
+                BlockingMode::BlockingUntil(deadline) => self.stream.poll_with_timeout(
+                    PollMode::ReadAndWritable,
+                    deadline.saturating_duration_since(Instant::now()),
+                )?,
+                _ => self.stream.poll(PollMode::ReadAndWritable)?,
+            }
             let flush_result = inner.write_buffer.flush(&self.stream);
             match flush_result {
                 // Flush completed
@@ -436,7 +551,7 @@ impl<S: Stream> RustConnection<S> {
                     // buffered replies have been read.
                     inner = self.read_packet_and_enqueue(inner, BlockingMode::NonBlocking)?;
                 }
-                Err(e) => return Err(e),
+                Err(e) => return Err(e.into()),
             }
         }
         Ok(inner)
@@ -458,19 +573,29 @@ impl<S: Stream> RustConnection<S> {
         mut inner: MutexGuardInner<'a>,
         mode: BlockingMode,
     ) -> Result<MutexGuardInner<'a>, std::io::Error> {
+        // 0.0. Nothing can be read anymore after a limit was exceeded.
+        if let Some(limit) = inner.limit_exceeded {
+            return Err(limit_exceeded_error(limit));
+        }
+
         // 0.1. Try to lock the `packet_reader` mutex.
         match self.packet_reader.try_lock() {
             Err(TryLockError::WouldBlock) => {
                 // In non-blocking mode, we just return immediately
-                match mode {
+                let deadline = match mode {
                     BlockingMode::NonBlocking => {
                         crate::trace!("read_packet_and_enqueue in NonBlocking mode doing nothing since reader is already locked");
                         return Ok(inner);
                     }
                     BlockingMode::Blocking => {
                         crate::trace!("read_packet_and_enqueue in Blocking mode waiting for pre-existing reader");
+                        None
                     }
-                }
+                    BlockingMode::BlockingUntil(deadline) => {
+                        crate::trace!("read_packet_and_enqueue in BlockingUntil mode waiting for pre-existing reader");
+                        Some(deadline)
+                    }
+                };
 
                 // 1.1. Someone else is reading (other thread is at 2.2);
                 // wait for it. `Condvar::wait` will unlock `inner`, so
@@ -479,8 +604,20 @@ impl<S: Stream> RustConnection<S> {
                 //
                 // When `wait` finishes, other thread has enqueued a packet,
                 // so the purpose of this function has been fulfilled. `wait`
-                // will relock `inner` when it returns.
-                Ok(self.reader_condition.wait(inner).unwrap())
+                // will relock `inner` when it returns. With a deadline, the
+                // wait might also end without a new packet. The caller
+                // checks the deadline again and tries again if necessary.
+                match deadline {
+                    None => Ok(self.reader_condition.wait(inner).unwrap()),
+                    Some(deadline) => {
+                        let timeout = deadline.saturating_duration_since(Instant::now());
+                        Ok(self
+                            .reader_condition
+                            .wait_timeout(inner, timeout)
+                            .unwrap()
+                            .0)
+                    }
+                }
             }
             Err(TryLockError::Poisoned(e)) => panic!("{}", e),
             Ok(mut packet_reader) => {
@@ -489,12 +626,19 @@ impl<S: Stream> RustConnection<S> {
                 let notify_on_drop = NotifyOnDrop(&self.reader_condition);
 
                 // 2.1. Poll for read if mode is blocking.
-                if mode == BlockingMode::Blocking {
+                if mode != BlockingMode::NonBlocking {
                     // 2.1.1. Unlock `inner`, so other threads can use it while
                     // during the poll.
                     drop(inner);
-                    // 2.1.2. Do the actual poll
-                    self.stream.poll(PollMode::Readable)?;
+                    // 2.1.2. Do the actual poll. With a deadline, this might
+                    // return without anything being readable.
+                    match mode {
+                        BlockingMode::BlockingUntil(deadline) => self.stream.poll_with_timeout(
+                            PollMode::Readable,
+                            deadline.saturating_duration_since(Instant::now()),
+                        )?,
+                        _ => self.stream.poll(PollMode::Readable)?,
+                    }
                     // 2.1.3. Relock inner
                     inner = self.inner.lock().unwrap();
                 }
@@ -502,7 +646,12 @@ impl<S: Stream> RustConnection<S> {
                 // 2.2. Try to read as many packets as possible without blocking.
                 let mut fds = Vec::new();
                 let mut packets = Vec::new();
-                packet_reader.try_read_packets(&self.stream, &mut packets, &mut fds)?;
+                let read_result = packet_reader.try_read_packets(
+                    &self.stream,
+                    &mut packets,
+                    &mut fds,
+                    inner.inner.limits().max_packet_length,
+                );
 
                 // 2.3. Once `inner` has been relocked, drop the
                 // lock on `packet_reader`. While inner is locked, other
@@ -513,11 +662,27 @@ impl<S: Stream> RustConnection<S> {
                 // for a reply that has been read but not enqueued yet.
                 drop(packet_reader);
 
-                // 2.4. Actually enqueue the read packets.
-                inner.inner.enqueue_fds(fds);
-                packets
-                    .into_iter()
-                    .for_each(|packet| inner.inner.enqueue_packet(packet));
+                // 2.4. Actually enqueue the read packets. An exceeded limit is remembered, so
+                // that all threads that wait for a packet get an error instead of waiting forever.
+                {
+                    let mut stats = self.stats.lock().unwrap();
+                    packets
+                        .iter()
+                        .for_each(|packet| stats.record_packet(packet));
+                }
+                let conn = &mut inner.inner;
+                let enqueue_result = conn.try_enqueue_fds(fds).and_then(|()| {
+                    packets
+                        .into_iter()
+                        .try_for_each(|packet| conn.try_enqueue_packet(packet))
+                });
+                let read_result = read_result.and(enqueue_result.map_err(limit_exceeded_error));
+                if let Err(err) = read_result {
+                    if let Some(limit) = err.get_ref().and_then(|e| e.downcast_ref()) {
+                        inner.limit_exceeded = Some(*limit);
+                    }
+                    return Err(err);
+                }
 
                 // 2.5. Notify the condvar by dropping the `notify_on_drop` object.
                 // The object would have been dropped when the function returns, so
@@ -531,6 +696,126 @@ impl<S: Stream> RustConnection<S> {
         }
     }
 
+    fn check_for_raw_error_impl(
+        &self,
+        sequence: SequenceNumber,
+        deadline: Option<Instant>,
+    ) -> Result<Option<Buffer>, ConnectionError> {
+        let _guard = crate::debug_span!("check_for_raw_error", sequence).entered();
+
+        let mut inner = self.inner.lock().unwrap();
+        if inner.inner.prepare_check_for_reply_or_error(sequence) {
+            crate::trace!("Inserting sync with the X11 server");
+            inner = self.send_sync(inner)?;
+            assert!(!inner.inner.prepare_check_for_reply_or_error(sequence));
+        }
+        // Ensure the request is sent
+        inner = self.flush_impl(inner, deadline)?;
+        let mut wait_start = None;
+        let result = loop {
+            crate::trace!({ sequence }, "Polling for reply or error");
+            let poll_result = inner.inner.poll_check_for_reply_or_error(sequence);
+            match poll_result {
+                PollReply::TryAgain => {}
+                PollReply::NoReply => break None,
+                PollReply::Reply(buffer) => break Some(buffer),
+            }
+            let _ = wait_start.get_or_insert_with(Instant::now);
+            inner = self.read_packet_and_enqueue(inner, BlockingMode::until(deadline)?)?;
+        };
+        self.record_round_trip(wait_start);
+        Ok(result)
+    }
+
+    fn wait_for_reply_with_fds_raw_impl(
+        &self,
+        sequence: SequenceNumber,
+        deadline: Option<Instant>,
+    ) -> Result<ReplyOrError<BufWithFds, Buffer>, ConnectionError> {
+        let _guard = crate::debug_span!("wait_for_reply_with_fds_raw", sequence).entered();
+
+        let mut inner = self.inner.lock().unwrap();
+        // Ensure the request is sent
+        inner = self.flush_impl(inner, deadline)?;
+        let mut wait_start = None;
+        let reply = loop {
+            crate::trace!({ sequence }, "Polling for reply or error");
+            if let Some(reply) = inner.inner.poll_for_reply_or_error(sequence) {
+                break reply;
+            }
+            let _ = wait_start.get_or_insert_with(Instant::now);
+            inner = self.read_packet_and_enqueue(inner, BlockingMode::until(deadline)?)?;
+        };
+        self.record_round_trip(wait_start);
+        if reply.0[0] == 0 {
+            crate::trace!("Got error");
+            Ok(ReplyOrError::Error(reply.0))
+        } else {
+            crate::trace!("Got reply");
+            Ok(ReplyOrError::Reply(reply))
+        }
+    }
+
+    fn wait_for_raw_event_with_sequence_impl(
+        &self,
+        deadline: Option<Instant>,
+    ) -> Result<RawEventAndSeqNumber<Vec<u8>>, ConnectionError> {
+        let _guard = crate::trace_span!("wait_for_raw_event_with_sequence").entered();
+
+        let mut inner = self.inner.lock().unwrap();
+        loop {
+            if let Some(event) = inner.inner.poll_for_event_with_sequence() {
+                // The error handler may use the connection
+                drop(inner);
+                if !self.error_handler.should_discard(self, &event.0) {
+                    return Ok(event);
+                }
+                inner = self.inner.lock().unwrap();
+                continue;
+            }
+            inner = self.read_packet_and_enqueue(inner, BlockingMode::until(deadline)?)?;
+        }
+    }
+
+    /// Get the first event matching `predicate`, reading from the X11 server as necessary.
+    ///
+    /// With `blocking == None`, this only reads packets that are available without blocking.
+    /// Otherwise, it waits until the given deadline (if any).
+    fn event_matching_impl(
+        &self,
+        predicate: &mut dyn FnMut(&Event) -> bool,
+        blocking: Option<Option<Instant>>,
+    ) -> Result<Option<RawEventAndSeqNumber<Vec<u8>>>, ConnectionError> {
+        let _guard = crate::trace_span!("event_matching").entered();
+
+        let mut did_read = false;
+        loop {
+            // Events are parsed while `inner` is locked, so `extension_manager` has to be locked
+            // first
+            let ext_mgr = self.extension_manager.lock().unwrap();
+            let mut inner = self.inner.lock().unwrap();
+            let event = inner.inner.poll_for_event_with_sequence_matching(|event| {
+                Event::parse(event, &*ext_mgr).map_or(false, |event| predicate(&event))
+            });
+            drop(ext_mgr);
+            if let Some(event) = event {
+                // The error handler may use the connection
+                drop(inner);
+                if !self.error_handler.should_discard(self, &event.0) {
+                    return Ok(Some(event));
+                }
+                continue;
+            }
+            let mode = match blocking {
+                None if did_read => return Ok(None),
+                None => BlockingMode::NonBlocking,
+                Some(deadline) => BlockingMode::until(deadline)?,
+            };
+            did_read = true;
+            let _inner = self.read_packet_and_enqueue(inner, mode)?;
+        }
+    }
+
     fn prefetch_maximum_request_bytes_impl(&self, max_bytes: &mut MutexGuard<'_, MaxRequestBytes>) {
         if let MaxRequestBytes::Unknown = **max_bytes {
             crate::info!("Prefetching maximum request length");
@@ -546,6 +831,126 @@ impl<S: Stream> RustConnection<S> {
     pub fn stream(&self) -> &S {
         &self.stream
     }
+
+    /// Enable or disable synchronous mode.
+    ///
+    /// This is a debugging aid similar to `XSynchronize` in Xlib. In synchronous mode, each request
+    /// without a reply is checked for errors right after it was sent. This requires a round trip
+    /// to the X11 server for each request and thus is slow. When an error is found, the given
+    /// handler is called with a [`SynchronousError`] that describes the failed request. With the
+    /// `backtrace` feature, this also contains a backtrace of the code that sent the request.
+    ///
+    /// Errors found this way are only passed to the handler. They do not appear as events and
+    /// checking the returned [`VoidCookie`] does not report them again.
+    ///
+    /// Passing `None` disables synchronous mode.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use x11rb::rust_connection::RustConnection;
+    /// let (conn, _) = RustConnection::connect(None).unwrap();
+    /// conn.set_synchronous(Some(Arc::new(|error| panic!("{error}"))));
+    /// ```
+    pub fn set_synchronous(&self, handler: Option<SynchronousErrorHandler>) {
+        self.synchronous.set(handler);
+    }
+
+    /// Check the request with the given sequence number in synchronous mode.
+    fn check_synchronously(&self, sequence: SequenceNumber) -> Result<(), ConnectionError> {
+        let handler = match self.synchronous.handler() {
+            Some(handler) => handler,
+            None => return Ok(()),
+        };
+        if let Some(error) = self.check_for_raw_error_impl(sequence, None)? {
+            let error = self.parse_error(&error)?;
+            crate::debug!("Synchronous mode found {:?}", error);
+            let description = error
+                .display(&*self.extension_manager.lock().unwrap())
+                .to_string();
+            handler(SynchronousError::new(error, description));
+        }
+        Ok(())
+    }
+
+    /// Register a separate queue for generic events of an extension.
+    ///
+    /// Generic events (`GE_GENERIC_EVENT`) with the given event ID (EID) that belong to the
+    /// extension with name `extension_name` are sent to the returned queue instead of being
+    /// returned by [`Connection::wait_for_event`] and friends. For example, Present's
+    /// `CompleteNotify` events carry the EID that was passed to `present_select_input`. This allows
+    /// libraries to receive their events on a connection that is shared with an application. This
+    /// is the equivalent of libxcb's `xcb_register_for_special_xge`.
+    ///
+    /// Returns `None` if a queue for this EID is already registered. Returns
+    /// [`ConnectionError::UnsupportedExtension`] if the X11 server does not support the extension.
+    pub fn register_for_special_event(
+        &self,
+        extension_name: &'static str,
+        eid: u32,
+    ) -> Result<Option<SpecialEventQueue<'_, S>>, ConnectionError> {
+        let extension = self
+            .extension_information(extension_name)?
+            .ok_or(ConnectionError::UnsupportedExtension)?
+            .major_opcode;
+        let mut inner = self.inner.lock().unwrap();
+        if inner.inner.register_for_special_event(extension, eid) {
+            Ok(Some(SpecialEventQueue::new(self, extension, eid)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Get statistics about the traffic on this connection.
+    ///
+    /// The statistics count everything since the connection was established or since the last call
+    /// to [`RustConnection::reset_stats`].
+    pub fn stats(&self) -> ConnectionStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Reset the statistics about the traffic on this connection to zero.
+    ///
+    /// The statistics before the reset are returned.
+    pub fn reset_stats(&self) -> ConnectionStats {
+        std::mem::take(&mut *self.stats.lock().unwrap())
+    }
+
+    /// Record a wait for a reply or a sync that started at `wait_start`.
+    ///
+    /// `None` means that the reply was already available and nothing is recorded.
+    fn record_round_trip(&self, wait_start: Option<Instant>) {
+        if let Some(start) = wait_start {
+            self.stats
+                .lock()
+                .unwrap()
+                .record_round_trip(start.elapsed());
+        }
+    }
+
+    /// Set limits on the data that is accepted from the X11 server.
+    ///
+    /// When the X11 server sends more data than these limits allow, the function that read the
+    /// data fails with [`ConnectionError::LimitExceeded`]. Since data was lost, all later functions
+    /// that need data from the X11 server fail with the same error. By default, there are no
+    /// limits.
+    pub fn set_limits(&self, limits: Limits) {
+        self.inner.lock().unwrap().inner.set_limits(limits);
+    }
+
+    /// Get the limits on the data that is accepted from the X11 server.
+    pub fn limits(&self) -> Limits {
+        self.inner.lock().unwrap().inner.limits()
+    }
+
+    /// Set the handler for X11 errors that would otherwise be delivered as events.
+    ///
+    /// Passing `None` removes the current handler. The previous handler is returned. See
+    /// [`ErrorHandler`] for details.
+    pub fn set_error_handler(&self, handler: Option<ErrorHandler>) -> Option<ErrorHandler> {
+        self.error_handler.replace(handler)
+    }
 }
 
 impl<S: Stream> RequestConnection for RustConnection<S> {
@@ -584,10 +989,9 @@ impl<S: Stream> RequestConnection for RustConnection<S> {
         bufs: &[IoSlice<'_>],
         fds: Vec<RawFdContainer>,
     ) -> Result<VoidCookie<'_, Self>, ConnectionError> {
-        Ok(VoidCookie::new(
-            self,
-            self.send_request(bufs, fds, ReplyFdKind::NoReply)?,
-        ))
+        let sequence = self.send_request(bufs, fds, ReplyFdKind::NoReply)?;
+        self.check_synchronously(sequence)?;
+        Ok(VoidCookie::new(self, sequence))
     }
 
     fn discard_reply(&self, sequence: SequenceNumber, _kind: RequestKind, mode: DiscardMode) {
@@ -637,42 +1041,47 @@ impl<S: Stream> RequestConnection for RustConnection<S> {
         let _guard = crate::debug_span!("wait_for_reply", sequence).entered();
 
         let mut inner = self.inner.lock().unwrap();
-        inner = self.flush_impl(inner)?;
-        loop {
+        inner = self.flush_impl(inner, None)?;
+        let mut wait_start = None;
+        let result = loop {
             crate::trace!({ sequence }, "Polling for reply");
             let poll_result = inner.inner.poll_for_reply(sequence);
             match poll_result {
                 PollReply::TryAgain => {}
-                PollReply::NoReply => return Ok(None),
-                PollReply::Reply(buffer) => return Ok(Some(buffer)),
+                PollReply::NoReply => break None,
+                PollReply::Reply(buffer) => break Some(buffer),
             }
+            let _ = wait_start.get_or_insert_with(Instant::now);
             inner = self.read_packet_and_enqueue(inner, BlockingMode::Blocking)?;
-        }
+        };
+        self.record_round_trip(wait_start);
+        Ok(result)
     }
 
     fn check_for_raw_error(
         &self,
         sequence: SequenceNumber,
     ) -> Result<Option<Buffer>, ConnectionError> {
-        let _guard = crate::debug_span!("check_for_raw_error", sequence).entered();
+        self.check_for_raw_error_impl(sequence, None)
+    }
 
-        let mut inner = self.inner.lock().unwrap();
-        if inner.inner.prepare_check_for_reply_or_error(sequence) {
-            crate::trace!("Inserting sync with the X11 server");
-            inner = self.send_sync(inner)?;
-            assert!(!inner.inner.prepare_check_for_reply_or_error(sequence));
-        }
-        // Ensure the request is sent
-        inner = self.flush_impl(inner)?;
-        loop {
-            crate::trace!({ sequence }, "Polling for reply or error");
-            let poll_result = inner.inner.poll_check_for_reply_or_error(sequence);
-            match poll_result {
-                PollReply::TryAgain => {}
-                PollReply::NoReply => return Ok(None),
-                PollReply::Reply(buffer) => return Ok(Some(buffer)),
-            }
-            inner = self.read_packet_and_enqueue(inner, BlockingMode::Blocking)?;
+    fn check_for_raw_error_timeout(
+        &self,
+        sequence: SequenceNumber,
+        timeout: Duration,
+    ) -> Result<Option<Buffer>, ConnectionError> {
+        self.check_for_raw_error_impl(sequence, deadline_after(timeout))
+    }
+
+    fn wait_for_reply_or_raw_error_timeout(
+        &self,
+        sequence: SequenceNumber,
+        timeout: Duration,
+    ) -> Result<ReplyOrError<Vec<u8>>, ConnectionError> {
+        let deadline = deadline_after(timeout);
+        match self.wait_for_reply_with_fds_raw_impl(sequence, deadline)? {
+            ReplyOrError::Reply((reply, _fds)) => Ok(ReplyOrError::Reply(reply)),
+            ReplyOrError::Error(e) => Ok(ReplyOrError::Error(e)),
         }
     }
 
@@ -680,24 +1089,7 @@ impl<S: Stream> RequestConnection for RustConnection<S> {
         &self,
         sequence: SequenceNumber,
     ) -> Result<ReplyOrError<BufWithFds, Buffer>, ConnectionError> {
-        let _guard = crate::debug_span!("wait_for_reply_with_fds_raw", sequence).entered();
-
-        let mut inner = self.inner.lock().unwrap();
-        // Ensure the request is sent
-        inner = self.flush_impl(inner)?;
-        loop {
-            crate::trace!({ sequence }, "Polling for reply or error");
-            if let Some(reply) = inner.inner.poll_for_reply_or_error(sequence) {
-                if reply.0[0] == 0 {
-                    crate::trace!("Got error");
-                    return Ok(ReplyOrError::Error(reply.0));
-                } else {
-                    crate::trace!("Got reply");
-                    return Ok(ReplyOrError::Reply(reply));
-                }
-            }
-            inner = self.read_packet_and_enqueue(inner, BlockingMode::Blocking)?;
-        }
+        self.wait_for_reply_with_fds_raw_impl(sequence, None)
     }
 
     fn maximum_request_bytes(&self) -> usize {
@@ -743,9 +1135,9 @@ impl<S: Stream> RequestConnection for RustConnection<S> {
         crate::x11_utils::X11Error::try_parse(error, &*ext_mgr)
     }
 
-    fn parse_event(&self, event: &[u8]) -> Result<crate::protocol::Event, ParseError> {
+    fn parse_event(&self, event: &[u8]) -> Result<Event, ParseError> {
         let ext_mgr = self.extension_manager.lock().unwrap();
-        crate::protocol::Event::parse(event, &*ext_mgr)
+        Event::parse(event, &*ext_mgr)
     }
 }
 
@@ -753,15 +1145,14 @@ impl<S: Stream> Connection for RustConnection<S> {
     fn wait_for_raw_event_with_sequence(
         &self,
     ) -> Result<RawEventAndSeqNumber<Vec<u8>>, ConnectionError> {
-        let _guard = crate::trace_span!("wait_for_raw_event_with_sequence").entered();
+        self.wait_for_raw_event_with_sequence_impl(None)
+    }
 
-        let mut inner = self.inner.lock().unwrap();
-        loop {
-            if let Some(event) = inner.inner.poll_for_event_with_sequence() {
-                return Ok(event);
-            }
-            inner = self.read_packet_and_enqueue(inner, BlockingMode::Blocking)?;
-        }
+    fn wait_for_raw_event_with_sequence_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<RawEventAndSeqNumber<Vec<u8>>, ConnectionError> {
+        self.wait_for_raw_event_with_sequence_impl(deadline_after(timeout))
     }
 
     fn poll_for_raw_event_with_sequence(
@@ -770,17 +1161,46 @@ impl<S: Stream> Connection for RustConnection<S> {
         let _guard = crate::trace_span!("poll_for_raw_event_with_sequence").entered();
 
         let mut inner = self.inner.lock().unwrap();
-        if let Some(event) = inner.inner.poll_for_event_with_sequence() {
-            Ok(Some(event))
-        } else {
-            inner = self.read_packet_and_enqueue(inner, BlockingMode::NonBlocking)?;
-            Ok(inner.inner.poll_for_event_with_sequence())
+        loop {
+            let event = match inner.inner.poll_for_event_with_sequence() {
+                Some(event) => event,
+                None => {
+                    inner = self.read_packet_and_enqueue(inner, BlockingMode::NonBlocking)?;
+                    match inner.inner.poll_for_event_with_sequence() {
+                        Some(event) => event,
+                        None => return Ok(None),
+                    }
+                }
+            };
+            // The error handler may use the connection
+            drop(inner);
+            if !self.error_handler.should_discard(self, &event.0) {
+                return Ok(Some(event));
+            }
+            inner = self.inner.lock().unwrap();
         }
     }
 
+    fn poll_for_raw_event_with_sequence_matching(
+        &self,
+        predicate: &mut dyn FnMut(&Event) -> bool,
+    ) -> Result<Option<RawEventAndSeqNumber<Vec<u8>>>, ConnectionError> {
+        self.event_matching_impl(predicate, None)
+    }
+
+    fn wait_for_raw_event_with_sequence_matching(
+        &self,
+        predicate: &mut dyn FnMut(&Event) -> bool,
+        timeout: Option<Duration>,
+    ) -> Result<RawEventAndSeqNumber<Vec<u8>>, ConnectionError> {
+        let deadline = timeout.and_then(deadline_after);
+        let event = self.event_matching_impl(predicate, Some(deadline))?;
+        Ok(event.expect("Blocking search should only return with an event"))
+    }
+
     fn flush(&self) -> Result<(), ConnectionError> {
         let inner = self.inner.lock().unwrap();
-        let _inner = self.flush_impl(inner)?;
+        let _inner = self.flush_impl(inner, None)?;
         Ok(())
     }
 
@@ -38,12 +38,17 @@ impl PacketReader {
     }
 
     /// Reads as many packets as possible from stream reader without blocking.
+    ///
+    /// Packets larger than `max_packet_length` cause an error that wraps a
+    /// [`LimitExceeded`](x11rb_protocol::errors::LimitExceeded).
     pub(crate) fn try_read_packets(
         &mut self,
         stream: &impl Stream,
         out_packets: &mut Vec<Vec<u8>>,
         fd_storage: &mut Vec<RawFdContainer>,
+        max_packet_length: usize,
     ) -> Result<()> {
+        self.inner.set_max_packet_length(max_packet_length);
         let original_length = out_packets.len();
         loop {
             // if the necessary packet size is larger than our buffer, just fill straight
@@ -63,7 +68,7 @@ impl PacketReader {
                     }
                     Ok(n) => {
                         crate::trace!("Read {} bytes directly into large packet", n);
-                        if let Some(packet) = self.inner.advance(n) {
+                        if let Some(packet) = advance(&mut self.inner, n)? {
                             out_packets.push(packet);
                         }
                     }
@@ -99,7 +104,7 @@ impl PacketReader {
                     src = &src[amt_to_read..];
 
                     // advance by the given amount
-                    if let Some(packet) = self.inner.advance(amt_to_read) {
+                    if let Some(packet) = advance(&mut self.inner, amt_to_read)? {
                         out_packets.push(packet);
                     }
                 }
@@ -114,6 +119,13 @@ impl PacketReader {
     }
 }
 
+/// Advance the packet reader, turning an exceeded limit into an I/O error.
+fn advance(inner: &mut ProtoPacketReader, amount: usize) -> Result<Option<Vec<u8>>> {
+    inner
+        .try_advance(amount)
+        .map_err(super::limit_exceeded_error)
+}
+
 #[cfg(test)]
 mod tests {
     use super::PacketReader;
@@ -167,7 +179,7 @@ mod tests {
         let mut fd_storage = Vec::new();
 
         reader
-            .try_read_packets(&stream, &mut packets, &mut fd_storage)
+            .try_read_packets(&stream, &mut packets, &mut fd_storage, usize::MAX)
             .unwrap();
 
         assert_eq!(packets.len(), 1);
@@ -0,0 +1,585 @@
+//! Recording a session on the wire level and replaying it later.
+//!
+//! A [`RecordingStream`] wraps another [`Stream`] and writes everything that goes through it into
+//! a compact binary log. A [`ReplayStream`] reads such a log and plays back the server side of the
+//! session, so that the client side can be reproduced without an X11 server. The format of the log
+//! is described on [`RecordingStream`].
+
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind, IoSlice, Read, Result, Write};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+use super::{PollMode, Stream};
+use crate::utils::RawFdContainer;
+
+const MAGIC: &[u8; 8] = b"x11rbrec";
+const VERSION: u8 = 1;
+
+const CLIENT_DATA: u8 = 0;
+const SERVER_DATA: u8 = 1;
+
+/// Get the length of the `SetupRequest` with the given first twelve bytes.
+fn setup_request_length(header: &[u8]) -> u64 {
+    let read_u16 = |bytes: [u8; 2]| {
+        if header[0] == b'B' {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        }
+    };
+    let pad = |length: u16| (u64::from(length) + 3) & !3;
+    let name_len = read_u16([header[6], header[7]]);
+    let data_len = read_u16([header[8], header[9]]);
+    12 + pad(name_len) + pad(data_len)
+}
+
+/// A stream that records all data and FDs that pass through it.
+///
+/// All data that the client writes and that the server sends is written to the given writer. The
+/// recording can be replayed with [`ReplayStream`].
+///
+/// The recording starts with the eight bytes `x11rbrec` and a version byte. It is followed by a
+/// list of records. Each record consists of a direction byte (0 for data sent by the client, 1 for
+/// data sent by the server), the number of passed file descriptors as a little endian `u16`, the
+/// length of the data as a little endian `u32` and the data itself. File descriptors cannot be
+/// recorded, so only their number is kept.
+///
+/// Note that the recording includes the authorization data that the client sends to the server
+/// during the connection setup. Recordings should thus be treated like the user's
+/// `~/.Xauthority`.
+///
+/// Errors while writing the recording do not affect the stream operation that caused them, since
+/// the data was already exchanged with the server at this point. Instead, the recording is stopped
+/// and the error can be retrieved with [`RecordingStream::take_recording_error`].
+#[derive(Debug)]
+pub struct RecordingStream<S, W> {
+    inner: S,
+    recorder: Mutex<Recorder<W>>,
+}
+
+#[derive(Debug)]
+struct Recorder<W> {
+    writer: W,
+    /// Whether recording was stopped because of an error.
+    stopped: bool,
+    /// The error that stopped the recording, until it is taken.
+    error: Option<Error>,
+}
+
+impl<S: Stream, W: Write> RecordingStream<S, W> {
+    /// Start recording the given stream into the given writer.
+    ///
+    /// This immediately writes the header of the recording.
+    pub fn new(inner: S, mut writer: W) -> Result<Self> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        Ok(Self {
+            inner,
+            recorder: Mutex::new(Recorder {
+                writer,
+                stopped: false,
+                error: None,
+            }),
+        })
+    }
+
+    /// Get access to the underlying stream.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Flush the writer that the recording is written to.
+    pub fn flush_recording(&self) -> Result<()> {
+        self.recorder.lock().unwrap().writer.flush()
+    }
+
+    /// Check whether the recording was stopped because of an error.
+    ///
+    /// Once writing the recording failed, no further data is recorded. The data that the stream
+    /// exchanges with the server is not affected by this.
+    pub fn is_recording_stopped(&self) -> bool {
+        self.recorder.lock().unwrap().stopped
+    }
+
+    /// Get the error that stopped the recording.
+    ///
+    /// This returns `None` if the recording was not stopped or if the error was already taken.
+    pub fn take_recording_error(&self) -> Option<Error> {
+        self.recorder.lock().unwrap().error.take()
+    }
+
+    /// Stop recording and get back the stream and the writer.
+    pub fn into_parts(self) -> (S, W) {
+        (self.inner, self.recorder.into_inner().unwrap().writer)
+    }
+
+    /// Record the given data. Errors stop the recording instead of being returned.
+    fn record(&self, direction: u8, bufs: &[&[u8]], num_fds: usize) {
+        let mut recorder = self.recorder.lock().unwrap();
+        if recorder.stopped {
+            return;
+        }
+        if let Err(err) = Self::write_record(&mut recorder.writer, direction, bufs, num_fds) {
+            crate::warning!("Stopping the recording after an error: {}", err);
+            recorder.stopped = true;
+            recorder.error = Some(err);
+        }
+    }
+
+    fn write_record(writer: &mut W, direction: u8, bufs: &[&[u8]], num_fds: usize) -> Result<()> {
+        let length: usize = bufs.iter().map(|buf| buf.len()).sum();
+        if length == 0 && num_fds == 0 {
+            return Ok(());
+        }
+        let too_large = |_| Error::new(ErrorKind::Other, "Too much data for a single record");
+        let num_fds = u16::try_from(num_fds).map_err(too_large)?;
+        let length = u32::try_from(length).map_err(too_large)?;
+
+        writer.write_all(&[direction])?;
+        writer.write_all(&num_fds.to_le_bytes())?;
+        writer.write_all(&length.to_le_bytes())?;
+        for buf in bufs {
+            writer.write_all(buf)?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: Stream, W: Write> Stream for RecordingStream<S, W> {
+    fn poll(&self, mode: PollMode) -> Result<()> {
+        self.inner.poll(mode)
+    }
+
+    fn poll_with_timeout(&self, mode: PollMode, timeout: Duration) -> Result<()> {
+        self.inner.poll_with_timeout(mode, timeout)
+    }
+
+    fn read(&self, buf: &mut [u8], fd_storage: &mut Vec<RawFdContainer>) -> Result<usize> {
+        let fds_before = fd_storage.len();
+        let count = self.inner.read(buf, fd_storage)?;
+        self.record(SERVER_DATA, &[&buf[..count]], fd_storage.len() - fds_before);
+        Ok(count)
+    }
+
+    fn write(&self, buf: &[u8], fds: &mut Vec<RawFdContainer>) -> Result<usize> {
+        let fds_before = fds.len();
+        let count = self.inner.write(buf, fds)?;
+        self.record(CLIENT_DATA, &[&buf[..count]], fds_before - fds.len());
+        Ok(count)
+    }
+
+    fn write_vectored(&self, bufs: &[IoSlice<'_>], fds: &mut Vec<RawFdContainer>) -> Result<usize> {
+        let fds_before = fds.len();
+        let count = self.inner.write_vectored(bufs, fds)?;
+        let mut remaining = count;
+        let written = bufs
+            .iter()
+            .map(|buf| {
+                let length = remaining.min(buf.len());
+                remaining -= length;
+                &buf[..length]
+            })
+            .collect::<Vec<_>>();
+        self.record(CLIENT_DATA, &written, fds_before - fds.len());
+        Ok(count)
+    }
+}
+
+/// Data that the server sent in the recorded session.
+#[derive(Debug)]
+struct ServerChunk {
+    /// The number of bytes that the client sent before this chunk was received.
+    client_bytes_before: u64,
+    data: Vec<u8>,
+    num_fds: u16,
+}
+
+#[derive(Debug)]
+struct ReplayState {
+    /// Server data that was not yet made available to the client.
+    chunks: VecDeque<ServerChunk>,
+    /// The length of the `SetupRequest` in the recording.
+    recorded_setup_length: Option<u64>,
+    /// The first bytes that the client wrote, until the `SetupRequest`'s length is known.
+    setup_header: Vec<u8>,
+    /// The length of the `SetupRequest` that the client sent during the replay.
+    setup_length: Option<u64>,
+    /// The number of bytes that the client wrote during the replay.
+    client_bytes: u64,
+    /// Server data that the client can read.
+    readable: VecDeque<u8>,
+    /// The number of FDs that the client can read.
+    readable_fds: usize,
+}
+
+impl ReplayState {
+    /// Make server data available for reading according to the progress of the client.
+    ///
+    /// Server data becomes readable once the client sent as much data as in the recording. The
+    /// `SetupRequest` is the exception: Its length depends on the authorization data. Thus, any
+    /// difference between the recorded and the replayed `SetupRequest` length is accounted for.
+    fn release_chunks(&mut self) {
+        let progress = match (self.recorded_setup_length, self.setup_length) {
+            (Some(recorded), Some(replayed)) if self.client_bytes >= replayed => {
+                self.client_bytes - replayed + recorded
+            }
+            _ => 0,
+        };
+        while self
+            .chunks
+            .front()
+            .map_or(false, |chunk| chunk.client_bytes_before <= progress)
+        {
+            let chunk = self.chunks.pop_front().unwrap();
+            self.readable.extend(chunk.data);
+            self.readable_fds += usize::from(chunk.num_fds);
+        }
+    }
+
+    fn is_readable(&self) -> bool {
+        !self.readable.is_empty() || self.readable_fds != 0
+    }
+}
+
+/// A stream that plays back the server side of a recorded session.
+///
+/// The recording is created by a [`RecordingStream`]. Data that the server sent becomes readable
+/// once the client wrote as many bytes as it did in the recorded session. The data that the
+/// client writes is not compared with the recording. Once all recorded data was read, waiting for
+/// more data fails with [`ErrorKind::UnexpectedEof`].
+///
+/// File descriptors cannot be recorded. Instead, the replay passes file descriptors for
+/// `/dev/null` to the client where the server passed file descriptors.
+#[derive(Debug)]
+pub struct ReplayStream {
+    state: Mutex<ReplayState>,
+    condvar: Condvar,
+}
+
+impl ReplayStream {
+    /// Load a recording for replaying.
+    pub fn new(mut reader: impl Read) -> Result<Self> {
+        let invalid = |msg| Error::new(ErrorKind::InvalidData, msg);
+
+        let mut header = [0; 9];
+        reader.read_exact(&mut header)?;
+        if header[..8] != MAGIC[..] {
+            return Err(invalid("Not an x11rb recording"));
+        }
+        if header[8] != VERSION {
+            return Err(invalid("Unsupported recording version"));
+        }
+
+        let mut chunks = VecDeque::new();
+        let mut client_bytes = 0;
+        let mut setup_header = Vec::new();
+        loop {
+            // The recording may only end between two records
+            let mut record_header = [0; 7];
+            match reader.read(&mut record_header[..1]) {
+                Ok(0) => break,
+                Ok(_) => reader.read_exact(&mut record_header[1..])?,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+            let num_fds = u16::from_le_bytes([record_header[1], record_header[2]]);
+            let length = u32::from_le_bytes([
+                record_header[3],
+                record_header[4],
+                record_header[5],
+                record_header[6],
+            ]);
+            // The length is not trusted for allocating memory, since the recording might be
+            // truncated or corrupted.
+            let mut data = Vec::new();
+            let _ = reader.by_ref().take(length.into()).read_to_end(&mut data)?;
+            if u64::try_from(data.len()) != Ok(length.into()) {
+                return Err(invalid("Truncated record"));
+            }
+            match record_header[0] {
+                CLIENT_DATA => {
+                    let missing = 12usize.saturating_sub(setup_header.len());
+                    setup_header.extend(data.iter().take(missing));
+                    client_bytes += u64::from(length);
+                }
+                SERVER_DATA => chunks.push_back(ServerChunk {
+                    client_bytes_before: client_bytes,
+                    data,
+                    num_fds,
+                }),
+                _ => return Err(invalid("Invalid record direction")),
+            }
+        }
+        let recorded_setup_length = if setup_header.len() == 12 {
+            Some(setup_request_length(&setup_header))
+        } else {
+            None
+        };
+
+        let state = ReplayState {
+            chunks,
+            recorded_setup_length,
+            setup_header: Vec::new(),
+            setup_length: None,
+            client_bytes: 0,
+            readable: VecDeque::new(),
+            readable_fds: 0,
+        };
+        Ok(Self {
+            state: Mutex::new(state),
+            condvar: Condvar::new(),
+        })
+    }
+
+    /// Check whether all of the recorded server data was read by the client.
+    pub fn is_finished(&self) -> bool {
+        let state = self.lock();
+        state.chunks.is_empty() && state.readable.is_empty() && state.readable_fds == 0
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ReplayState> {
+        self.state.lock().unwrap()
+    }
+
+    fn poll_impl(&self, mode: PollMode, timeout: Option<Duration>) -> Result<()> {
+        // Writing never blocks
+        if mode.writable() {
+            return Ok(());
+        }
+        // Wait until something is readable or the end of the recording was reached
+        let condition = |state: &mut ReplayState| !state.is_readable() && !state.chunks.is_empty();
+        let state = self.lock();
+        let state = match timeout {
+            None => self.condvar.wait_while(state, condition).unwrap(),
+            Some(timeout) => {
+                self.condvar
+                    .wait_timeout_while(state, timeout, condition)
+                    .unwrap()
+                    .0
+            }
+        };
+        if !state.is_readable() && state.chunks.is_empty() {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "The recorded session ended",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn placeholder_fd() -> Result<RawFdContainer> {
+    Ok(std::fs::File::open("/dev/null")?.into())
+}
+
+#[cfg(not(unix))]
+fn placeholder_fd() -> Result<RawFdContainer> {
+    Err(Error::new(
+        ErrorKind::Unsupported,
+        "FD passing is only supported on Unix-like systems",
+    ))
+}
+
+impl Stream for ReplayStream {
+    fn poll(&self, mode: PollMode) -> Result<()> {
+        self.poll_impl(mode, None)
+    }
+
+    fn poll_with_timeout(&self, mode: PollMode, timeout: Duration) -> Result<()> {
+        self.poll_impl(mode, Some(timeout))
+    }
+
+    fn read(&self, buf: &mut [u8], fd_storage: &mut Vec<RawFdContainer>) -> Result<usize> {
+        let mut state = self.lock();
+        while state.readable_fds > 0 {
+            fd_storage.push(placeholder_fd()?);
+            state.readable_fds -= 1;
+        }
+        if state.readable.is_empty() {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        let length = buf.len().min(state.readable.len());
+        for (target, source) in buf.iter_mut().zip(state.readable.drain(..length)) {
+            *target = source;
+        }
+        Ok(length)
+    }
+
+    fn write(&self, buf: &[u8], fds: &mut Vec<RawFdContainer>) -> Result<usize> {
+        let mut state = self.lock();
+        fds.clear();
+        if state.setup_length.is_none() {
+            let missing = 12 - state.setup_header.len();
+            let header = &buf[..missing.min(buf.len())];
+            state.setup_header.extend_from_slice(header);
+            if state.setup_header.len() == 12 {
+                state.setup_length = Some(setup_request_length(&state.setup_header));
+            }
+        }
+        state.client_bytes += u64::try_from(buf.len()).unwrap();
+        state.release_chunks();
+        drop(state);
+        self.condvar.notify_all();
+        Ok(buf.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    use super::{RecordingStream, ReplayStream};
+    use crate::rust_connection::{PollMode, Stream};
+    use crate::utils::RawFdContainer;
+
+    /// A writer that can still be accessed after it was given away.
+    #[derive(Debug, Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A stream where the server answers each write with fixed data.
+    #[derive(Debug, Default)]
+    struct EchoStream(Mutex<Vec<u8>>);
+
+    impl Stream for EchoStream {
+        fn poll(&self, _mode: PollMode) -> std::io::Result<()> {
+            Ok(())
+        }
+
+        fn read(&self, buf: &mut [u8], _fds: &mut Vec<RawFdContainer>) -> std::io::Result<usize> {
+            let mut data = self.0.lock().unwrap();
+            let length = data.len().min(buf.len());
+            buf[..length].copy_from_slice(&data[..length]);
+            let _ = data.drain(..length);
+            Ok(length)
+        }
+
+        fn write(&self, buf: &[u8], _fds: &mut Vec<RawFdContainer>) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend(buf.iter().map(|b| b + 1));
+            Ok(buf.len())
+        }
+    }
+
+    fn setup_request(auth_name: &[u8]) -> Vec<u8> {
+        let mut request = vec![b'l', 0, 11, 0, 0, 0];
+        request.extend((auth_name.len() as u16).to_le_bytes());
+        request.extend([0; 4]);
+        request.extend(auth_name);
+        request.resize((request.len() + 3) & !3, 0);
+        request
+    }
+
+    fn read_all(stream: &impl Stream) -> Vec<u8> {
+        let mut buf = [0; 64];
+        let length = stream.read(&mut buf, &mut Vec::new()).unwrap();
+        buf[..length].to_vec()
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let recording = SharedBuffer::default();
+        let stream = RecordingStream::new(EchoStream::default(), recording.clone()).unwrap();
+        assert_eq!(
+            stream
+                .write(&setup_request(b"auth"), &mut Vec::new())
+                .unwrap(),
+            16
+        );
+        assert_eq!(read_all(&stream).len(), 16);
+        assert_eq!(stream.write(&[1, 2, 3, 4], &mut Vec::new()).unwrap(), 4);
+        assert_eq!(read_all(&stream), [2, 3, 4, 5]);
+        drop(stream);
+
+        let recording = recording.0.lock().unwrap().clone();
+        assert_eq!(&recording[..8], b"x11rbrec");
+        let replay = ReplayStream::new(&recording[..]).unwrap();
+
+        // Nothing is readable before the client sent its setup request. The replayed setup
+        // request does not have the same length as the recorded one.
+        let err = replay.read(&mut [0; 4], &mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::WouldBlock);
+        assert_eq!(
+            replay.write(&setup_request(b""), &mut Vec::new()).unwrap(),
+            12
+        );
+        assert_eq!(read_all(&replay).len(), 16);
+
+        // The reply only becomes readable after the request was completely written
+        assert_eq!(replay.write(&[9, 9], &mut Vec::new()).unwrap(), 2);
+        let err = replay.read(&mut [0; 4], &mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::WouldBlock);
+        assert_eq!(replay.write(&[9, 9], &mut Vec::new()).unwrap(), 2);
+        assert_eq!(read_all(&replay), [2, 3, 4, 5]);
+
+        // The session ends here
+        assert!(replay.is_finished());
+        let err = replay.poll(PollMode::Readable).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    /// A writer that fails once it got the given number of bytes.
+    #[derive(Debug)]
+    struct LimitedWriter(usize);
+
+    impl Write for LimitedWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.0 == 0 {
+                return Err(std::io::ErrorKind::WriteZero.into());
+            }
+            let length = self.0.min(buf.len());
+            self.0 -= length;
+            Ok(length)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn recording_error() {
+        let stream = RecordingStream::new(EchoStream::default(), LimitedWriter(12)).unwrap();
+        assert!(!stream.is_recording_stopped());
+
+        // Recording fails, but the data still reaches the server
+        assert_eq!(stream.write(&[1, 2, 3, 4], &mut Vec::new()).unwrap(), 4);
+        assert!(stream.is_recording_stopped());
+        let err = stream.take_recording_error().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::WriteZero);
+        assert!(stream.take_recording_error().is_none());
+
+        // Nothing is recorded anymore
+        assert_eq!(read_all(&stream), [2, 3, 4, 5]);
+        assert!(stream.is_recording_stopped());
+        assert!(stream.take_recording_error().is_none());
+        assert_eq!(stream.into_parts().1 .0, 0);
+    }
+
+    #[test]
+    fn invalid_recording() {
+        assert!(ReplayStream::new(&b"not a recording"[..]).is_err());
+        assert!(ReplayStream::new(&b"x11rbrec\x02"[..]).is_err());
+        assert!(ReplayStream::new(&b"x11rbrec\x01\x01\x00\x00\x10\x00\x00\x00"[..]).is_err());
+
+        // A record that claims to be huge is not trusted
+        let err = ReplayStream::new(&b"x11rbrec\x01\x01\x00\x00\xff\xff\xff\xff"[..]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        // A truncated record header is not taken as the end of the recording
+        let err = ReplayStream::new(&b"x11rbrec\x01\x01\x00\x00"[..]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        assert!(ReplayStream::new(&b"x11rbrec\x01"[..]).is_ok());
+    }
+}
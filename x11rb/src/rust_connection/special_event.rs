@@ -0,0 +1,100 @@
+//! Separate queues for generic events, see [`RustConnection::register_for_special_event`].
+
+use super::{BlockingMode, RustConnection, Stream};
+use crate::connection::RequestConnection;
+use crate::errors::ConnectionError;
+use crate::protocol::Event;
+use x11rb_protocol::RawEventAndSeqNumber;
+
+/// A queue for the generic events of one extension with one event ID.
+///
+/// This is created by [`RustConnection::register_for_special_event`]. The events that end up in
+/// this queue are not returned by the usual event functions of the connection. Dropping the queue
+/// unregisters it and discards all events that are still queued.
+#[derive(Debug)]
+pub struct SpecialEventQueue<'c, S: Stream> {
+    conn: &'c RustConnection<S>,
+    extension: u8,
+    eid: u32,
+}
+
+impl<'c, S: Stream> SpecialEventQueue<'c, S> {
+    pub(super) fn new(conn: &'c RustConnection<S>, extension: u8, eid: u32) -> Self {
+        Self {
+            conn,
+            extension,
+            eid,
+        }
+    }
+
+    /// Get the major opcode of the extension whose events are queued here.
+    pub fn extension(&self) -> u8 {
+        self.extension
+    }
+
+    /// Get the event ID whose events are queued here.
+    pub fn eid(&self) -> u32 {
+        self.eid
+    }
+
+    /// Poll for a new event in this queue.
+    pub fn poll_for_event(&self) -> Result<Option<Event>, ConnectionError> {
+        Ok(match self.poll_for_raw_event_with_sequence()? {
+            Some((event, _seq)) => Some(self.conn.parse_event(&event)?),
+            None => None,
+        })
+    }
+
+    /// Wait for a new event in this queue.
+    pub fn wait_for_event(&self) -> Result<Event, ConnectionError> {
+        let (event, _seq) = self.wait_for_raw_event_with_sequence()?;
+        self.conn.parse_event(&event).map_err(Into::into)
+    }
+
+    /// Poll for a new raw/unparsed event in this queue.
+    pub fn poll_for_raw_event_with_sequence(
+        &self,
+    ) -> Result<Option<RawEventAndSeqNumber<Vec<u8>>>, ConnectionError> {
+        let mut inner = self.conn.inner.lock().unwrap();
+        if let Some(event) = self.poll_queue(&mut inner.inner) {
+            return Ok(Some(event));
+        }
+        let mut inner = self
+            .conn
+            .read_packet_and_enqueue(inner, BlockingMode::NonBlocking)?;
+        Ok(self.poll_queue(&mut inner.inner))
+    }
+
+    /// Wait for a new raw/unparsed event in this queue.
+    pub fn wait_for_raw_event_with_sequence(
+        &self,
+    ) -> Result<RawEventAndSeqNumber<Vec<u8>>, ConnectionError> {
+        let mut inner = self.conn.inner.lock().unwrap();
+        loop {
+            if let Some(event) = self.poll_queue(&mut inner.inner) {
+                return Ok(event);
+            }
+            inner = self
+                .conn
+                .read_packet_and_enqueue(inner, BlockingMode::Blocking)?;
+        }
+    }
+
+    fn poll_queue(
+        &self,
+        inner: &mut x11rb_protocol::connection::Connection,
+    ) -> Option<RawEventAndSeqNumber<Vec<u8>>> {
+        inner.poll_for_special_event_with_sequence(self.extension, self.eid)
+    }
+}
+
+impl<S: Stream> Drop for SpecialEventQueue<'_, S> {
+    fn drop(&mut self) {
+        self.conn
+            .inner
+            .lock()
+            .unwrap()
+            .inner
+            .unregister_for_special_event(self.extension, self.eid);
+    }
+}
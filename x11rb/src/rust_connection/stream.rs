@@ -1,6 +1,6 @@
 use rustix::fd::{AsFd, BorrowedFd};
 use std::io::{IoSlice, Result};
-use std::net::TcpStream;
+use std::net::{TcpStream, ToSocketAddrs};
 #[cfg(unix)]
 use std::os::unix::io::{AsRawFd, IntoRawFd, OwnedFd, RawFd};
 #[cfg(unix)]
@@ -9,10 +9,11 @@ use std::os::unix::net::UnixStream;
 use std::os::windows::io::{
     AsRawSocket, AsSocket, BorrowedSocket, IntoRawSocket, OwnedSocket, RawSocket,
 };
+use std::time::{Duration, Instant};
 
 use crate::utils::RawFdContainer;
 use x11rb_protocol::parse_display::ConnectAddress;
-use x11rb_protocol::xauth::Family;
+use x11rb_protocol::xauth::{Family, LocalAddress};
 
 /// The kind of operation that one want to poll for.
 #[derive(Debug, Clone, Copy)]
@@ -67,6 +68,25 @@ pub trait Stream {
     /// `read` is `true`) or writable (when `write` is `true`).
     fn poll(&self, mode: PollMode) -> Result<()>;
 
+    /// Waits for level-triggered read and/or write events on the stream, but at most for the
+    /// given duration.
+    ///
+    /// This function works like [`Stream::poll`], except that it also returns once `timeout` has
+    /// elapsed. Just like `poll`, this function does not report why it returned. Callers have to
+    /// check for themselves whether the timeout expired.
+    ///
+    /// The default implementation ignores the timeout and just calls [`Stream::poll`]. This means
+    /// that deadline-aware functions like [`crate::cookie::Cookie::reply_timeout`] might block
+    /// for longer than requested with streams that do not override this function.
+    ///
+    /// # Multithreading
+    ///
+    /// Same as `poll`.
+    fn poll_with_timeout(&self, mode: PollMode, timeout: Duration) -> Result<()> {
+        let _ = timeout;
+        self.poll(mode)
+    }
+
     /// Read some bytes and FDs from this reader without blocking, returning how many bytes
     /// were read.
     ///
@@ -143,6 +163,7 @@ pub trait Stream {
 #[derive(Debug)]
 pub struct DefaultStream {
     inner: DefaultStreamInner,
+    local_address: LocalAddress,
 }
 
 #[cfg(unix)]
@@ -173,7 +194,10 @@ impl DefaultStream {
                     // TODO: Does it make sense to add a constructor similar to from_unix_stream()?
                     // If this is done: Move the set_nonblocking() from
                     // connect_abstract_unix_stream() to that new function.
-                    let stream = DefaultStream { inner: stream };
+                    let stream = DefaultStream {
+                        inner: stream,
+                        local_address: LocalAddress::Local,
+                    };
                     return Ok((stream, peer_addr::local()));
                 }
 
@@ -196,6 +220,42 @@ impl DefaultStream {
         }
     }
 
+    /// Try to connect to the X11 server described by the given arguments, giving up on TCP
+    /// connections after `timeout`.
+    ///
+    /// A host name may resolve to more than one IP address. These are tried in order until
+    /// `timeout` elapsed in total. Resolving the host name and connecting to Unix domain sockets
+    /// is not affected by the timeout.
+    pub fn connect_timeout(
+        addr: &ConnectAddress<'_>,
+        timeout: Duration,
+    ) -> Result<(Self, PeerAddr)> {
+        match addr {
+            ConnectAddress::Hostname(host, port) => {
+                let mut error = None;
+                let addrs = (*host, *port).to_socket_addrs()?;
+                let deadline = Instant::now().checked_add(timeout);
+                for socket_addr in addrs {
+                    let timeout = match deadline {
+                        None => timeout,
+                        Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                            Some(timeout) if !timeout.is_zero() => timeout,
+                            _ => return Err(std::io::ErrorKind::TimedOut.into()),
+                        },
+                    };
+                    match TcpStream::connect_timeout(&socket_addr, timeout) {
+                        Ok(stream) => return Self::from_tcp_stream(stream),
+                        Err(e) => error = Some(e),
+                    }
+                }
+                Err(error.unwrap_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::Other, "no address resolved")
+                }))
+            }
+            _ => Self::connect(addr),
+        }
+    }
+
     /// Creates a new `Stream` from an already connected `TcpStream`.
     ///
     /// The stream will be set in non-blocking mode.
@@ -203,9 +263,11 @@ impl DefaultStream {
     /// This returns the peer address in a format suitable for [`x11rb_protocol::xauth::get_auth`].
     pub fn from_tcp_stream(stream: TcpStream) -> Result<(Self, PeerAddr)> {
         let peer_addr = peer_addr::tcp(&stream.peer_addr()?);
+        let local_address = LocalAddress::Tcp(stream.local_addr()?);
         stream.set_nonblocking(true)?;
         let result = Self {
             inner: stream.into(),
+            local_address,
         };
         Ok((result, peer_addr))
     }
@@ -220,10 +282,18 @@ impl DefaultStream {
         stream.set_nonblocking(true)?;
         let result = Self {
             inner: stream.into(),
+            local_address: LocalAddress::Local,
         };
         Ok((result, peer_addr::local()))
     }
 
+    /// Get the local end of this connection.
+    ///
+    /// This is in a format suitable for [`x11rb_protocol::xauth::get_auth_for_connection`].
+    pub fn local_address(&self) -> LocalAddress {
+        self.local_address
+    }
+
     fn as_fd(&self) -> BorrowedFd<'_> {
         self.inner.as_fd()
     }
@@ -334,27 +404,11 @@ fn do_write(
 
 impl Stream for DefaultStream {
     fn poll(&self, mode: PollMode) -> Result<()> {
-        use rustix::event::{poll, PollFd, PollFlags};
-        use rustix::io::Errno;
+        poll_fd(self.as_fd(), mode, None)
+    }
 
-        let mut poll_flags = PollFlags::empty();
-        if mode.readable() {
-            poll_flags |= PollFlags::IN;
-        }
-        if mode.writable() {
-            poll_flags |= PollFlags::OUT;
-        }
-        let fd = self.as_fd();
-        let mut poll_fds = [PollFd::from_borrowed_fd(fd, poll_flags)];
-        loop {
-            match poll(&mut poll_fds, None) {
-                Ok(_) => break,
-                Err(Errno::INTR) => {}
-                Err(e) => return Err(e.into()),
-            }
-        }
-        // Let the errors (POLLERR) be handled when trying to read or write.
-        Ok(())
+    fn poll_with_timeout(&self, mode: PollMode, timeout: Duration) -> Result<()> {
+        poll_fd(self.as_fd(), mode, Some(timeout))
     }
 
     fn read(&self, buf: &mut [u8], fd_storage: &mut Vec<RawFdContainer>) -> Result<usize> {
@@ -460,6 +514,32 @@ impl Stream for DefaultStream {
     }
 }
 
+/// Wait for the given FD to become readable and/or writable, or for the timeout to expire.
+pub(crate) fn poll_fd(fd: BorrowedFd<'_>, mode: PollMode, timeout: Option<Duration>) -> Result<()> {
+    use rustix::event::{poll, PollFd, PollFlags, Timespec};
+    use rustix::io::Errno;
+
+    let mut poll_flags = PollFlags::empty();
+    if mode.readable() {
+        poll_flags |= PollFlags::IN;
+    }
+    if mode.writable() {
+        poll_flags |= PollFlags::OUT;
+    }
+    // A timeout that does not fit into a Timespec is as good as no timeout at all
+    let timeout = timeout.and_then(|timeout| Timespec::try_from(timeout).ok());
+    let mut poll_fds = [PollFd::from_borrowed_fd(fd, poll_flags)];
+    loop {
+        match poll(&mut poll_fds, timeout.as_ref()) {
+            Ok(_) => break,
+            Err(Errno::INTR) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    // Let the errors (POLLERR) be handled when trying to read or write.
+    Ok(())
+}
+
 #[cfg(any(target_os = "linux", target_os = "android"))]
 fn connect_abstract_unix_stream(
     path: &[u8],
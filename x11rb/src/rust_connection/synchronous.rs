@@ -0,0 +1,76 @@
+//! Support for checking every request immediately, see [`RustConnection::set_synchronous`].
+//!
+//! [`RustConnection::set_synchronous`]: super::RustConnection::set_synchronous
+
+// std::backtrace is only used with the `backtrace` feature, which documents the higher MSRV
+#![cfg_attr(feature = "backtrace", allow(clippy::incompatible_msrv))]
+
+use std::sync::{Arc, Mutex};
+
+use crate::x11_utils::X11Error;
+
+/// An X11 error that was detected by the synchronous mode of a [`RustConnection`].
+///
+/// [`RustConnection`]: super::RustConnection
+#[derive(Debug)]
+pub struct SynchronousError {
+    /// The error that the X11 server sent.
+    pub error: X11Error,
+    /// The stack of the call that sent the failed request.
+    ///
+    /// This is only available with the `backtrace` feature, which requires Rust 1.65.
+    #[cfg(feature = "backtrace")]
+    pub backtrace: std::backtrace::Backtrace,
+    /// The description of the error, see [`X11Error::display`].
+    ///
+    /// This is created right away, because the names of extension errors and requests can only
+    /// be looked up with the connection.
+    description: String,
+}
+
+impl SynchronousError {
+    pub(crate) fn new(error: X11Error, description: String) -> Self {
+        Self {
+            error,
+            description,
+            // Backtraces are the whole point of this feature; do not depend on RUST_BACKTRACE
+            #[cfg(feature = "backtrace")]
+            backtrace: std::backtrace::Backtrace::force_capture(),
+        }
+    }
+}
+
+impl std::fmt::Display for SynchronousError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.description)?;
+        #[cfg(feature = "backtrace")]
+        write!(f, "\n{}", self.backtrace)?;
+        Ok(())
+    }
+}
+
+impl std::error::Error for SynchronousError {}
+
+/// A callback for errors detected in synchronous mode.
+pub type SynchronousErrorHandler = Arc<dyn Fn(SynchronousError) + Send + Sync>;
+
+/// The handler for synchronous mode, if enabled.
+#[derive(Default)]
+pub(crate) struct SynchronousMode(Mutex<Option<SynchronousErrorHandler>>);
+
+impl SynchronousMode {
+    pub(crate) fn set(&self, handler: Option<SynchronousErrorHandler>) {
+        *self.0.lock().unwrap() = handler;
+    }
+
+    pub(crate) fn handler(&self) -> Option<SynchronousErrorHandler> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl std::fmt::Debug for SynchronousMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let enabled = self.0.lock().unwrap().is_some();
+        f.debug_tuple("SynchronousMode").field(&enabled).finish()
+    }
+}
@@ -0,0 +1,668 @@
+//! Support for transferring data via ICCCM selections
+//!
+//! Selections are the mechanism behind copy and paste in X11. The owner of a selection, for
+//! example `CLIPBOARD`, offers its content in various formats, called targets. Another client, the
+//! requestor, asks the owner to convert the selection to a target and to store the result in a
+//! property on the requestor's window. Data that is too large for a single request is transferred
+//! in chunks with the `INCR` mechanism. See section 2 of the ICCCM for details.
+//!
+//! This module contains [`Owner`] for the owner side and [`Transfer`] for the requestor side. Both
+//! do not read events from the connection themselves. Instead, the application feeds them the
+//! events from its own event loop.
+//!
+//! Usage example for fetching the clipboard as UTF-8 text:
+//! ```no_run
+//! use x11rb::connection::Connection;
+//! use x11rb::protocol::xproto::{
+//!     ChangeWindowAttributesAux, ConnectionExt as _, EventMask, Timestamp, Window,
+//! };
+//! use x11rb::selection::{SelectionAtoms, Transfer, TransferStatus};
+//!
+//! fn paste(
+//!     conn: &impl Connection,
+//!     window: Window,
+//!     time: Timestamp,
+//! ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+//!     let atoms = SelectionAtoms::new(conn)?.reply()?;
+//!     // Large transfers use PropertyNotify events
+//!     let aux = ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE);
+//!     conn.change_window_attributes(window, &aux)?;
+//!
+//!     let mut transfer = Transfer::start(
+//!         conn,
+//!         &atoms,
+//!         window,
+//!         atoms.CLIPBOARD,
+//!         atoms.UTF8_STRING,
+//!         atoms.X11RB_SELECTION,
+//!         time,
+//!     )?;
+//!     conn.flush()?;
+//!     loop {
+//!         let event = conn.wait_for_event()?;
+//!         match transfer.handle_event(conn, &event)? {
+//!             TransferStatus::Done(data) => return Ok(String::from_utf8(data.data).ok()),
+//!             TransferStatus::Pending => conn.flush()?,
+//!             TransferStatus::Ignored => { /* Handle other events */ }
+//!         }
+//!     }
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+
+use crate::connection::RequestConnection;
+use crate::cookie::VoidCookie;
+use crate::errors::{ConnectionError, ReplyError};
+use crate::protocol::xproto::{
+    self, Atom, AtomEnum, ChangeWindowAttributesAux, EventMask, PropMode, Property,
+    SelectionNotifyEvent, Timestamp, Window, SELECTION_NOTIFY_EVENT,
+};
+use crate::protocol::Event;
+use crate::{CURRENT_TIME, NONE};
+
+crate::atom_manager! {
+    /// The atoms that are used for selection transfers.
+    ///
+    /// The fields are named after the atoms they contain. `X11RB_SELECTION` is a property name
+    /// that can be used for [`Transfer::start`].
+    #[allow(missing_docs)]
+    pub SelectionAtoms:
+    /// A cookie for interning [`SelectionAtoms`].
+    SelectionAtomsCookie {
+        CLIPBOARD,
+        TARGETS,
+        MULTIPLE,
+        TIMESTAMP,
+        INCR,
+        ATOM_PAIR,
+        UTF8_STRING,
+        TEXT,
+        X11RB_SELECTION,
+    }
+}
+
+/// The content of a selection, converted to some target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectionData {
+    /// The type of the data.
+    pub type_: Atom,
+    /// The format of the data, which is 8, 16 or 32.
+    pub format: u8,
+    /// The data in the byte order of this client.
+    pub data: Vec<u8>,
+}
+
+impl SelectionData {
+    /// Create new selection data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `format` is not 8, 16 or 32, or if the length of `data` is not a multiple of
+    /// the size of an element.
+    pub fn new(type_: impl Into<Atom>, format: u8, data: Vec<u8>) -> Self {
+        assert!(
+            matches!(format, 8 | 16 | 32),
+            "Invalid selection format {format}"
+        );
+        assert_eq!(data.len() % usize::from(format / 8), 0);
+        Self {
+            type_: type_.into(),
+            format,
+            data,
+        }
+    }
+
+    /// Create new selection data containing an UTF-8 string.
+    pub fn utf8_string(atoms: &SelectionAtoms, text: &str) -> Self {
+        Self::new(atoms.UTF8_STRING, 8, text.as_bytes().to_vec())
+    }
+
+    /// Create new selection data containing 32 bit values.
+    pub fn from_u32(type_: impl Into<Atom>, values: &[u32]) -> Self {
+        let data = values
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect();
+        Self::new(type_, 32, data)
+    }
+}
+
+/// Write `data` to a property.
+fn change_property<'c, C: RequestConnection + ?Sized>(
+    conn: &'c C,
+    window: Window,
+    property: Atom,
+    data: &SelectionData,
+    range: std::ops::Range<usize>,
+) -> Result<VoidCookie<'c, C>, ConnectionError> {
+    let element_size = usize::from(data.format / 8);
+    let length = (range.end - range.start) / element_size;
+    xproto::change_property(
+        conn,
+        PropMode::REPLACE,
+        window,
+        property,
+        data.type_,
+        data.format,
+        length.try_into().expect("Too much data for a property"),
+        &data.data[range],
+    )
+}
+
+/// The largest number of bytes that fits into a single `ChangeProperty` request.
+fn max_chunk_size<C: RequestConnection + ?Sized>(conn: &C) -> usize {
+    // Leave room for the request header, including the BIG-REQUESTS length field
+    (conn.maximum_request_bytes().saturating_sub(28) & !3).max(4)
+}
+
+/// An ongoing `INCR` transfer from an [`Owner`] to some requestor.
+#[derive(Debug, Clone)]
+struct IncrTransfer {
+    requestor: Window,
+    property: Atom,
+    data: SelectionData,
+    offset: usize,
+    /// Our event mask on the requestor window before the first transfer to it started.
+    saved_event_mask: EventMask,
+}
+
+/// The owner side of a selection.
+///
+/// The owner serves the targets that were registered with [`Owner::set_target`]. Additionally,
+/// `TARGETS`, `TIMESTAMP` and `MULTIPLE` are handled automatically. Data that does not fit into a
+/// single request is sent with the `INCR` mechanism.
+///
+/// All events have to be passed to [`Owner::handle_event`].
+#[derive(Debug, Clone)]
+pub struct Owner {
+    atoms: SelectionAtoms,
+    window: Window,
+    selection: Atom,
+    time: Timestamp,
+    owned: bool,
+    targets: BTreeMap<Atom, SelectionData>,
+    incr_transfers: Vec<IncrTransfer>,
+}
+
+impl Owner {
+    /// Create a new owner for `selection` with the given window.
+    ///
+    /// `time` is the timestamp of the user action that caused taking ownership of the selection.
+    /// According to the ICCCM, this must not be `CURRENT_TIME`.
+    pub fn new(atoms: &SelectionAtoms, window: Window, selection: Atom, time: Timestamp) -> Self {
+        Self {
+            atoms: *atoms,
+            window,
+            selection,
+            time,
+            owned: false,
+            targets: BTreeMap::new(),
+            incr_transfers: Vec::new(),
+        }
+    }
+
+    /// Offer the selection as the given target.
+    pub fn set_target(&mut self, target: Atom, data: SelectionData) {
+        let _ = self.targets.insert(target, data);
+    }
+
+    /// Stop offering the selection as the given target.
+    pub fn remove_target(&mut self, target: Atom) {
+        let _ = self.targets.remove(&target);
+    }
+
+    /// Get whether this owner currently owns the selection.
+    ///
+    /// This is `false` before [`Owner::acquire`] succeeded and after another client took
+    /// ownership of the selection.
+    pub fn is_owner(&self) -> bool {
+        self.owned
+    }
+
+    /// Take ownership of the selection.
+    ///
+    /// Returns whether the X11 server accepted the new owner.
+    pub fn acquire<C: RequestConnection + ?Sized>(&mut self, conn: &C) -> Result<bool, ReplyError> {
+        let _ = xproto::set_selection_owner(conn, self.window, self.selection, self.time)?;
+        let owner = xproto::get_selection_owner(conn, self.selection)?.reply()?;
+        self.owned = owner.owner == self.window;
+        Ok(self.owned)
+    }
+
+    /// Give up ownership of the selection.
+    ///
+    /// Ongoing `INCR` transfers are still completed.
+    pub fn release<C: RequestConnection + ?Sized>(
+        &mut self,
+        conn: &C,
+    ) -> Result<(), ConnectionError> {
+        if self.owned {
+            self.owned = false;
+            let _ = xproto::set_selection_owner(conn, NONE, self.selection, self.time)?;
+        }
+        Ok(())
+    }
+
+    /// Handle an event.
+    ///
+    /// This handles `SelectionRequest`, `SelectionClear` and `PropertyNotify` events for this
+    /// selection. Returns `true` if the event was handled.
+    pub fn handle_event<C: RequestConnection + ?Sized>(
+        &mut self,
+        conn: &C,
+        event: &Event,
+    ) -> Result<bool, ReplyError> {
+        match event {
+            Event::SelectionRequest(event)
+                if event.owner == self.window && event.selection == self.selection =>
+            {
+                self.handle_selection_request(conn, event)?;
+                Ok(true)
+            }
+            Event::SelectionClear(event)
+                if event.owner == self.window && event.selection == self.selection =>
+            {
+                self.owned = false;
+                Ok(true)
+            }
+            Event::PropertyNotify(event) if event.state == Property::DELETE => {
+                self.continue_incr_transfer(conn, event.window, event.atom)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn handle_selection_request<C: RequestConnection + ?Sized>(
+        &mut self,
+        conn: &C,
+        event: &xproto::SelectionRequestEvent,
+    ) -> Result<(), ReplyError> {
+        // Obsolete clients do not specify a property
+        let mut property = if event.property == NONE {
+            event.target
+        } else {
+            event.property
+        };
+        let too_old = event.time != CURRENT_TIME && event.time < self.time;
+        if !self.owned || too_old {
+            property = NONE;
+        } else if event.target == self.atoms.MULTIPLE {
+            if event.property == NONE {
+                property = NONE;
+            } else {
+                self.convert_multiple(conn, event.requestor, property)?;
+            }
+        } else if !self.convert(conn, event.requestor, event.target, property)? {
+            property = NONE;
+        }
+
+        let notify = SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: event.time,
+            requestor: event.requestor,
+            selection: event.selection,
+            target: event.target,
+            property,
+        };
+        let _ = xproto::send_event(conn, false, event.requestor, EventMask::NO_EVENT, notify)?;
+        Ok(())
+    }
+
+    /// Handle a `MULTIPLE` request by converting each target that is listed in the property.
+    fn convert_multiple<C: RequestConnection + ?Sized>(
+        &mut self,
+        conn: &C,
+        requestor: Window,
+        property: Atom,
+    ) -> Result<(), ReplyError> {
+        let reply =
+            xproto::get_property(conn, false, requestor, property, AtomEnum::ANY, 0, u32::MAX)?
+                .reply()?;
+        let mut pairs: Vec<u32> = match reply.value32() {
+            Some(values) => values.collect(),
+            None => Vec::new(),
+        };
+        for pair in pairs.chunks_exact_mut(2) {
+            if !self.convert(conn, requestor, pair[0], pair[1])? {
+                pair[1] = NONE;
+            }
+        }
+        let data = SelectionData::from_u32(self.atoms.ATOM_PAIR, &pairs);
+        let _ = change_property(conn, requestor, property, &data, 0..data.data.len())?;
+        Ok(())
+    }
+
+    /// Store the selection converted to `target` in the given property.
+    ///
+    /// Returns `false` if the selection cannot be converted to the target.
+    fn convert<C: RequestConnection + ?Sized>(
+        &mut self,
+        conn: &C,
+        requestor: Window,
+        target: Atom,
+        property: Atom,
+    ) -> Result<bool, ReplyError> {
+        let data = if target == self.atoms.TARGETS {
+            let mut targets = vec![
+                self.atoms.TARGETS,
+                self.atoms.MULTIPLE,
+                self.atoms.TIMESTAMP,
+            ];
+            targets.extend(self.targets.keys());
+            SelectionData::from_u32(AtomEnum::ATOM, &targets)
+        } else if target == self.atoms.TIMESTAMP {
+            SelectionData::from_u32(AtomEnum::INTEGER, &[self.time])
+        } else if let Some(data) = self.targets.get(&target) {
+            data.clone()
+        } else {
+            return Ok(false);
+        };
+
+        if data.data.len() <= max_chunk_size(conn) {
+            let _ = change_property(conn, requestor, property, &data, 0..data.data.len())?;
+            return Ok(true);
+        }
+
+        // Start an INCR transfer. The requestor deletes the property to ask for the next chunk.
+        // Our event mask on the requestor window is restored once all transfers to it are done.
+        let saved_event_mask = match self
+            .incr_transfers
+            .iter()
+            .find(|t| t.requestor == requestor)
+        {
+            Some(transfer) => transfer.saved_event_mask,
+            None => {
+                let attributes = match xproto::get_window_attributes(conn, requestor)?.reply() {
+                    Ok(attributes) => attributes,
+                    // The requestor window does not exist (anymore)
+                    Err(ReplyError::X11Error(_)) => return Ok(false),
+                    Err(err) => return Err(err),
+                };
+                let event_mask = attributes.your_event_mask;
+                if !event_mask.contains(EventMask::PROPERTY_CHANGE) {
+                    let aux = ChangeWindowAttributesAux::new()
+                        .event_mask(event_mask | EventMask::PROPERTY_CHANGE);
+                    let _ = xproto::change_window_attributes(conn, requestor, &aux)?;
+                }
+                event_mask
+            }
+        };
+        let size = u32::try_from(data.data.len()).unwrap_or(u32::MAX);
+        let incr = SelectionData::from_u32(self.atoms.INCR, &[size]);
+        let _ = change_property(conn, requestor, property, &incr, 0..4)?;
+        self.incr_transfers
+            .retain(|t| (t.requestor, t.property) != (requestor, property));
+        self.incr_transfers.push(IncrTransfer {
+            requestor,
+            property,
+            data,
+            offset: 0,
+            saved_event_mask,
+        });
+        Ok(true)
+    }
+
+    /// Send the next chunk of an `INCR` transfer after the requestor deleted the property.
+    fn continue_incr_transfer<C: RequestConnection + ?Sized>(
+        &mut self,
+        conn: &C,
+        requestor: Window,
+        property: Atom,
+    ) -> Result<bool, ReplyError> {
+        let index = match self
+            .incr_transfers
+            .iter()
+            .position(|t| (t.requestor, t.property) == (requestor, property))
+        {
+            Some(index) => index,
+            None => return Ok(false),
+        };
+        let chunk_size = max_chunk_size(conn);
+        let transfer = &mut self.incr_transfers[index];
+        let start = transfer.offset;
+        let end = transfer.data.data.len().min(start + chunk_size);
+        let _ = change_property(conn, requestor, property, &transfer.data, start..end)?;
+        transfer.offset = end;
+
+        // An empty chunk marks the end of the transfer
+        if start == end {
+            let event_mask = self.incr_transfers.remove(index).saved_event_mask;
+            let done = self.incr_transfers.iter().all(|t| t.requestor != requestor);
+            if done && !event_mask.contains(EventMask::PROPERTY_CHANGE) {
+                let aux = ChangeWindowAttributesAux::new().event_mask(event_mask);
+                let _ = xproto::change_window_attributes(conn, requestor, &aux)?;
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// An error that occurred while fetching a selection.
+#[derive(Debug)]
+pub enum TransferError {
+    /// The selection has no owner, the owner cannot convert it to the requested target, or the
+    /// owner did not store the result in the property.
+    Refused,
+    /// The data is larger than the limit set with [`Transfer::set_max_size`].
+    TooLarge,
+    /// The X11 server sent an error or the connection failed.
+    ReplyError(ReplyError),
+}
+
+impl std::error::Error for TransferError {}
+
+impl std::fmt::Display for TransferError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransferError::Refused => f.write_str("The selection owner refused the conversion"),
+            TransferError::TooLarge => f.write_str("The selection data is too large"),
+            TransferError::ReplyError(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<ReplyError> for TransferError {
+    fn from(err: ReplyError) -> Self {
+        TransferError::ReplyError(err)
+    }
+}
+
+impl From<ConnectionError> for TransferError {
+    fn from(err: ConnectionError) -> Self {
+        TransferError::ReplyError(err.into())
+    }
+}
+
+/// The result of passing an event to [`Transfer::handle_event`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransferStatus {
+    /// The event does not belong to this transfer.
+    Ignored,
+    /// The event was handled, but the transfer is not yet complete.
+    Pending,
+    /// The transfer is complete.
+    Done(SelectionData),
+}
+
+/// The state of a [`Transfer`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum TransferState {
+    /// Waiting for the `SelectionNotify` event.
+    WaitingForNotify,
+    /// Receiving the data in chunks.
+    Incr,
+    /// The transfer is complete or failed.
+    Finished,
+}
+
+/// The requestor side of a selection transfer.
+///
+/// A transfer fetches the content of a selection converted to one target. All events have to be
+/// passed to [`Transfer::handle_event`] until it returns [`TransferStatus::Done`] or an error.
+///
+/// If the owner uses the `INCR` mechanism, the transfer relies on `PropertyNotify` events for the
+/// requestor window. The application has to select `PROPERTY_CHANGE` events on this window
+/// before starting the transfer.
+#[derive(Debug, Clone)]
+pub struct Transfer {
+    window: Window,
+    selection: Atom,
+    target: Atom,
+    property: Atom,
+    incr: Atom,
+    max_size: usize,
+    state: TransferState,
+    data: Option<SelectionData>,
+}
+
+impl Transfer {
+    /// Ask the owner of `selection` to convert it to `target`.
+    ///
+    /// The owner stores the result in `property` on `window`. `time` should be the timestamp of
+    /// the user action that caused the transfer.
+    pub fn start<C: RequestConnection + ?Sized>(
+        conn: &C,
+        atoms: &SelectionAtoms,
+        window: Window,
+        selection: Atom,
+        target: Atom,
+        property: Atom,
+        time: Timestamp,
+    ) -> Result<Self, ConnectionError> {
+        let _ = xproto::convert_selection(conn, window, selection, target, property, time)?;
+        Ok(Self {
+            window,
+            selection,
+            target,
+            property,
+            incr: atoms.INCR,
+            max_size: usize::MAX,
+            state: TransferState::WaitingForNotify,
+            data: None,
+        })
+    }
+
+    /// Limit the size of the data that is accepted from the owner.
+    ///
+    /// Transfers of larger data fail with [`TransferError::TooLarge`]. By default, there is no
+    /// limit.
+    pub fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size;
+    }
+
+    /// Handle an event.
+    ///
+    /// This handles the `SelectionNotify` event for this transfer and, during `INCR` transfers,
+    /// `PropertyNotify` events for the property.
+    pub fn handle_event<C: RequestConnection + ?Sized>(
+        &mut self,
+        conn: &C,
+        event: &Event,
+    ) -> Result<TransferStatus, TransferError> {
+        match (self.state, event) {
+            (TransferState::WaitingForNotify, Event::SelectionNotify(event))
+                if event.requestor == self.window
+                    && event.selection == self.selection
+                    && event.target == self.target =>
+            {
+                self.state = TransferState::Finished;
+                if event.property == NONE {
+                    return Err(TransferError::Refused);
+                }
+                let data = self.read_property(conn, event.property)?;
+                if data.type_ == self.incr {
+                    // The INCR property contains a lower bound for the size of the data
+                    let size_hint = data
+                        .data
+                        .get(..4)
+                        .map_or(0, |hint| u32::from_ne_bytes(hint.try_into().unwrap()));
+                    if usize::try_from(size_hint).unwrap_or(usize::MAX) > self.max_size {
+                        return Err(TransferError::TooLarge);
+                    }
+                    // Deleting the property (done by read_property()) starts the transfer
+                    self.property = event.property;
+                    self.state = TransferState::Incr;
+                    Ok(TransferStatus::Pending)
+                } else {
+                    Ok(TransferStatus::Done(data))
+                }
+            }
+            (TransferState::Incr, Event::PropertyNotify(event))
+                if event.window == self.window
+                    && event.atom == self.property
+                    && event.state == Property::NEW_VALUE =>
+            {
+                let chunk = self.read_property(conn, self.property)?;
+                let done = chunk.data.is_empty();
+                match &mut self.data {
+                    Some(data) => data.data.extend(chunk.data),
+                    None => self.data = Some(chunk),
+                }
+                if self.data.as_ref().map_or(0, |data| data.data.len()) > self.max_size {
+                    self.state = TransferState::Finished;
+                    return Err(TransferError::TooLarge);
+                }
+                if done {
+                    self.state = TransferState::Finished;
+                    Ok(TransferStatus::Done(self.data.take().unwrap()))
+                } else {
+                    Ok(TransferStatus::Pending)
+                }
+            }
+            _ => Ok(TransferStatus::Ignored),
+        }
+    }
+
+    /// Read and delete the given property on the requestor window.
+    ///
+    /// The property is read in pieces that are limited by the maximum request size. A missing
+    /// property means that the owner did not store the data and results in
+    /// [`TransferError::Refused`].
+    fn read_property<C: RequestConnection + ?Sized>(
+        &self,
+        conn: &C,
+        property: Atom,
+    ) -> Result<SelectionData, TransferError> {
+        let piece_length = u32::try_from(max_chunk_size(conn) / 4).unwrap_or(u32::MAX);
+        let mut data: Option<SelectionData> = None;
+        let mut offset = 0;
+        loop {
+            // With delete=true, the server deletes the property after the last piece was read
+            let reply = xproto::get_property(
+                conn,
+                true,
+                self.window,
+                property,
+                AtomEnum::ANY,
+                offset,
+                piece_length,
+            )?
+            .reply()?;
+            if reply.type_ == NONE {
+                return Err(TransferError::Refused);
+            }
+            let (type_, format, bytes_after) = (reply.type_, reply.format, reply.bytes_after);
+            let size = data.as_ref().map_or(0, |data| data.data.len()) + reply.value.len();
+            if size.saturating_add(bytes_after.try_into().unwrap_or(usize::MAX)) > self.max_size {
+                let _ = xproto::delete_property(conn, self.window, property)?;
+                return Err(TransferError::TooLarge);
+            }
+            offset += piece_length;
+            match &mut data {
+                Some(data) => data.data.extend(reply.value),
+                None => {
+                    data = Some(SelectionData {
+                        type_,
+                        format,
+                        data: reply.value,
+                    })
+                }
+            }
+            if bytes_after == 0 {
+                return Ok(data.unwrap());
+            }
+        }
+    }
+}
@@ -0,0 +1,692 @@
+//! An in-process fake X11 server for unit tests.
+//!
+//! Code that uses a [`Connection`](crate::connection::Connection) is hard to test without a real
+//! X11 server. This module provides [`FakeServer`], which implements just enough of the server
+//! side of the X11 protocol to be used with [`RustConnection`]: It answers the connection
+//! handshake with a configurable [`Setup`], decodes all incoming requests via
+//! [`Request::parse`] and answers them with canned replies, errors and events.
+//!
+//! This module is only available when the `request-parsing` feature is enabled.
+//!
+//! ```
+//! use x11rb::connection::Connection;
+//! use x11rb::protocol::xproto::{ConnectionExt, GetInputFocusReply, GET_INPUT_FOCUS_REQUEST};
+//! use x11rb::testing::{FakeServer, Response};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let server = FakeServer::default();
+//! let reply = GetInputFocusReply {
+//!     focus: 42,
+//!     ..Default::default()
+//! };
+//! server.set_responses(GET_INPUT_FOCUS_REQUEST, vec![Response::reply(&reply)]);
+//!
+//! let conn = server.connect(0)?;
+//! assert_eq!(conn.get_input_focus()?.reply()?.focus, 42);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{Error, ErrorKind, IoSlice, Result};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::errors::{ConnectError, ParseError};
+use crate::protocol::xproto::{
+    GetInputFocusReply, QueryExtensionReply, Screen, Setup, SetupRequest, IMPLEMENTATION_ERROR,
+    KEYMAP_NOTIFY_EVENT,
+};
+use crate::protocol::Request;
+use crate::rust_connection::{PollMode, RustConnection, Stream};
+use crate::utils::RawFdContainer;
+use crate::x11_utils::{
+    parse_request_header, BigRequests, ExtInfoProvider, ExtensionInformation, RequestHeader,
+    Serialize, TryParse,
+};
+
+/// A response that the [`FakeServer`] sends to a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    /// A reply to the request.
+    ///
+    /// The sequence number and the length field are filled in by the server. The reply is padded
+    /// to at least 32 bytes.
+    Reply(Vec<u8>),
+
+    /// An X11 error for the request.
+    ///
+    /// The sequence number and the opcodes are filled in by the server.
+    Error {
+        /// The error code, e.g. [`crate::protocol::xproto::WINDOW_ERROR`].
+        error_code: u8,
+        /// The bad value, e.g. the window ID for a `Window` error.
+        bad_value: u32,
+    },
+
+    /// An event that is sent after the request was processed.
+    ///
+    /// The sequence number is filled in by the server.
+    Event([u8; 32]),
+}
+
+impl Response {
+    /// Create a reply response from a reply structure.
+    pub fn reply(reply: &impl Serialize) -> Self {
+        let mut bytes = Vec::new();
+        reply.serialize_into(&mut bytes);
+        Response::Reply(bytes)
+    }
+
+    /// Create an event response from an event structure.
+    pub fn event(event: impl Into<[u8; 32]>) -> Self {
+        Response::Event(event.into())
+    }
+}
+
+/// Round the given length up to a multiple of four.
+fn padded_length(length: usize) -> usize {
+    (length + 3) & !3
+}
+
+type Handler = Arc<Mutex<dyn FnMut(&Request<'_>) -> Vec<Response> + Send>>;
+
+/// The extensions that the fake server claims to support.
+#[derive(Debug, Default)]
+struct Extensions(Vec<(String, ExtensionInformation)>);
+
+impl ExtInfoProvider for Extensions {
+    fn get_from_major_opcode(&self, major_opcode: u8) -> Option<(&str, ExtensionInformation)> {
+        self.0
+            .iter()
+            .find(|(_, info)| info.major_opcode == major_opcode)
+            .map(|(name, info)| (&name[..], *info))
+    }
+
+    fn get_from_event_code(&self, event_code: u8) -> Option<(&str, ExtensionInformation)> {
+        self.0
+            .iter()
+            .filter(|(_, info)| info.first_event <= event_code)
+            .max_by_key(|(_, info)| info.first_event)
+            .map(|(name, info)| (&name[..], *info))
+    }
+
+    fn get_from_error_code(&self, error_code: u8) -> Option<(&str, ExtensionInformation)> {
+        self.0
+            .iter()
+            .filter(|(_, info)| info.first_error <= error_code)
+            .max_by_key(|(_, info)| info.first_error)
+            .map(|(name, info)| (&name[..], *info))
+    }
+}
+
+struct ServerState {
+    setup: Setup,
+    extensions: Extensions,
+    handlers: HashMap<u8, Handler>,
+    handshake_done: bool,
+    /// Bytes sent by the client that were not yet processed.
+    from_client: Vec<u8>,
+    /// FDs sent by the client that were not yet processed.
+    fds_from_client: Vec<RawFdContainer>,
+    /// Bytes that the client did not yet read.
+    to_client: VecDeque<u8>,
+    /// The sequence number of the last request that was processed.
+    last_sequence: u16,
+    /// All requests that were processed so far.
+    requests: Vec<Request<'static>>,
+}
+
+impl std::fmt::Debug for ServerState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ServerState")
+            .field("extensions", &self.extensions)
+            .field("handlers", &self.handlers.keys())
+            .field("handshake_done", &self.handshake_done)
+            .field("from_client", &self.from_client.len())
+            .field("to_client", &self.to_client.len())
+            .field("last_sequence", &self.last_sequence)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ServerState {
+    /// Get the next complete request that the client sent.
+    ///
+    /// This also processes the connection handshake.
+    fn next_request(
+        &mut self,
+    ) -> std::result::Result<Option<(RequestHeader, Request<'static>)>, ParseError> {
+        if !self.handshake_done && !self.process_setup_request()? {
+            return Ok(None);
+        }
+        // A length of zero means that this is a BigRequests request with a longer length field
+        let length = match self.from_client[..] {
+            [_, _, 0, 0, a, b, c, d, ..] => u32::from_ne_bytes([a, b, c, d]),
+            [_, _, 0, 0, ..] => return Ok(None),
+            [_, _, a, b, ..] => u16::from_ne_bytes([a, b]).into(),
+            _ => return Ok(None),
+        };
+        let length = usize::try_from(length).unwrap() * 4;
+        if length < 4 {
+            return Err(ParseError::InvalidValue);
+        }
+        if self.from_client.len() < length {
+            return Ok(None);
+        }
+        let packet = self.from_client.drain(..length).collect::<Vec<_>>();
+        self.last_sequence = self.last_sequence.wrapping_add(1);
+        let (header, body) = parse_request_header(&packet, BigRequests::Enabled)?;
+        let mut fds = std::mem::take(&mut self.fds_from_client);
+        let request = Request::parse(header, body, &mut fds, &self.extensions)?;
+        self.fds_from_client = fds;
+        Ok(Some((header, request.into_owned())))
+    }
+
+    /// Process the `SetupRequest`, if it was completely received.
+    fn process_setup_request(&mut self) -> std::result::Result<bool, ParseError> {
+        let length = match self.from_client.get(..12) {
+            Some(header) => {
+                let name_len = usize::from(u16::from_ne_bytes([header[6], header[7]]));
+                let data_len = usize::from(u16::from_ne_bytes([header[8], header[9]]));
+                12 + padded_length(name_len) + padded_length(data_len)
+            }
+            None => return Ok(false),
+        };
+        if self.from_client.len() < length {
+            return Ok(false);
+        }
+        let packet = self.from_client.drain(..length).collect::<Vec<_>>();
+        let _ = SetupRequest::try_parse(&packet)?;
+
+        let mut setup = self.setup.serialize();
+        // Fix up the length field, which counts 4 byte units after the first eight bytes
+        let length = u16::try_from((setup.len() - 8) / 4).unwrap();
+        setup[6..8].copy_from_slice(&length.to_ne_bytes());
+        self.to_client.extend(setup);
+        self.handshake_done = true;
+        Ok(true)
+    }
+
+    /// Get the responses for a request for which no handler was registered.
+    fn default_responses(&self, request: &Request<'_>) -> Vec<Response> {
+        match request {
+            Request::QueryExtension(request) => {
+                let info = self
+                    .extensions
+                    .0
+                    .iter()
+                    .find(|(name, _)| name.as_bytes() == &*request.name)
+                    .map(|(_, info)| *info);
+                let reply = QueryExtensionReply {
+                    present: info.is_some(),
+                    major_opcode: info.map_or(0, |info| info.major_opcode),
+                    first_event: info.map_or(0, |info| info.first_event),
+                    first_error: info.map_or(0, |info| info.first_error),
+                    ..Default::default()
+                };
+                vec![Response::reply(&reply)]
+            }
+            // x11rb uses this request for synchronisation
+            Request::GetInputFocus(_) => vec![Response::reply(&GetInputFocusReply::default())],
+            request if request.reply_parser().is_some() => vec![Response::Error {
+                error_code: IMPLEMENTATION_ERROR,
+                bad_value: 0,
+            }],
+            _ => Vec::new(),
+        }
+    }
+
+    fn send_response(&mut self, response: Response, header: &RequestHeader) {
+        let sequence = self.last_sequence.to_ne_bytes();
+        match response {
+            Response::Reply(mut reply) => {
+                reply.resize(padded_length(reply.len().max(32)), 0);
+                let length = u32::try_from((reply.len() - 32) / 4).unwrap();
+                reply[0] = 1;
+                reply[2..4].copy_from_slice(&sequence);
+                reply[4..8].copy_from_slice(&length.to_ne_bytes());
+                self.to_client.extend(reply);
+            }
+            Response::Error {
+                error_code,
+                bad_value,
+            } => {
+                let mut error = [0; 32];
+                error[1] = error_code;
+                error[2..4].copy_from_slice(&sequence);
+                error[4..8].copy_from_slice(&bad_value.to_ne_bytes());
+                error[8..10].copy_from_slice(&u16::from(header.minor_opcode).to_ne_bytes());
+                error[10] = header.major_opcode;
+                self.to_client.extend(error);
+            }
+            Response::Event(event) => self.send_event(event),
+        }
+    }
+
+    fn send_event(&mut self, mut event: [u8; 32]) {
+        // KeymapNotify is the only event without a sequence number
+        if event[0] & 0x7f != KEYMAP_NOTIFY_EVENT {
+            event[2..4].copy_from_slice(&self.last_sequence.to_ne_bytes());
+        }
+        self.to_client.extend(event);
+    }
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<ServerState>,
+    /// Notified when new data for the client is available.
+    readable: Condvar,
+    /// Held while requests are processed, so that responses are sent in the order of requests.
+    processing: Mutex<()>,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, ServerState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Process all complete requests that the client sent.
+    ///
+    /// Handlers run without the state lock held, so that they can use the [`FakeServer`].
+    fn process(&self) -> std::result::Result<(), ParseError> {
+        let _processing = self.processing.lock().unwrap();
+        loop {
+            let mut state = self.lock();
+            let (header, request) = match state.next_request()? {
+                Some(next) => next,
+                None => return Ok(()),
+            };
+            let responses = match state.handlers.get(&header.major_opcode).cloned() {
+                Some(handler) => {
+                    drop(state);
+                    let responses = (handler.lock().unwrap())(&request);
+                    state = self.lock();
+                    responses
+                }
+                None => state.default_responses(&request),
+            };
+            for response in responses {
+                state.send_response(response, &header);
+            }
+            state.requests.push(request);
+        }
+    }
+}
+
+/// A fake X11 server that lives in the same process.
+///
+/// Use [`FakeServer::connect`] to get a [`RustConnection`] to this server. Requests are
+/// processed synchronously while the client writes them. Thus, the responses that are
+/// configured via [`FakeServer::set_handler`] and [`FakeServer::set_responses`] only apply to
+/// requests that are sent afterwards.
+///
+/// Without configuration, the server answers `QueryExtension` requests based on the extensions
+/// added via [`FakeServer::add_extension`] and `GetInputFocus` requests with a default reply.
+/// Other requests that have a reply are answered with an `Implementation` error, so that a test
+/// fails instead of hanging. Requests without a reply are silently accepted.
+#[derive(Debug, Clone)]
+pub struct FakeServer {
+    shared: Arc<Shared>,
+}
+
+impl FakeServer {
+    /// Create a new fake server that sends the given setup to connecting clients.
+    ///
+    /// The setup's `resource_id_mask` must not be zero, since `RustConnection` would refuse the
+    /// connection otherwise.
+    pub fn new(setup: Setup) -> Self {
+        let state = ServerState {
+            setup,
+            extensions: Default::default(),
+            handlers: Default::default(),
+            handshake_done: false,
+            from_client: Vec::new(),
+            fds_from_client: Vec::new(),
+            to_client: VecDeque::new(),
+            last_sequence: 0,
+            requests: Vec::new(),
+        };
+        let shared = Shared {
+            state: Mutex::new(state),
+            readable: Condvar::new(),
+            processing: Mutex::new(()),
+        };
+        FakeServer {
+            shared: Arc::new(shared),
+        }
+    }
+
+    /// Get a stream for talking to this server.
+    ///
+    /// Only a single client is supported per server.
+    pub fn stream(&self) -> FakeStream {
+        FakeStream {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    /// Connect to this server.
+    ///
+    /// This performs the connection handshake and checks that `screen` exists.
+    pub fn connect(
+        &self,
+        screen: usize,
+    ) -> std::result::Result<RustConnection<FakeStream>, ConnectError> {
+        RustConnection::connect_to_stream(self.stream(), screen)
+    }
+
+    /// Claim support for an extension.
+    ///
+    /// `QueryExtension` requests for the extension are answered with the given information and
+    /// requests with this major opcode are parsed as requests for the extension.
+    pub fn add_extension(&self, name: &str, info: ExtensionInformation) {
+        self.shared
+            .lock()
+            .extensions
+            .0
+            .push((name.to_string(), info));
+    }
+
+    /// Set the handler for requests with the given major opcode.
+    ///
+    /// The handler is called for each such request and returns the responses to send. For
+    /// extension requests, the major opcode identifies the extension and the handler has to look
+    /// at the request itself to tell requests apart. This replaces any previously set handler for
+    /// this opcode.
+    ///
+    /// The handler may use the `FakeServer`, for example to send events or to replace handlers.
+    /// Requests from other threads wait until the handler returns, so the handler must not send
+    /// requests to this server itself.
+    pub fn set_handler<F>(&self, major_opcode: u8, handler: F)
+    where
+        F: FnMut(&Request<'_>) -> Vec<Response> + Send + 'static,
+    {
+        let _ = self
+            .shared
+            .lock()
+            .handlers
+            .insert(major_opcode, Arc::new(Mutex::new(handler)));
+    }
+
+    /// Answer every request with the given major opcode with the given responses.
+    pub fn set_responses(&self, major_opcode: u8, responses: Vec<Response>) {
+        self.set_handler(major_opcode, move |_| responses.clone());
+    }
+
+    /// Send an event to the client.
+    ///
+    /// The event gets the sequence number of the last request that was processed.
+    pub fn send_event(&self, event: impl Into<[u8; 32]>) {
+        self.shared.lock().send_event(event.into());
+        self.shared.readable.notify_all();
+    }
+
+    /// Get all requests that were processed since the last call to this function.
+    pub fn take_requests(&self) -> Vec<Request<'static>> {
+        std::mem::take(&mut self.shared.lock().requests)
+    }
+}
+
+impl Default for FakeServer {
+    /// Create a fake server with a setup that contains a single screen.
+    fn default() -> Self {
+        FakeServer::new(Setup {
+            status: 1,
+            protocol_major_version: 11,
+            resource_id_mask: 0x1f_ffff,
+            maximum_request_length: u16::MAX,
+            roots: vec![Screen::default()],
+            ..Default::default()
+        })
+    }
+}
+
+/// The client side of a connection to a [`FakeServer`].
+#[derive(Debug, Clone)]
+pub struct FakeStream {
+    shared: Arc<Shared>,
+}
+
+impl FakeStream {
+    fn poll_impl(&self, mode: PollMode, timeout: Option<Duration>) {
+        // Writing never blocks
+        if mode.writable() {
+            return;
+        }
+        let state = self.shared.lock();
+        let has_data = |state: &mut ServerState| !state.to_client.is_empty();
+        match timeout {
+            None => drop(
+                self.shared
+                    .readable
+                    .wait_while(state, |state| !has_data(state)),
+            ),
+            Some(timeout) => drop(self.shared.readable.wait_timeout_while(
+                state,
+                timeout,
+                |state| !has_data(state),
+            )),
+        }
+    }
+}
+
+impl Stream for FakeStream {
+    fn poll(&self, mode: PollMode) -> Result<()> {
+        self.poll_impl(mode, None);
+        Ok(())
+    }
+
+    fn poll_with_timeout(&self, mode: PollMode, timeout: Duration) -> Result<()> {
+        self.poll_impl(mode, Some(timeout));
+        Ok(())
+    }
+
+    fn read(&self, buf: &mut [u8], _fd_storage: &mut Vec<RawFdContainer>) -> Result<usize> {
+        let mut state = self.shared.lock();
+        if state.to_client.is_empty() {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        let length = buf.len().min(state.to_client.len());
+        for (target, source) in buf.iter_mut().zip(state.to_client.drain(..length)) {
+            *target = source;
+        }
+        Ok(length)
+    }
+
+    fn write(&self, buf: &[u8], fds: &mut Vec<RawFdContainer>) -> Result<usize> {
+        let mut state = self.shared.lock();
+        state.from_client.extend_from_slice(buf);
+        state.fds_from_client.append(fds);
+        drop(state);
+        let result = self.shared.process();
+        self.shared.readable.notify_all();
+        result.map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+        Ok(buf.len())
+    }
+
+    fn write_vectored(&self, bufs: &[IoSlice<'_>], fds: &mut Vec<RawFdContainer>) -> Result<usize> {
+        let buf = bufs
+            .iter()
+            .flat_map(|buf| buf.iter().copied())
+            .collect::<Vec<_>>();
+        self.write(&buf, fds)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc::channel;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use super::{FakeServer, Response};
+    use crate::connection::{Connection, RequestConnection};
+    use crate::errors::ReplyError;
+    use crate::protocol::xproto::{
+        ClientMessageEvent, ConnectionExt, GetGeometryReply, GetGeometryRequest, SetupRequest,
+        GET_GEOMETRY_REQUEST, WINDOW_ERROR,
+    };
+    use crate::protocol::{Event, Request};
+    use crate::rust_connection::Stream;
+    use crate::x11_utils::Serialize;
+
+    #[test]
+    fn canned_reply() {
+        let server = FakeServer::default();
+        let reply = GetGeometryReply {
+            width: 640,
+            height: 480,
+            ..Default::default()
+        };
+        server.set_responses(GET_GEOMETRY_REQUEST, vec![Response::reply(&reply)]);
+        let conn = server.connect(0).unwrap();
+
+        let reply = conn.get_geometry(1234u32).unwrap().reply().unwrap();
+        assert_eq!((reply.width, reply.height), (640, 480));
+
+        match &server.take_requests()[..] {
+            [Request::GetGeometry(request)] => assert_eq!(request.drawable, 1234),
+            requests => panic!("Unexpected requests {requests:?}"),
+        }
+    }
+
+    #[test]
+    fn handler_error_and_event() {
+        let server = FakeServer::default();
+        server.set_handler(GET_GEOMETRY_REQUEST, |request| {
+            let drawable = match request {
+                Request::GetGeometry(request) => request.drawable,
+                _ => unreachable!(),
+            };
+            let event = ClientMessageEvent::new(32, drawable, 0u32, [0; 5]);
+            vec![
+                Response::event(event),
+                Response::Error {
+                    error_code: WINDOW_ERROR,
+                    bad_value: drawable,
+                },
+            ]
+        });
+        let conn = server.connect(0).unwrap();
+
+        let cookie = conn.get_geometry(1234u32).unwrap();
+        let sequence = cookie.sequence_number();
+        match cookie.reply() {
+            Err(ReplyError::X11Error(error)) => {
+                assert_eq!(error.bad_value, 1234);
+                assert_eq!(error.major_opcode, GET_GEOMETRY_REQUEST);
+                assert_eq!(u64::from(error.sequence), sequence);
+            }
+            other => panic!("Unexpected result {other:?}"),
+        }
+        match conn.wait_for_event().unwrap() {
+            Event::ClientMessage(event) => assert_eq!(event.window, 1234),
+            event => panic!("Unexpected event {event:?}"),
+        }
+    }
+
+    #[test]
+    fn unhandled_request() {
+        let server = FakeServer::default();
+        let conn = server.connect(0).unwrap();
+
+        // Requests without a reply are accepted, others cause an error
+        conn.map_window(1u32).unwrap().check().unwrap();
+        assert!(matches!(
+            conn.get_geometry(1u32).unwrap().reply(),
+            Err(ReplyError::X11Error(_))
+        ));
+        // No extensions are supported
+        assert_eq!(conn.extension_information("RANDR").unwrap(), None);
+    }
+
+    #[test]
+    fn injected_event() {
+        let server = FakeServer::default();
+        let conn = server.connect(0).unwrap();
+        assert!(conn.poll_for_event().unwrap().is_none());
+
+        server.send_event(ClientMessageEvent::new(32, 5u32, 0u32, [0; 5]));
+        match conn.wait_for_event() {
+            Ok(Event::ClientMessage(event)) => assert_eq!(event.window, 5),
+            other => panic!("Unexpected result {other:?}"),
+        }
+    }
+
+    #[test]
+    fn handler_uses_server() {
+        let server = FakeServer::default();
+        let handler_server = server.clone();
+        server.set_handler(GET_GEOMETRY_REQUEST, move |_| {
+            // All of this would deadlock if the handler ran with the server's state locked
+            handler_server.send_event(ClientMessageEvent::new(32, 5u32, 0u32, [0; 5]));
+            let _ = handler_server.take_requests();
+            handler_server.set_responses(GET_GEOMETRY_REQUEST, Vec::new());
+            vec![Response::reply(&GetGeometryReply::default())]
+        });
+        let conn = server.connect(0).unwrap();
+
+        assert!(conn.get_geometry(1u32).unwrap().reply().is_ok());
+        match conn.poll_for_event() {
+            Ok(Some(Event::ClientMessage(event))) => assert_eq!(event.window, 5),
+            other => panic!("Unexpected result {other:?}"),
+        }
+
+        // The handler replaced itself, so the next request does not get a reply
+        drop(conn.get_geometry(1u32).unwrap());
+        conn.flush().unwrap();
+        assert_eq!(server.take_requests().len(), 2);
+    }
+
+    #[test]
+    fn concurrent_requests() {
+        let server = FakeServer::default();
+        let (entered_send, entered) = channel();
+        let (release, release_recv) = channel::<()>();
+        let release_recv = Mutex::new(release_recv);
+        server.set_handler(GET_GEOMETRY_REQUEST, move |_| {
+            entered_send.send(()).unwrap();
+            let _ = release_recv.lock().unwrap().recv();
+            vec![Response::reply(&GetGeometryReply::default())]
+        });
+        let stream = server.stream();
+        let setup = SetupRequest {
+            byte_order: if cfg!(target_endian = "little") {
+                b'l'
+            } else {
+                b'B'
+            },
+            protocol_major_version: 11,
+            ..Default::default()
+        };
+        let _ = stream.write(&setup.serialize(), &mut Vec::new()).unwrap();
+        let request = || {
+            let (request, _fds) = GetGeometryRequest { drawable: 1 }.serialize();
+            let request = request.concat();
+            let stream = stream.clone();
+            std::thread::spawn(move || stream.write(&request, &mut Vec::new()).unwrap())
+        };
+
+        // The second request arrives while the handler runs for the first one
+        let first = request();
+        entered.recv().unwrap();
+        let second = request();
+        std::thread::sleep(Duration::from_millis(50));
+        drop(release);
+        let _ = (first.join().unwrap(), second.join().unwrap());
+
+        // Both requests are answered by the handler, in order
+        let mut data = vec![0; 1024];
+        let length = stream.read(&mut data, &mut Vec::new()).unwrap();
+        let setup_length = 8 + 4 * usize::from(u16::from_ne_bytes([data[6], data[7]]));
+        assert_eq!(length, setup_length + 64);
+        let replies = &data[setup_length..length];
+        assert_eq!((replies[0], &replies[2..4]), (1, &1u16.to_ne_bytes()[..]));
+        assert_eq!(
+            (replies[32], &replies[34..36]),
+            (1, &2u16.to_ne_bytes()[..])
+        );
+    }
+}
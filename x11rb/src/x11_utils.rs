@@ -2,7 +2,7 @@
 
 pub use x11rb_protocol::x11_utils::{
     parse_request_header, BigRequests, ExtInfoProvider, ExtensionInformation, ReplyParsingFunction,
-    Request, RequestHeader, Serialize, TryParse, TryParseFd, X11Error,
+    Request, RequestHeader, Serialize, TryParse, TryParseFd, X11Error, X11ErrorDisplay,
 };
 
 /// A helper macro for managing atoms
@@ -112,6 +112,8 @@ macro_rules! atom_manager {
         }
 
         impl $struct_name {
+            /// Send `InternAtom` requests for all atoms.
+            #[allow(clippy::new_ret_no_self)]
             $vis fn new<C: $crate::protocol::xproto::ConnectionExt>(
                 _conn: &C,
             ) -> ::std::result::Result<$cookie_name<'_, C>, $crate::errors::ConnectionError> {
@@ -128,6 +130,7 @@ macro_rules! atom_manager {
         }
 
         impl<'a, C: $crate::protocol::xproto::ConnectionExt> $cookie_name<'a, C> {
+            /// Wait for the replies to the `InternAtom` requests.
             $vis fn reply(self) -> ::std::result::Result<$struct_name, $crate::errors::ReplyError> {
                 let mut replies = self.__private_cookies.into_iter();
                 Ok($struct_name {
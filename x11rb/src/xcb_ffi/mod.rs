@@ -2,6 +2,7 @@
 //!
 //! This module is only available when the `allow-unsafe-code` feature is enabled.
 
+use std::collections::VecDeque;
 use std::ffi::CStr;
 use std::io::{Error as IOError, ErrorKind, IoSlice};
 use std::os::raw::c_int;
@@ -9,17 +10,22 @@ use std::os::raw::c_int;
 use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
 use std::ptr::{null, null_mut};
 use std::sync::{atomic::Ordering, Mutex};
+use std::time::Duration;
+#[cfg(unix)]
+use std::time::Instant;
 
 use libc::c_void;
 
 use crate::connection::{
-    compute_length_field, Connection, ReplyOrError, RequestConnection, RequestKind,
+    compute_length_field, Connection, ErrorHandler, ErrorHandlerSlot, ReplyOrError,
+    RequestConnection, RequestKind,
 };
 use crate::cookie::{Cookie, CookieWithFds, VoidCookie};
 use crate::errors::DisplayParsingError;
 pub use crate::errors::{ConnectError, ConnectionError, ParseError, ReplyError, ReplyOrIdError};
 use crate::extension_manager::ExtensionManager;
 use crate::protocol::xproto::Setup;
+use crate::protocol::Event;
 use crate::utils::{CSlice, RawFdContainer};
 use crate::x11_utils::{ExtensionInformation, TryParse, TryParseFd};
 
@@ -28,10 +34,17 @@ use x11rb_protocol::{DiscardMode, SequenceNumber};
 mod atomic_u64;
 mod pending_errors;
 mod raw_ffi;
+mod special_event;
 
 use atomic_u64::AtomicU64;
+
+/// The longest time that [`XCBConnection::wait_until`] sleeps before checking libxcb's queues
+/// again.
+#[cfg(unix)]
+const POLL_SLICE: Duration = Duration::from_millis(10);
 #[cfg(all(not(test), feature = "dl-libxcb"))]
 pub use raw_ffi::libxcb_library::load_libxcb;
+pub use special_event::SpecialEventQueue;
 
 type Buffer = <XCBConnection as RequestConnection>::Buf;
 /// The raw bytes of an event received by [`XCBConnection`] and its sequence number.
@@ -51,6 +64,9 @@ pub struct XCBConnection<Conn = raw_ffi::XcbConnectionWrapper> {
     ext_mgr: Mutex<ExtensionManager>,
     errors: pending_errors::PendingErrors,
     maximum_sequence_received: AtomicU64,
+    error_handler: ErrorHandlerSlot,
+    // Events that were skipped while looking for an event matching some predicate
+    skipped_events: Mutex<VecDeque<RawEventAndSeqNumber>>,
 }
 
 impl XCBConnection {
@@ -80,6 +96,8 @@ impl XCBConnection {
                     ext_mgr: Default::default(),
                     errors: Default::default(),
                     maximum_sequence_received: AtomicU64::new(0),
+                    error_handler: Default::default(),
+                    skipped_events: Default::default(),
                 };
                 Ok((conn, screen as usize))
             }
@@ -106,6 +124,100 @@ impl XCBConnection {
             should_drop,
         ))
     }
+
+    /// Register a separate queue for generic events of an extension.
+    ///
+    /// Generic events (`GE_GENERIC_EVENT`) with the given event ID (EID) that belong to the
+    /// extension with name `extension_name` are sent to the returned queue instead of being
+    /// returned by [`Connection::wait_for_event`] and friends. This allows libraries to receive
+    /// their events on a connection that is shared with an application. This uses libxcb's
+    /// `xcb_register_for_special_xge` with an `xcb_extension_t` that is private to x11rb. The
+    /// queue can only be read through the returned [`SpecialEventQueue`]; it is not shared with C
+    /// code that uses the same `xcb_connection_t`.
+    ///
+    /// Returns `None` if a queue for this EID is already registered. Returns
+    /// [`ConnectionError::UnsupportedExtension`] if the X11 server does not support the extension.
+    pub fn register_for_special_event(
+        &self,
+        extension_name: &'static str,
+        eid: u32,
+    ) -> Result<Option<SpecialEventQueue<'_>>, ConnectionError> {
+        if self.extension_information(extension_name)?.is_none() {
+            return Err(ConnectionError::UnsupportedExtension);
+        }
+        let extension = special_event::xcb_extension(extension_name);
+        let mut stamp = 0;
+        let special = unsafe {
+            raw_ffi::xcb_register_for_special_xge(self.as_ptr(), extension, eid, &mut stamp)
+        };
+        match std::ptr::NonNull::new(special) {
+            Some(special) => Ok(Some(SpecialEventQueue::new(self, special))),
+            None => match self.has_error() {
+                Some(error) => Err(error),
+                None => Ok(None),
+            },
+        }
+    }
+
+    /// Take the first skipped event that matches `predicate` out of the queue.
+    fn take_skipped_event(
+        &self,
+        predicate: &mut dyn FnMut(&Event) -> bool,
+    ) -> Option<RawEventAndSeqNumber> {
+        let mut skipped = self.skipped_events.lock().unwrap();
+        let index = skipped
+            .iter()
+            .position(|(event, _)| self.event_matches(event, predicate))?;
+        skipped.remove(index)
+    }
+
+    fn event_matches(&self, event: &[u8], predicate: &mut dyn FnMut(&Event) -> bool) -> bool {
+        self.parse_event(event)
+            .map_or(false, |event| predicate(&event))
+    }
+
+    fn wait_for_new_raw_event(&self) -> Result<RawEventAndSeqNumber, ConnectionError> {
+        loop {
+            let event = if let Some(error) = self.errors.get(self) {
+                (error.1, error.0)
+            } else {
+                unsafe {
+                    let event = raw_ffi::xcb_wait_for_event(self.conn.as_ptr());
+                    if event.is_null() {
+                        return Err(Self::connection_error_from_connection(self.conn.as_ptr()));
+                    }
+                    self.wrap_event(event as _)?
+                }
+            };
+            if !self.error_handler.should_discard(self, &event.0) {
+                return Ok(event);
+            }
+        }
+    }
+
+    fn poll_for_new_raw_event(&self) -> Result<Option<RawEventAndSeqNumber>, ConnectionError> {
+        loop {
+            let event = if let Some(error) = self.errors.get(self) {
+                (error.1, error.0)
+            } else {
+                unsafe {
+                    let event = raw_ffi::xcb_poll_for_event(self.conn.as_ptr());
+                    if event.is_null() {
+                        let err = raw_ffi::xcb_connection_has_error(self.conn.as_ptr());
+                        if err == 0 {
+                            return Ok(None);
+                        } else {
+                            return Err(Self::connection_error_from_c_error(err));
+                        }
+                    }
+                    self.wrap_event(event as _)?
+                }
+            };
+            if !self.error_handler.should_discard(self, &event.0) {
+                return Ok(Some(event));
+            }
+        }
+    }
 }
 
 impl<Conn: as_raw_xcb_connection::AsRawXcbConnection> XCBConnection<Conn> {
@@ -119,6 +231,8 @@ impl<Conn: as_raw_xcb_connection::AsRawXcbConnection> XCBConnection<Conn> {
             ext_mgr: Default::default(),
             errors: Default::default(),
             maximum_sequence_received: AtomicU64::new(0),
+            error_handler: Default::default(),
+            skipped_events: Default::default(),
         })
     }
 
@@ -166,6 +280,8 @@ impl<Conn: as_raw_xcb_connection::AsRawXcbConnection> XCBConnection<Conn> {
             ext_mgr: Default::default(),
             errors: Default::default(),
             maximum_sequence_received: AtomicU64::new(0),
+            error_handler: Default::default(),
+            skipped_events: Default::default(),
         })
     }
 
@@ -294,6 +410,14 @@ impl<Conn: as_raw_xcb_connection::AsRawXcbConnection> XCBConnection<Conn> {
         }
     }
 
+    /// Set the handler for X11 errors that would otherwise be delivered as events.
+    ///
+    /// Passing `None` removes the current handler. The previous handler is returned. See
+    /// [`ErrorHandler`] for details.
+    pub fn set_error_handler(&self, handler: Option<ErrorHandler>) -> Option<ErrorHandler> {
+        self.error_handler.replace(handler)
+    }
+
     /// Get access to the raw libxcb `xcb_connection_t`.
     ///
     /// The returned pointer is valid for as long as the original object was not dropped. No
@@ -326,6 +450,48 @@ impl<Conn: as_raw_xcb_connection::AsRawXcbConnection> XCBConnection<Conn> {
         }
     }
 
+    /// Repeatedly call `poll` until it produces a result or the deadline expires.
+    ///
+    /// Between calls, this waits for the connection's FD to become readable, but at most for
+    /// [`POLL_SLICE`]. `poll` is expected to read from the connection, e.g. via
+    /// `xcb_poll_for_reply64` or `xcb_poll_for_event`.
+    #[cfg(unix)]
+    fn wait_until<T>(
+        &self,
+        deadline: Option<Instant>,
+        mut poll: impl FnMut() -> Result<Option<T>, ConnectionError>,
+    ) -> Result<T, ConnectionError> {
+        // xcb_flush() returns 0 if the connection is in (or just entered) an error state, else 1.
+        if unsafe { raw_ffi::xcb_flush(self.as_ptr()) } == 0 {
+            return Err(unsafe { Self::connection_error_from_connection(self.as_ptr()) });
+        }
+        loop {
+            if let Some(result) = poll()? {
+                return Ok(result);
+            }
+            if let Some(error) = self.has_error() {
+                return Err(error);
+            }
+            let timeout = match deadline {
+                None => POLL_SLICE,
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(timeout) if !timeout.is_zero() => timeout.min(POLL_SLICE),
+                    _ => return Err(ConnectionError::Timeout),
+                },
+            };
+            // libxcb might be reading in another thread and could put what we are waiting for
+            // into its own queue. The FD then stays quiet, so only sleep for a short slice
+            // before calling `poll` again.
+            let fd =
+                unsafe { BorrowedFd::borrow_raw(raw_ffi::xcb_get_file_descriptor(self.as_ptr())) };
+            crate::rust_connection::poll_fd(
+                fd,
+                crate::rust_connection::PollMode::Readable,
+                Some(timeout),
+            )?;
+        }
+    }
+
     unsafe fn wrap_reply(&self, reply: *const u8, sequence: SequenceNumber) -> CSlice {
         // Update our "max sequence number received" field
         let _ = self
@@ -477,6 +643,22 @@ impl<Conn: as_raw_xcb_connection::AsRawXcbConnection> RequestConnection for XCBC
         }
     }
 
+    #[cfg(unix)]
+    fn wait_for_reply_or_raw_error_timeout(
+        &self,
+        sequence: SequenceNumber,
+        timeout: Duration,
+    ) -> Result<ReplyOrError<CSlice>, ConnectionError> {
+        let deadline = Instant::now().checked_add(timeout);
+        self.wait_until(deadline, || match self.poll_for_reply(sequence) {
+            Err(()) => Ok(None),
+            Ok(Some(buffer)) if buffer[0] == 0 => Ok(Some(ReplyOrError::Error(buffer))),
+            Ok(Some(buffer)) => Ok(Some(ReplyOrError::Reply(buffer))),
+            // libxcb only reports "no reply" for requests with a reply if the connection broke
+            Ok(None) => Err(self.has_error().unwrap_or(ConnectionError::UnknownError)),
+        })
+    }
+
     fn wait_for_reply(&self, sequence: SequenceNumber) -> Result<Option<CSlice>, ConnectionError> {
         match self.wait_for_reply_or_raw_error(sequence)? {
             ReplyOrError::Reply(reply) => Ok(Some(reply)),
@@ -537,6 +719,33 @@ impl<Conn: as_raw_xcb_connection::AsRawXcbConnection> RequestConnection for XCBC
         }
     }
 
+    #[cfg(unix)]
+    fn check_for_raw_error_timeout(
+        &self,
+        sequence: SequenceNumber,
+        timeout: Duration,
+    ) -> Result<Option<Buffer>, ConnectionError> {
+        let deadline = Instant::now().checked_add(timeout);
+        // Once the reply to this sync arrived, libxcb knows about any error to the request
+        let sync = crate::protocol::xproto::get_input_focus(self)?.into_sequence_number();
+        let result = self.wait_until(deadline, || Ok(self.poll_for_reply(sync).ok()));
+        if result.is_err() {
+            self.discard_reply(
+                sync,
+                RequestKind::HasResponse,
+                DiscardMode::DiscardReplyAndError,
+            );
+        }
+        let _ = result?;
+        if let Some(error) = self.has_error() {
+            return Err(error);
+        }
+        match self.poll_for_reply(sequence) {
+            Ok(error) => Ok(error),
+            Err(()) => Err(self.has_error().unwrap_or(ConnectionError::UnknownError)),
+        }
+    }
+
     fn maximum_request_bytes(&self) -> usize {
         4 * unsafe { raw_ffi::xcb_get_maximum_request_length(self.as_ptr()) as usize }
     }
@@ -550,43 +759,77 @@ impl<Conn: as_raw_xcb_connection::AsRawXcbConnection> RequestConnection for XCBC
         crate::x11_utils::X11Error::try_parse(error, &*ext_mgr)
     }
 
-    fn parse_event(&self, event: &[u8]) -> Result<crate::protocol::Event, ParseError> {
+    fn parse_event(&self, event: &[u8]) -> Result<Event, ParseError> {
         let ext_mgr = self.ext_mgr.lock().unwrap();
-        crate::protocol::Event::parse(event, &*ext_mgr)
+        Event::parse(event, &*ext_mgr)
     }
 }
 
 impl Connection for XCBConnection {
     fn wait_for_raw_event_with_sequence(&self) -> Result<RawEventAndSeqNumber, ConnectionError> {
-        if let Some(error) = self.errors.get(self) {
-            return Ok((error.1, error.0));
-        }
-        unsafe {
-            let event = raw_ffi::xcb_wait_for_event(self.conn.as_ptr());
-            if event.is_null() {
-                return Err(Self::connection_error_from_connection(self.conn.as_ptr()));
-            }
-            Ok(self.wrap_event(event as _)?)
+        if let Some(event) = self.skipped_events.lock().unwrap().pop_front() {
+            return Ok(event);
         }
+        self.wait_for_new_raw_event()
+    }
+
+    #[cfg(unix)]
+    fn wait_for_raw_event_with_sequence_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<RawEventAndSeqNumber, ConnectionError> {
+        let deadline = Instant::now().checked_add(timeout);
+        self.wait_until(deadline, || self.poll_for_raw_event_with_sequence())
     }
 
     fn poll_for_raw_event_with_sequence(
         &self,
     ) -> Result<Option<RawEventAndSeqNumber>, ConnectionError> {
-        if let Some(error) = self.errors.get(self) {
-            return Ok(Some((error.1, error.0)));
+        if let Some(event) = self.skipped_events.lock().unwrap().pop_front() {
+            return Ok(Some(event));
         }
-        unsafe {
-            let event = raw_ffi::xcb_poll_for_event(self.conn.as_ptr());
-            if event.is_null() {
-                let err = raw_ffi::xcb_connection_has_error(self.conn.as_ptr());
-                if err == 0 {
-                    return Ok(None);
-                } else {
-                    return Err(Self::connection_error_from_c_error(err));
-                }
+        self.poll_for_new_raw_event()
+    }
+
+    fn poll_for_raw_event_with_sequence_matching(
+        &self,
+        predicate: &mut dyn FnMut(&Event) -> bool,
+    ) -> Result<Option<RawEventAndSeqNumber>, ConnectionError> {
+        if let Some(event) = self.take_skipped_event(predicate) {
+            return Ok(Some(event));
+        }
+        while let Some(event) = self.poll_for_new_raw_event()? {
+            if self.event_matches(&event.0, predicate) {
+                return Ok(Some(event));
+            }
+            self.skipped_events.lock().unwrap().push_back(event);
+        }
+        Ok(None)
+    }
+
+    fn wait_for_raw_event_with_sequence_matching(
+        &self,
+        predicate: &mut dyn FnMut(&Event) -> bool,
+        timeout: Option<Duration>,
+    ) -> Result<RawEventAndSeqNumber, ConnectionError> {
+        #[cfg(unix)]
+        if let Some(timeout) = timeout {
+            let deadline = Instant::now().checked_add(timeout);
+            return self.wait_until(deadline, || {
+                self.poll_for_raw_event_with_sequence_matching(predicate)
+            });
+        }
+        // Without poll(), the timeout is ignored, like in wait_for_raw_event_with_sequence_timeout()
+        let _ = timeout;
+        if let Some(event) = self.take_skipped_event(predicate) {
+            return Ok(event);
+        }
+        loop {
+            let event = self.wait_for_new_raw_event()?;
+            if self.event_matches(&event.0, predicate) {
+                return Ok(event);
             }
-            Ok(Some(self.wrap_event(event as _)?))
+            self.skipped_events.lock().unwrap().push_back(event);
         }
     }
 
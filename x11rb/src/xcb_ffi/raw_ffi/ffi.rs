@@ -8,8 +8,9 @@
 //! `libxcb.so` at runtime. Most of the code is actually responsible for this later feature.
 
 use super::{
-    c_char, c_int, c_uint, c_void, iovec, xcb_connection_t, xcb_generic_error_t,
-    xcb_generic_event_t, xcb_protocol_request_t, xcb_setup_t, xcb_void_cookie_t,
+    c_char, c_int, c_uint, c_void, iovec, xcb_connection_t, xcb_extension_t, xcb_generic_error_t,
+    xcb_generic_event_t, xcb_protocol_request_t, xcb_setup_t, xcb_special_event_t,
+    xcb_void_cookie_t,
 };
 
 #[cfg(feature = "dl-libxcb")]
@@ -191,4 +192,22 @@ make_ffi_fn_defs! {
         reply: *mut *mut c_void,
         error: *mut *mut xcb_generic_error_t
     ) -> c_int;
+    fn xcb_register_for_special_xge(
+        c: *mut xcb_connection_t,
+        ext: *mut xcb_extension_t,
+        eid: u32,
+        stamp: *mut u32
+    ) -> *mut xcb_special_event_t;
+    fn xcb_unregister_for_special_event(
+        c: *mut xcb_connection_t,
+        se: *mut xcb_special_event_t
+    );
+    fn xcb_poll_for_special_event(
+        c: *mut xcb_connection_t,
+        se: *mut xcb_special_event_t
+    ) -> *mut xcb_generic_event_t;
+    fn xcb_wait_for_special_event(
+        c: *mut xcb_connection_t,
+        se: *mut xcb_special_event_t
+    ) -> *mut xcb_generic_event_t;
 }
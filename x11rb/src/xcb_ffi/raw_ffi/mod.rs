@@ -106,6 +106,12 @@ pub(crate) struct xcb_extension_t {
     pub(crate) global_id: c_int,
 }
 
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub(crate) struct xcb_special_event_t {
+    _unused: [u8; 0],
+}
+
 #[allow(non_camel_case_types)]
 #[repr(C)]
 pub(crate) struct xcb_protocol_request_t {
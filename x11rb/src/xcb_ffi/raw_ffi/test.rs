@@ -5,8 +5,8 @@ use std::ffi::CStr;
 use libc::{c_char, c_int, c_uint, c_void};
 
 use super::{
-    iovec, xcb_connection_t, xcb_generic_error_t, xcb_generic_event_t, xcb_protocol_request_t,
-    xcb_setup_t, xcb_void_cookie_t,
+    iovec, xcb_connection_t, xcb_extension_t, xcb_generic_error_t, xcb_generic_event_t,
+    xcb_protocol_request_t, xcb_setup_t, xcb_special_event_t, xcb_void_cookie_t,
 };
 use crate::protocol::xproto::{ImageOrder, Setup};
 use crate::x11_utils::Serialize;
@@ -154,3 +154,33 @@ pub(crate) unsafe fn xcb_poll_for_reply64(
 ) -> c_int {
     unimplemented!();
 }
+
+pub(crate) unsafe fn xcb_register_for_special_xge(
+    _c: *mut xcb_connection_t,
+    _ext: *mut xcb_extension_t,
+    _eid: u32,
+    _stamp: *mut u32,
+) -> *mut xcb_special_event_t {
+    unimplemented!();
+}
+
+pub(crate) unsafe fn xcb_unregister_for_special_event(
+    _c: *mut xcb_connection_t,
+    _se: *mut xcb_special_event_t,
+) {
+    unimplemented!();
+}
+
+pub(crate) unsafe fn xcb_poll_for_special_event(
+    _c: *mut xcb_connection_t,
+    _se: *mut xcb_special_event_t,
+) -> *mut xcb_generic_event_t {
+    unimplemented!();
+}
+
+pub(crate) unsafe fn xcb_wait_for_special_event(
+    _c: *mut xcb_connection_t,
+    _se: *mut xcb_special_event_t,
+) -> *mut xcb_generic_event_t {
+    unimplemented!();
+}
@@ -0,0 +1,116 @@
+//! Separate queues for generic events, see [`XCBConnection::register_for_special_event`].
+
+use std::ffi::CString;
+use std::ptr::NonNull;
+use std::sync::Mutex;
+
+use super::{raw_ffi, RawEventAndSeqNumber, XCBConnection};
+use crate::connection::RequestConnection;
+use crate::errors::ConnectionError;
+use crate::protocol::Event;
+
+/// A queue for the generic events of one extension with one event ID.
+///
+/// This wraps libxcb's `xcb_special_event_t` and is created by
+/// [`XCBConnection::register_for_special_event`]. The events that end up in this queue are not
+/// returned by the usual event functions of the connection. Dropping the queue unregisters it and
+/// discards all events that are still queued.
+#[derive(Debug)]
+pub struct SpecialEventQueue<'c> {
+    conn: &'c XCBConnection,
+    special: NonNull<raw_ffi::xcb_special_event_t>,
+}
+
+// libxcb protects special event queues with the connection's lock
+unsafe impl Send for SpecialEventQueue<'_> {}
+unsafe impl Sync for SpecialEventQueue<'_> {}
+
+impl<'c> SpecialEventQueue<'c> {
+    pub(super) fn new(
+        conn: &'c XCBConnection,
+        special: NonNull<raw_ffi::xcb_special_event_t>,
+    ) -> Self {
+        Self { conn, special }
+    }
+
+    /// Poll for a new event in this queue.
+    pub fn poll_for_event(&self) -> Result<Option<Event>, ConnectionError> {
+        Ok(match self.poll_for_raw_event_with_sequence()? {
+            Some((event, _seq)) => Some(self.conn.parse_event(&event)?),
+            None => None,
+        })
+    }
+
+    /// Wait for a new event in this queue.
+    pub fn wait_for_event(&self) -> Result<Event, ConnectionError> {
+        let (event, _seq) = self.wait_for_raw_event_with_sequence()?;
+        self.conn.parse_event(&event).map_err(Into::into)
+    }
+
+    /// Poll for a new raw/unparsed event in this queue.
+    pub fn poll_for_raw_event_with_sequence(
+        &self,
+    ) -> Result<Option<RawEventAndSeqNumber>, ConnectionError> {
+        unsafe {
+            let event =
+                raw_ffi::xcb_poll_for_special_event(self.conn.as_ptr(), self.special.as_ptr());
+            if event.is_null() {
+                return match self.conn.has_error() {
+                    Some(error) => Err(error),
+                    None => Ok(None),
+                };
+            }
+            Ok(Some(self.conn.wrap_event(event as _)?))
+        }
+    }
+
+    /// Wait for a new raw/unparsed event in this queue.
+    pub fn wait_for_raw_event_with_sequence(
+        &self,
+    ) -> Result<RawEventAndSeqNumber, ConnectionError> {
+        unsafe {
+            let event =
+                raw_ffi::xcb_wait_for_special_event(self.conn.as_ptr(), self.special.as_ptr());
+            if event.is_null() {
+                return Err(<XCBConnection>::connection_error_from_connection(
+                    self.conn.as_ptr(),
+                ));
+            }
+            Ok(self.conn.wrap_event(event as _)?)
+        }
+    }
+}
+
+impl Drop for SpecialEventQueue<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            raw_ffi::xcb_unregister_for_special_event(self.conn.as_ptr(), self.special.as_ptr());
+        }
+    }
+}
+
+/// Get the `xcb_extension_t` for the extension with the given name.
+///
+/// libxcb caches extension information per `xcb_extension_t`, so there is only one such struct
+/// per extension name. It is never freed. These structs are private to x11rb and are not the ones
+/// that C libraries like libxcb-present use.
+pub(super) fn xcb_extension(name: &'static str) -> *mut raw_ffi::xcb_extension_t {
+    struct Extension(*mut raw_ffi::xcb_extension_t);
+    // After creation, the struct is only accessed by libxcb, which synchronises this access
+    unsafe impl Send for Extension {}
+
+    static EXTENSIONS: Mutex<Vec<(&str, Extension)>> = Mutex::new(Vec::new());
+
+    let mut extensions = EXTENSIONS.lock().unwrap();
+    if let Some((_, extension)) = extensions.iter().find(|(known, _)| *known == name) {
+        return extension.0;
+    }
+    let extension = Box::into_raw(Box::new(raw_ffi::xcb_extension_t {
+        name: CString::new(name)
+            .expect("Extension names do not contain NUL bytes")
+            .into_raw(),
+        global_id: 0,
+    }));
+    extensions.push((name, Extension(extension)));
+    extension
+}
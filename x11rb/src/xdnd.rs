@@ -0,0 +1,692 @@
+//! Support for drag and drop via the XDND protocol
+//!
+//! [XDND](https://freedesktop.org/wiki/Specifications/XDND/) lets the user drag data from one
+//! window, the source, to another window, the target. The source and the target talk to each
+//! other with client messages. The data itself is transferred with the `XdndSelection` selection,
+//! see [`crate::selection`].
+//!
+//! This module contains the state machines [`DragSource`] and [`DropTarget`]. They do not read
+//! events from the connection themselves. Instead, the application feeds them the events from its
+//! own event loop and reacts to what they report.
+//!
+//! A drag works like this:
+//! 1. The target window announces its support via [`DropTarget::make_aware`].
+//! 2. The source offers its data and takes ownership of `XdndSelection` via
+//!    [`DragSource::start`]. While the user moves the pointer, the application calls
+//!    [`DragSource::motion`] with the top-level window under the pointer.
+//! 3. The target reports [`TargetEvent::Enter`] and [`TargetEvent::Position`]. It answers each
+//!    position with [`DropTarget::send_status`], which the source reports as
+//!    [`SourceEvent::Status`].
+//! 4. When the user releases the button, the application calls [`DragSource::drop`]. The target
+//!    reports [`TargetEvent::Drop`] and fetches the data with [`DropTarget::request_data`].
+//! 5. After the target reported [`TargetEvent::Data`], it calls [`DropTarget::finish`]. The
+//!    source reports [`SourceEvent::Finished`] and gives up ownership of `XdndSelection`.
+
+use crate::connection::RequestConnection;
+use crate::cookie::VoidCookie;
+use crate::errors::{ConnectionError, ReplyError};
+use crate::protocol::xproto::{
+    self, Atom, AtomEnum, ClientMessageEvent, EventMask, PropMode, Timestamp, Window,
+};
+use crate::protocol::Event;
+use crate::selection::{
+    Owner, SelectionAtoms, SelectionData, Transfer, TransferError, TransferStatus,
+};
+use crate::wrapper::ConnectionExt as _;
+use crate::NONE;
+
+/// The version of the XDND protocol that this module implements.
+pub const XDND_VERSION: u32 = 5;
+
+/// The oldest version of the XDND protocol that this module supports.
+const MIN_XDND_VERSION: u32 = 3;
+
+crate::atom_manager! {
+    /// The atoms that are used by the XDND protocol.
+    ///
+    /// The fields are named after the atoms they contain.
+    #[allow(missing_docs)]
+    pub XdndAtoms:
+    /// A cookie for interning [`XdndAtoms`].
+    XdndAtomsCookie {
+        XdndAware,
+        XdndEnter,
+        XdndPosition,
+        XdndStatus,
+        XdndLeave,
+        XdndDrop,
+        XdndFinished,
+        XdndSelection,
+        XdndTypeList,
+        XdndActionCopy,
+        XdndActionMove,
+        XdndActionLink,
+        XdndActionAsk,
+        XdndActionPrivate,
+    }
+}
+
+/// Send a client message with 32 bit data to a window.
+fn send_message<C: RequestConnection + ?Sized>(
+    conn: &C,
+    destination: Window,
+    window: Window,
+    type_: Atom,
+    data: [u32; 5],
+) -> Result<(), ConnectionError> {
+    let event = ClientMessageEvent::new(32, window, type_, data);
+    let _ = xproto::send_event(conn, false, destination, EventMask::NO_EVENT, event)?;
+    Ok(())
+}
+
+/// Get the client message of the given type from an event.
+fn client_message(event: &Event, type_: Atom) -> Option<[u32; 5]> {
+    match event {
+        Event::ClientMessage(event) if event.type_ == type_ && event.format == 32 => {
+            Some(event.data.as_data32())
+        }
+        _ => None,
+    }
+}
+
+/// Pack a point or a size into a single value, as XDND does.
+fn pack(x: i16, y: i16) -> u32 {
+    (u32::from(x as u16) << 16) | u32::from(y as u16)
+}
+
+/// Unpack a value created by [`pack`].
+fn unpack(value: u32) -> (i16, i16) {
+    ((value >> 16) as u16 as i16, value as u16 as i16)
+}
+
+/// Get the XDND version that a window supports.
+///
+/// This reads the window's `XdndAware` property. Returns `None` if the window does not support
+/// XDND or only a version that is too old for this module.
+pub fn aware_version<C: RequestConnection + ?Sized>(
+    conn: &C,
+    atoms: &XdndAtoms,
+    window: Window,
+) -> Result<Option<u32>, ReplyError> {
+    let reply = xproto::get_property(conn, false, window, atoms.XdndAware, AtomEnum::ATOM, 0, 1)?
+        .reply()?;
+    let version = reply.value32().and_then(|mut values| values.next());
+    Ok(version.filter(|&version| version >= MIN_XDND_VERSION))
+}
+
+/// What a [`DragSource`] learned from an event.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SourceEvent {
+    /// The target answered a position.
+    Status {
+        /// The target window.
+        target: Window,
+        /// The action that the target would perform, or `None` if it would not accept a drop.
+        action: Option<Atom>,
+    },
+
+    /// The target finished a drop.
+    Finished {
+        /// The target window.
+        target: Window,
+        /// The action that the target performed, or `None` if the drop failed.
+        action: Option<Atom>,
+    },
+
+    /// A drop that [`DragSource::drop`] deferred until the target answered the last position was
+    /// refused by the target.
+    ///
+    /// The source sent `XdndLeave` instead of `XdndDrop` and the drag is over.
+    DropRefused {
+        /// The target window.
+        target: Window,
+    },
+}
+
+/// The target that a [`DragSource`] is currently over.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct CurrentTarget {
+    window: Window,
+    version: u32,
+    /// The action that the target accepted in its last status, if any.
+    accepted: Option<Atom>,
+    /// Whether a position was sent for which no status arrived yet.
+    awaiting_status: bool,
+    /// A position that has to be sent once the status for the previous one arrived.
+    pending_position: Option<(i16, i16, Timestamp, Atom)>,
+    /// A drop that has to be sent once the status for the last position arrived.
+    pending_drop: Option<Timestamp>,
+}
+
+/// The source side of a drag.
+///
+/// The data is offered via [`DragSource::set_data`] before [`DragSource::start`]. All events have
+/// to be passed to [`DragSource::handle_event`], which also serves the data to the target.
+#[derive(Debug, Clone)]
+pub struct DragSource {
+    atoms: XdndAtoms,
+    window: Window,
+    owner: Owner,
+    types: Vec<Atom>,
+    target: Option<CurrentTarget>,
+    /// The target that a drop was sent to and that did not finish yet.
+    dropped_on: Option<CurrentTarget>,
+}
+
+impl DragSource {
+    /// Create a new drag source for the given window.
+    ///
+    /// `time` is the timestamp of the user action that started the drag.
+    pub fn new(
+        selection_atoms: &SelectionAtoms,
+        atoms: &XdndAtoms,
+        window: Window,
+        time: Timestamp,
+    ) -> Self {
+        Self {
+            atoms: *atoms,
+            window,
+            owner: Owner::new(selection_atoms, window, atoms.XdndSelection, time),
+            types: Vec::new(),
+            target: None,
+            dropped_on: None,
+        }
+    }
+
+    /// Offer the dragged data as the given type.
+    pub fn set_data(&mut self, type_: Atom, data: SelectionData) {
+        if !self.types.contains(&type_) {
+            self.types.push(type_);
+        }
+        self.owner.set_target(type_, data);
+    }
+
+    /// Start the drag.
+    ///
+    /// This takes ownership of `XdndSelection` and, if more than three types are offered,
+    /// writes `XdndTypeList`. Returns whether the X11 server accepted the selection owner.
+    pub fn start<C: RequestConnection + ?Sized>(&mut self, conn: &C) -> Result<bool, ReplyError> {
+        if self.types.len() > 3 {
+            let _ = conn.change_property32(
+                PropMode::REPLACE,
+                self.window,
+                self.atoms.XdndTypeList,
+                AtomEnum::ATOM,
+                &self.types,
+            )?;
+        }
+        self.owner.acquire(conn)
+    }
+
+    /// Get the window that the pointer is currently over, if it supports XDND.
+    pub fn current_target(&self) -> Option<Window> {
+        self.target.map(|target| target.window)
+    }
+
+    /// Handle pointer motion.
+    ///
+    /// `target` is the top-level window under the pointer, `x_root` and `y_root` are the pointer
+    /// position on the root window, and `action` is the requested action, for example
+    /// `XdndActionCopy`. When the pointer enters a new window, this checks whether the window
+    /// supports XDND.
+    pub fn motion<C: RequestConnection + ?Sized>(
+        &mut self,
+        conn: &C,
+        target: Option<Window>,
+        x_root: i16,
+        y_root: i16,
+        time: Timestamp,
+        action: Atom,
+    ) -> Result<(), ReplyError> {
+        if self.current_target() != target {
+            self.leave(conn)?;
+            if let Some(window) = target {
+                self.enter(conn, window)?;
+            }
+        }
+        let current = match &mut self.target {
+            Some(current) => current,
+            None => return Ok(()),
+        };
+        if current.awaiting_status {
+            current.pending_position = Some((x_root, y_root, time, action));
+            return Ok(());
+        }
+        current.awaiting_status = true;
+        let data = [self.window, 0, pack(x_root, y_root), time, action];
+        send_message(
+            conn,
+            current.window,
+            current.window,
+            self.atoms.XdndPosition,
+            data,
+        )?;
+        Ok(())
+    }
+
+    /// Send `XdndEnter` to a window if it supports XDND.
+    fn enter<C: RequestConnection + ?Sized>(
+        &mut self,
+        conn: &C,
+        window: Window,
+    ) -> Result<(), ReplyError> {
+        let version = match aware_version(conn, &self.atoms, window)? {
+            Some(version) => version.min(XDND_VERSION),
+            None => return Ok(()),
+        };
+        let more_types = u32::from(self.types.len() > 3);
+        let mut data = [self.window, (version << 24) | more_types, NONE, NONE, NONE];
+        for (slot, &type_) in data[2..].iter_mut().zip(&self.types) {
+            *slot = type_;
+        }
+        send_message(conn, window, window, self.atoms.XdndEnter, data)?;
+        self.target = Some(CurrentTarget {
+            window,
+            version,
+            accepted: None,
+            awaiting_status: false,
+            pending_position: None,
+            pending_drop: None,
+        });
+        Ok(())
+    }
+
+    /// Send `XdndLeave` to the current target.
+    fn leave<C: RequestConnection + ?Sized>(&mut self, conn: &C) -> Result<(), ConnectionError> {
+        if let Some(target) = self.target.take() {
+            let data = [self.window, 0, 0, 0, 0];
+            send_message(
+                conn,
+                target.window,
+                target.window,
+                self.atoms.XdndLeave,
+                data,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Drop the data on the current target.
+    ///
+    /// If the target did not answer the last position yet, the drop is sent once it does. If the
+    /// target then refuses the drop, [`DragSource::handle_event`] reports
+    /// [`SourceEvent::DropRefused`] instead of [`SourceEvent::Status`].
+    ///
+    /// Returns `false` if there is no target or the target does not accept the drop. In this case,
+    /// the drag is over and ownership of `XdndSelection` is given up.
+    pub fn drop<C: RequestConnection + ?Sized>(
+        &mut self,
+        conn: &C,
+        time: Timestamp,
+    ) -> Result<bool, ConnectionError> {
+        let current = match &mut self.target {
+            Some(current) => current,
+            None => return Ok(false),
+        };
+        if current.awaiting_status {
+            current.pending_drop = Some(time);
+            return Ok(true);
+        }
+        self.send_drop(conn, time)
+    }
+
+    /// Send `XdndDrop` if the current target accepts it and `XdndLeave` otherwise.
+    ///
+    /// In the latter case, the drag is over and `XdndSelection` is released.
+    fn send_drop<C: RequestConnection + ?Sized>(
+        &mut self,
+        conn: &C,
+        time: Timestamp,
+    ) -> Result<bool, ConnectionError> {
+        match self.target {
+            Some(target) if target.accepted.is_some() => {
+                self.target = None;
+                self.dropped_on = Some(target);
+                let data = [self.window, 0, time, 0, 0];
+                send_message(
+                    conn,
+                    target.window,
+                    target.window,
+                    self.atoms.XdndDrop,
+                    data,
+                )?;
+                Ok(true)
+            }
+            _ => {
+                self.leave(conn)?;
+                self.owner.release(conn)?;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Cancel the drag.
+    pub fn cancel<C: RequestConnection + ?Sized>(
+        &mut self,
+        conn: &C,
+    ) -> Result<(), ConnectionError> {
+        self.leave(conn)?;
+        self.owner.release(conn)
+    }
+
+    /// Handle an event.
+    ///
+    /// This handles `XdndStatus` and `XdndFinished` messages as well as the selection events that
+    /// are needed to transfer the data.
+    pub fn handle_event<C: RequestConnection + ?Sized>(
+        &mut self,
+        conn: &C,
+        event: &Event,
+    ) -> Result<Option<SourceEvent>, ReplyError> {
+        if self.owner.handle_event(conn, event)? {
+            return Ok(None);
+        }
+        if let Some(data) = client_message(event, self.atoms.XdndStatus) {
+            let current = match &mut self.target {
+                Some(current) if current.window == data[0] => current,
+                _ => return Ok(None),
+            };
+            current.awaiting_status = false;
+            current.accepted = if data[1] & 1 != 0 {
+                Some(data[4]).filter(|&action| action != NONE)
+            } else {
+                None
+            };
+            let status = SourceEvent::Status {
+                target: current.window,
+                action: current.accepted,
+            };
+            if let Some(time) = current.pending_drop.take() {
+                let target = current.window;
+                if !self.send_drop(conn, time)? {
+                    return Ok(Some(SourceEvent::DropRefused { target }));
+                }
+            } else if let Some((x, y, time, action)) = current.pending_position.take() {
+                let target = Some(current.window);
+                self.motion(conn, target, x, y, time, action)?;
+            }
+            return Ok(Some(status));
+        }
+        if let Some(data) = client_message(event, self.atoms.XdndFinished) {
+            let target = match self.dropped_on {
+                Some(target) if target.window == data[0] => target,
+                _ => return Ok(None),
+            };
+            self.dropped_on = None;
+            self.owner.release(conn)?;
+            // Before version 5, the target cannot report failure or the performed action
+            let action = if target.version < 5 {
+                target.accepted
+            } else if data[1] & 1 != 0 {
+                Some(data[2]).filter(|&action| action != NONE)
+            } else {
+                None
+            };
+            return Ok(Some(SourceEvent::Finished {
+                target: data[0],
+                action,
+            }));
+        }
+        Ok(None)
+    }
+}
+
+/// What a [`DropTarget`] learned from an event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TargetEvent {
+    /// A drag entered the window.
+    Enter {
+        /// The source window.
+        source: Window,
+        /// The types that the source offers.
+        types: Vec<Atom>,
+    },
+
+    /// The pointer moved during a drag.
+    ///
+    /// This has to be answered with [`DropTarget::send_status`].
+    Position {
+        /// The x coordinate of the pointer on the root window.
+        x_root: i16,
+        /// The y coordinate of the pointer on the root window.
+        y_root: i16,
+        /// The time of the pointer motion.
+        time: Timestamp,
+        /// The action that the source requested.
+        action: Atom,
+    },
+
+    /// The drag left the window without a drop.
+    Leave,
+
+    /// The data was dropped on the window.
+    ///
+    /// The data can now be fetched with [`DropTarget::request_data`]. Afterwards,
+    /// [`DropTarget::finish`] has to be called.
+    Drop {
+        /// The time of the drop.
+        time: Timestamp,
+    },
+
+    /// The data that was requested with [`DropTarget::request_data`] arrived.
+    Data(SelectionData),
+}
+
+/// The drag that a [`DropTarget`] is currently involved in.
+#[derive(Debug, Clone)]
+struct CurrentSource {
+    window: Window,
+    version: u32,
+    drop_time: Option<Timestamp>,
+    transfer: Option<Transfer>,
+}
+
+/// The target side of a drag.
+///
+/// All events have to be passed to [`DropTarget::handle_event`].
+#[derive(Debug, Clone)]
+pub struct DropTarget {
+    selection_atoms: SelectionAtoms,
+    atoms: XdndAtoms,
+    window: Window,
+    source: Option<CurrentSource>,
+}
+
+impl DropTarget {
+    /// Create a new drop target for the given top-level window.
+    pub fn new(selection_atoms: &SelectionAtoms, atoms: &XdndAtoms, window: Window) -> Self {
+        Self {
+            selection_atoms: *selection_atoms,
+            atoms: *atoms,
+            window,
+            source: None,
+        }
+    }
+
+    /// Announce support for XDND by setting the `XdndAware` property on the window.
+    pub fn make_aware<'c, C: RequestConnection + ?Sized>(
+        &self,
+        conn: &'c C,
+    ) -> Result<VoidCookie<'c, C>, ConnectionError> {
+        conn.change_property32(
+            PropMode::REPLACE,
+            self.window,
+            self.atoms.XdndAware,
+            AtomEnum::ATOM,
+            &[XDND_VERSION],
+        )
+    }
+
+    /// Get the window of the source of the current drag, if any.
+    pub fn source(&self) -> Option<Window> {
+        self.source.as_ref().map(|source| source.window)
+    }
+
+    /// Answer the last [`TargetEvent::Position`].
+    ///
+    /// `action` is the action that would be performed on a drop, or `None` if a drop would be
+    /// refused.
+    pub fn send_status<C: RequestConnection + ?Sized>(
+        &mut self,
+        conn: &C,
+        action: Option<Atom>,
+    ) -> Result<(), ConnectionError> {
+        let source = match &self.source {
+            Some(source) => source,
+            None => return Ok(()),
+        };
+        // Ask for a position message for every pointer motion by sending an empty rectangle
+        let flags = 0b10 | u32::from(action.is_some());
+        let data = [self.window, flags, 0, 0, action.unwrap_or(NONE)];
+        send_message(
+            conn,
+            source.window,
+            self.window,
+            self.atoms.XdndStatus,
+            data,
+        )
+    }
+
+    /// Request the dropped data as the given type.
+    ///
+    /// This can only be called after [`TargetEvent::Drop`]. The data is reported as
+    /// [`TargetEvent::Data`].
+    pub fn request_data<C: RequestConnection + ?Sized>(
+        &mut self,
+        conn: &C,
+        type_: Atom,
+    ) -> Result<(), ConnectionError> {
+        let source = match &mut self.source {
+            Some(source) => source,
+            None => return Ok(()),
+        };
+        let time = match source.drop_time {
+            Some(time) => time,
+            None => return Ok(()),
+        };
+        source.transfer = Some(Transfer::start(
+            conn,
+            &self.selection_atoms,
+            self.window,
+            self.atoms.XdndSelection,
+            type_,
+            self.atoms.XdndSelection,
+            time,
+        )?);
+        Ok(())
+    }
+
+    /// Finish the drop.
+    ///
+    /// `action` is the action that was performed, or `None` if the drop failed.
+    pub fn finish<C: RequestConnection + ?Sized>(
+        &mut self,
+        conn: &C,
+        action: Option<Atom>,
+    ) -> Result<(), ConnectionError> {
+        let source = match self.source.take() {
+            Some(source) => source,
+            None => return Ok(()),
+        };
+        let (flags, action) = match action {
+            Some(action) if source.version >= 5 => (1, action),
+            _ => (0, NONE),
+        };
+        let data = [self.window, flags, action, 0, 0];
+        send_message(
+            conn,
+            source.window,
+            self.window,
+            self.atoms.XdndFinished,
+            data,
+        )
+    }
+
+    /// Handle an event.
+    ///
+    /// This handles the XDND client messages that are sent to the window and the selection events
+    /// of a data transfer.
+    pub fn handle_event<C: RequestConnection + ?Sized>(
+        &mut self,
+        conn: &C,
+        event: &Event,
+    ) -> Result<Option<TargetEvent>, TransferError> {
+        if let Some(transfer) = self.source.as_mut().and_then(|s| s.transfer.as_mut()) {
+            match transfer.handle_event(conn, event)? {
+                TransferStatus::Ignored => {}
+                TransferStatus::Pending => return Ok(None),
+                TransferStatus::Done(data) => {
+                    if let Some(source) = &mut self.source {
+                        source.transfer = None;
+                    }
+                    return Ok(Some(TargetEvent::Data(data)));
+                }
+            }
+        }
+        match event {
+            Event::ClientMessage(message) if message.window == self.window => {}
+            _ => return Ok(None),
+        }
+
+        let atoms = &self.atoms;
+        if let Some(data) = client_message(event, atoms.XdndEnter) {
+            let version = data[1] >> 24;
+            if version < MIN_XDND_VERSION {
+                return Ok(None);
+            }
+            let types = if data[1] & 1 != 0 {
+                let reply = xproto::get_property(
+                    conn,
+                    false,
+                    data[0],
+                    atoms.XdndTypeList,
+                    AtomEnum::ATOM,
+                    0,
+                    u32::MAX,
+                )?
+                .reply()?;
+                reply.value32().map(Iterator::collect).unwrap_or_default()
+            } else {
+                data[2..].iter().copied().filter(|&t| t != NONE).collect()
+            };
+            self.source = Some(CurrentSource {
+                window: data[0],
+                version: version.min(XDND_VERSION),
+                drop_time: None,
+                transfer: None,
+            });
+            return Ok(Some(TargetEvent::Enter {
+                source: data[0],
+                types,
+            }));
+        }
+
+        // All other messages have to come from the current source
+        let data = match event {
+            Event::ClientMessage(message) if Some(message.data.as_data32()[0]) == self.source() => {
+                message.data.as_data32()
+            }
+            _ => return Ok(None),
+        };
+        if client_message(event, atoms.XdndPosition).is_some() {
+            let (x_root, y_root) = unpack(data[2]);
+            Ok(Some(TargetEvent::Position {
+                x_root,
+                y_root,
+                time: data[3],
+                action: data[4],
+            }))
+        } else if client_message(event, atoms.XdndLeave).is_some() {
+            self.source = None;
+            Ok(Some(TargetEvent::Leave))
+        } else if client_message(event, atoms.XdndDrop).is_some() {
+            let time = data[2];
+            if let Some(source) = &mut self.source {
+                source.drop_time = Some(time);
+            }
+            Ok(Some(TargetEvent::Drop { time }))
+        } else {
+            Ok(None)
+        }
+    }
+}
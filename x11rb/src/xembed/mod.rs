@@ -0,0 +1,405 @@
+//! Support for the XEMBED protocol and the system tray
+//!
+//! [XEMBED](https://specifications.freedesktop.org/xembed-spec/latest/) describes how a window of
+//! one client, the embedder, hosts a window of another client. The embedder reparents the
+//! embedded window into one of its own windows. Afterwards, both sides exchange `_XEMBED`
+//! client messages, for example about the keyboard focus.
+//!
+//! This module contains the XEMBED messages ([`XembedMessage`]) and the `_XEMBED_INFO` property
+//! ([`XembedInfo`]). The [system tray protocol](tray), which uses XEMBED to dock icons, is in the
+//! [`tray`] module.
+
+use crate::connection::RequestConnection;
+use crate::cookie::{Cookie, VoidCookie};
+use crate::errors::{ConnectionError, ParseError, ReplyError};
+use crate::protocol::xproto::{
+    self, AtomEnum, ClientMessageEvent, EventMask, GetPropertyReply, PropMode, Timestamp, Window,
+};
+use crate::wrapper::ConnectionExt as _;
+
+pub mod tray;
+
+/// The version of the XEMBED protocol that this module implements.
+pub const XEMBED_VERSION: u32 = 0;
+
+/// The flag in `_XEMBED_INFO` that indicates that the embedded window should be mapped.
+pub const XEMBED_MAPPED: u32 = 1;
+
+crate::atom_manager! {
+    /// The atoms that are used by XEMBED and the system tray protocol.
+    ///
+    /// The fields are named after the atoms they contain. The selection of the system tray
+    /// depends on the screen and is interned by [`tray::selection_atom`].
+    #[allow(missing_docs)]
+    pub XembedAtoms:
+    /// A cookie for interning [`XembedAtoms`].
+    XembedAtomsCookie {
+        _XEMBED,
+        _XEMBED_INFO,
+        MANAGER,
+        _NET_SYSTEM_TRAY_OPCODE,
+        _NET_SYSTEM_TRAY_ORIENTATION,
+        _NET_SYSTEM_TRAY_VISUAL,
+    }
+}
+
+/// The contents of the `_XEMBED_INFO` property.
+///
+/// The embedded window sets this property to announce the XEMBED version it supports and
+/// whether it wants to be mapped.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct XembedInfo {
+    /// The supported version of the XEMBED protocol.
+    pub version: u32,
+    /// Flags, for example [`XEMBED_MAPPED`].
+    pub flags: u32,
+}
+
+impl XembedInfo {
+    /// Create a new `_XEMBED_INFO` for the version that this module implements.
+    pub fn new(mapped: bool) -> Self {
+        Self {
+            version: XEMBED_VERSION,
+            flags: if mapped { XEMBED_MAPPED } else { 0 },
+        }
+    }
+
+    /// Parse a `_XEMBED_INFO` property.
+    pub fn from_reply(
+        reply: &GetPropertyReply,
+        atoms: &XembedAtoms,
+    ) -> Result<Option<Self>, ParseError> {
+        if reply.type_ == AtomEnum::NONE.into() {
+            return Ok(None);
+        }
+        if reply.type_ != atoms._XEMBED_INFO || reply.format != 32 {
+            return Err(ParseError::InvalidValue);
+        }
+        let mut values = reply.value32().ok_or(ParseError::InvalidValue)?;
+        match (values.next(), values.next()) {
+            (Some(version), Some(flags)) => Ok(Some(Self { version, flags })),
+            _ => Err(ParseError::InsufficientData),
+        }
+    }
+
+    /// Check whether the embedded window should be mapped.
+    pub fn mapped(&self) -> bool {
+        self.flags & XEMBED_MAPPED != 0
+    }
+}
+
+/// A cookie for getting a window's `_XEMBED_INFO` property.
+#[derive(Debug)]
+pub struct XembedInfoCookie<'a, Conn: RequestConnection + ?Sized> {
+    cookie: Cookie<'a, Conn, GetPropertyReply>,
+    atoms: XembedAtoms,
+}
+
+impl<'a, Conn> XembedInfoCookie<'a, Conn>
+where
+    Conn: RequestConnection + ?Sized,
+{
+    /// Send a `GetProperty` request for the `_XEMBED_INFO` property of the given window.
+    pub fn new(
+        conn: &'a Conn,
+        atoms: &XembedAtoms,
+        window: Window,
+    ) -> Result<Self, ConnectionError> {
+        let property = atoms._XEMBED_INFO;
+        let cookie = xproto::get_property(conn, false, window, property, property, 0, 2)?;
+        Ok(Self {
+            cookie,
+            atoms: *atoms,
+        })
+    }
+
+    /// Get the reply that the server sent.
+    pub fn reply(self) -> Result<Option<XembedInfo>, ReplyError> {
+        Ok(XembedInfo::from_reply(&self.cookie.reply()?, &self.atoms)?)
+    }
+
+    /// Get the reply that the server sent, but have errors handled as events.
+    pub fn reply_unchecked(self) -> Result<Option<XembedInfo>, ConnectionError> {
+        let atoms = self.atoms;
+        self.cookie
+            .reply_unchecked()?
+            .map(|reply| XembedInfo::from_reply(&reply, &atoms))
+            .transpose()
+            .map(|e| e.flatten())
+            .map_err(Into::into)
+    }
+}
+
+/// Set the `_XEMBED_INFO` property of a window that should be embedded.
+pub fn set_xembed_info<'a, C: RequestConnection + ?Sized>(
+    conn: &'a C,
+    atoms: &XembedAtoms,
+    window: Window,
+    info: XembedInfo,
+) -> Result<VoidCookie<'a, C>, ConnectionError> {
+    conn.change_property32(
+        PropMode::REPLACE,
+        window,
+        atoms._XEMBED_INFO,
+        atoms._XEMBED_INFO,
+        &[info.version, info.flags],
+    )
+}
+
+/// Where the focus should go in an embedded window that receives it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Focus {
+    /// Keep the focus where it was before.
+    Current,
+    /// Focus the first widget, for example after the user pressed `Tab`.
+    First,
+    /// Focus the last widget, for example after the user pressed `Shift+Tab`.
+    Last,
+}
+
+impl From<Focus> for u32 {
+    fn from(focus: Focus) -> Self {
+        match focus {
+            Focus::Current => 0,
+            Focus::First => 1,
+            Focus::Last => 2,
+        }
+    }
+}
+
+impl TryFrom<u32> for Focus {
+    type Error = ParseError;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Focus::Current),
+            1 => Ok(Focus::First),
+            2 => Ok(Focus::Last),
+            _ => Err(ParseError::InvalidValue),
+        }
+    }
+}
+
+// The opcodes of the XEMBED messages
+const EMBEDDED_NOTIFY: u32 = 0;
+const WINDOW_ACTIVATE: u32 = 1;
+const WINDOW_DEACTIVATE: u32 = 2;
+const REQUEST_FOCUS: u32 = 3;
+const FOCUS_IN: u32 = 4;
+const FOCUS_OUT: u32 = 5;
+const FOCUS_NEXT: u32 = 6;
+const FOCUS_PREV: u32 = 7;
+const MODALITY_ON: u32 = 10;
+const MODALITY_OFF: u32 = 11;
+const REGISTER_ACCELERATOR: u32 = 12;
+const UNREGISTER_ACCELERATOR: u32 = 13;
+const ACTIVATE_ACCELERATOR: u32 = 14;
+
+/// A message of the XEMBED protocol.
+///
+/// The messages from the embedder to the embedded window are `EmbeddedNotify`, `WindowActivate`,
+/// `WindowDeactivate`, `FocusIn`, `FocusOut`, `ModalityOn`, `ModalityOff` and
+/// `ActivateAccelerator`. The other messages go from the embedded window to the embedder.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum XembedMessage {
+    /// The window was embedded.
+    EmbeddedNotify {
+        /// The window that the embedded window was reparented into.
+        embedder: Window,
+        /// The XEMBED version that is used.
+        version: u32,
+    },
+    /// The top-level window of the embedder got the focus.
+    WindowActivate,
+    /// The top-level window of the embedder lost the focus.
+    WindowDeactivate,
+    /// The embedded window asks for the focus.
+    RequestFocus,
+    /// The embedded window got the focus.
+    FocusIn(Focus),
+    /// The embedded window lost the focus.
+    FocusOut,
+    /// The embedded window passes the focus on to the next widget of the embedder.
+    FocusNext,
+    /// The embedded window passes the focus on to the previous widget of the embedder.
+    FocusPrev,
+    /// A modal dialog of the embedder was opened.
+    ModalityOn,
+    /// The modal dialog of the embedder was closed.
+    ModalityOff,
+    /// The embedded window registers a keyboard accelerator.
+    RegisterAccelerator {
+        /// An identifier that the embedded window chose for the accelerator.
+        id: u32,
+        /// The keysym of the accelerator.
+        keysym: u32,
+        /// The modifiers of the accelerator.
+        modifiers: u32,
+    },
+    /// The embedded window removes a keyboard accelerator.
+    UnregisterAccelerator {
+        /// The identifier of the accelerator.
+        id: u32,
+    },
+    /// The embedder activates a keyboard accelerator.
+    ActivateAccelerator {
+        /// The identifier of the accelerator.
+        id: u32,
+        /// Flags of the activation.
+        flags: u32,
+    },
+}
+
+impl XembedMessage {
+    /// Parse an `_XEMBED` client message.
+    ///
+    /// Returns the timestamp of the message and the message itself. Returns `None` if the event is
+    /// not an `_XEMBED` message or a message that is not known to this module. Unknown messages
+    /// should be ignored.
+    pub fn parse(
+        atoms: &XembedAtoms,
+        event: &ClientMessageEvent,
+    ) -> Result<Option<(Timestamp, Self)>, ParseError> {
+        if event.type_ != atoms._XEMBED {
+            return Ok(None);
+        }
+        if event.format != 32 {
+            return Err(ParseError::InvalidValue);
+        }
+        let [time, opcode, detail, data1, data2] = event.data.as_data32();
+        let message = match opcode {
+            EMBEDDED_NOTIFY => Self::EmbeddedNotify {
+                embedder: data1,
+                version: data2,
+            },
+            WINDOW_ACTIVATE => Self::WindowActivate,
+            WINDOW_DEACTIVATE => Self::WindowDeactivate,
+            REQUEST_FOCUS => Self::RequestFocus,
+            FOCUS_IN => Self::FocusIn(detail.try_into()?),
+            FOCUS_OUT => Self::FocusOut,
+            FOCUS_NEXT => Self::FocusNext,
+            FOCUS_PREV => Self::FocusPrev,
+            MODALITY_ON => Self::ModalityOn,
+            MODALITY_OFF => Self::ModalityOff,
+            REGISTER_ACCELERATOR => Self::RegisterAccelerator {
+                id: detail,
+                keysym: data1,
+                modifiers: data2,
+            },
+            UNREGISTER_ACCELERATOR => Self::UnregisterAccelerator { id: detail },
+            ACTIVATE_ACCELERATOR => Self::ActivateAccelerator {
+                id: detail,
+                flags: data1,
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some((time, message)))
+    }
+
+    /// Create an `_XEMBED` client message for the given window.
+    pub fn to_event(
+        self,
+        atoms: &XembedAtoms,
+        window: Window,
+        time: Timestamp,
+    ) -> ClientMessageEvent {
+        let (opcode, detail, data1, data2) = match self {
+            Self::EmbeddedNotify { embedder, version } => (EMBEDDED_NOTIFY, 0, embedder, version),
+            Self::WindowActivate => (WINDOW_ACTIVATE, 0, 0, 0),
+            Self::WindowDeactivate => (WINDOW_DEACTIVATE, 0, 0, 0),
+            Self::RequestFocus => (REQUEST_FOCUS, 0, 0, 0),
+            Self::FocusIn(focus) => (FOCUS_IN, focus.into(), 0, 0),
+            Self::FocusOut => (FOCUS_OUT, 0, 0, 0),
+            Self::FocusNext => (FOCUS_NEXT, 0, 0, 0),
+            Self::FocusPrev => (FOCUS_PREV, 0, 0, 0),
+            Self::ModalityOn => (MODALITY_ON, 0, 0, 0),
+            Self::ModalityOff => (MODALITY_OFF, 0, 0, 0),
+            Self::RegisterAccelerator {
+                id,
+                keysym,
+                modifiers,
+            } => (REGISTER_ACCELERATOR, id, keysym, modifiers),
+            Self::UnregisterAccelerator { id } => (UNREGISTER_ACCELERATOR, id, 0, 0),
+            Self::ActivateAccelerator { id, flags } => (ACTIVATE_ACCELERATOR, id, flags, 0),
+        };
+        let data = [time, opcode, detail, data1, data2];
+        ClientMessageEvent::new(32, window, atoms._XEMBED, data)
+    }
+}
+
+/// Send an XEMBED message to a window.
+///
+/// `window` is the embedder or the embedded window, depending on the message.
+pub fn send_message<'a, C: RequestConnection + ?Sized>(
+    conn: &'a C,
+    atoms: &XembedAtoms,
+    window: Window,
+    time: Timestamp,
+    message: XembedMessage,
+) -> Result<VoidCookie<'a, C>, ConnectionError> {
+    let event = message.to_event(atoms, window, time);
+    xproto::send_event(conn, false, window, EventMask::NO_EVENT, event)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Focus, XembedAtoms, XembedMessage};
+    use crate::errors::ParseError;
+    use crate::protocol::xproto::ClientMessageEvent;
+
+    fn atoms() -> XembedAtoms {
+        XembedAtoms {
+            _XEMBED: 1,
+            _XEMBED_INFO: 2,
+            MANAGER: 3,
+            _NET_SYSTEM_TRAY_OPCODE: 4,
+            _NET_SYSTEM_TRAY_ORIENTATION: 5,
+            _NET_SYSTEM_TRAY_VISUAL: 6,
+        }
+    }
+
+    #[test]
+    fn message_round_trip() {
+        let atoms = atoms();
+        let messages = [
+            XembedMessage::EmbeddedNotify {
+                embedder: 42,
+                version: 0,
+            },
+            XembedMessage::WindowActivate,
+            XembedMessage::FocusIn(Focus::Last),
+            XembedMessage::FocusPrev,
+            XembedMessage::RegisterAccelerator {
+                id: 1,
+                keysym: 2,
+                modifiers: 3,
+            },
+            XembedMessage::ActivateAccelerator { id: 1, flags: 4 },
+        ];
+        for message in messages {
+            let event = message.to_event(&atoms, 10, 20);
+            assert_eq!(event.window, 10);
+            let parsed = XembedMessage::parse(&atoms, &event).unwrap();
+            assert_eq!(parsed, Some((20, message)));
+        }
+    }
+
+    #[test]
+    fn parse_invalid_messages() {
+        let atoms = atoms();
+
+        // Not an _XEMBED message
+        let event = ClientMessageEvent::new(32, 10, atoms.MANAGER, [0; 5]);
+        assert_eq!(XembedMessage::parse(&atoms, &event), Ok(None));
+
+        // Unknown opcode
+        let event = ClientMessageEvent::new(32, 10, atoms._XEMBED, [0, 100, 0, 0, 0]);
+        assert_eq!(XembedMessage::parse(&atoms, &event), Ok(None));
+
+        // Unknown focus detail
+        let event = ClientMessageEvent::new(32, 10, atoms._XEMBED, [0, 4, 3, 0, 0]);
+        let result = XembedMessage::parse(&atoms, &event);
+        assert_eq!(result, Err(ParseError::InvalidValue));
+    }
+}
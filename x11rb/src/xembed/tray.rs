@@ -0,0 +1,397 @@
+//! Support for the system tray protocol
+//!
+//! The [system tray protocol](https://specifications.freedesktop.org/systemtray-spec/latest/)
+//! lets applications dock small icon windows into a tray, for example in a panel. The tray is
+//! managed by the owner of the `_NET_SYSTEM_TRAY_Sn` selection, where `n` is the screen number.
+//! Icons are embedded via XEMBED.
+//!
+//! An application that wants to show an icon
+//! 1. creates the icon window and sets its `_XEMBED_INFO` property via
+//!    [`super::set_xembed_info`],
+//! 2. looks up the tray manager via [`find_manager`]. If there is none, it selects
+//!    `STRUCTURE_NOTIFY` on the root window and waits until [`manager_from_event`] reports a new
+//!    manager,
+//! 3. asks the manager to dock the icon via [`dock`].
+//!
+//! The tray itself is implemented by [`TrayManager`].
+
+use crate::connection::RequestConnection;
+use crate::cookie::{Cookie, VoidCookie};
+use crate::errors::{ConnectionError, ReplyError};
+use crate::protocol::xproto::{
+    self, Atom, AtomEnum, ChangeWindowAttributesAux, ClientMessageEvent, EventMask,
+    InternAtomReply, PropMode, SetMode, Timestamp, Visualid, Window,
+};
+use crate::protocol::Event;
+use crate::wrapper::ConnectionExt as _;
+use crate::NONE;
+
+use super::{XembedAtoms, XembedInfo, XembedInfoCookie, XembedMessage, XEMBED_VERSION};
+
+/// The opcode of the `_NET_SYSTEM_TRAY_OPCODE` message that asks for docking an icon.
+const SYSTEM_TRAY_REQUEST_DOCK: u32 = 0;
+
+/// Send an `InternAtom` request for the selection of the system tray on the given screen.
+pub fn selection_atom<C: RequestConnection + ?Sized>(
+    conn: &C,
+    screen_num: usize,
+) -> Result<Cookie<'_, C, InternAtomReply>, ConnectionError> {
+    let name = format!("_NET_SYSTEM_TRAY_S{}", screen_num);
+    xproto::intern_atom(conn, false, name.as_bytes())
+}
+
+/// Get the window of the current tray manager, if any.
+///
+/// `selection` is the atom returned by [`selection_atom`].
+pub fn find_manager<C: RequestConnection + ?Sized>(
+    conn: &C,
+    selection: Atom,
+) -> Result<Option<Window>, ReplyError> {
+    let owner = xproto::get_selection_owner(conn, selection)?.reply()?.owner;
+    Ok(Some(owner).filter(|&owner| owner != NONE))
+}
+
+/// Check whether an event announces a new tray manager.
+///
+/// Tray managers send a `MANAGER` client message to the root window. Applications receive it
+/// when they selected `STRUCTURE_NOTIFY` on the root window. Returns the window of the new
+/// manager.
+pub fn manager_from_event(atoms: &XembedAtoms, selection: Atom, event: &Event) -> Option<Window> {
+    match event {
+        Event::ClientMessage(event) if event.type_ == atoms.MANAGER && event.format == 32 => {
+            let data = event.data.as_data32();
+            Some(data[2]).filter(|_| data[1] == selection)
+        }
+        _ => None,
+    }
+}
+
+/// Ask a tray manager to dock an icon window.
+///
+/// The icon window should have an `_XEMBED_INFO` property. Once the icon was docked, it receives
+/// [`XembedMessage::EmbeddedNotify`].
+pub fn dock<'a, C: RequestConnection + ?Sized>(
+    conn: &'a C,
+    atoms: &XembedAtoms,
+    manager: Window,
+    icon: Window,
+    time: Timestamp,
+) -> Result<VoidCookie<'a, C>, ConnectionError> {
+    let data = [time, SYSTEM_TRAY_REQUEST_DOCK, icon, 0, 0];
+    let event = ClientMessageEvent::new(32, manager, atoms._NET_SYSTEM_TRAY_OPCODE, data);
+    xproto::send_event(conn, false, manager, EventMask::NO_EVENT, event)
+}
+
+/// The orientation of a tray.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Orientation {
+    /// The icons are arranged in a row.
+    Horizontal,
+    /// The icons are arranged in a column.
+    Vertical,
+}
+
+impl From<Orientation> for u32 {
+    fn from(orientation: Orientation) -> Self {
+        match orientation {
+            Orientation::Horizontal => 0,
+            Orientation::Vertical => 1,
+        }
+    }
+}
+
+/// What a [`TrayManager`] learned from an event.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TrayEvent {
+    /// An application asks for docking an icon.
+    ///
+    /// The icon can be embedded with [`TrayManager::embed`].
+    DockRequest {
+        /// The icon window.
+        icon: Window,
+        /// The timestamp of the request.
+        time: Timestamp,
+    },
+
+    /// An embedded icon changed whether it wants to be mapped.
+    ///
+    /// The manager already mapped or unmapped the icon.
+    MappedChanged {
+        /// The icon window.
+        icon: Window,
+        /// Whether the icon is now mapped.
+        mapped: bool,
+    },
+
+    /// An embedded icon was destroyed or reparented away.
+    Removed {
+        /// The icon window.
+        icon: Window,
+    },
+
+    /// Another tray manager took over the selection.
+    ///
+    /// The manager already unmapped all icons and reparented them back to the root window, so
+    /// that they can dock into the new tray.
+    SelectionLost,
+}
+
+/// An icon that is embedded into the tray.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Icon {
+    window: Window,
+    parent: Window,
+    mapped: bool,
+}
+
+/// The tray side of the system tray protocol.
+///
+/// All events have to be passed to [`TrayManager::handle_event`].
+#[derive(Debug, Clone)]
+pub struct TrayManager {
+    atoms: XembedAtoms,
+    selection: Atom,
+    window: Window,
+    root: Window,
+    time: Timestamp,
+    owned: bool,
+    icons: Vec<Icon>,
+}
+
+impl TrayManager {
+    /// Create a new tray manager.
+    ///
+    /// `selection` is the atom returned by [`selection_atom`] and `window` is the window that
+    /// owns the selection. It receives the requests of the applications. `root` is the root window
+    /// of the screen. `time` is used for acquiring the selection and must not be `CurrentTime`.
+    pub fn new(
+        atoms: &XembedAtoms,
+        selection: Atom,
+        window: Window,
+        root: Window,
+        time: Timestamp,
+    ) -> Self {
+        Self {
+            atoms: *atoms,
+            selection,
+            window,
+            root,
+            time,
+            owned: false,
+            icons: Vec::new(),
+        }
+    }
+
+    /// Check whether this tray manager currently owns the selection.
+    pub fn is_owner(&self) -> bool {
+        self.owned
+    }
+
+    /// Get the currently embedded icons.
+    pub fn icons(&self) -> impl Iterator<Item = Window> + '_ {
+        self.icons.iter().map(|icon| icon.window)
+    }
+
+    /// Become the tray manager.
+    ///
+    /// This takes ownership of the selection and announces the new manager with a `MANAGER`
+    /// client message on the root window. Returns whether the X11 server accepted the selection
+    /// owner.
+    pub fn acquire<C: RequestConnection + ?Sized>(&mut self, conn: &C) -> Result<bool, ReplyError> {
+        let _ = xproto::set_selection_owner(conn, self.window, self.selection, self.time)?;
+        let owner = xproto::get_selection_owner(conn, self.selection)?
+            .reply()?
+            .owner;
+        self.owned = owner == self.window;
+        if self.owned {
+            let data = [self.time, self.selection, self.window, 0, 0];
+            let event = ClientMessageEvent::new(32, self.root, self.atoms.MANAGER, data);
+            let _ = xproto::send_event(conn, false, self.root, EventMask::STRUCTURE_NOTIFY, event)?;
+        }
+        Ok(self.owned)
+    }
+
+    /// Set the `_NET_SYSTEM_TRAY_ORIENTATION` property of the manager window.
+    pub fn set_orientation<'a, C: RequestConnection + ?Sized>(
+        &self,
+        conn: &'a C,
+        orientation: Orientation,
+    ) -> Result<VoidCookie<'a, C>, ConnectionError> {
+        conn.change_property32(
+            PropMode::REPLACE,
+            self.window,
+            self.atoms._NET_SYSTEM_TRAY_ORIENTATION,
+            AtomEnum::CARDINAL,
+            &[orientation.into()],
+        )
+    }
+
+    /// Set the `_NET_SYSTEM_TRAY_VISUAL` property of the manager window.
+    ///
+    /// This is the visual that icons should use for their windows.
+    pub fn set_visual<'a, C: RequestConnection + ?Sized>(
+        &self,
+        conn: &'a C,
+        visual: Visualid,
+    ) -> Result<VoidCookie<'a, C>, ConnectionError> {
+        conn.change_property32(
+            PropMode::REPLACE,
+            self.window,
+            self.atoms._NET_SYSTEM_TRAY_VISUAL,
+            AtomEnum::VISUALID,
+            &[visual],
+        )
+    }
+
+    /// Embed an icon window.
+    ///
+    /// The icon is reparented into `parent` at the given position and told about it with
+    /// [`XembedMessage::EmbeddedNotify`]. It is mapped if its `_XEMBED_INFO` asks for it or if it
+    /// does not have a valid property. Returns whether the icon was mapped.
+    ///
+    /// If getting `_XEMBED_INFO` fails, for example because the icon was already destroyed, the
+    /// error is returned before the icon is touched.
+    pub fn embed<C: RequestConnection + ?Sized>(
+        &mut self,
+        conn: &C,
+        icon: Window,
+        parent: Window,
+        x: i16,
+        y: i16,
+    ) -> Result<bool, ReplyError> {
+        let info = match XembedInfoCookie::new(conn, &self.atoms, icon)?.reply() {
+            Ok(info) => info.unwrap_or_else(|| XembedInfo::new(true)),
+            // Icons with a broken _XEMBED_INFO are treated like icons without one
+            Err(ReplyError::ConnectionError(ConnectionError::ParseError(_))) => {
+                XembedInfo::new(true)
+            }
+            Err(err) => return Err(err),
+        };
+        let aux = ChangeWindowAttributesAux::new()
+            .event_mask(EventMask::STRUCTURE_NOTIFY | EventMask::PROPERTY_CHANGE);
+        let _ = xproto::change_window_attributes(conn, icon, &aux)?;
+        let _ = xproto::change_save_set(conn, SetMode::INSERT, icon)?;
+        let _ = xproto::reparent_window(conn, icon, parent, x, y)?;
+
+        // Only version 0 exists so far, but the negotiation must not break with newer versions
+        #[allow(clippy::unnecessary_min_or_max)]
+        let version = info.version.min(XEMBED_VERSION);
+        let message = XembedMessage::EmbeddedNotify {
+            embedder: parent,
+            version,
+        };
+        let _ = super::send_message(conn, &self.atoms, icon, self.time, message)?;
+        if info.mapped() {
+            let _ = xproto::map_window(conn, icon)?;
+        }
+        self.icons.retain(|other| other.window != icon);
+        self.icons.push(Icon {
+            window: icon,
+            parent,
+            mapped: info.mapped(),
+        });
+        Ok(info.mapped())
+    }
+
+    /// Give up being the tray manager.
+    ///
+    /// All icons are unmapped and reparented back to the root window.
+    pub fn release<C: RequestConnection + ?Sized>(
+        &mut self,
+        conn: &C,
+    ) -> Result<(), ConnectionError> {
+        self.unembed_all(conn)?;
+        if self.owned {
+            self.owned = false;
+            let _ = xproto::set_selection_owner(conn, NONE, self.selection, self.time)?;
+        }
+        Ok(())
+    }
+
+    /// Unmap all icons and reparent them back to the root window.
+    fn unembed_all<C: RequestConnection + ?Sized>(
+        &mut self,
+        conn: &C,
+    ) -> Result<(), ConnectionError> {
+        while let Some(icon) = self.icons.first() {
+            let _ = xproto::unmap_window(conn, icon.window)?;
+            let _ = xproto::reparent_window(conn, icon.window, self.root, 0, 0)?;
+            let _ = xproto::change_save_set(conn, SetMode::DELETE, icon.window)?;
+            let _ = self.icons.remove(0);
+        }
+        Ok(())
+    }
+
+    /// Remove an icon from the list of embedded icons.
+    fn remove(&mut self, window: Window) -> Option<TrayEvent> {
+        let index = self.icons.iter().position(|icon| icon.window == window)?;
+        let _ = self.icons.remove(index);
+        Some(TrayEvent::Removed { icon: window })
+    }
+
+    /// Handle an event.
+    ///
+    /// This handles dock requests, changes of `_XEMBED_INFO` on embedded icons, the destruction of
+    /// icons and the loss of the selection.
+    pub fn handle_event<C: RequestConnection + ?Sized>(
+        &mut self,
+        conn: &C,
+        event: &Event,
+    ) -> Result<Option<TrayEvent>, ReplyError> {
+        match event {
+            Event::ClientMessage(event)
+                if event.window == self.window
+                    && event.type_ == self.atoms._NET_SYSTEM_TRAY_OPCODE
+                    && event.format == 32 =>
+            {
+                let [time, opcode, icon, _, _] = event.data.as_data32();
+                if opcode == SYSTEM_TRAY_REQUEST_DOCK && self.owned {
+                    Ok(Some(TrayEvent::DockRequest { icon, time }))
+                } else {
+                    Ok(None)
+                }
+            }
+            Event::PropertyNotify(event) if event.atom == self.atoms._XEMBED_INFO => {
+                let icon = match self.icons.iter_mut().find(|i| i.window == event.window) {
+                    Some(icon) => icon,
+                    None => return Ok(None),
+                };
+                let info = XembedInfoCookie::new(conn, &self.atoms, icon.window)?.reply()?;
+                let mapped = info.map_or(true, |info| info.mapped());
+                if mapped == icon.mapped {
+                    return Ok(None);
+                }
+                icon.mapped = mapped;
+                if mapped {
+                    let _ = xproto::map_window(conn, icon.window)?;
+                } else {
+                    let _ = xproto::unmap_window(conn, icon.window)?;
+                }
+                Ok(Some(TrayEvent::MappedChanged {
+                    icon: icon.window,
+                    mapped,
+                }))
+            }
+            Event::DestroyNotify(event) => Ok(self.remove(event.window)),
+            Event::ReparentNotify(event) => {
+                let moved_away = self
+                    .icons
+                    .iter()
+                    .any(|icon| icon.window == event.window && icon.parent != event.parent);
+                if moved_away {
+                    Ok(self.remove(event.window))
+                } else {
+                    Ok(None)
+                }
+            }
+            Event::SelectionClear(event)
+                if event.owner == self.window && event.selection == self.selection =>
+            {
+                self.owned = false;
+                self.unembed_all(conn)?;
+                Ok(Some(TrayEvent::SelectionLost))
+            }
+            _ => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,34 @@
+//! Helpers that are shared between the integration tests
+
+// Every test only uses some of the helpers
+#![allow(dead_code)]
+
+#[cfg(feature = "request-parsing")]
+mod world;
+#[cfg(feature = "request-parsing")]
+#[allow(unused_imports)]
+pub use world::server;
+
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+#[cfg(unix)]
+use x11rb::protocol::xproto::Setup;
+#[cfg(unix)]
+use x11rb::rust_connection::{DefaultStream, RustConnection};
+
+/// Create a connection that talks to the returned socket instead of an X11 server.
+///
+/// The test plays the role of the X11 server by reading requests from the socket and writing
+/// replies, errors and events to it.
+#[cfg(unix)]
+pub fn connect() -> (RustConnection, UnixStream) {
+    let (client, server) = UnixStream::pair().unwrap();
+    let (stream, _) = DefaultStream::from_unix_stream(client).unwrap();
+    let setup = Setup {
+        resource_id_mask: 0xff,
+        ..Default::default()
+    };
+    let conn = RustConnection::for_connected_stream(stream, setup).unwrap();
+    (conn, server)
+}
@@ -0,0 +1,161 @@
+//! A fake X11 server for the tests of selection-based protocols
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use x11rb::protocol::xproto::{
+    Atom, EventMask, GetPropertyReply, GetSelectionOwnerReply, GetWindowAttributesReply,
+    InternAtomReply, Property, PropertyNotifyEvent, Screen, SelectionNotifyEvent,
+    SelectionRequestEvent, Setup, Window, PROPERTY_NOTIFY_EVENT, SELECTION_NOTIFY_EVENT,
+    SELECTION_REQUEST_EVENT,
+};
+use x11rb::protocol::Request;
+use x11rb::testing::{FakeServer, Response};
+use x11rb::NONE;
+
+/// The parts of an X11 server that selection transfers use.
+#[derive(Debug, Default)]
+struct World {
+    atoms: HashMap<Vec<u8>, Atom>,
+    properties: HashMap<(Window, Atom), (Atom, u8, Vec<u8>)>,
+    event_masks: HashMap<Window, EventMask>,
+    owners: HashMap<Atom, Window>,
+}
+
+impl World {
+    fn property_notify(&self, window: Window, atom: Atom, state: Property) -> Vec<Response> {
+        let mask = self.event_masks.get(&window).copied();
+        if !mask.map_or(false, |mask| mask.contains(EventMask::PROPERTY_CHANGE)) {
+            return Vec::new();
+        }
+        let event = PropertyNotifyEvent {
+            response_type: PROPERTY_NOTIFY_EVENT,
+            window,
+            atom,
+            state,
+            ..Default::default()
+        };
+        vec![Response::event(event)]
+    }
+
+    fn handle(&mut self, request: &Request<'_>) -> Vec<Response> {
+        match request {
+            Request::InternAtom(request) => {
+                let next = u32::try_from(self.atoms.len()).unwrap() + 100;
+                let atom = *self.atoms.entry(request.name.to_vec()).or_insert(next);
+                vec![Response::reply(&InternAtomReply {
+                    atom,
+                    ..Default::default()
+                })]
+            }
+            Request::GetWindowAttributes(request) => {
+                let mask = self.event_masks.get(&request.window).copied();
+                vec![Response::reply(&GetWindowAttributesReply {
+                    your_event_mask: mask.unwrap_or(EventMask::NO_EVENT),
+                    ..Default::default()
+                })]
+            }
+            Request::ChangeWindowAttributes(request) => {
+                if let Some(mask) = request.value_list.event_mask {
+                    let _ = self.event_masks.insert(request.window, mask);
+                }
+                Vec::new()
+            }
+            Request::ChangeProperty(request) => {
+                let value = (request.type_, request.format, request.data.to_vec());
+                let _ = self
+                    .properties
+                    .insert((request.window, request.property), value);
+                self.property_notify(request.window, request.property, Property::NEW_VALUE)
+            }
+            Request::DeleteProperty(request) => {
+                let _ = self.properties.remove(&(request.window, request.property));
+                self.property_notify(request.window, request.property, Property::DELETE)
+            }
+            Request::GetProperty(request) => {
+                let key = (request.window, request.property);
+                let (type_, format, value) = match self.properties.get(&key) {
+                    Some(value) => value.clone(),
+                    None => return vec![Response::reply(&GetPropertyReply::default())],
+                };
+                let offset = usize::try_from(request.long_offset * 4).unwrap();
+                let length = usize::try_from(request.long_length)
+                    .unwrap()
+                    .saturating_mul(4)
+                    .min(value.len() - offset);
+                let bytes_after = value.len() - offset - length;
+                let value = value[offset..offset + length].to_vec();
+                let reply = GetPropertyReply {
+                    format,
+                    type_,
+                    bytes_after: bytes_after.try_into().unwrap(),
+                    value_len: (value.len() / usize::from(format / 8)).try_into().unwrap(),
+                    value,
+                    ..Default::default()
+                };
+                let mut responses = vec![Response::reply(&reply)];
+                if request.delete && bytes_after == 0 {
+                    let _ = self.properties.remove(&key);
+                    responses.extend(self.property_notify(key.0, key.1, Property::DELETE));
+                }
+                responses
+            }
+            Request::SetSelectionOwner(request) => {
+                let _ = self.owners.insert(request.selection, request.owner);
+                Vec::new()
+            }
+            Request::GetSelectionOwner(request) => {
+                let owner = self.owners.get(&request.selection).copied();
+                vec![Response::reply(&GetSelectionOwnerReply {
+                    owner: owner.unwrap_or(NONE),
+                    ..Default::default()
+                })]
+            }
+            Request::ConvertSelection(request) => match self.owners.get(&request.selection) {
+                Some(&owner) if owner != NONE => vec![Response::event(SelectionRequestEvent {
+                    response_type: SELECTION_REQUEST_EVENT,
+                    time: request.time,
+                    owner,
+                    requestor: request.requestor,
+                    selection: request.selection,
+                    target: request.target,
+                    property: request.property,
+                    ..Default::default()
+                })],
+                _ => vec![Response::event(SelectionNotifyEvent {
+                    response_type: SELECTION_NOTIFY_EVENT,
+                    time: request.time,
+                    requestor: request.requestor,
+                    selection: request.selection,
+                    target: request.target,
+                    property: NONE,
+                    ..Default::default()
+                })],
+            },
+            Request::SendEvent(request) => {
+                let mut event = *request.event;
+                event[0] |= 0x80;
+                vec![Response::Event(event)]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Create a fake server that accepts requests of at most `max_request_bytes` bytes.
+pub fn server(max_request_bytes: u16) -> FakeServer {
+    let server = FakeServer::new(Setup {
+        status: 1,
+        protocol_major_version: 11,
+        resource_id_mask: 0xff,
+        maximum_request_length: max_request_bytes / 4,
+        roots: vec![Screen::default()],
+        ..Default::default()
+    });
+    let world = Arc::new(Mutex::new(World::default()));
+    for opcode in [2, 3, 16, 18, 19, 20, 22, 23, 24, 25] {
+        let world = Arc::clone(&world);
+        server.set_handler(opcode, move |request| world.lock().unwrap().handle(request));
+    }
+    server
+}
@@ -0,0 +1,72 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpListener;
+use std::time::{Duration, Instant};
+
+use x11rb::connection::Connection as _;
+use x11rb::errors::ConnectError;
+use x11rb::protocol::xproto::{Screen, Setup};
+use x11rb::rust_connection::{ConnectOptions, RustConnection};
+use x11rb::x11_utils::Serialize;
+
+/// Listen on a TCP port that corresponds to an X11 display on localhost.
+fn listen() -> (TcpListener, String) {
+    (100..1000)
+        .find_map(|display| {
+            let listener = TcpListener::bind(("127.0.0.1", 6000 + display)).ok()?;
+            Some((listener, format!("127.0.0.1:{display}")))
+        })
+        .expect("No free port for a fake X11 server")
+}
+
+#[test]
+fn handshake_timeout() {
+    let (_listener, display) = listen();
+    let options = ConnectOptions::default()
+        .with_connect_timeout(Duration::from_secs(10))
+        .with_handshake_timeout(Duration::from_millis(50));
+
+    // The connection ends up in the listener's backlog, but nobody answers
+    let start = Instant::now();
+    match RustConnection::connect_with_options(Some(&display), &options) {
+        Err(ConnectError::IoError(e)) => assert_eq!(e.kind(), ErrorKind::TimedOut),
+        result => panic!("Unexpected result {:?}", result.map(|_| ())),
+    }
+    assert!(start.elapsed() >= Duration::from_millis(50));
+}
+
+#[test]
+fn handshake_within_timeout() {
+    let (listener, display) = listen();
+    let options = ConnectOptions::default()
+        .with_connect_timeout(Duration::from_secs(10))
+        .with_handshake_timeout(Duration::from_secs(10));
+
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        // Read the setup request, including any authorization data
+        let mut request = [0; 12];
+        stream.read_exact(&mut request).unwrap();
+        let padded = |length: u16| (usize::from(length) + 3) / 4 * 4;
+        let auth_name = u16::from_ne_bytes([request[6], request[7]]);
+        let auth_data = u16::from_ne_bytes([request[8], request[9]]);
+        let mut auth = vec![0; padded(auth_name) + padded(auth_data)];
+        stream.read_exact(&mut auth).unwrap();
+
+        let setup = Setup {
+            status: 1,
+            resource_id_mask: 0xff,
+            roots: vec![Screen::default()],
+            ..Default::default()
+        };
+        let mut setup = setup.serialize();
+        let length = u16::try_from((setup.len() - 8) / 4).unwrap();
+        setup[6..8].copy_from_slice(&length.to_ne_bytes());
+        stream.write_all(&setup).unwrap();
+        stream
+    });
+
+    let (conn, screen) = RustConnection::connect_with_options(Some(&display), &options).unwrap();
+    assert_eq!(screen, 0);
+    assert_eq!(conn.setup().resource_id_mask, 0xff);
+    drop(server.join().unwrap());
+}
@@ -0,0 +1,81 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+
+use x11rb::connection::{Connection as _, ErrorDisposition};
+use x11rb::protocol::xproto::{ConnectionExt as _, GET_WINDOW_ATTRIBUTES_REQUEST};
+use x11rb::protocol::{ErrorKind, Event};
+
+fn error(code: u8, seqno: u16, major_opcode: u8) -> [u8; 32] {
+    let mut error = [0; 32];
+    error[1] = code;
+    error[2..4].copy_from_slice(&seqno.to_ne_bytes());
+    error[10] = major_opcode;
+    error
+}
+
+#[test]
+fn discard_some_errors() {
+    let (conn, mut server) = common::connect();
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let seen2 = Arc::clone(&seen);
+    let previous = conn.set_error_handler(Some(Arc::new(move |error| {
+        seen2.lock().unwrap().push((
+            error.sequence,
+            error.request_name,
+            error.extension_name.clone(),
+        ));
+        if error.error_kind == ErrorKind::Window {
+            ErrorDisposition::Discard
+        } else {
+            ErrorDisposition::Deliver
+        }
+    })));
+    assert!(previous.is_none());
+
+    for _ in 0..3 {
+        drop(conn.get_window_attributes(42).unwrap());
+    }
+    conn.flush().unwrap();
+    let mut requests = [0; 24];
+    server.read_exact(&mut requests).unwrap();
+    server
+        .write_all(&error(3, 1, GET_WINDOW_ATTRIBUTES_REQUEST))
+        .unwrap();
+    server
+        .write_all(&error(2, 2, GET_WINDOW_ATTRIBUTES_REQUEST))
+        .unwrap();
+
+    // The first error is discarded, the second one is delivered
+    match conn.wait_for_event().unwrap() {
+        Event::Error(error) => {
+            assert_eq!((error.error_kind, error.sequence), (ErrorKind::Value, 2))
+        }
+        event => panic!("Unexpected event {event:?}"),
+    }
+    assert_eq!(
+        *seen.lock().unwrap(),
+        [
+            (1, Some("GetWindowAttributes"), None),
+            (2, Some("GetWindowAttributes"), None)
+        ]
+    );
+
+    // Without the handler, errors are delivered again
+    assert!(conn.set_error_handler(None).is_some());
+    server
+        .write_all(&error(3, 3, GET_WINDOW_ATTRIBUTES_REQUEST))
+        .unwrap();
+    match conn.wait_for_event().unwrap() {
+        Event::Error(error) => {
+            assert_eq!((error.error_kind, error.sequence), (ErrorKind::Window, 3))
+        }
+        event => panic!("Unexpected event {event:?}"),
+    }
+    assert_eq!(seen.lock().unwrap().len(), 2);
+    assert!(conn.poll_for_event().unwrap().is_none());
+}
@@ -0,0 +1,75 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::io::Write;
+use std::time::Duration;
+
+use x11rb::connection::Connection as _;
+use x11rb::errors::ConnectionError;
+use x11rb::protocol::xproto::{Window, CONFIGURE_NOTIFY_EVENT, EXPOSE_EVENT};
+use x11rb::protocol::Event;
+
+fn event(response_type: u8, window: Window) -> [u8; 32] {
+    let mut event = [0; 32];
+    event[0] = response_type;
+    event[4..8].copy_from_slice(&window.to_ne_bytes());
+    event[8..12].copy_from_slice(&window.to_ne_bytes());
+    event
+}
+
+fn is_expose(window: Window) -> impl FnMut(&Event) -> bool {
+    move |event| matches!(event, Event::Expose(expose) if expose.window == window)
+}
+
+fn describe(event: &Event) -> (&'static str, Window) {
+    match event {
+        Event::Expose(expose) => ("Expose", expose.window),
+        Event::ConfigureNotify(configure) => ("ConfigureNotify", configure.window),
+        event => panic!("Unexpected event {event:?}"),
+    }
+}
+
+#[test]
+fn poll_keeps_other_events() {
+    let (conn, mut server) = common::connect();
+    server.write_all(&event(EXPOSE_EVENT, 1)).unwrap();
+    server.write_all(&event(CONFIGURE_NOTIFY_EVENT, 2)).unwrap();
+    server.write_all(&event(EXPOSE_EVENT, 2)).unwrap();
+    server.write_all(&event(EXPOSE_EVENT, 3)).unwrap();
+
+    let found = conn.poll_for_event_matching(is_expose(2)).unwrap().unwrap();
+    assert_eq!(describe(&found), ("Expose", 2));
+    assert!(conn
+        .poll_for_event_matching(is_expose(2))
+        .unwrap()
+        .is_none());
+
+    let remaining = std::iter::from_fn(|| conn.poll_for_event().unwrap())
+        .map(|event| describe(&event))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        remaining,
+        [("Expose", 1), ("ConfigureNotify", 2), ("Expose", 3)]
+    );
+}
+
+#[test]
+fn wait_with_timeout() {
+    let (conn, mut server) = common::connect();
+    server.write_all(&event(CONFIGURE_NOTIFY_EVENT, 1)).unwrap();
+
+    let timeout = Some(Duration::from_millis(10));
+    match conn.wait_for_event_matching(is_expose(1), timeout) {
+        Err(ConnectionError::Timeout) => {}
+        result => panic!("Unexpected result {result:?}"),
+    }
+
+    // The connection is still usable and the skipped event is still queued
+    server.write_all(&event(EXPOSE_EVENT, 1)).unwrap();
+    let found = conn.wait_for_event_matching(is_expose(1), None).unwrap();
+    assert_eq!(describe(&found), ("Expose", 1));
+    let skipped = conn.poll_for_event().unwrap().unwrap();
+    assert_eq!(describe(&skipped), ("ConfigureNotify", 1));
+    assert!(conn.poll_for_event().unwrap().is_none());
+}
@@ -0,0 +1,342 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+
+use x11rb::connection::Connection as _;
+use x11rb::properties::ewmh::wm::{ClientRequest, RootState};
+use x11rb::properties::ewmh::{
+    self, ClientList, EwmhAtoms, MoveResizeDirection, SourceIndication, Viewport, WmName,
+    WmStateAction,
+};
+use x11rb::protocol::xproto::{ClientMessageEvent, ConnectionExt as _, EventMask, PropMode};
+use x11rb::rust_connection::RustConnection;
+
+/// Read a request from the fake server's end of the connection.
+fn read_request(server: &mut UnixStream) -> Vec<u8> {
+    let mut request = vec![0; 4];
+    server.read_exact(&mut request).unwrap();
+    let length = usize::from(u16::from_ne_bytes([request[2], request[3]])) * 4;
+    request.resize(length, 0);
+    server.read_exact(&mut request[4..]).unwrap();
+    request
+}
+
+/// Write a reply with the given extra words and value to the fake server's end of the connection.
+fn write_reply(server: &mut UnixStream, sequence: u16, format: u8, words: [u32; 3], value: &[u8]) {
+    let mut reply = vec![1, format];
+    reply.extend(sequence.to_ne_bytes());
+    reply.extend(u32::try_from(value.len() / 4).unwrap().to_ne_bytes());
+    reply.extend(words.iter().flat_map(|word| word.to_ne_bytes()));
+    reply.resize(32, 0);
+    reply.extend(value);
+    server.write_all(&reply).unwrap();
+}
+
+/// Intern the EWMH atoms. Each atom's value is its sequence number plus 100.
+fn intern_atoms(conn: &RustConnection, server: &mut UnixStream) -> EwmhAtoms {
+    let cookie = EwmhAtoms::new(conn).unwrap();
+    conn.flush().unwrap();
+    let count = std::mem::size_of::<EwmhAtoms>() / 4;
+    for sequence in 1..=count {
+        let request = read_request(server);
+        assert_eq!(request[0], 16); // InternAtom
+        let sequence = u16::try_from(sequence).unwrap();
+        write_reply(server, sequence, 0, [100 + u32::from(sequence), 0, 0], &[]);
+    }
+    cookie.reply().unwrap()
+}
+
+#[test]
+fn state_message() {
+    let (conn, mut server) = common::connect();
+    let atoms = intern_atoms(&conn, &mut server);
+
+    let message = ewmh::wm_state_message(
+        &atoms,
+        42,
+        WmStateAction::Toggle,
+        atoms._NET_WM_STATE_MAXIMIZED_VERT,
+        Some(atoms._NET_WM_STATE_MAXIMIZED_HORZ),
+        SourceIndication::Application,
+    );
+    assert_eq!(message.type_, atoms._NET_WM_STATE);
+    assert_eq!(message.window, 42);
+    assert_eq!(
+        message.data.as_data32(),
+        [
+            2,
+            atoms._NET_WM_STATE_MAXIMIZED_VERT,
+            atoms._NET_WM_STATE_MAXIMIZED_HORZ,
+            1,
+            0
+        ]
+    );
+
+    let _ = ewmh::send_root_message(&conn, 7, message).unwrap();
+    conn.flush().unwrap();
+    let request = read_request(&mut server);
+    assert_eq!(request[0], 25); // SendEvent
+    assert_eq!(request[4..8], 7u32.to_ne_bytes());
+    let mask = EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY;
+    assert_eq!(request[8..12], u32::from(mask).to_ne_bytes());
+    let event: [u8; 32] = message.into();
+    assert_eq!(request[12..], event);
+}
+
+#[test]
+fn active_window_message() {
+    let (conn, mut server) = common::connect();
+    let atoms = intern_atoms(&conn, &mut server);
+
+    let message: ClientMessageEvent =
+        ewmh::active_window_message(&atoms, 42, SourceIndication::Pager, 1234, None);
+    assert_eq!(message.type_, atoms._NET_ACTIVE_WINDOW);
+    assert_eq!(message.data.as_data32(), [2, 1234, 0, 0, 0]);
+}
+
+#[test]
+fn set_and_get_properties() {
+    let (conn, mut server) = common::connect();
+    let atoms = intern_atoms(&conn, &mut server);
+
+    let _ = WmName("Hello".to_string()).set(&conn, &atoms, 42).unwrap();
+    conn.flush().unwrap();
+    let request = read_request(&mut server);
+    assert_eq!(request[0], 18); // ChangeProperty
+    assert_eq!(request[8..12], atoms._NET_WM_NAME.to_ne_bytes());
+    assert_eq!(request[12..16], atoms.UTF8_STRING.to_ne_bytes());
+    assert_eq!(request[16], 8);
+    assert_eq!(request[20..24], 5u32.to_ne_bytes());
+    assert_eq!(&request[24..29], b"Hello");
+
+    let cookie = ClientList::get(&conn, &atoms, 7).unwrap();
+    conn.flush().unwrap();
+    let request = read_request(&mut server);
+    assert_eq!(request[0], 20); // GetProperty
+    let sequence = u16::try_from(std::mem::size_of::<EwmhAtoms>() / 4 + 2).unwrap();
+    let windows: Vec<u8> = [1u32, 2, 3].iter().flat_map(|w| w.to_ne_bytes()).collect();
+    // type WINDOW, no bytes after, three values
+    write_reply(&mut server, sequence, 32, [33, 0, 3], &windows);
+    assert_eq!(cookie.reply().unwrap(), Some(ClientList(vec![1, 2, 3])));
+}
+
+#[test]
+fn parse_client_requests() {
+    let (conn, mut server) = common::connect();
+    let atoms = intern_atoms(&conn, &mut server);
+
+    let message = ewmh::wm_state_message(
+        &atoms,
+        42,
+        WmStateAction::Remove,
+        atoms._NET_WM_STATE_ABOVE,
+        None,
+        SourceIndication::Pager,
+    );
+    assert_eq!(
+        ClientRequest::parse(&atoms, &message),
+        Ok(Some(ClientRequest::WmState {
+            window: 42,
+            action: WmStateAction::Remove,
+            first: atoms._NET_WM_STATE_ABOVE,
+            second: None,
+            source: SourceIndication::Pager,
+        }))
+    );
+
+    let message = ewmh::wm_moveresize_message(
+        &atoms,
+        42,
+        -10,
+        20,
+        MoveResizeDirection::SizeBottomRight,
+        1,
+        SourceIndication::Application,
+    );
+    assert_eq!(
+        ClientRequest::parse(&atoms, &message),
+        Ok(Some(ClientRequest::WmMoveResize {
+            window: 42,
+            x_root: -10,
+            y_root: 20,
+            direction: MoveResizeDirection::SizeBottomRight,
+            button: 1,
+            source: SourceIndication::Application,
+        }))
+    );
+
+    let message = ClientMessageEvent::new(
+        32,
+        42,
+        atoms._NET_MOVERESIZE_WINDOW,
+        [
+            (2 << 12) | (1 << 9) | (1 << 10) | 1,
+            1,
+            u32::from_ne_bytes((-5i32).to_ne_bytes()),
+            300,
+            4,
+        ],
+    );
+    match ClientRequest::parse(&atoms, &message) {
+        Ok(Some(ClientRequest::MoveResizeWindow {
+            source,
+            x,
+            y,
+            width,
+            height,
+            ..
+        })) => {
+            assert_eq!(source, SourceIndication::Pager);
+            assert_eq!((x, y, width, height), (None, Some(-5), Some(300), None));
+        }
+        result => panic!("Unexpected result {result:?}"),
+    }
+
+    // Unknown source indications are treated as legacy
+    let message = ClientMessageEvent::new(32, 42, atoms._NET_CLOSE_WINDOW, [0, 7, 0, 0, 0]);
+    assert_eq!(
+        ClientRequest::parse(&atoms, &message),
+        Ok(Some(ClientRequest::CloseWindow {
+            window: 42,
+            timestamp: 0,
+            source: SourceIndication::Legacy,
+        }))
+    );
+
+    let message = ClientMessageEvent::new(32, 7, atoms._NET_DESKTOP_VIEWPORT, [1920, 0, 0, 0, 0]);
+    assert_eq!(
+        ClientRequest::parse(&atoms, &message),
+        Ok(Some(ClientRequest::DesktopViewport { x: 1920, y: 0 }))
+    );
+
+    let message =
+        ClientMessageEvent::new(32, 42, atoms._NET_WM_FULLSCREEN_MONITORS, [0, 1, 0, 2, 2]);
+    assert_eq!(
+        ClientRequest::parse(&atoms, &message),
+        Ok(Some(ClientRequest::WmFullscreenMonitors {
+            window: 42,
+            top: 0,
+            bottom: 1,
+            left: 0,
+            right: 2,
+            source: SourceIndication::Pager,
+        }))
+    );
+
+    // The pong is sent to the root window and names the pinged window in the data
+    let message = ClientMessageEvent::new(
+        32,
+        7,
+        atoms.WM_PROTOCOLS,
+        [atoms._NET_WM_PING, 1234, 42, 0, 0],
+    );
+    assert_eq!(
+        ClientRequest::parse(&atoms, &message),
+        Ok(Some(ClientRequest::WmPing {
+            window: 42,
+            timestamp: 1234,
+        }))
+    );
+
+    // Other protocols are not EWMH requests
+    let message =
+        ClientMessageEvent::new(32, 7, atoms.WM_PROTOCOLS, [atoms.UTF8_STRING, 0, 0, 0, 0]);
+    assert_eq!(ClientRequest::parse(&atoms, &message), Ok(None));
+
+    // Not an EWMH message
+    let message = ClientMessageEvent::new(32, 42, atoms.UTF8_STRING, [0; 5]);
+    assert_eq!(ClientRequest::parse(&atoms, &message), Ok(None));
+}
+
+#[test]
+fn root_state_writes_changes() {
+    let (conn, mut server) = common::connect();
+    let atoms = intern_atoms(&conn, &mut server);
+    let property = |request: &[u8]| u32::from_ne_bytes(request[8..12].try_into().unwrap());
+    let mode = |request: &[u8]| PropMode::from(request[1]);
+
+    // The first flush writes the client lists and the properties that the EWMH requires
+    let mut state = RootState::new(7, 8, vec![atoms._NET_CLIENT_LIST]);
+    state.add_client(42);
+    state.flush(&conn, &atoms).unwrap();
+    conn.flush().unwrap();
+    let mut properties = Vec::new();
+    for _ in 0..4 {
+        let request = read_request(&mut server);
+        assert_eq!((request[0], mode(&request)), (18, PropMode::REPLACE));
+        properties.push(property(&request));
+    }
+    assert_eq!(
+        properties,
+        [
+            atoms._NET_CLIENT_LIST,
+            atoms._NET_CLIENT_LIST_STACKING,
+            atoms._NET_NUMBER_OF_DESKTOPS,
+            atoms._NET_CURRENT_DESKTOP,
+        ]
+    );
+
+    // Other properties are written once they are set, even to their initial value
+    state.set_desktop_geometry(0, 0);
+    state.flush(&conn, &atoms).unwrap();
+    conn.flush().unwrap();
+    let request = read_request(&mut server);
+    assert_eq!(property(&request), atoms._NET_DESKTOP_GEOMETRY);
+    assert_eq!(request[24..], [0; 8]);
+
+    let viewports = vec![Viewport { x: 0, y: 0 }, Viewport { x: 1920, y: 0 }];
+    state.set_desktop_viewport(viewports.clone());
+    state.set_desktop_viewport(viewports);
+    state.flush(&conn, &atoms).unwrap();
+    let _ = conn.get_input_focus().unwrap();
+    conn.flush().unwrap();
+    let request = read_request(&mut server);
+    assert_eq!(property(&request), atoms._NET_DESKTOP_VIEWPORT);
+    let values: Vec<u8> = [0u32, 0, 1920, 0]
+        .iter()
+        .flat_map(|v| v.to_ne_bytes())
+        .collect();
+    assert_eq!(request[24..], values);
+    assert_eq!(read_request(&mut server)[0], 43); // GetInputFocus
+    assert_eq!(state.desktop_viewport()[1], Viewport { x: 1920, y: 0 });
+
+    // New clients are appended, other properties are only written when they changed
+    state.add_client(43);
+    state.set_current_desktop(0);
+    state.set_active_window(Some(43));
+    state.flush(&conn, &atoms).unwrap();
+    let _ = conn.get_input_focus().unwrap();
+    conn.flush().unwrap();
+    for expected in [atoms._NET_CLIENT_LIST, atoms._NET_CLIENT_LIST_STACKING] {
+        let request = read_request(&mut server);
+        assert_eq!(
+            (property(&request), mode(&request)),
+            (expected, PropMode::APPEND)
+        );
+        assert_eq!(request[24..], 43u32.to_ne_bytes());
+    }
+    let request = read_request(&mut server);
+    assert_eq!(property(&request), atoms._NET_ACTIVE_WINDOW);
+    assert_eq!(read_request(&mut server)[0], 43); // GetInputFocus
+
+    // Removing the active client replaces the lists and clears the active window
+    state.remove_client(43);
+    assert_eq!(state.client_list(), [42]);
+    assert_eq!(state.active_window(), None);
+    state.flush(&conn, &atoms).unwrap();
+    conn.flush().unwrap();
+    for expected in [
+        atoms._NET_CLIENT_LIST,
+        atoms._NET_CLIENT_LIST_STACKING,
+        atoms._NET_ACTIVE_WINDOW,
+    ] {
+        let request = read_request(&mut server);
+        assert_eq!(
+            (property(&request), mode(&request)),
+            (expected, PropMode::REPLACE)
+        );
+    }
+}
@@ -0,0 +1,125 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+
+use x11rb::connection::Connection as _;
+use x11rb::errors::{ConnectionError, LimitExceeded, ReplyError};
+use x11rb::protocol::xproto::{ConnectionExt as _, MapNotifyEvent};
+use x11rb::rust_connection::{Limits, RustConnection};
+
+fn connect(limits: Limits) -> (RustConnection, UnixStream) {
+    let (conn, server) = common::connect();
+    conn.set_limits(limits);
+    (conn, server)
+}
+
+#[test]
+fn packet_too_large() {
+    let mut limits = Limits::default();
+    limits.max_packet_length = 1024;
+    let (conn, mut server) = connect(limits);
+
+    let cookie = conn.get_input_focus().unwrap();
+    conn.flush().unwrap();
+    let mut request = [0; 4];
+    server.read_exact(&mut request).unwrap();
+
+    // A reply that claims to be 4 MiB large
+    let mut reply = [0; 32];
+    reply[0] = 1;
+    reply[2..4].copy_from_slice(&1u16.to_ne_bytes());
+    reply[4..8].copy_from_slice(&(1u32 << 20).to_ne_bytes());
+    server.write_all(&reply).unwrap();
+
+    match cookie.reply() {
+        Err(ReplyError::ConnectionError(ConnectionError::LimitExceeded(
+            LimitExceeded::PacketLength(length),
+        ))) => assert_eq!(length, 32 + (4 << 20)),
+        result => panic!("Unexpected result {result:?}"),
+    }
+
+    // The rest of the reply was not read, so the connection stays broken
+    server.write_all(&[0; 1024]).unwrap();
+    match conn.poll_for_event() {
+        Err(ConnectionError::LimitExceeded(LimitExceeded::PacketLength(_))) => {}
+        result => panic!("Unexpected result {result:?}"),
+    }
+    match conn.get_input_focus().unwrap().reply() {
+        Err(ReplyError::ConnectionError(ConnectionError::LimitExceeded(
+            LimitExceeded::PacketLength(_),
+        ))) => {}
+        result => panic!("Unexpected result {result:?}"),
+    }
+}
+
+#[test]
+fn too_many_events() {
+    let mut limits = Limits::default();
+    limits.max_queued_events = 2;
+    let (conn, mut server) = connect(limits);
+    assert_eq!(conn.limits(), limits);
+
+    let event: [u8; 32] = MapNotifyEvent {
+        response_type: 19,
+        ..Default::default()
+    }
+    .into();
+    server.write_all(&[event; 3].concat()).unwrap();
+
+    // All three events are read at once, but only two fit into the queue
+    match conn.poll_for_event() {
+        Err(ConnectionError::LimitExceeded(LimitExceeded::QueuedEvents)) => {}
+        result => panic!("Unexpected result {result:?}"),
+    }
+
+    // The queued events can still be fetched, but the third event is lost for good
+    for _ in 0..2 {
+        assert!(conn.poll_for_event().unwrap().is_some());
+    }
+    match conn.wait_for_event() {
+        Err(ConnectionError::LimitExceeded(LimitExceeded::QueuedEvents)) => {}
+        result => panic!("Unexpected result {result:?}"),
+    }
+}
+
+#[test]
+fn too_many_replies() {
+    let mut limits = Limits::default();
+    limits.max_pending_replies = 1;
+    let (conn, mut server) = connect(limits);
+
+    let cookies = [(); 3].map(|()| conn.get_input_focus().unwrap());
+    conn.flush().unwrap();
+    let mut requests = [0; 12];
+    server.read_exact(&mut requests).unwrap();
+
+    let replies = (1..=3u16)
+        .map(|seqno| {
+            let mut reply = [0; 32];
+            reply[0] = 1;
+            reply[2..4].copy_from_slice(&seqno.to_ne_bytes());
+            reply
+        })
+        .collect::<Vec<_>>();
+    server.write_all(&replies.concat()).unwrap();
+
+    // Only the first reply fits into the queue. The other replies are lost and waiting for them
+    // fails instead of blocking forever.
+    let [first, second, third] = cookies;
+    match second.reply() {
+        Err(ReplyError::ConnectionError(ConnectionError::LimitExceeded(
+            LimitExceeded::PendingReplies,
+        ))) => {}
+        result => panic!("Unexpected result {result:?}"),
+    }
+    assert!(first.reply().is_ok());
+    match third.reply() {
+        Err(ReplyError::ConnectionError(ConnectionError::LimitExceeded(
+            LimitExceeded::PendingReplies,
+        ))) => {}
+        result => panic!("Unexpected result {result:?}"),
+    }
+}
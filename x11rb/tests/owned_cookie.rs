@@ -0,0 +1,83 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::sync::Arc;
+
+use x11rb::connection::Connection as _;
+use x11rb::cookie::{OwnedCookie, OwnedVoidCookie};
+use x11rb::protocol::xproto::{ConnectionExt as _, GetInputFocusReply};
+use x11rb::protocol::{ErrorKind, Event};
+use x11rb::rust_connection::RustConnection;
+
+fn connect() -> (Arc<RustConnection>, UnixStream) {
+    let (conn, server) = common::connect();
+    (Arc::new(conn), server)
+}
+
+fn get_input_focus_reply(seqno: u16) -> [u8; 32] {
+    let mut reply = [0; 32];
+    reply[0] = 1;
+    reply[2..4].copy_from_slice(&seqno.to_ne_bytes());
+    reply
+}
+
+fn window_error(seqno: u16) -> [u8; 32] {
+    let mut error = [0; 32];
+    error[1] = 3;
+    error[2..4].copy_from_slice(&seqno.to_ne_bytes());
+    error
+}
+
+struct PendingFocus {
+    cookie: OwnedCookie<RustConnection, GetInputFocusReply>,
+}
+
+#[test]
+fn reply_on_another_thread() {
+    let (conn, mut server) = connect();
+
+    let pending = PendingFocus {
+        cookie: conn.get_input_focus().unwrap().into_owned(),
+    };
+    assert_eq!(pending.cookie.sequence_number(), 1);
+    conn.flush().unwrap();
+    let thread = std::thread::spawn(move || pending.cookie.reply().unwrap());
+
+    let mut request = [0; 4];
+    server.read_exact(&mut request).unwrap();
+    server.write_all(&get_input_focus_reply(1)).unwrap();
+    let reply = thread.join().unwrap();
+    assert_eq!(reply.sequence, 1);
+}
+
+#[test]
+fn void_cookie_drop_and_ignore() {
+    let (conn, mut server) = connect();
+
+    // A dropped cookie causes errors to be treated as events
+    let cookie: OwnedVoidCookie<_> = conn.no_operation().unwrap().into_owned();
+    assert_eq!(cookie.sequence_number(), 1);
+    drop(cookie);
+    // An ignored error does not show up anywhere
+    conn.no_operation().unwrap().into_owned().ignore_error();
+    let cookie = conn.get_input_focus().unwrap().into_owned();
+    conn.flush().unwrap();
+
+    let mut requests = [0; 12];
+    server.read_exact(&mut requests).unwrap();
+    server.write_all(&window_error(1)).unwrap();
+    server.write_all(&window_error(2)).unwrap();
+    server.write_all(&get_input_focus_reply(3)).unwrap();
+    let _ = cookie.reply().unwrap();
+
+    match conn.poll_for_event().unwrap() {
+        Some(Event::Error(error)) => {
+            assert_eq!((error.error_kind, error.sequence), (ErrorKind::Window, 1))
+        }
+        event => panic!("Unexpected event {event:?}"),
+    }
+    assert!(conn.poll_for_event().unwrap().is_none());
+}
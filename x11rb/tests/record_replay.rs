@@ -0,0 +1,72 @@
+#![cfg(feature = "request-parsing")]
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use x11rb::connection::Connection as _;
+use x11rb::protocol::xproto::{
+    ClientMessageEvent, ConnectionExt as _, GetGeometryReply, GET_GEOMETRY_REQUEST,
+};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::{RecordingStream, ReplayStream, RustConnection, Stream};
+use x11rb::testing::{FakeServer, Response};
+
+/// A writer that can still be accessed after it was given away.
+#[derive(Debug, Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The "application" whose session is recorded and replayed.
+fn session(conn: &RustConnection<impl Stream>) -> (u16, Event) {
+    let reply = conn.get_geometry(1u32).unwrap().reply().unwrap();
+    let event = conn.wait_for_event().unwrap();
+    (reply.width, event)
+}
+
+#[test]
+fn record_and_replay_session() {
+    let server = FakeServer::default();
+    let reply = GetGeometryReply {
+        width: 123,
+        ..Default::default()
+    };
+    server.set_responses(
+        GET_GEOMETRY_REQUEST,
+        vec![
+            Response::reply(&reply),
+            Response::event(ClientMessageEvent::new(32, 7u32, 0u32, [0; 5])),
+        ],
+    );
+
+    let recording = SharedBuffer::default();
+    let stream = RecordingStream::new(server.stream(), recording.clone()).unwrap();
+    let conn = RustConnection::connect_to_stream(stream, 0).unwrap();
+    let recorded = session(&conn);
+    drop(conn);
+
+    let recording = recording.0.lock().unwrap().clone();
+    let stream = ReplayStream::new(&recording[..]).unwrap();
+    let conn = RustConnection::connect_to_stream(stream, 0).unwrap();
+    let replayed = session(&conn);
+    assert!(conn.stream().is_finished());
+
+    assert_eq!(recorded.0, 123);
+    assert_eq!(replayed.0, 123);
+    match (recorded.1, replayed.1) {
+        (Event::ClientMessage(recorded), Event::ClientMessage(replayed)) => {
+            assert_eq!(recorded.window, 7);
+            assert_eq!(replayed.window, 7);
+            assert_eq!(recorded.sequence, replayed.sequence);
+        }
+        events => panic!("Unexpected events {events:?}"),
+    }
+}
@@ -0,0 +1,61 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::io::{Read, Write};
+
+use x11rb::connection::RequestBatch;
+use x11rb::errors::ReplyError;
+use x11rb::protocol::xproto::ConnectionExt as _;
+use x11rb::protocol::ErrorKind;
+
+fn response(kind: u8, code: u8, seqno: u16) -> [u8; 32] {
+    let mut response = [0; 32];
+    response[0] = kind;
+    response[1] = code;
+    response[2..4].copy_from_slice(&seqno.to_ne_bytes());
+    response
+}
+
+#[test]
+fn batch_with_errors() {
+    let (conn, mut server) = common::connect();
+
+    let server = std::thread::spawn(move || {
+        // All requests arrive before any response is sent: NoOperation, 3x GetInputFocus,
+        // GetWindowAttributes
+        let mut requests = [0; 4 + 3 * 4 + 8];
+        server.read_exact(&mut requests).unwrap();
+        server.write_all(&response(0, 3, 1)).unwrap();
+        server.write_all(&response(1, 0, 2)).unwrap();
+        server.write_all(&response(0, 3, 3)).unwrap();
+        server.write_all(&response(1, 0, 4)).unwrap();
+        server.write_all(&response(0, 3, 5)).unwrap();
+        server
+    });
+
+    let (void, focus, attributes) = RequestBatch::new(&conn)
+        .push(conn.no_operation())
+        .push_all((0..3).map(|_| conn.get_input_focus()))
+        .push(conn.get_window_attributes(42))
+        .send()
+        .unwrap();
+
+    let is_window_error = |result: Result<_, ReplyError>, seqno| match result {
+        Err(ReplyError::X11Error(error)) => {
+            assert_eq!(
+                (error.error_kind, error.sequence),
+                (ErrorKind::Window, seqno)
+            )
+        }
+        other => panic!("Expected an error, got {other:?}"),
+    };
+    is_window_error(void, 1);
+    let mut focus = focus.into_iter();
+    assert_eq!(focus.next().unwrap().unwrap().sequence, 2);
+    is_window_error(focus.next().unwrap().map(|_| ()), 3);
+    assert_eq!(focus.next().unwrap().unwrap().sequence, 4);
+    is_window_error(attributes.map(|_| ()), 5);
+
+    drop(server.join().unwrap());
+}
@@ -0,0 +1,243 @@
+#![cfg(feature = "request-parsing")]
+
+mod common;
+
+use x11rb::connection::{Connection, RequestConnection as _};
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ChangeWindowAttributesAux, ConnectionExt as _, EventMask, PropMode,
+    SelectionNotifyEvent, Window,
+};
+use x11rb::protocol::{Event, Request};
+use x11rb::selection::{
+    Owner, SelectionAtoms, SelectionData, Transfer, TransferError, TransferStatus,
+};
+use x11rb::wrapper::ConnectionExt as _;
+use x11rb::NONE;
+
+const OWNER: Window = 1;
+const REQUESTOR: Window = 2;
+
+/// Set up an owner of the CLIPBOARD that offers the given UTF-8 text.
+fn owner(conn: &impl Connection, atoms: &SelectionAtoms, text: &str) -> Owner {
+    let mut owner = Owner::new(atoms, OWNER, atoms.CLIPBOARD, 10);
+    owner.set_target(atoms.UTF8_STRING, SelectionData::utf8_string(atoms, text));
+    assert!(owner.acquire(conn).unwrap());
+
+    let aux = ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE);
+    let _ = conn.change_window_attributes(REQUESTOR, &aux).unwrap();
+    owner
+}
+
+/// Feed events to the owner and the transfer until the transfer is complete.
+fn run(
+    conn: &impl Connection,
+    owner: &mut Owner,
+    transfer: &mut Transfer,
+) -> Result<SelectionData, TransferError> {
+    loop {
+        conn.flush().unwrap();
+        let event = conn
+            .poll_for_event()
+            .unwrap()
+            .expect("The transfer is stuck");
+        let _ = owner.handle_event(conn, &event).unwrap();
+        if let TransferStatus::Done(data) = transfer.handle_event(conn, &event)? {
+            return Ok(data);
+        }
+    }
+}
+
+fn start(conn: &impl Connection, atoms: &SelectionAtoms, target: Atom) -> Transfer {
+    let property = atoms.X11RB_SELECTION;
+    Transfer::start(
+        conn,
+        atoms,
+        REQUESTOR,
+        atoms.CLIPBOARD,
+        target,
+        property,
+        20,
+    )
+    .unwrap()
+}
+
+#[test]
+fn small_transfer() {
+    let conn = common::server(u16::MAX).connect(0).unwrap();
+    let atoms = SelectionAtoms::new(&conn).unwrap().reply().unwrap();
+    let mut owner = owner(&conn, &atoms, "Hello");
+
+    let mut transfer = start(&conn, &atoms, atoms.UTF8_STRING);
+    let data = run(&conn, &mut owner, &mut transfer).unwrap();
+    assert_eq!(data, SelectionData::utf8_string(&atoms, "Hello"));
+
+    let mut transfer = start(&conn, &atoms, atoms.TARGETS);
+    let data = run(&conn, &mut owner, &mut transfer).unwrap();
+    let targets = [
+        atoms.TARGETS,
+        atoms.MULTIPLE,
+        atoms.TIMESTAMP,
+        atoms.UTF8_STRING,
+    ];
+    assert_eq!(data, SelectionData::from_u32(AtomEnum::ATOM, &targets));
+}
+
+#[test]
+fn multiple() {
+    let conn = common::server(u16::MAX).connect(0).unwrap();
+    let atoms = SelectionAtoms::new(&conn).unwrap().reply().unwrap();
+    let mut owner = owner(&conn, &atoms, "Hello");
+
+    // Ask for UTF8_STRING in property 1000 and for TEXT in property 1001
+    let pairs = [atoms.UTF8_STRING, 1000, atoms.TEXT, 1001];
+    let _ = conn
+        .change_property32(
+            PropMode::REPLACE,
+            REQUESTOR,
+            atoms.X11RB_SELECTION,
+            atoms.ATOM_PAIR,
+            &pairs,
+        )
+        .unwrap();
+    let mut transfer = start(&conn, &atoms, atoms.MULTIPLE);
+    let data = run(&conn, &mut owner, &mut transfer).unwrap();
+
+    // The owner cannot convert to TEXT
+    let pairs = [atoms.UTF8_STRING, 1000, atoms.TEXT, NONE];
+    assert_eq!(data, SelectionData::from_u32(atoms.ATOM_PAIR, &pairs));
+    let reply = conn
+        .get_property(false, REQUESTOR, 1000u32, AtomEnum::ANY, 0, 100)
+        .unwrap()
+        .reply()
+        .unwrap();
+    assert_eq!(reply.value, b"Hello");
+}
+
+#[test]
+fn incr_transfer() {
+    let server = common::server(256);
+    let conn = server.connect(0).unwrap();
+    let atoms = SelectionAtoms::new(&conn).unwrap().reply().unwrap();
+    assert_eq!(conn.maximum_request_bytes(), 256);
+    let text = "0123456789".repeat(100);
+    let mut owner = owner(&conn, &atoms, &text);
+
+    let _ = server.take_requests();
+    let mut transfer = start(&conn, &atoms, atoms.UTF8_STRING);
+    let data = run(&conn, &mut owner, &mut transfer).unwrap();
+    assert_eq!(data, SelectionData::utf8_string(&atoms, &text));
+
+    // The INCR property and five chunks, the last of which is empty
+    let chunks: Vec<usize> = server
+        .take_requests()
+        .iter()
+        .filter_map(|request| match request {
+            Request::ChangeProperty(request) => Some(request.data.len()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(chunks, [4, 228, 228, 228, 228, 88, 0]);
+}
+
+#[test]
+fn incr_transfer_keeps_event_mask() {
+    let server = common::server(256);
+    let conn = server.connect(0).unwrap();
+    let atoms = SelectionAtoms::new(&conn).unwrap().reply().unwrap();
+    let mut owner = owner(&conn, &atoms, &"x".repeat(1000));
+    let event_mask = || {
+        conn.get_window_attributes(REQUESTOR)
+            .unwrap()
+            .reply()
+            .unwrap()
+            .your_event_mask
+    };
+
+    // PROPERTY_CHANGE is added for the transfer and the previous event mask is restored afterwards
+    let aux = ChangeWindowAttributesAux::new().event_mask(EventMask::STRUCTURE_NOTIFY);
+    let _ = conn.change_window_attributes(REQUESTOR, &aux).unwrap();
+    let mut transfer = start(&conn, &atoms, atoms.UTF8_STRING);
+    let _ = run(&conn, &mut owner, &mut transfer).unwrap();
+    assert_eq!(event_mask(), EventMask::STRUCTURE_NOTIFY);
+
+    // An event mask that already contains PROPERTY_CHANGE is left alone
+    let mask = EventMask::STRUCTURE_NOTIFY | EventMask::PROPERTY_CHANGE;
+    let aux = ChangeWindowAttributesAux::new().event_mask(mask);
+    let _ = conn.change_window_attributes(REQUESTOR, &aux).unwrap();
+    conn.flush().unwrap();
+    let _ = server.take_requests();
+    let mut transfer = start(&conn, &atoms, atoms.UTF8_STRING);
+    let _ = run(&conn, &mut owner, &mut transfer).unwrap();
+    assert_eq!(event_mask(), mask);
+    assert!(!server
+        .take_requests()
+        .iter()
+        .any(|request| matches!(request, Request::ChangeWindowAttributes(_))));
+}
+
+#[test]
+fn size_limit() {
+    let server = common::server(256);
+    let conn = server.connect(0).unwrap();
+    let atoms = SelectionAtoms::new(&conn).unwrap().reply().unwrap();
+    let mut owner = owner(&conn, &atoms, &"x".repeat(1000));
+
+    let _ = server.take_requests();
+    let mut transfer = start(&conn, &atoms, atoms.UTF8_STRING);
+    transfer.set_max_size(500);
+    match run(&conn, &mut owner, &mut transfer) {
+        Err(TransferError::TooLarge) => {}
+        result => panic!("Unexpected result {result:?}"),
+    }
+
+    // The size in the INCR property is checked before any chunk is transferred
+    conn.flush().unwrap();
+    let properties = server
+        .take_requests()
+        .iter()
+        .filter(|request| matches!(request, Request::ChangeProperty(_)))
+        .count();
+    assert_eq!(properties, 1);
+}
+
+#[test]
+fn missing_property() {
+    let conn = common::server(u16::MAX).connect(0).unwrap();
+    let atoms = SelectionAtoms::new(&conn).unwrap().reply().unwrap();
+
+    // The owner claims to have stored the data, but the property does not exist
+    let mut transfer = start(&conn, &atoms, atoms.UTF8_STRING);
+    let event = SelectionNotifyEvent {
+        requestor: REQUESTOR,
+        selection: atoms.CLIPBOARD,
+        target: atoms.UTF8_STRING,
+        property: atoms.X11RB_SELECTION,
+        ..Default::default()
+    };
+    match transfer.handle_event(&conn, &Event::SelectionNotify(event)) {
+        Err(TransferError::Refused) => {}
+        result => panic!("Unexpected result {result:?}"),
+    }
+}
+
+#[test]
+fn refused() {
+    let conn = common::server(u16::MAX).connect(0).unwrap();
+    let atoms = SelectionAtoms::new(&conn).unwrap().reply().unwrap();
+    let mut owner = owner(&conn, &atoms, "Hello");
+
+    // The owner cannot convert to TEXT
+    let mut transfer = start(&conn, &atoms, atoms.TEXT);
+    match run(&conn, &mut owner, &mut transfer) {
+        Err(TransferError::Refused) => {}
+        result => panic!("Unexpected result {result:?}"),
+    }
+
+    // After giving up the selection, there is no owner
+    owner.release(&conn).unwrap();
+    let mut transfer = start(&conn, &atoms, atoms.UTF8_STRING);
+    match run(&conn, &mut owner, &mut transfer) {
+        Err(TransferError::Refused) => {}
+        result => panic!("Unexpected result {result:?}"),
+    }
+}
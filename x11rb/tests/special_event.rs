@@ -0,0 +1,79 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::io::{Read, Write};
+
+use x11rb::connection::Connection as _;
+use x11rb::errors::ConnectionError;
+use x11rb::protocol::xproto::GE_GENERIC_EVENT;
+
+const PRESENT_NAME: &str = "Present";
+const PRESENT_OPCODE: u8 = 140;
+
+fn query_extension_reply(present: bool) -> [u8; 32] {
+    let mut reply = [0; 32];
+    reply[0] = 1;
+    reply[2..4].copy_from_slice(&1u16.to_ne_bytes());
+    reply[8] = present.into();
+    reply[9] = PRESENT_OPCODE;
+    reply
+}
+
+fn generic_event(eid: u32) -> [u8; 32] {
+    let mut event = [0; 32];
+    event[0] = GE_GENERIC_EVENT;
+    event[1] = PRESENT_OPCODE;
+    event[2..4].copy_from_slice(&1u16.to_ne_bytes());
+    event[12..16].copy_from_slice(&eid.to_ne_bytes());
+    event
+}
+
+fn eid(event: &[u8]) -> u32 {
+    u32::from_ne_bytes(event[12..16].try_into().unwrap())
+}
+
+#[test]
+fn events_go_to_special_queue() {
+    let (conn, mut server) = common::connect();
+
+    // The reply to QueryExtension can be sent before the request arrives
+    server.write_all(&query_extension_reply(true)).unwrap();
+    let queue = conn
+        .register_for_special_event(PRESENT_NAME, 5)
+        .unwrap()
+        .unwrap();
+    let mut request = [0; 16];
+    server.read_exact(&mut request).unwrap();
+    assert!(conn
+        .register_for_special_event(PRESENT_NAME, 5)
+        .unwrap()
+        .is_none());
+
+    server.write_all(&generic_event(6)).unwrap();
+    server.write_all(&generic_event(5)).unwrap();
+
+    let (event, _seq) = queue.wait_for_raw_event_with_sequence().unwrap();
+    assert_eq!(eid(&event), 5);
+    assert!(queue.poll_for_raw_event_with_sequence().unwrap().is_none());
+
+    let event = conn.poll_for_raw_event().unwrap().unwrap();
+    assert_eq!(eid(&event), 6);
+    assert!(conn.poll_for_raw_event().unwrap().is_none());
+
+    // Without the queue, the events end up in the normal event queue again
+    drop(queue);
+    server.write_all(&generic_event(5)).unwrap();
+    let event = conn.wait_for_raw_event().unwrap();
+    assert_eq!(eid(&event), 5);
+}
+
+#[test]
+fn missing_extension() {
+    let (conn, mut server) = common::connect();
+    server.write_all(&query_extension_reply(false)).unwrap();
+    match conn.register_for_special_event(PRESENT_NAME, 5) {
+        Err(ConnectionError::UnsupportedExtension) => {}
+        result => panic!("Unexpected result {result:?}"),
+    };
+}
@@ -0,0 +1,50 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::io::{IoSlice, Read, Write};
+
+use x11rb::connection::{Connection as _, ConnectionStats, RequestConnection as _};
+use x11rb::protocol::xproto::{ConnectionExt as _, MapNotifyEvent};
+
+#[test]
+fn count_traffic() {
+    let (conn, mut server) = common::connect();
+
+    // A request for an extension with major opcode 140 and a core GetInputFocus request
+    drop(
+        conn.send_request_without_reply(&[IoSlice::new(&[140, 1, 2, 0, 0, 0, 0, 0])], Vec::new())
+            .unwrap(),
+    );
+    let cookie = conn.get_input_focus().unwrap();
+    conn.flush().unwrap();
+    let mut requests = [0; 12];
+    server.read_exact(&mut requests).unwrap();
+
+    let mut reply = [0; 32];
+    reply[0] = 1;
+    reply[2..4].copy_from_slice(&2u16.to_ne_bytes());
+    server.write_all(&reply).unwrap();
+    let _ = cookie.reply().unwrap();
+
+    let event: [u8; 32] = MapNotifyEvent {
+        response_type: 19,
+        ..Default::default()
+    }
+    .into();
+    server.write_all(&event).unwrap();
+    let _ = conn.wait_for_event().unwrap();
+
+    let stats = conn.stats();
+    assert_eq!((stats.requests, stats.bytes_sent), (2, 12));
+    assert_eq!((stats.core.requests, stats.core.bytes_sent), (1, 4));
+    let extension = stats.extensions[&140];
+    assert_eq!((extension.requests, extension.bytes_sent), (1, 8));
+    assert_eq!((stats.replies, stats.errors, stats.events), (1, 0, 1));
+    assert_eq!(stats.bytes_received, 64);
+    assert_eq!(stats.flushes, 1);
+    assert_eq!(stats.round_trips, 1);
+
+    assert_eq!(conn.reset_stats(), stats);
+    assert_eq!(conn.stats(), ConnectionStats::default());
+}
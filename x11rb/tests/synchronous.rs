@@ -0,0 +1,75 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+
+use x11rb::connection::Connection as _;
+use x11rb::protocol::xproto::{ConnectionExt as _, MAP_WINDOW_REQUEST};
+use x11rb::protocol::ErrorKind;
+
+fn response(kind: u8, code: u8, seqno: u16, major_opcode: u8) -> [u8; 32] {
+    let mut response = [0; 32];
+    response[0] = kind;
+    response[1] = code;
+    response[2..4].copy_from_slice(&seqno.to_ne_bytes());
+    response[10] = major_opcode;
+    response
+}
+
+#[test]
+fn errors_are_reported_immediately() {
+    let (conn, mut server) = common::connect();
+
+    let server = std::thread::spawn(move || {
+        // MapWindow and the GetInputFocus for syncing
+        let mut requests = [0; 12];
+        server.read_exact(&mut requests).unwrap();
+        assert_eq!(requests[0], MAP_WINDOW_REQUEST);
+        server
+            .write_all(&response(0, 3, 1, MAP_WINDOW_REQUEST))
+            .unwrap();
+        server.write_all(&response(1, 0, 2, 0)).unwrap();
+
+        // NoOperation and the GetInputFocus for syncing
+        let mut requests = [0; 8];
+        server.read_exact(&mut requests).unwrap();
+        server.write_all(&response(1, 0, 4, 0)).unwrap();
+        server
+    });
+
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let errors2 = Arc::clone(&errors);
+    conn.set_synchronous(Some(Arc::new(move |error| {
+        errors2.lock().unwrap().push(error)
+    })));
+
+    // The error is reported before the cookie is even returned
+    let cookie = conn.map_window(42).unwrap();
+    {
+        let errors = errors.lock().unwrap();
+        assert_eq!(errors.len(), 1);
+        let error = &errors[0];
+        assert_eq!(error.error.error_kind, ErrorKind::Window);
+        assert_eq!(error.error.sequence, 1);
+        assert_eq!(error.error.request_name, Some("MapWindow"));
+        assert!(error
+            .to_string()
+            .starts_with("BadWindow (3) in xproto::MapWindow"));
+        #[cfg(feature = "backtrace")]
+        assert!(error
+            .backtrace
+            .to_string()
+            .contains("errors_are_reported_immediately"));
+    }
+    // ...and is not reported again
+    cookie.check().unwrap();
+
+    conn.no_operation().unwrap().check().unwrap();
+    assert_eq!(errors.lock().unwrap().len(), 1);
+
+    conn.set_synchronous(None);
+    assert!(conn.poll_for_event().unwrap().is_none());
+    drop(server.join().unwrap());
+}
@@ -0,0 +1,83 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use x11rb::connection::Connection as _;
+use x11rb::errors::{ConnectionError, ReplyError};
+use x11rb::protocol::xproto::{ConnectionExt as _, CLIENT_MESSAGE_EVENT};
+
+const TIMEOUT: Duration = Duration::from_millis(50);
+
+fn get_input_focus_reply(seqno: u16) -> [u8; 32] {
+    let mut reply = [0; 32];
+    reply[0] = 1;
+    reply[2..4].copy_from_slice(&seqno.to_ne_bytes());
+    reply
+}
+
+fn assert_timeout<T: std::fmt::Debug>(result: Result<T, ReplyError>) {
+    match result {
+        Err(ReplyError::ConnectionError(ConnectionError::Timeout)) => {}
+        other => panic!("Expected a timeout, got {other:?}"),
+    }
+}
+
+#[test]
+fn reply_timeout() {
+    let (conn, mut server) = common::connect();
+
+    // The server does not answer in time
+    let cookie = conn.get_input_focus().unwrap();
+    assert_eq!(cookie.sequence_number(), 1);
+    assert_timeout(cookie.reply_timeout(TIMEOUT));
+
+    // The connection is still usable afterwards
+    let cookie = conn.get_input_focus().unwrap();
+    assert_eq!(cookie.sequence_number(), 2);
+    conn.flush().unwrap();
+    let mut requests = [0; 8];
+    server.read_exact(&mut requests).unwrap();
+    server.write_all(&get_input_focus_reply(1)).unwrap();
+    server.write_all(&get_input_focus_reply(2)).unwrap();
+    let _ = cookie.reply_timeout(TIMEOUT).unwrap();
+}
+
+#[test]
+fn check_timeout() {
+    let (conn, mut server) = common::connect();
+
+    // The check needs a sync with the server, which does not answer in time
+    let cookie = conn.no_operation().unwrap();
+    assert_timeout(cookie.check_timeout(TIMEOUT));
+
+    // The connection is still usable afterwards
+    let cookie = conn.get_input_focus().unwrap();
+    assert_eq!(cookie.sequence_number(), 3);
+    conn.flush().unwrap();
+    let mut requests = [0; 12];
+    server.read_exact(&mut requests).unwrap();
+    server.write_all(&get_input_focus_reply(2)).unwrap();
+    server.write_all(&get_input_focus_reply(3)).unwrap();
+    let _ = cookie.reply_timeout(TIMEOUT).unwrap();
+}
+
+#[test]
+fn wait_for_event_timeout() {
+    let (conn, mut server) = common::connect();
+
+    match conn.wait_for_raw_event_with_sequence_timeout(TIMEOUT) {
+        Err(ConnectionError::Timeout) => {}
+        other => panic!("Expected a timeout, got {other:?}"),
+    }
+
+    let mut event = [0; 32];
+    event[0] = CLIENT_MESSAGE_EVENT;
+    server.write_all(&event).unwrap();
+    let (event, seqno) = conn
+        .wait_for_raw_event_with_sequence_timeout(TIMEOUT)
+        .unwrap();
+    assert_eq!((event[0], seqno), (CLIENT_MESSAGE_EVENT, 0));
+}
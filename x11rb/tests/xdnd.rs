@@ -0,0 +1,218 @@
+#![cfg(feature = "request-parsing")]
+
+mod common;
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{AtomEnum, ConnectionExt as _, Window};
+use x11rb::rust_connection::RustConnection;
+use x11rb::selection::{SelectionAtoms, SelectionData};
+use x11rb::testing::FakeStream;
+use x11rb::xdnd::{DragSource, DropTarget, SourceEvent, TargetEvent, XdndAtoms};
+use x11rb::NONE;
+
+const SOURCE: Window = 1;
+const TARGET: Window = 2;
+const UNAWARE: Window = 3;
+
+struct Drag {
+    conn: RustConnection<FakeStream>,
+    atoms: XdndAtoms,
+    selection_atoms: SelectionAtoms,
+    source: DragSource,
+    target: DropTarget,
+}
+
+impl Drag {
+    /// Set up a drag from `SOURCE` to `TARGET`.
+    fn new() -> Self {
+        let conn = common::server(u16::MAX).connect(0).unwrap();
+        let selection_atoms = SelectionAtoms::new(&conn).unwrap().reply().unwrap();
+        let atoms = XdndAtoms::new(&conn).unwrap().reply().unwrap();
+        let source = DragSource::new(&selection_atoms, &atoms, SOURCE, 10);
+        let target = DropTarget::new(&selection_atoms, &atoms, TARGET);
+        let _ = target.make_aware(&conn).unwrap();
+        Self {
+            conn,
+            atoms,
+            selection_atoms,
+            source,
+            target,
+        }
+    }
+
+    /// Feed the next event to the source and the target.
+    fn next(&mut self) -> (Option<SourceEvent>, Option<TargetEvent>) {
+        self.conn.flush().unwrap();
+        let event = self
+            .conn
+            .poll_for_event()
+            .unwrap()
+            .expect("The drag is stuck");
+        let source = self.source.handle_event(&self.conn, &event).unwrap();
+        let target = self.target.handle_event(&self.conn, &event).unwrap();
+        (source, target)
+    }
+
+    /// Get the owner of `XdndSelection`.
+    fn selection_owner(&self) -> Window {
+        let selection = self.atoms.XdndSelection;
+        let reply = self.conn.get_selection_owner(selection).unwrap();
+        reply.reply().unwrap().owner
+    }
+
+    fn assert_idle(&self) {
+        self.conn.flush().unwrap();
+        assert!(self.conn.poll_for_event().unwrap().is_none());
+    }
+}
+
+#[test]
+fn full_drag() {
+    let mut drag = Drag::new();
+    let (atoms, conn) = (drag.atoms, &drag.conn);
+    let utf8_string = drag.selection_atoms.UTF8_STRING;
+    let data = SelectionData::utf8_string(&drag.selection_atoms, "Hello");
+    drag.source.set_data(utf8_string, data.clone());
+    assert!(drag.source.start(conn).unwrap());
+
+    // Windows without XdndAware are ignored
+    let copy = atoms.XdndActionCopy;
+    drag.source.motion(conn, None, 0, 0, 11, copy).unwrap();
+    drag.source
+        .motion(conn, Some(UNAWARE), 1, 2, 11, copy)
+        .unwrap();
+    assert_eq!(drag.source.current_target(), None);
+    drag.assert_idle();
+
+    let conn = &drag.conn;
+    drag.source
+        .motion(conn, Some(TARGET), 5, 6, 12, copy)
+        .unwrap();
+    let enter = TargetEvent::Enter {
+        source: SOURCE,
+        types: vec![utf8_string],
+    };
+    assert_eq!(drag.next(), (None, Some(enter)));
+    let position = TargetEvent::Position {
+        x_root: 5,
+        y_root: 6,
+        time: 12,
+        action: copy,
+    };
+    assert_eq!(drag.next(), (None, Some(position)));
+
+    // The second position is only sent after the status for the first one arrived
+    drag.target.send_status(&drag.conn, Some(copy)).unwrap();
+    drag.source
+        .motion(&drag.conn, Some(TARGET), -7, 8, 13, copy)
+        .unwrap();
+    let status = SourceEvent::Status {
+        target: TARGET,
+        action: Some(copy),
+    };
+    assert_eq!(drag.next(), (Some(status), None));
+    let position = TargetEvent::Position {
+        x_root: -7,
+        y_root: 8,
+        time: 13,
+        action: copy,
+    };
+    assert_eq!(drag.next(), (None, Some(position)));
+
+    // The same goes for the drop
+    assert!(drag.source.drop(&drag.conn, 14).unwrap());
+    drag.target.send_status(&drag.conn, Some(copy)).unwrap();
+    assert_eq!(drag.next(), (Some(status), None));
+    assert_eq!(drag.next(), (None, Some(TargetEvent::Drop { time: 14 })));
+
+    // The target fetches the data via XdndSelection
+    drag.target.request_data(&drag.conn, utf8_string).unwrap();
+    assert_eq!(drag.next(), (None, None));
+    assert_eq!(drag.next(), (None, Some(TargetEvent::Data(data))));
+
+    drag.target.finish(&drag.conn, Some(copy)).unwrap();
+    let finished = SourceEvent::Finished {
+        target: TARGET,
+        action: Some(copy),
+    };
+    assert_eq!(drag.next(), (Some(finished), None));
+    assert_eq!(drag.target.source(), None);
+    assert_eq!(drag.selection_owner(), NONE);
+    drag.assert_idle();
+}
+
+#[test]
+fn refused_drop() {
+    let mut drag = Drag::new();
+    let (atoms, conn) = (drag.atoms, &drag.conn);
+    let types = [
+        drag.selection_atoms.UTF8_STRING,
+        drag.selection_atoms.TEXT,
+        AtomEnum::STRING.into(),
+        AtomEnum::INTEGER.into(),
+    ];
+    for type_ in types {
+        let data = SelectionData::new(type_, 8, b"data".to_vec());
+        drag.source.set_data(type_, data);
+    }
+    assert!(drag.source.start(conn).unwrap());
+
+    // With more than three types, the target reads them from XdndTypeList
+    let move_ = atoms.XdndActionMove;
+    drag.source
+        .motion(conn, Some(TARGET), 5, 6, 11, move_)
+        .unwrap();
+    let enter = TargetEvent::Enter {
+        source: SOURCE,
+        types: types.to_vec(),
+    };
+    assert_eq!(drag.next(), (None, Some(enter)));
+    let position = TargetEvent::Position {
+        x_root: 5,
+        y_root: 6,
+        time: 11,
+        action: move_,
+    };
+    assert_eq!(drag.next(), (None, Some(position)));
+
+    // The target refuses, so the drop turns into a leave
+    drag.target.send_status(&drag.conn, None).unwrap();
+    let status = SourceEvent::Status {
+        target: TARGET,
+        action: None,
+    };
+    assert_eq!(drag.next(), (Some(status), None));
+    assert!(!drag.source.drop(&drag.conn, 12).unwrap());
+    assert_eq!(drag.next(), (None, Some(TargetEvent::Leave)));
+    assert_eq!(drag.target.source(), None);
+    assert_eq!(drag.selection_owner(), NONE);
+    drag.assert_idle();
+}
+
+#[test]
+fn deferred_drop_refused() {
+    let mut drag = Drag::new();
+    let (atoms, conn) = (drag.atoms, &drag.conn);
+    let utf8_string = drag.selection_atoms.UTF8_STRING;
+    let data = SelectionData::utf8_string(&drag.selection_atoms, "Hello");
+    drag.source.set_data(utf8_string, data);
+    assert!(drag.source.start(conn).unwrap());
+    assert_eq!(drag.selection_owner(), SOURCE);
+
+    let copy = atoms.XdndActionCopy;
+    drag.source
+        .motion(conn, Some(TARGET), 5, 6, 11, copy)
+        .unwrap();
+    let _ = drag.next(); // XdndEnter
+    let _ = drag.next(); // XdndPosition
+
+    // The drop happens before the status arrives, so it is deferred
+    assert!(drag.source.drop(&drag.conn, 12).unwrap());
+    drag.target.send_status(&drag.conn, None).unwrap();
+    let refused = SourceEvent::DropRefused { target: TARGET };
+    assert_eq!(drag.next(), (Some(refused), None));
+    assert_eq!(drag.next(), (None, Some(TargetEvent::Leave)));
+    assert_eq!(drag.source.current_target(), None);
+    assert_eq!(drag.selection_owner(), NONE);
+    drag.assert_idle();
+}
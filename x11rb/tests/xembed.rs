@@ -0,0 +1,197 @@
+#![cfg(feature = "request-parsing")]
+
+mod common;
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+    ConnectionExt as _, DestroyNotifyEvent, EventMask, PropMode, SelectionClearEvent, SetMode,
+    Window, DESTROY_NOTIFY_EVENT, SELECTION_CLEAR_EVENT,
+};
+use x11rb::protocol::{Event, Request};
+use x11rb::rust_connection::RustConnection;
+use x11rb::testing::FakeStream;
+use x11rb::wrapper::ConnectionExt as _;
+use x11rb::xembed::tray::{self, TrayEvent, TrayManager};
+use x11rb::xembed::{self, Focus, XembedAtoms, XembedInfo, XembedMessage};
+
+const ROOT: Window = 0;
+const MANAGER: Window = 1;
+const ICON: Window = 2;
+
+fn next_event(conn: &RustConnection<FakeStream>) -> Event {
+    conn.flush().unwrap();
+    conn.poll_for_event().unwrap().expect("No event arrived")
+}
+
+fn xembed_message(conn: &RustConnection<FakeStream>, atoms: &XembedAtoms) -> XembedMessage {
+    match next_event(conn) {
+        Event::ClientMessage(event) => {
+            assert_eq!(event.window, ICON);
+            XembedMessage::parse(atoms, &event).unwrap().unwrap().1
+        }
+        event => panic!("Unexpected event {event:?}"),
+    }
+}
+
+/// Set up a tray manager that owns the selection.
+fn manager(conn: &RustConnection<FakeStream>) -> (XembedAtoms, u32, TrayManager) {
+    let atoms = XembedAtoms::new(conn).unwrap().reply().unwrap();
+    let selection = tray::selection_atom(conn, 0).unwrap().reply().unwrap().atom;
+    assert_eq!(tray::find_manager(conn, selection).unwrap(), None);
+
+    let mut manager = TrayManager::new(&atoms, selection, MANAGER, ROOT, 10);
+    assert!(manager.acquire(conn).unwrap());
+    let event = next_event(conn);
+    assert_eq!(
+        tray::manager_from_event(&atoms, selection, &event),
+        Some(MANAGER)
+    );
+    assert_eq!(manager.handle_event(conn, &event).unwrap(), None);
+    assert_eq!(tray::find_manager(conn, selection).unwrap(), Some(MANAGER));
+    (atoms, selection, manager)
+}
+
+#[test]
+fn dock_icon() {
+    let server = common::server(u16::MAX);
+    let conn = server.connect(0).unwrap();
+    let (atoms, _, mut manager) = manager(&conn);
+
+    // The icon asks for being docked
+    let info = XembedInfo::new(true);
+    let _ = xembed::set_xembed_info(&conn, &atoms, ICON, info).unwrap();
+    let _ = tray::dock(&conn, &atoms, MANAGER, ICON, 11).unwrap();
+    let event = next_event(&conn);
+    let request = TrayEvent::DockRequest {
+        icon: ICON,
+        time: 11,
+    };
+    assert_eq!(manager.handle_event(&conn, &event).unwrap(), Some(request));
+
+    // The manager embeds it by reparenting
+    let _ = server.take_requests();
+    assert!(manager.embed(&conn, ICON, MANAGER, 4, 5).unwrap());
+    assert_eq!(manager.icons().collect::<Vec<_>>(), [ICON]);
+    let message = XembedMessage::EmbeddedNotify {
+        embedder: MANAGER,
+        version: 0,
+    };
+    assert_eq!(xembed_message(&conn, &atoms), message);
+    let requests = server.take_requests();
+    // _XEMBED_INFO is checked before the icon is touched
+    assert!(matches!(requests[0], Request::GetProperty(_)));
+    assert!(requests.iter().any(|request| matches!(
+        request,
+        Request::ChangeSaveSet(r) if r.mode == SetMode::INSERT && r.window == ICON
+    )));
+    assert!(requests.iter().any(|request| matches!(
+        request,
+        Request::ReparentWindow(r) if (r.window, r.parent, r.x, r.y) == (ICON, MANAGER, 4, 5)
+    )));
+    assert!(requests
+        .iter()
+        .any(|request| matches!(request, Request::MapWindow(r) if r.window == ICON)));
+
+    // The icon hides itself
+    let info = XembedInfo::new(false);
+    let _ = xembed::set_xembed_info(&conn, &atoms, ICON, info).unwrap();
+    let event = next_event(&conn);
+    let changed = TrayEvent::MappedChanged {
+        icon: ICON,
+        mapped: false,
+    };
+    assert_eq!(manager.handle_event(&conn, &event).unwrap(), Some(changed));
+    conn.flush().unwrap();
+    assert!(server
+        .take_requests()
+        .iter()
+        .any(|request| matches!(request, Request::UnmapWindow(r) if r.window == ICON)));
+
+    // Focus messages
+    let message = XembedMessage::FocusIn(Focus::First);
+    let _ = xembed::send_message(&conn, &atoms, ICON, 12, message).unwrap();
+    assert_eq!(xembed_message(&conn, &atoms), message);
+
+    // The icon goes away
+    let event = DestroyNotifyEvent {
+        response_type: DESTROY_NOTIFY_EVENT,
+        event: ICON,
+        window: ICON,
+        ..Default::default()
+    };
+    let _ = conn
+        .send_event(false, ICON, EventMask::NO_EVENT, event)
+        .unwrap();
+    let event = next_event(&conn);
+    let removed = TrayEvent::Removed { icon: ICON };
+    assert_eq!(manager.handle_event(&conn, &event).unwrap(), Some(removed));
+    assert_eq!(manager.icons().count(), 0);
+}
+
+#[test]
+fn invalid_info() {
+    let conn = common::server(u16::MAX).connect(0).unwrap();
+    let (atoms, _, mut manager) = manager(&conn);
+
+    // A newer version is answered with ours and a broken property is treated as missing
+    let info = XembedInfo {
+        version: 3,
+        flags: 0,
+    };
+    let _ = xembed::set_xembed_info(&conn, &atoms, ICON, info).unwrap();
+    assert!(!manager.embed(&conn, ICON, MANAGER, 0, 0).unwrap());
+    let message = XembedMessage::EmbeddedNotify {
+        embedder: MANAGER,
+        version: 0,
+    };
+    assert_eq!(xembed_message(&conn, &atoms), message);
+
+    let _ = conn
+        .change_property8(
+            PropMode::REPLACE,
+            ICON,
+            atoms._XEMBED_INFO,
+            atoms._XEMBED_INFO,
+            b"x",
+        )
+        .unwrap();
+    assert!(manager.embed(&conn, ICON, MANAGER, 0, 0).unwrap());
+    assert_eq!(manager.icons().collect::<Vec<_>>(), [ICON]);
+}
+
+#[test]
+fn selection_lost() {
+    let server = common::server(u16::MAX);
+    let conn = server.connect(0).unwrap();
+    let (atoms, selection, mut manager) = manager(&conn);
+    assert!(manager.embed(&conn, ICON, MANAGER, 0, 0).unwrap());
+    let _ = xembed_message(&conn, &atoms);
+
+    let event = SelectionClearEvent {
+        response_type: SELECTION_CLEAR_EVENT,
+        owner: MANAGER,
+        selection,
+        ..Default::default()
+    };
+    let _ = conn
+        .send_event(false, MANAGER, EventMask::NO_EVENT, event)
+        .unwrap();
+    let event = next_event(&conn);
+    let _ = server.take_requests();
+    let lost = Some(TrayEvent::SelectionLost);
+    assert_eq!(manager.handle_event(&conn, &event).unwrap(), lost);
+    assert!(!manager.is_owner());
+
+    // The icons are handed back to the root window
+    assert_eq!(manager.icons().count(), 0);
+    conn.flush().unwrap();
+    assert!(server.take_requests().iter().any(|request| matches!(
+        request,
+        Request::ReparentWindow(r) if (r.window, r.parent) == (ICON, ROOT)
+    )));
+
+    // Dock requests are ignored afterwards
+    let _ = tray::dock(&conn, &atoms, MANAGER, ICON, 11).unwrap();
+    let event = next_event(&conn);
+    assert_eq!(manager.handle_event(&conn, &event).unwrap(), None);
+}